@@ -4,9 +4,22 @@ use bevy::{
     window::{Window, WindowPlugin},
 };
 
-use aettesaga::GamePlugin;
+use aettesaga::{
+    GamePlugin,
+    plugins::world::config::{WORLD_CONFIG_PATH, WorldConfig, WorldConfigArgs},
+};
 
 fn main() -> AppExit {
+    let config = match WorldConfigArgs::parse(std::env::args().skip(1))
+        .and_then(|args| WorldConfig::resolve(&args, WORLD_CONFIG_PATH))
+    {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}\n{}", WorldConfigArgs::USAGE);
+            return AppExit::error();
+        }
+    };
+
     App::new()
         .add_plugins(DefaultPlugins::set(
             DefaultPlugins,
@@ -18,6 +31,7 @@ fn main() -> AppExit {
                 ..Default::default()
             },
         ))
+        .insert_resource(config)
         .add_plugins(GamePlugin)
         .run()
 }
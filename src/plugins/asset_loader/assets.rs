@@ -1,6 +1,17 @@
 use bevy::prelude::*;
 
-use crate::plugins::world::material::VoxelAtlasMaterial;
+use crate::plugins::world::{blocks::TileId, material::VoxelAtlasMaterial};
+
+/// Side of a square tile of the block atlas, in pixels.
+pub const ATLAS_TILE_SIZE: u32 = 32;
+/// Rows of tiles in the block atlas: the top, side and bottom of each block column.
+pub const ATLAS_ROWS: u32 = 3;
+
+/// Column and row of `tile` in the block atlas, whose tiles count down each column in turn.
+pub fn atlas_cell(tile: TileId) -> UVec2 {
+    let tile = tile as u32;
+    UVec2::new(tile / ATLAS_ROWS, tile % ATLAS_ROWS)
+}
 
 #[derive(Resource)]
 pub struct GameAssets {
@@ -10,4 +21,6 @@ pub struct GameAssets {
 #[derive(Resource)]
 pub struct VoxelAtlasHandles {
     pub material: Handle<VoxelAtlasMaterial>,
+    /// Alpha blended variant of `material` for fluids.
+    pub translucent_material: Handle<VoxelAtlasMaterial>,
 }
@@ -57,13 +57,13 @@ fn finalize_assets(
     let h = img.size().y;
 
     assert!(
-        w.is_multiple_of(32) && h == 32 * 3,
+        w.is_multiple_of(ATLAS_TILE_SIZE) && h == ATLAS_TILE_SIZE * ATLAS_ROWS,
         "textures/blocks.png is {}x{} but must be divisible by 32 (tile size 32x32, no padding).",
         w,
         h
     );
 
-    let grid = UVec2::new(w / 32, h / 32);
+    let grid = UVec2::new(w / ATLAS_TILE_SIZE, h / ATLAS_TILE_SIZE);
 
     let material = materials.add(ExtendedMaterial {
         base: StandardMaterial::default(),
@@ -73,7 +73,23 @@ fn finalize_assets(
         },
     });
 
-    commands.insert_resource(VoxelAtlasHandles { material });
+    let translucent_material = materials.add(ExtendedMaterial {
+        base: StandardMaterial {
+            alpha_mode: AlphaMode::Blend,
+            cull_mode: None,
+            double_sided: true,
+            ..default()
+        },
+        extension: VoxelAtlasMaterialExtension {
+            atlas: assets.block_atlas.clone(),
+            grid,
+        },
+    });
+
+    commands.insert_resource(VoxelAtlasHandles {
+        material,
+        translucent_material,
+    });
 
     next_state.set(LoadingState::Initialized);
 }
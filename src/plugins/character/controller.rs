@@ -0,0 +1,232 @@
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    ChunkEntityMap, Chunks,
+    blocks::{BlockRegistry, BlockRegistryRes},
+    voxel_world::get_voxel,
+};
+
+/// Small gap kept between a character and the voxels it touches, so it doesn't count as
+/// inside them on the next move.
+const SKIN: f32 = 1e-3;
+
+#[derive(Resource, Clone, Debug)]
+pub struct CharacterControllerSettings {
+    /// Downward acceleration in voxels per second squared.
+    pub gravity: f32,
+    /// Maximum fall speed in voxels per second.
+    pub terminal_velocity: f32,
+    /// Longest step simulated at once, in seconds. Longer frames are split up.
+    pub max_step: f32,
+}
+
+impl Default for CharacterControllerSettings {
+    fn default() -> Self {
+        Self {
+            gravity: 30.0,
+            terminal_velocity: 40.0,
+            max_step: 1.0 / 30.0,
+        }
+    }
+}
+
+/// A character moved through the voxel grid as an upright box, stopped by every block
+/// that isn't a fluid. Unloaded chunks are solid, so characters wait at the edge of the
+/// loaded world instead of falling out of it.
+///
+/// The `Transform` holds the middle of the bottom of the box.
+#[derive(Component, Clone, Debug)]
+pub struct CharacterController {
+    pub half_width: f32,
+    pub height: f32,
+    /// Highest ledge walked up without jumping.
+    pub step_height: f32,
+    /// Upward speed of a jump.
+    pub jump_speed: f32,
+    /// Horizontal velocity to move at; `y` is ignored.
+    pub movement: Vec3,
+    /// Jump on the next update, if standing on something.
+    pub jump: bool,
+    pub velocity: Vec3,
+    pub grounded: bool,
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        Self {
+            half_width: 0.3,
+            height: 1.8,
+            step_height: 0.6,
+            jump_speed: 9.0,
+            movement: Vec3::ZERO,
+            jump: false,
+            velocity: Vec3::ZERO,
+            grounded: false,
+        }
+    }
+}
+
+impl CharacterController {
+    /// Corners of the box with its bottom middle at `position`.
+    pub fn bounds(&self, position: Vec3) -> (Vec3, Vec3) {
+        let half = Vec3::new(self.half_width, 0.0, self.half_width);
+        (position - half, position + half + Vec3::Y * self.height)
+    }
+}
+
+pub(super) fn move_characters(
+    time: Res<Time>,
+    settings: Res<CharacterControllerSettings>,
+    chunk_map: Res<ChunkEntityMap>,
+    chunks: Res<Chunks>,
+    block_registry: Res<BlockRegistryRes>,
+    mut characters: Query<(&mut CharacterController, &mut Transform)>,
+) {
+    let registry = &block_registry.0;
+    let blocked = |voxel: IVec3| {
+        get_voxel(&chunk_map, &chunks, voxel)
+            .is_none_or(|voxel| registry.is_opaque(voxel.block_id()))
+    };
+
+    let dt = time.delta_secs();
+    let steps = (dt / settings.max_step).ceil().max(1.0);
+    let step = dt / steps;
+    for (mut controller, mut transform) in &mut characters {
+        for _ in 0..steps as u32 {
+            step_character(
+                &mut controller,
+                &mut transform.translation,
+                step,
+                &settings,
+                &blocked,
+            );
+        }
+    }
+}
+
+fn step_character(
+    controller: &mut CharacterController,
+    position: &mut Vec3,
+    dt: f32,
+    settings: &CharacterControllerSettings,
+    blocked: &dyn Fn(IVec3) -> bool,
+) {
+    controller.velocity.x = controller.movement.x;
+    controller.velocity.z = controller.movement.z;
+    if controller.jump && controller.grounded {
+        controller.velocity.y = controller.jump_speed;
+    }
+    controller.jump = false;
+    controller.velocity.y =
+        (controller.velocity.y - settings.gravity * dt).max(-settings.terminal_velocity);
+
+    let delta = controller.velocity * dt;
+    controller.grounded = false;
+    if let Some(stop) = sweep(controller, *position, Vec3::Y * delta.y, blocked) {
+        if delta.y < 0.0 {
+            controller.grounded = true;
+        }
+        position.y = stop;
+        controller.velocity.y = 0.0;
+    } else {
+        position.y += delta.y;
+    }
+
+    for axis in [Vec3::X, Vec3::Z] {
+        let along = axis * delta.dot(axis);
+        if along == Vec3::ZERO {
+            continue;
+        }
+        let Some(stop) = sweep(controller, *position, along, blocked) else {
+            *position += along;
+            continue;
+        };
+
+        // Walk up low ledges instead of stopping at them.
+        let raised = *position + Vec3::Y * controller.step_height;
+        if controller.grounded
+            && sweep(
+                controller,
+                *position,
+                Vec3::Y * controller.step_height,
+                blocked,
+            )
+            .is_none()
+            && sweep(controller, raised, along, blocked).is_none()
+        {
+            let landed = raised + along;
+            let down = Vec3::NEG_Y * controller.step_height;
+            position.x = landed.x;
+            position.z = landed.z;
+            position.y = sweep(controller, landed, down, blocked).unwrap_or(landed.y + down.y);
+            continue;
+        }
+
+        if axis == Vec3::X {
+            position.x = stop;
+        } else {
+            position.z = stop;
+        }
+    }
+}
+
+/// Move the box at `position` by `delta`, along a single axis. Returns the coordinate on
+/// that axis where it stops against a blocked voxel, or `None` if nothing is in the way.
+fn sweep(
+    controller: &CharacterController,
+    position: Vec3,
+    delta: Vec3,
+    blocked: &dyn Fn(IVec3) -> bool,
+) -> Option<f32> {
+    let (min, max) = controller.bounds(position + delta);
+    let first = (min + Vec3::splat(SKIN)).floor().as_ivec3();
+    let last = (max - Vec3::splat(SKIN)).floor().as_ivec3();
+
+    let axis = if delta.x != 0.0 {
+        0
+    } else if delta.y != 0.0 {
+        1
+    } else {
+        2
+    };
+    let positive = delta[axis] > 0.0;
+    let mut stop: Option<f32> = None;
+    for z in first.z..=last.z {
+        for y in first.y..=last.y {
+            for x in first.x..=last.x {
+                let voxel = IVec3::new(x, y, z);
+                if !blocked(voxel) {
+                    continue;
+                }
+                let face = voxel[axis] as f32;
+                let candidate = match (axis, positive) {
+                    (1, true) => face - controller.height - SKIN,
+                    (1, false) => face + 1.0 + SKIN,
+                    (_, true) => face - controller.half_width - SKIN,
+                    (_, false) => face + 1.0 + controller.half_width + SKIN,
+                };
+                stop = Some(match stop {
+                    None => candidate,
+                    Some(stop) if positive => stop.min(candidate),
+                    Some(stop) => stop.max(candidate),
+                });
+            }
+        }
+    }
+    stop
+}
+
+/// Whether a box like `controller`'s fits at `position` without overlapping a block that
+/// isn't a fluid.
+pub fn fits(
+    controller: &CharacterController,
+    position: Vec3,
+    registry: &BlockRegistry,
+    chunk_map: &ChunkEntityMap,
+    chunks: &Chunks,
+) -> bool {
+    let blocked = |voxel: IVec3| {
+        get_voxel(chunk_map, chunks, voxel).is_none_or(|voxel| registry.is_opaque(voxel.block_id()))
+    };
+    sweep(controller, position, Vec3::Y * SKIN, &blocked).is_none()
+}
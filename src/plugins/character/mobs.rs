@@ -0,0 +1,845 @@
+use core::ops::RangeInclusive;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use crate::plugins::{
+    character::controller::{CharacterController, fits},
+    world::{
+        ChunkEntityMap, Chunks,
+        blocks::{BLOCK_DIRT, BLOCK_GRASS, BLOCK_STONE, BlockId, BlockRegistryRes},
+        colliders::TerrainCollider,
+        config::WorldHeightLimits,
+        events::PathFailed,
+        heightmap::{HeightmapKind, Heightmaps},
+        lighting::LightMap,
+        noise::FeatureRng,
+        pathfinding::{
+            NavPath, PathRequest,
+            astar::{Movement, NavAgent},
+        },
+        voxel::Voxel,
+        voxel_world::get_voxel,
+        worldgen::jobs::ChunkViewer,
+    },
+};
+
+pub type MobKind = u8;
+
+pub const MOB_SHEEP: MobKind = 0;
+pub const MOB_CAVE_CRAWLER: MobKind = 1;
+
+/// Where and how often a mob appears.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MobSpawnRule {
+    /// Blocks it spawns on top of.
+    pub ground: Vec<BlockId>,
+    /// Sky light it spawns in.
+    pub light: RangeInclusive<u8>,
+    /// Chance relative to the other mobs that could spawn at the same place.
+    pub weight: u32,
+}
+
+/// How a kind of mob looks, moves and behaves.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MobArchetype {
+    pub name: &'static str,
+    pub color: Color,
+    pub half_width: f32,
+    pub height: f32,
+    /// Speed when idling about, in voxels per second.
+    pub walk_speed: f32,
+    /// Speed when following or fleeing.
+    pub run_speed: f32,
+    pub jump_speed: f32,
+    pub nav: NavAgent,
+    /// Furthest it wanders from where it stands.
+    pub wander_radius: i32,
+    /// Distance it notices [`MobTarget`]s from.
+    pub sight_range: f32,
+    /// Follows targets it sees.
+    pub follows: bool,
+    /// Runs from targets it sees, `flee_distance` away.
+    pub flees: bool,
+    pub flee_distance: i32,
+    pub spawn: MobSpawnRule,
+}
+
+/// Mob archetypes by kind. Kinds are assigned in insertion order.
+#[derive(Clone, Debug, Default)]
+pub struct MobRegistry {
+    mobs: Vec<MobArchetype>,
+}
+
+impl MobRegistry {
+    pub fn get(&self, kind: MobKind) -> Option<&MobArchetype> {
+        self.mobs.get(kind as usize)
+    }
+
+    pub fn insert(&mut self, archetype: MobArchetype) -> MobKind {
+        self.mobs.push(archetype);
+        (self.mobs.len() - 1) as MobKind
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (MobKind, &MobArchetype)> {
+        self.mobs
+            .iter()
+            .enumerate()
+            .map(|(kind, archetype)| (kind as MobKind, archetype))
+    }
+
+    pub fn len(&self) -> usize {
+        self.mobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mobs.is_empty()
+    }
+}
+
+#[derive(Resource)]
+pub struct MobRegistryRes(pub MobRegistry);
+
+impl Default for MobRegistryRes {
+    fn default() -> Self {
+        let mut registry = MobRegistry::default();
+
+        let sheep = registry.insert(MobArchetype {
+            name: "sheep",
+            color: Color::srgb(0.92, 0.92, 0.88),
+            half_width: 0.4,
+            height: 1.2,
+            walk_speed: 1.5,
+            run_speed: 4.0,
+            jump_speed: 9.0,
+            nav: NavAgent::default(),
+            wander_radius: 8,
+            sight_range: 6.0,
+            follows: false,
+            flees: true,
+            flee_distance: 12,
+            spawn: MobSpawnRule {
+                ground: vec![BLOCK_GRASS],
+                light: 9..=15,
+                weight: 10,
+            },
+        });
+        debug_assert_eq!(sheep, MOB_SHEEP);
+
+        let crawler = registry.insert(MobArchetype {
+            name: "cave_crawler",
+            color: Color::srgb(0.25, 0.2, 0.3),
+            half_width: 0.45,
+            height: 0.9,
+            walk_speed: 1.2,
+            run_speed: 3.5,
+            jump_speed: 8.0,
+            nav: NavAgent {
+                height: 1,
+                ..default()
+            },
+            wander_radius: 6,
+            sight_range: 16.0,
+            follows: true,
+            flees: false,
+            flee_distance: 0,
+            spawn: MobSpawnRule {
+                ground: vec![BLOCK_STONE, BLOCK_DIRT],
+                light: 0..=4,
+                weight: 10,
+            },
+        });
+        debug_assert_eq!(crawler, MOB_CAVE_CRAWLER);
+
+        Self(registry)
+    }
+}
+
+/// Entities mobs notice, follow and run from, like the player.
+#[derive(Component, Copy, Clone, Debug, Default)]
+pub struct MobTarget;
+
+#[derive(Component, Copy, Clone, Debug, Eq, PartialEq)]
+#[require(CharacterController, Transform)]
+pub struct Mob {
+    pub kind: MobKind,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MobState {
+    /// Standing still for `seconds` more.
+    Idle {
+        seconds: f32,
+    },
+    /// Walking to a random spot nearby.
+    Wander,
+    Follow(Entity),
+    Flee(Entity),
+}
+
+/// What a mob is doing, and the random numbers it decides with.
+#[derive(Component, Clone, Debug)]
+pub struct MobBehaviour {
+    pub state: MobState,
+    /// Seconds until a followed target is looked for again.
+    pub repath: f32,
+    rng: FeatureRng,
+}
+
+impl MobBehaviour {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: MobState::Idle { seconds: 0.0 },
+            repath: 0.0,
+            rng: FeatureRng::for_cell(seed, IVec3::ZERO),
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct MobSpawnSettings {
+    /// Seconds between spawn attempts.
+    pub interval: f32,
+    /// Columns tried per attempt.
+    pub attempts: u32,
+    /// Mobs spawn between these distances, in voxels across the ground, from a
+    /// [`ChunkViewer`].
+    pub min_distance: i32,
+    pub max_distance: i32,
+    /// Mobs further than this from every viewer are removed.
+    pub despawn_distance: f32,
+    /// No more spawn while this many mobs are around.
+    pub max_mobs: usize,
+    pub seed: u64,
+}
+
+impl Default for MobSpawnSettings {
+    fn default() -> Self {
+        Self {
+            interval: 2.0,
+            attempts: 4,
+            min_distance: 16,
+            max_distance: 64,
+            despawn_distance: 128.0,
+            max_mobs: 24,
+            seed: 0x0B_5EED,
+        }
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub(super) struct MobSpawner {
+    timer: f32,
+    attempt: i32,
+}
+
+pub trait SpawnMobCommandExt {
+    /// Spawn a mob of `kind` standing at `position`, deciding what it does with random
+    /// numbers seeded from `settings.seed` and `position`. Returns `None` without
+    /// spawning anything if `kind` isn't in `registry`.
+    fn spawn_mob(
+        &mut self,
+        registry: &MobRegistry,
+        settings: &MobSpawnSettings,
+        kind: MobKind,
+        position: Vec3,
+    ) -> Option<Entity>;
+}
+
+impl<'w, 's> SpawnMobCommandExt for Commands<'w, 's> {
+    fn spawn_mob(
+        &mut self,
+        registry: &MobRegistry,
+        settings: &MobSpawnSettings,
+        kind: MobKind,
+        position: Vec3,
+    ) -> Option<Entity> {
+        let archetype = registry.get(kind)?.clone();
+        let seed = settings.seed
+            ^ (position.x.to_bits() as u64)
+            ^ (position.y.to_bits() as u64) << 16
+            ^ (position.z.to_bits() as u64) << 32;
+        let entity = self
+            .spawn((
+                Mob { kind },
+                MobBehaviour::new(seed),
+                archetype.nav,
+                CharacterController {
+                    half_width: archetype.half_width,
+                    height: archetype.height,
+                    step_height: 1.0,
+                    jump_speed: archetype.jump_speed,
+                    ..default()
+                },
+                TerrainCollider,
+                Transform::from_translation(position),
+                Visibility::default(),
+            ))
+            .id();
+
+        self.queue(move |world: &mut World| {
+            // Headless worlds have nothing to draw with.
+            if !world.contains_resource::<Assets<Mesh>>()
+                || !world.contains_resource::<Assets<StandardMaterial>>()
+            {
+                return;
+            }
+            let size = Vec3::new(
+                archetype.half_width * 2.0,
+                archetype.height,
+                archetype.half_width * 2.0,
+            );
+            let mesh = world
+                .resource_mut::<Assets<Mesh>>()
+                .add(Cuboid::from_size(size));
+            let material = world
+                .resource_mut::<Assets<StandardMaterial>>()
+                .add(StandardMaterial::from_color(archetype.color));
+            world.entity_mut(entity).with_child((
+                Mesh3d(mesh),
+                MeshMaterial3d(material),
+                Transform::from_translation(Vec3::Y * archetype.height / 2.0),
+            ));
+        });
+        Some(entity)
+    }
+}
+
+/// The voxel the feet at `position` are in.
+fn feet_cell(position: Vec3) -> IVec3 {
+    (position + Vec3::Y * 0.01).floor().as_ivec3()
+}
+
+/// The loaded terrain mobs stand on, spawn on and walk to.
+#[derive(SystemParam)]
+pub(super) struct MobGround<'w> {
+    heightmaps: Res<'w, Heightmaps>,
+    chunk_map: Res<'w, ChunkEntityMap>,
+    chunks: Res<'w, Chunks>,
+    registry: Res<'w, BlockRegistryRes>,
+    limits: Res<'w, WorldHeightLimits>,
+}
+
+impl MobGround<'_> {
+    fn get(&self, world: IVec3) -> Option<Voxel> {
+        get_voxel(&self.chunk_map, &self.chunks, world)
+    }
+
+    /// Voxels at `(x, z)` to stand in, from the top down: open voxels on top of ground,
+    /// with `clearance` open voxels from the feet up. Caves count as much as the surface.
+    /// The scan stops at the first chunk that isn't loaded.
+    fn floors(&self, x: i32, z: i32, clearance: i32) -> Vec<IVec3> {
+        let registry = &self.registry.0;
+        let mut floors = Vec::new();
+        let Some(top) = self.heightmaps.top_solid(x, z) else {
+            return floors;
+        };
+        // Open voxels seen so far above the one being looked at.
+        let mut open = 0;
+        for y in (self.limits.min_y()..=top.y + clearance).rev() {
+            let cell = IVec3::new(x, y, z);
+            let id = match self.get(cell) {
+                Some(voxel) => voxel.block_id(),
+                // Nothing is loaded above the ground yet, so it's open sky.
+                None if y > top.y => 0,
+                None => break,
+            };
+            if HeightmapKind::Solid.matches(registry, id) {
+                if open >= clearance {
+                    floors.push(cell + IVec3::Y);
+                }
+                open = 0;
+            } else if !registry.is_opaque(id) && !registry.is_fluid(id) {
+                open += 1;
+            } else {
+                open = 0;
+            }
+        }
+        floors
+    }
+
+    /// The floor at `(x, z)` closest in height to `y`.
+    fn floor_near(&self, x: i32, z: i32, y: i32, clearance: i32) -> Option<IVec3> {
+        self.floors(x, z, clearance)
+            .into_iter()
+            .min_by_key(|floor| (floor.y - y).abs())
+    }
+
+    fn fits(&self, body: &CharacterController, position: Vec3) -> bool {
+        fits(
+            body,
+            position,
+            &self.registry.0,
+            &self.chunk_map,
+            &self.chunks,
+        )
+    }
+}
+
+/// Voxels a body of `height` needs open to stand in.
+fn clearance(height: f32) -> i32 {
+    height.ceil() as i32
+}
+
+/// Pick what each mob does next, and ask for the paths to do it.
+pub(super) fn update_mob_behaviour(
+    mut commands: Commands,
+    time: Res<Time>,
+    registry: Res<MobRegistryRes>,
+    ground: MobGround,
+    targets: Query<(Entity, &GlobalTransform), With<MobTarget>>,
+    mut mobs: Query<(Entity, &Mob, &mut MobBehaviour, &Transform)>,
+    paths: Query<(Option<&NavPath>, Has<PathRequest>)>,
+) {
+    let dt = time.delta_secs();
+    for (entity, mob, mut behaviour, transform) in &mut mobs {
+        let Ok((path, requested)) = paths.get(entity) else {
+            continue;
+        };
+        let Some(archetype) = registry.0.get(mob.kind) else {
+            continue;
+        };
+        let position = transform.translation;
+        let cell = feet_cell(position);
+        let clearance = clearance(archetype.height);
+        behaviour.repath -= dt;
+
+        let seen = targets
+            .iter()
+            .map(|(target, transform)| (target, transform.translation()))
+            .filter(|(_, at)| at.distance(position) <= archetype.sight_range)
+            .min_by(|a, b| a.1.distance(position).total_cmp(&b.1.distance(position)));
+
+        // Seeing a target overrides whatever the mob was doing.
+        if let Some((target, _)) = seen {
+            let state = if archetype.flees {
+                Some(MobState::Flee(target))
+            } else if archetype.follows {
+                Some(MobState::Follow(target))
+            } else {
+                None
+            };
+            if let Some(state) = state
+                && behaviour.state != state
+            {
+                behaviour.state = state;
+                behaviour.repath = 0.0;
+            }
+        }
+
+        match behaviour.state {
+            MobState::Idle { seconds } => {
+                let seconds = seconds - dt;
+                if seconds > 0.0 {
+                    behaviour.state = MobState::Idle { seconds };
+                    continue;
+                }
+                let radius = archetype.wander_radius;
+                let offset = IVec2::new(
+                    behaviour.rng.range(-radius, radius),
+                    behaviour.rng.range(-radius, radius),
+                );
+                let goal = cell + IVec3::new(offset.x, 0, offset.y);
+                if let Some(goal) = ground.floor_near(goal.x, goal.z, cell.y, clearance) {
+                    commands
+                        .entity(entity)
+                        .insert(PathRequest { start: cell, goal });
+                    behaviour.state = MobState::Wander;
+                } else {
+                    behaviour.state = MobState::Idle { seconds: 1.0 };
+                }
+            }
+            MobState::Wander => {
+                if !requested && path.is_none_or(NavPath::is_finished) {
+                    let seconds = behaviour.rng.range_f32(2.0, 6.0);
+                    behaviour.state = MobState::Idle { seconds };
+                    commands.entity(entity).remove::<NavPath>();
+                }
+            }
+            MobState::Follow(target) | MobState::Flee(target) => {
+                let Ok((_, target_transform)) = targets.get(target) else {
+                    behaviour.state = MobState::Idle { seconds: 1.0 };
+                    continue;
+                };
+                let target_at = target_transform.translation();
+                if target_at.distance(position) > archetype.sight_range * 1.5 {
+                    behaviour.state = MobState::Idle { seconds: 1.0 };
+                    commands.entity(entity).remove::<NavPath>();
+                    continue;
+                }
+                if behaviour.repath > 0.0 || requested {
+                    continue;
+                }
+                behaviour.repath = 1.0;
+
+                let goal = if matches!(behaviour.state, MobState::Follow(_)) {
+                    if target_at.xz().distance(position.xz()) < 2.0 {
+                        commands.entity(entity).remove::<NavPath>();
+                        continue;
+                    }
+                    target_at
+                } else {
+                    let away = (position - target_at).xz().normalize_or(Vec2::X);
+                    position + (away * archetype.flee_distance as f32).extend(0.0).xzy()
+                };
+                let goal = goal.floor().as_ivec3();
+                if let Some(goal) = ground.floor_near(goal.x, goal.z, goal.y, clearance) {
+                    commands
+                        .entity(entity)
+                        .insert(PathRequest { start: cell, goal });
+                }
+            }
+        }
+    }
+}
+
+/// Mobs that couldn't find their way stand around for a bit.
+pub(super) fn on_mob_path_failed(event: On<PathFailed>, mut mobs: Query<&mut MobBehaviour>) {
+    if let Ok(mut behaviour) = mobs.get_mut(event.entity)
+        && matches!(behaviour.state, MobState::Wander)
+    {
+        behaviour.state = MobState::Idle { seconds: 1.0 };
+    }
+}
+
+/// Walk mobs along their paths with their character controllers.
+pub(super) fn steer_mobs(
+    registry: Res<MobRegistryRes>,
+    mut mobs: Query<(
+        &Mob,
+        &MobBehaviour,
+        &Transform,
+        &mut CharacterController,
+        Option<&mut NavPath>,
+    )>,
+) {
+    for (mob, behaviour, transform, mut controller, path) in &mut mobs {
+        controller.movement = Vec3::ZERO;
+        let (Some(archetype), Some(mut path)) = (registry.0.get(mob.kind), path) else {
+            continue;
+        };
+        let position = transform.translation;
+
+        // Skip the waypoints already reached.
+        while let Some(waypoint) = path.next_waypoint()
+            && waypoint.xz().distance(position.xz()) < 0.2
+            && (waypoint.y - position.y).abs() < 0.6
+        {
+            path.advance();
+        }
+        let Some(waypoint) = path.next_waypoint() else {
+            continue;
+        };
+
+        let speed = match behaviour.state {
+            MobState::Follow(_) | MobState::Flee(_) => archetype.run_speed,
+            _ => archetype.walk_speed,
+        };
+        let towards = (waypoint - position).xz();
+        let movement = towards.normalize_or_zero() * speed.min(towards.length() * 8.0);
+        controller.movement = Vec3::new(movement.x, 0.0, movement.y);
+
+        // Ledges too high to step onto and gaps are jumped.
+        let step = path.path.steps[path.next];
+        if step.movement == Movement::Jump || waypoint.y - position.y > controller.step_height {
+            controller.jump = true;
+        }
+    }
+}
+
+/// Spawn mobs on the ground around viewers, where their spawn rules allow. Any floor
+/// of a column can be picked, so mobs that want the dark spawn in caves.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn spawn_mobs(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<MobSpawnSettings>,
+    registry: Res<MobRegistryRes>,
+    ground: MobGround,
+    light_map: Res<LightMap>,
+    mut spawner: ResMut<MobSpawner>,
+    viewers: Query<&GlobalTransform, With<ChunkViewer>>,
+    mobs: Query<(), With<Mob>>,
+) {
+    spawner.timer -= time.delta_secs();
+    if spawner.timer > 0.0 {
+        return;
+    }
+    spawner.timer = settings.interval;
+    spawner.attempt += 1;
+    let mut count = mobs.iter().len();
+
+    for (index, viewer) in viewers.iter().enumerate() {
+        let origin = viewer.translation().floor().as_ivec3();
+        let mut rng =
+            FeatureRng::for_cell(settings.seed, IVec3::new(spawner.attempt, index as i32, 0));
+        for _ in 0..settings.attempts {
+            if count >= settings.max_mobs {
+                return;
+            }
+            let angle = rng.range_f32(0.0, core::f32::consts::TAU);
+            let distance = rng.range(settings.min_distance, settings.max_distance) as f32;
+            let column = origin.xz() + (Vec2::from_angle(angle) * distance).as_ivec2();
+            let floors = ground.floors(column.x, column.y, 1);
+            if floors.is_empty() {
+                continue;
+            }
+            let feet = floors[rng.below(floors.len() as u32) as usize];
+            let Some(block) = ground.get(feet - IVec3::Y) else {
+                continue;
+            };
+            // Unlit chunks would read as dark, and so as caves.
+            let Some(light) = light_map.sky_light_at(feet) else {
+                continue;
+            };
+
+            let candidates: Vec<(MobKind, &MobArchetype)> = registry
+                .0
+                .iter()
+                .filter(|(_, archetype)| {
+                    archetype.spawn.ground.contains(&block.block_id())
+                        && archetype.spawn.light.contains(&light)
+                })
+                .collect();
+            let total: u32 = candidates
+                .iter()
+                .map(|(_, archetype)| archetype.spawn.weight)
+                .sum();
+            if total == 0 {
+                continue;
+            }
+            let mut pick = rng.below(total);
+            let Some((kind, archetype)) = candidates.into_iter().find(|(_, archetype)| {
+                let hit = pick < archetype.spawn.weight;
+                pick = pick.saturating_sub(archetype.spawn.weight);
+                hit
+            }) else {
+                continue;
+            };
+
+            let position = feet.as_vec3() + Vec3::new(0.5, 0.0, 0.5);
+            let body = CharacterController {
+                half_width: archetype.half_width,
+                height: archetype.height,
+                ..default()
+            };
+            if ground.fits(&body, position) {
+                commands.spawn_mob(&registry.0, &settings, kind, position);
+                count += 1;
+            }
+        }
+    }
+}
+
+/// Remove mobs that are far from every viewer.
+pub(super) fn despawn_distant_mobs(
+    mut commands: Commands,
+    settings: Res<MobSpawnSettings>,
+    viewers: Query<&GlobalTransform, With<ChunkViewer>>,
+    mobs: Query<(Entity, &Transform), With<Mob>>,
+) {
+    if viewers.is_empty() {
+        return;
+    }
+    for (entity, transform) in &mobs {
+        let near = viewers.iter().any(|viewer| {
+            viewer
+                .translation()
+                .xz()
+                .distance(transform.translation.xz())
+                <= settings.despawn_distance
+        });
+        if !near {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::plugins::{
+        character::CharacterPlugin,
+        world::{
+            chunk::Chunk,
+            lighting::LightingPlugin,
+            pathfinding::PathfindingPlugin,
+            test_support::{headless_app, spawn_chunks},
+            voxel::Voxel,
+        },
+    };
+
+    /// Top of the grass in [`flat_app`].
+    const SURFACE: f32 = 4.0;
+
+    /// A headless app with one chunk of grass over stone, its surface at [`SURFACE`].
+    fn flat_app() -> App {
+        let mut chunk = Chunk::new();
+        for x in 0..32 {
+            for z in 0..32 {
+                for y in 0..SURFACE as usize {
+                    let block = if y + 1 == SURFACE as usize {
+                        BLOCK_GRASS
+                    } else {
+                        BLOCK_STONE
+                    };
+                    chunk.set(x, y, z, Voxel::new(block));
+                }
+            }
+        }
+        app_with(chunk)
+    }
+
+    /// A headless app with `chunk` at the origin.
+    fn app_with(chunk: Chunk) -> App {
+        let mut app = headless_app(1);
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+            1.0 / 30.0,
+        )))
+        .init_resource::<LightMap>()
+        .add_plugins((PathfindingPlugin, CharacterPlugin));
+        spawn_chunks(app.world_mut(), [(IVec3::ZERO, chunk)]);
+        app
+    }
+
+    fn spawn(app: &mut App, kind: MobKind, position: Vec3) -> Option<Entity> {
+        let registry = MobRegistryRes::default().0;
+        let settings = MobSpawnSettings::default();
+        let entity = app
+            .world_mut()
+            .commands()
+            .spawn_mob(&registry, &settings, kind, position);
+        app.world_mut().flush();
+        entity
+    }
+
+    #[test]
+    fn sheep_wanders_without_falling_through() {
+        let mut app = flat_app();
+        let start = Vec3::new(16.5, SURFACE, 16.5);
+        let sheep = spawn(&mut app, MOB_SHEEP, start).unwrap();
+        assert!(app.world().get::<Children>(sheep).is_none());
+
+        let mut wandered = false;
+        let mut goals = Vec::new();
+        let mut farthest = 0.0f32;
+        let mut closest_to_goal = f32::INFINITY;
+        for _ in 0..600 {
+            app.update();
+            let position = app.world().get::<Transform>(sheep).unwrap().translation;
+            assert!(position.y >= SURFACE - 0.01, "fell to {position}");
+            let state = app.world().get::<MobBehaviour>(sheep).unwrap().state;
+            wandered |= state == MobState::Wander;
+
+            // Goals a walk away, which the sheep has to actually get to.
+            if let Some(path) = app.world().get::<NavPath>(sheep)
+                && let Some(goal) = path.path.waypoints().last()
+                && goal.xz().distance(start.xz()) > 1.0
+                && !goals.contains(&goal)
+            {
+                goals.push(goal);
+            }
+            farthest = farthest.max(position.xz().distance(start.xz()));
+            for goal in &goals {
+                closest_to_goal = closest_to_goal.min(position.xz().distance(goal.xz()));
+            }
+        }
+        assert!(wandered);
+        assert!(!goals.is_empty());
+        assert!(farthest > 1.0, "stayed within {farthest} of the start");
+        assert!(
+            closest_to_goal < 0.5,
+            "came within {closest_to_goal} of {goals:?}"
+        );
+        assert!(
+            app.world()
+                .get::<CharacterController>(sheep)
+                .unwrap()
+                .grounded
+        );
+    }
+
+    #[test]
+    fn unknown_kind_spawns_nothing() {
+        let mut app = flat_app();
+        assert_eq!(spawn(&mut app, 200, Vec3::new(16.5, SURFACE, 16.5)), None);
+        let mut mobs = app.world_mut().query::<&Mob>();
+        assert_eq!(mobs.iter(app.world()).count(), 0);
+    }
+
+    #[test]
+    fn seed_depends_on_position_only() {
+        let mut app = flat_app();
+        let position = Vec3::new(8.5, SURFACE, 8.5);
+        let first = spawn(&mut app, MOB_SHEEP, position).unwrap();
+        let second = spawn(&mut app, MOB_SHEEP, position).unwrap();
+        let elsewhere = spawn(&mut app, MOB_SHEEP, position + Vec3::X).unwrap();
+        let mut rng = |entity| {
+            let mut behaviour = app.world_mut().get_mut::<MobBehaviour>(entity).unwrap();
+            behaviour.rng.next_u64()
+        };
+        let (first, second, elsewhere) = (rng(first), rng(second), rng(elsewhere));
+        assert_eq!(first, second);
+        assert_ne!(first, elsewhere);
+    }
+
+    /// Stone up to y = 20, hollowed out from y = 5 to 8 under all of it if `cave`.
+    fn stone_chunk(cave: bool) -> Chunk {
+        let mut chunk = Chunk::new();
+        for x in 0..32 {
+            for z in 0..32 {
+                for y in 0..20 {
+                    if !(cave && (5..=8).contains(&y)) {
+                        chunk.set(x, y, z, Voxel::new(BLOCK_STONE));
+                    }
+                }
+            }
+        }
+        chunk
+    }
+
+    /// Mobs spawned around a viewer above `chunk`, lit by [`LightingPlugin`] if `lit`.
+    fn spawned_mobs(chunk: Chunk, lit: bool) -> Vec<(MobKind, Vec3)> {
+        let mut app = app_with(chunk);
+        if lit {
+            app.add_plugins(LightingPlugin);
+        }
+        app.insert_resource(MobSpawnSettings {
+            interval: 0.0,
+            attempts: 16,
+            min_distance: 0,
+            max_distance: 12,
+            ..default()
+        });
+        app.world_mut().spawn((
+            ChunkViewer,
+            GlobalTransform::from_translation(Vec3::new(16.0, 21.0, 16.0)),
+        ));
+        for _ in 0..10 {
+            app.update();
+        }
+        let mut mobs = app.world_mut().query::<(&Mob, &Transform)>();
+        mobs.iter(app.world())
+            .map(|(mob, transform)| (mob.kind, transform.translation))
+            .collect()
+    }
+
+    #[test]
+    fn crawlers_spawn_in_dark_caves_only() {
+        let in_cave = spawned_mobs(stone_chunk(true), true);
+        assert!(!in_cave.is_empty());
+        for (kind, position) in in_cave {
+            assert_eq!(kind, MOB_CAVE_CRAWLER);
+            assert!((5.0..6.0).contains(&position.y), "spawned at {position}");
+        }
+
+        // The top of the stone is in daylight.
+        assert_eq!(spawned_mobs(stone_chunk(false), true), vec![]);
+        // And until it is lit, nothing is known to be dark.
+        assert_eq!(spawned_mobs(stone_chunk(true), false), vec![]);
+    }
+}
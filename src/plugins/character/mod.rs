@@ -1,10 +1,37 @@
+pub mod controller;
+pub mod mobs;
+
 use bevy::prelude::*;
 
-#[allow(unused)]
+use controller::{CharacterControllerSettings, move_characters};
+use mobs::{
+    MobRegistryRes, MobSpawnSettings, MobSpawner, despawn_distant_mobs, on_mob_path_failed,
+    spawn_mobs, steer_mobs, update_mob_behaviour,
+};
+
+use crate::state::LoadingState;
+
+/// Characters that walk the voxel world: the controller that moves them and the mobs
+/// built on it.
 pub struct CharacterPlugin;
 
 impl Plugin for CharacterPlugin {
-    fn build(&self, _app: &mut App) {
-        todo!()
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CharacterControllerSettings>()
+            .init_resource::<MobRegistryRes>()
+            .init_resource::<MobSpawnSettings>()
+            .init_resource::<MobSpawner>()
+            .add_observer(on_mob_path_failed)
+            .add_systems(
+                Update,
+                (
+                    (spawn_mobs, despawn_distant_mobs),
+                    update_mob_behaviour,
+                    steer_mobs,
+                    move_characters,
+                )
+                    .chain()
+                    .run_if(in_state(LoadingState::Initialized)),
+            );
     }
 }
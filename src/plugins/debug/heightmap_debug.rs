@@ -0,0 +1,84 @@
+use bevy::prelude::*;
+
+use crate::plugins::world::heightmap::{HeightmapKind, Heightmaps};
+
+/// Draws one of the [`Heightmaps`] around the camera as a grid of lines over the top of
+/// each voxel column. F5 cycles through the kinds and off.
+pub struct HeightmapDebugPlugin;
+
+impl Plugin for HeightmapDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HeightmapDebugOverlay>()
+            .add_systems(Update, (toggle_heightmap_overlay, draw_heightmap_overlay));
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct HeightmapDebugOverlay {
+    /// The heightmap drawn, or `None` when the overlay is off.
+    pub kind: Option<HeightmapKind>,
+    /// Half the width of the drawn grid, in voxels.
+    pub radius: i32,
+}
+
+impl Default for HeightmapDebugOverlay {
+    fn default() -> Self {
+        Self {
+            kind: None,
+            radius: 24,
+        }
+    }
+}
+
+fn toggle_heightmap_overlay(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut overlay: ResMut<HeightmapDebugOverlay>,
+) {
+    if keys.just_pressed(KeyCode::F5) {
+        overlay.kind = match overlay.kind {
+            None => Some(HeightmapKind::Solid),
+            Some(HeightmapKind::Solid) => Some(HeightmapKind::NonFluid),
+            Some(HeightmapKind::NonFluid) => Some(HeightmapKind::MotionBlocking),
+            Some(HeightmapKind::MotionBlocking) => None,
+        };
+        info!("Heightmap overlay: {:?}", overlay.kind);
+    }
+}
+
+fn draw_heightmap_overlay(
+    mut gizmos: Gizmos,
+    overlay: Res<HeightmapDebugOverlay>,
+    heightmaps: Res<Heightmaps>,
+    camera_q: Query<&GlobalTransform, With<Camera3d>>,
+) {
+    let Some(kind) = overlay.kind else {
+        return;
+    };
+    let Ok(camera) = camera_q.single() else {
+        return;
+    };
+    let color = match kind {
+        HeightmapKind::Solid => Color::srgb(0.2, 1.0, 0.2),
+        HeightmapKind::NonFluid => Color::srgb(1.0, 0.8, 0.2),
+        HeightmapKind::MotionBlocking => Color::srgb(0.2, 0.6, 1.0),
+    };
+
+    // Top face centre of the highest voxel, slightly raised so it isn't hidden by it.
+    let surface = |x: i32, z: i32| {
+        heightmaps
+            .top(kind, x, z)
+            .map(|y| Vec3::new(x as f32 + 0.5, y as f32 + 1.02, z as f32 + 0.5))
+    };
+
+    let centre = camera.translation().floor().as_ivec3();
+    for z in centre.z - overlay.radius..=centre.z + overlay.radius {
+        for x in centre.x - overlay.radius..=centre.x + overlay.radius {
+            let Some(point) = surface(x, z) else {
+                continue;
+            };
+            for neighbour in [surface(x + 1, z), surface(x, z + 1)].into_iter().flatten() {
+                gizmos.line(point, neighbour, color);
+            }
+        }
+    }
+}
@@ -1 +1,2 @@
+pub mod heightmap_debug;
 pub mod mesh_debug;
@@ -4,4 +4,10 @@ pub mod debug;
 pub mod player;
 pub mod world;
 
-pub use {asset_loader::AssetLoaderPlugin, debug::mesh_debug::MeshDebugPlugin, world::WorldPlugin};
+pub use {
+    asset_loader::AssetLoaderPlugin,
+    character::CharacterPlugin,
+    debug::{heightmap_debug::HeightmapDebugPlugin, mesh_debug::MeshDebugPlugin},
+    player::hotbar_ui::HotbarUiPlugin,
+    world::WorldPlugin,
+};
@@ -0,0 +1,181 @@
+use bevy::prelude::*;
+
+use crate::plugins::{
+    asset_loader::assets::{ATLAS_ROWS, ATLAS_TILE_SIZE, GameAssets, atlas_cell},
+    player::{
+        PlayerCollider,
+        inventory::{HOTBAR_SLOTS, Inventory},
+    },
+    world::{
+        blocks::TileId,
+        items::{ItemIcon, ItemRegistryRes},
+    },
+};
+use crate::state::LoadingState;
+
+const SLOT_SIZE: f32 = 52.0;
+
+const SLOT_BORDER: Color = Color::srgba(0.2, 0.2, 0.2, 0.9);
+const SELECTED_BORDER: Color = Color::WHITE;
+
+/// Shows the player's hotbar along the bottom of the screen.
+pub struct HotbarUiPlugin;
+
+impl Plugin for HotbarUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(LoadingState::Initialized), spawn_hotbar)
+            .add_systems(
+                Update,
+                update_hotbar.run_if(in_state(LoadingState::Initialized)),
+            );
+    }
+}
+
+/// The block atlas cut into tiles for item icons.
+#[derive(Resource)]
+struct HotbarAtlas {
+    image: Handle<Image>,
+    layout: Handle<TextureAtlasLayout>,
+    columns: u32,
+}
+
+impl HotbarAtlas {
+    /// Layout index of `tile`; the layout counts along each row in turn.
+    fn index(&self, tile: TileId) -> usize {
+        let cell = atlas_cell(tile);
+        (cell.y * self.columns + cell.x) as usize
+    }
+}
+
+#[derive(Component)]
+struct HotbarSlot(usize);
+
+#[derive(Component)]
+struct HotbarIcon(usize);
+
+#[derive(Component)]
+struct HotbarCount(usize);
+
+fn spawn_hotbar(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    images: Res<Assets<Image>>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
+    let columns = images
+        .get(&assets.block_atlas)
+        .map_or(1, |image| image.width() / ATLAS_TILE_SIZE);
+    let layout = TextureAtlasLayout::from_grid(
+        UVec2::splat(ATLAS_TILE_SIZE),
+        columns,
+        ATLAS_ROWS,
+        None,
+        None,
+    );
+    commands.insert_resource(HotbarAtlas {
+        image: assets.block_atlas.clone(),
+        layout: layouts.add(layout),
+        columns,
+    });
+
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            column_gap: Val::Px(4.0),
+            ..default()
+        })
+        .with_children(|bar| {
+            for slot in 0..HOTBAR_SLOTS {
+                bar.spawn((
+                    HotbarSlot(slot),
+                    Node {
+                        width: Val::Px(SLOT_SIZE),
+                        height: Val::Px(SLOT_SIZE),
+                        border: UiRect::all(Val::Px(3.0)),
+                        padding: UiRect::all(Val::Px(5.0)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+                    BorderColor::all(SLOT_BORDER),
+                ))
+                .with_children(|cell| {
+                    cell.spawn((
+                        HotbarIcon(slot),
+                        ImageNode::default(),
+                        Node {
+                            width: Val::Percent(100.0),
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        Visibility::Hidden,
+                    ));
+                    cell.spawn((
+                        HotbarCount(slot),
+                        Text::default(),
+                        TextFont {
+                            font_size: 14.0,
+                            ..default()
+                        },
+                        TextColor(Color::WHITE),
+                        Node {
+                            position_type: PositionType::Absolute,
+                            right: Val::Px(3.0),
+                            bottom: Val::Px(1.0),
+                            ..default()
+                        },
+                    ));
+                });
+            }
+        });
+}
+
+fn update_hotbar(
+    inventories: Query<&Inventory, (With<PlayerCollider>, Changed<Inventory>)>,
+    registry: Res<ItemRegistryRes>,
+    atlas: Res<HotbarAtlas>,
+    mut slots: Query<(&HotbarSlot, &mut BorderColor)>,
+    mut icons: Query<(&HotbarIcon, &mut ImageNode, &mut Visibility)>,
+    mut counts: Query<(&HotbarCount, &mut Text)>,
+) {
+    let Ok(inventory) = inventories.single() else {
+        return;
+    };
+
+    for (slot, mut border) in &mut slots {
+        let color = if slot.0 == inventory.selected {
+            SELECTED_BORDER
+        } else {
+            SLOT_BORDER
+        };
+        *border = BorderColor::all(color);
+    }
+
+    for (icon, mut image, mut visibility) in &mut icons {
+        let Some(info) = inventory.slots[icon.0].and_then(|stack| registry.0.get(stack.item))
+        else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        *image = match info.icon {
+            ItemIcon::Tile(tile) => ImageNode::from_atlas_image(
+                atlas.image.clone(),
+                TextureAtlas {
+                    layout: atlas.layout.clone(),
+                    index: atlas.index(tile),
+                },
+            ),
+            ItemIcon::Color(color) => ImageNode::solid_color(color),
+        };
+        *visibility = Visibility::Inherited;
+    }
+
+    for (count, mut text) in &mut counts {
+        text.0 = match inventory.slots[count.0] {
+            Some(stack) if stack.count > 1 => stack.count.to_string(),
+            _ => String::new(),
+        };
+    }
+}
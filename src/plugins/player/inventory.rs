@@ -0,0 +1,258 @@
+use bevy::prelude::*;
+
+use crate::plugins::world::items::{ItemId, ItemRegistry, ItemStack};
+
+/// Slots at the start of an [`Inventory`] shown in the hotbar.
+pub const HOTBAR_SLOTS: usize = 9;
+pub const INVENTORY_SLOTS: usize = 36;
+
+/// Items carried by a player. The first [`HOTBAR_SLOTS`] slots form the hotbar, and
+/// `selected` indexes into them.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct Inventory {
+    pub slots: Vec<Option<ItemStack>>,
+    pub selected: usize,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::new(INVENTORY_SLOTS)
+    }
+}
+
+impl Inventory {
+    /// An empty inventory of `size` slots, at least the hotbar.
+    pub fn new(size: usize) -> Self {
+        Self {
+            slots: vec![None; size.max(HOTBAR_SLOTS)],
+            selected: 0,
+        }
+    }
+
+    pub fn hotbar(&self) -> &[Option<ItemStack>] {
+        &self.slots[..HOTBAR_SLOTS]
+    }
+
+    pub fn selected_stack(&self) -> Option<ItemStack> {
+        self.slots[self.selected]
+    }
+
+    pub fn select(&mut self, slot: usize) {
+        if slot < HOTBAR_SLOTS {
+            self.selected = slot;
+        }
+    }
+
+    /// Select the hotbar slot holding `item`, or swap it into the selected slot from the
+    /// rest of the inventory. Returns whether `item` is carried at all.
+    pub fn pick(&mut self, item: ItemId) -> bool {
+        match self.find(item) {
+            Some(slot) if slot < HOTBAR_SLOTS => self.selected = slot,
+            Some(slot) => self.slots.swap(slot, self.selected),
+            None => return false,
+        }
+        true
+    }
+
+    /// First slot holding `item`.
+    pub fn find(&self, item: ItemId) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|slot| slot.is_some_and(|stack| stack.item == item))
+    }
+
+    /// How many of `item` are carried in total.
+    pub fn count(&self, item: ItemId) -> u32 {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|stack| stack.item == item)
+            .map(|stack| stack.count)
+            .sum()
+    }
+
+    /// Add `stack`, topping up stacks of the same item before filling empty slots, both in
+    /// slot order. Returns what didn't fit.
+    pub fn insert(&mut self, stack: ItemStack, registry: &ItemRegistry) -> Option<ItemStack> {
+        let max_stack = registry.max_stack(stack.item);
+        let mut left = Some(stack).filter(|stack| !stack.is_empty());
+
+        for slot in self.slots.iter_mut().flatten() {
+            left = slot.merge(left?, max_stack);
+        }
+        for slot in self.slots.iter_mut().filter(|slot| slot.is_none()) {
+            let mut stack = left?;
+            *slot = stack.split(max_stack);
+            left = Some(stack).filter(|stack| !stack.is_empty());
+        }
+        left
+    }
+
+    /// Take up to `count` items out of `slot`, emptying it if none are left.
+    pub fn take(&mut self, slot: usize, count: u32) -> Option<ItemStack> {
+        let stack = self.slots.get_mut(slot)?.as_mut()?;
+        let taken = stack.split(count);
+        if stack.is_empty() {
+            self.slots[slot] = None;
+        }
+        taken
+    }
+
+    /// Take all of `stack` out of the inventory, from the last slots first. Returns `false`
+    /// and takes nothing if there aren't that many.
+    pub fn remove(&mut self, stack: ItemStack) -> bool {
+        if self.count(stack.item) < stack.count {
+            return false;
+        }
+        let mut left = stack.count;
+        for slot in (0..self.slots.len()).rev() {
+            if left == 0 {
+                break;
+            }
+            if self.slots[slot].is_some_and(|held| held.item == stack.item) {
+                left -= self.take(slot, left).map_or(0, |taken| taken.count);
+            }
+        }
+        true
+    }
+
+    /// Take the larger half of the stack in `slot`.
+    pub fn split(&mut self, slot: usize) -> Option<ItemStack> {
+        let count = self.slots.get(slot)?.as_ref()?.count;
+        self.take(slot, count.div_ceil(2))
+    }
+
+    /// Move the stack in `from` onto `to`: merged as far as it fits if both hold the same
+    /// item, swapped otherwise.
+    pub fn move_stack(&mut self, from: usize, to: usize, registry: &ItemRegistry) {
+        if from == to || from >= self.slots.len() || to >= self.slots.len() {
+            return;
+        }
+        match (self.slots[from], self.slots[to]) {
+            (Some(moving), Some(mut target)) if moving.item == target.item => {
+                self.slots[from] = target.merge(moving, registry.max_stack(moving.item));
+                self.slots[to] = Some(target);
+            }
+            _ => self.slots.swap(from, to),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::world::{
+        blocks::{BLOCK_DIRT, BLOCK_LOG, BLOCK_SAND, BLOCK_STONE},
+        items::{ITEM_COAL, ItemRegistryRes},
+    };
+
+    fn stack(item: ItemId, count: u32) -> Option<ItemStack> {
+        Some(ItemStack::new(item, count))
+    }
+
+    #[test]
+    fn insert_tops_up_before_filling_empty_slots() {
+        let registry = ItemRegistryRes::default().0;
+        let mut inventory = Inventory::default();
+        assert_eq!(
+            inventory.insert(ItemStack::new(BLOCK_STONE, 100), &registry),
+            None
+        );
+        assert_eq!(inventory.slots[0], stack(BLOCK_STONE, 64));
+        assert_eq!(inventory.slots[1], stack(BLOCK_STONE, 36));
+
+        assert_eq!(
+            inventory.insert(ItemStack::new(ITEM_COAL, 1), &registry),
+            None
+        );
+        assert_eq!(inventory.slots[2], stack(ITEM_COAL, 1));
+        assert_eq!(
+            inventory.insert(ItemStack::new(BLOCK_STONE, 30), &registry),
+            None
+        );
+        assert_eq!(inventory.slots[1], stack(BLOCK_STONE, 64));
+        assert_eq!(inventory.slots[3], stack(BLOCK_STONE, 2));
+        assert_eq!(inventory.count(BLOCK_STONE), 130);
+    }
+
+    #[test]
+    fn insert_returns_what_does_not_fit() {
+        let registry = ItemRegistryRes::default().0;
+        let mut inventory = Inventory::new(HOTBAR_SLOTS);
+        assert_eq!(
+            inventory.insert(ItemStack::new(BLOCK_DIRT, 64 * 9 + 5), &registry),
+            stack(BLOCK_DIRT, 5)
+        );
+        assert_eq!(
+            inventory.insert(ItemStack::new(BLOCK_SAND, 1), &registry),
+            stack(BLOCK_SAND, 1)
+        );
+        assert_eq!(
+            inventory.insert(ItemStack::new(BLOCK_DIRT, 0), &registry),
+            None
+        );
+    }
+
+    #[test]
+    fn take_and_split_empty_the_slot() {
+        let mut inventory = Inventory::default();
+        inventory.slots[0] = stack(BLOCK_STONE, 3);
+        assert_eq!(inventory.split(0), stack(BLOCK_STONE, 2));
+        assert_eq!(inventory.split(0), stack(BLOCK_STONE, 1));
+        assert_eq!(inventory.slots[0], None);
+        assert_eq!(inventory.split(0), None);
+
+        inventory.slots[1] = stack(ITEM_COAL, 2);
+        assert_eq!(inventory.take(1, 5), stack(ITEM_COAL, 2));
+        assert_eq!(inventory.slots[1], None);
+        assert_eq!(inventory.take(INVENTORY_SLOTS, 1), None);
+    }
+
+    #[test]
+    fn move_stack_merges_or_swaps() {
+        let registry = ItemRegistryRes::default().0;
+        let mut inventory = Inventory::default();
+        inventory.slots[0] = stack(BLOCK_STONE, 40);
+        inventory.slots[1] = stack(BLOCK_STONE, 30);
+        inventory.slots[2] = stack(ITEM_COAL, 1);
+
+        inventory.move_stack(1, 0, &registry);
+        assert_eq!(inventory.slots[0], stack(BLOCK_STONE, 64));
+        assert_eq!(inventory.slots[1], stack(BLOCK_STONE, 6));
+
+        inventory.move_stack(2, 1, &registry);
+        assert_eq!(inventory.slots[1], stack(ITEM_COAL, 1));
+        assert_eq!(inventory.slots[2], stack(BLOCK_STONE, 6));
+
+        inventory.move_stack(2, 5, &registry);
+        assert_eq!(inventory.slots[2], None);
+        assert_eq!(inventory.slots[5], stack(BLOCK_STONE, 6));
+    }
+
+    #[test]
+    fn remove_takes_all_or_nothing() {
+        let mut inventory = Inventory::default();
+        inventory.slots[0] = stack(BLOCK_STONE, 4);
+        inventory.slots[7] = stack(BLOCK_STONE, 2);
+        assert!(!inventory.remove(ItemStack::new(BLOCK_STONE, 7)));
+        assert_eq!(inventory.count(BLOCK_STONE), 6);
+
+        assert!(inventory.remove(ItemStack::new(BLOCK_STONE, 3)));
+        assert_eq!(inventory.slots[0], stack(BLOCK_STONE, 3));
+        assert_eq!(inventory.slots[7], None);
+    }
+
+    #[test]
+    fn pick_selects_or_swaps_into_the_hotbar() {
+        let mut inventory = Inventory::default();
+        inventory.slots[20] = stack(BLOCK_LOG, 4);
+        inventory.select(2);
+        assert!(inventory.pick(BLOCK_LOG));
+        assert_eq!(inventory.selected_stack(), stack(BLOCK_LOG, 4));
+
+        inventory.select(0);
+        assert!(inventory.pick(BLOCK_LOG));
+        assert_eq!(inventory.selected, 2);
+        assert!(!inventory.pick(BLOCK_SAND));
+    }
+}
@@ -1 +1,26 @@
+pub mod hotbar_ui;
+pub mod inventory;
 
+use bevy::prelude::*;
+
+/// Axis-aligned box around the player, used to keep voxel edits from entombing them.
+#[derive(Component, Copy, Clone, Debug)]
+pub struct PlayerCollider {
+    pub half_extents: Vec3,
+    /// Offset of the box center from the entity's translation.
+    pub offset: Vec3,
+}
+
+impl PlayerCollider {
+    /// Whether the unit voxel at `voxel_world` overlaps this collider at `translation`.
+    pub fn intersects_voxel(&self, translation: Vec3, voxel_world: IVec3) -> bool {
+        let center = translation + self.offset;
+        let min = center - self.half_extents;
+        let max = center + self.half_extents;
+
+        let voxel_min = voxel_world.as_vec3();
+        let voxel_max = voxel_min + Vec3::ONE;
+
+        min.cmplt(voxel_max).all() && max.cmpgt(voxel_min).all()
+    }
+}
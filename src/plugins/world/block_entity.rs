@@ -0,0 +1,318 @@
+use bevy::ecs::{lifecycle::HookContext, world::DeferredWorld};
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::plugins::world::{
+    ChunkEntityMap, blocks::BlockId, chunk::world_to_chunk_local, events::VoxelChanged,
+};
+use crate::state::LoadingState;
+
+pub struct BlockEntityPlugin;
+
+impl Plugin for BlockEntityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BlockEntities>()
+            .init_resource::<BlockEntityTypes>()
+            .add_systems(
+                Update,
+                sync_block_entities.run_if(in_state(LoadingState::Initialized)),
+            );
+    }
+}
+
+/// Data a block entity carries. Saved with its chunk as RON.
+pub trait BlockEntityData: Component + Default + Serialize + DeserializeOwned {}
+
+impl<T: Component + Default + Serialize + DeserializeOwned> BlockEntityData for T {}
+
+/// Marks the entity owned by the voxel at `world`.
+///
+/// Block entities are children of their chunk's entity, positioned at the voxel's min
+/// corner, so they are despawned with the chunk.
+#[derive(Component, Copy, Clone, Debug, Eq, PartialEq)]
+#[component(on_add = on_add_block_entity, on_remove = on_remove_block_entity)]
+pub struct BlockEntity {
+    pub world: IVec3,
+    pub block_id: BlockId,
+}
+
+fn on_add_block_entity(mut world: DeferredWorld, context: HookContext) {
+    let block_entity = *world.get::<BlockEntity>(context.entity).unwrap();
+    world
+        .resource_mut::<BlockEntities>()
+        .entities
+        .insert(block_entity.world, context.entity);
+}
+
+fn on_remove_block_entity(mut world: DeferredWorld, context: HookContext) {
+    let block_entity = *world.get::<BlockEntity>(context.entity).unwrap();
+    let mut block_entities = world.resource_mut::<BlockEntities>();
+    if block_entities.entities.get(&block_entity.world) == Some(&context.entity) {
+        block_entities.entities.remove(&block_entity.world);
+    }
+}
+
+/// Index of loaded block entities by world voxel coordinate.
+#[derive(Resource, Debug, Default)]
+pub struct BlockEntities {
+    entities: HashMap<IVec3, Entity>,
+}
+
+impl BlockEntities {
+    pub fn get(&self, world: IVec3) -> Option<Entity> {
+        self.entities.get(&world).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (IVec3, Entity)> + '_ {
+        self.entities
+            .iter()
+            .map(|(world, entity)| (*world, *entity))
+    }
+
+    /// Block entities inside the chunk at `chunk_coord`.
+    pub fn in_chunk(&self, chunk_coord: IVec3) -> impl Iterator<Item = (IVec3, Entity)> + '_ {
+        self.iter()
+            .filter(move |(world, _)| world_to_chunk_local(*world).0 == chunk_coord)
+    }
+}
+
+/// How to create, save and restore the data of one block's block entities.
+#[derive(Clone, Copy)]
+pub struct BlockEntityType {
+    insert_default: fn(&mut EntityWorldMut),
+    save: fn(EntityRef) -> Option<String>,
+    load: fn(&mut EntityWorldMut, &str) -> bool,
+}
+
+impl BlockEntityType {
+    pub fn of<T: BlockEntityData>() -> Self {
+        Self {
+            insert_default: |entity| {
+                entity.insert(T::default());
+            },
+            save: |entity| {
+                let data = entity.get::<T>()?;
+                ron::to_string(data)
+                    .inspect_err(|err| warn!("failed to save block entity: {err}"))
+                    .ok()
+            },
+            load: |entity, data| match ron::from_str::<T>(data) {
+                Ok(data) => {
+                    entity.insert(data);
+                    true
+                }
+                Err(err) => {
+                    warn!("failed to load block entity, using defaults: {err}");
+                    entity.insert(T::default());
+                    false
+                }
+            },
+        }
+    }
+
+    pub fn save(&self, entity: EntityRef) -> Option<String> {
+        (self.save)(entity)
+    }
+}
+
+/// Blocks that own a block entity, see [`RegisterBlockEntityExt`].
+#[derive(Resource, Default)]
+pub struct BlockEntityTypes {
+    types: HashMap<BlockId, BlockEntityType>,
+}
+
+impl BlockEntityTypes {
+    pub fn get(&self, block_id: BlockId) -> Option<BlockEntityType> {
+        self.types.get(&block_id).copied()
+    }
+
+    pub fn contains(&self, block_id: BlockId) -> bool {
+        self.types.contains_key(&block_id)
+    }
+
+    pub fn insert(&mut self, block_id: BlockId, block_entity_type: BlockEntityType) {
+        self.types.insert(block_id, block_entity_type);
+    }
+}
+
+pub trait RegisterBlockEntityExt {
+    /// Give every `block_id` voxel a block entity carrying `T`.
+    fn register_block_entity<T: BlockEntityData>(&mut self, block_id: BlockId) -> &mut Self;
+}
+
+impl RegisterBlockEntityExt for App {
+    fn register_block_entity<T: BlockEntityData>(&mut self, block_id: BlockId) -> &mut Self {
+        self.init_resource::<BlockEntityTypes>();
+        self.world_mut()
+            .resource_mut::<BlockEntityTypes>()
+            .insert(block_id, BlockEntityType::of::<T>());
+        self
+    }
+}
+
+/// Spawn the block entity for a `block_id` voxel at `world`, with its data restored from
+/// `data` or defaulted. Returns `None` if the block has no block entity type or the chunk
+/// isn't loaded. Replaces any block entity already at `world`.
+pub fn spawn_block_entity(
+    world: &mut World,
+    position: IVec3,
+    block_id: BlockId,
+    data: Option<&str>,
+) -> Option<Entity> {
+    let block_entity_type = world.resource::<BlockEntityTypes>().get(block_id)?;
+    let (chunk_coord, local) = world_to_chunk_local(position);
+    let chunk_entity = world.resource::<ChunkEntityMap>().get(&chunk_coord)?;
+
+    despawn_block_entity(world, position);
+
+    let mut entity = world.spawn((
+        BlockEntity {
+            world: position,
+            block_id,
+        },
+        Transform::from_translation(local.as_vec3()),
+        ChildOf(chunk_entity),
+    ));
+    match data {
+        Some(data) => {
+            (block_entity_type.load)(&mut entity, data);
+        }
+        None => (block_entity_type.insert_default)(&mut entity),
+    }
+    Some(entity.id())
+}
+
+/// Despawn the block entity at `world`, if any.
+pub fn despawn_block_entity(world: &mut World, position: IVec3) -> bool {
+    let Some(entity) = world.resource::<BlockEntities>().get(position) else {
+        return false;
+    };
+    world.despawn(entity)
+}
+
+/// Create and destroy block entities as voxels change through [`VoxelWorld`](super::voxel_world::VoxelWorld).
+fn sync_block_entities(
+    mut commands: Commands,
+    mut changes: MessageReader<VoxelChanged>,
+    types: Res<BlockEntityTypes>,
+) {
+    for change in changes.read() {
+        let (previous, new) = (change.previous.block_id(), change.new.block_id());
+        if previous == new {
+            continue;
+        }
+
+        let position = change.world;
+        if types.contains(previous) {
+            commands.queue(move |world: &mut World| {
+                despawn_block_entity(world, position);
+            });
+        }
+        if types.contains(new) {
+            commands.queue(move |world: &mut World| {
+                spawn_block_entity(world, position, new, None);
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use bevy::ecs::system::RunSystemOnce;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::plugins::world::{
+        blocks::{BLOCK_LOG, BLOCK_STONE},
+        chunk::{CHUNK_SIZE, Chunk},
+        persistence::{ChunkPersistence, load_chunk, unload_chunk},
+        test_support::{headless_app, spawn_chunks},
+        voxel::Voxel,
+        voxel_world::VoxelWorld,
+    };
+
+    #[derive(Component, Default, Serialize, Deserialize, Debug, PartialEq)]
+    struct Sign {
+        text: String,
+    }
+
+    const CHUNK: IVec3 = IVec3::new(-1, 0, 2);
+
+    /// Logs carry a [`Sign`], in one empty chunk at [`CHUNK`] saved under a fresh
+    /// directory.
+    fn block_entity_app(name: &str) -> (App, PathBuf) {
+        let directory = std::env::temp_dir().join(format!("aettesaga-block-entity-{name}"));
+        let _ = std::fs::remove_dir_all(&directory);
+
+        let mut app = headless_app(0);
+        app.insert_resource(ChunkPersistence {
+            directory: directory.clone(),
+        })
+        .add_plugins(BlockEntityPlugin)
+        .register_block_entity::<Sign>(BLOCK_LOG);
+        spawn_chunks(app.world_mut(), [(CHUNK, Chunk::new())]);
+        (app, directory)
+    }
+
+    fn set(app: &mut App, position: IVec3, voxel: Voxel) {
+        app.world_mut()
+            .run_system_once(move |mut voxel_world: VoxelWorld| {
+                voxel_world.set(position, voxel);
+            })
+            .unwrap();
+        app.update();
+    }
+
+    fn block_entity(app: &App, position: IVec3) -> Option<Entity> {
+        app.world().resource::<BlockEntities>().get(position)
+    }
+
+    #[test]
+    fn voxel_changes_spawn_and_despawn_block_entities() {
+        let (mut app, _) = block_entity_app("sync");
+        let position = CHUNK * CHUNK_SIZE as i32 + IVec3::new(1, 2, 3);
+
+        set(&mut app, position, Voxel::new(BLOCK_LOG));
+        let entity = block_entity(&app, position).expect("placing a log spawns its sign");
+        let chunk = app.world().resource::<ChunkEntityMap>().get(&CHUNK);
+        assert_eq!(
+            app.world().get::<ChildOf>(entity).map(ChildOf::parent),
+            chunk
+        );
+        assert_eq!(app.world().get::<Sign>(entity), Some(&Sign::default()));
+
+        // Changing only the state keeps it.
+        set(&mut app, position, Voxel::with_state(BLOCK_LOG, 1));
+        assert_eq!(block_entity(&app, position), Some(entity));
+
+        set(&mut app, position, Voxel::new(BLOCK_STONE));
+        assert_eq!(block_entity(&app, position), None);
+        assert!(app.world().get_entity(entity).is_err());
+    }
+
+    #[test]
+    fn block_entity_data_survives_unloading() {
+        let (mut app, directory) = block_entity_app("unload");
+        let position = CHUNK * CHUNK_SIZE as i32 + IVec3::new(1, 2, 3);
+        set(&mut app, position, Voxel::new(BLOCK_LOG));
+        let entity = block_entity(&app, position).unwrap();
+        app.world_mut().get_mut::<Sign>(entity).unwrap().text = "hello".into();
+
+        let world = app.world_mut();
+        assert!(unload_chunk(world, CHUNK).unwrap());
+        assert!(world.resource::<ChunkEntityMap>().get(&CHUNK).is_none());
+        assert_eq!(world.resource::<BlockEntities>().get(position), None);
+        assert!(world.get_entity(entity).is_err());
+
+        assert!(load_chunk(world, CHUNK).unwrap());
+        world.flush();
+        let loaded = world.resource::<BlockEntities>().get(position).unwrap();
+        assert_eq!(world.get::<Sign>(loaded).unwrap().text, "hello");
+        let chunk = world.resource::<ChunkEntityMap>().get(&CHUNK);
+        assert_eq!(world.get::<ChildOf>(loaded).map(ChildOf::parent), chunk);
+        let _ = std::fs::remove_dir_all(directory);
+    }
+}
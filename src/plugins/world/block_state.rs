@@ -0,0 +1,404 @@
+use core::fmt;
+use core::marker::PhantomData;
+
+use crate::plugins::world::voxel::Voxel;
+
+/// Values a block state property can take.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PropertyValues {
+    /// Inclusive integer range.
+    Int {
+        min: u16,
+        max: u16,
+    },
+    Bool,
+    /// Named variants, indexed in declaration order.
+    Enum(&'static [&'static str]),
+}
+
+impl PropertyValues {
+    /// Number of values, `0` for an empty range or enum.
+    pub fn cardinality(&self) -> u32 {
+        match *self {
+            PropertyValues::Int { min, max } => (max as u32 + 1).saturating_sub(min as u32),
+            PropertyValues::Bool => 2,
+            PropertyValues::Enum(names) => u32::try_from(names.len()).unwrap_or(u32::MAX),
+        }
+    }
+
+    /// Human readable value at `index`, for debugging and tooling.
+    pub fn label(&self, index: u16) -> String {
+        match *self {
+            PropertyValues::Int { min, .. } => (min + index).to_string(),
+            PropertyValues::Bool => (index != 0).to_string(),
+            PropertyValues::Enum(names) => names[index as usize].to_string(),
+        }
+    }
+}
+
+/// Untyped description of a property, as stored in a [`BlockStateLayout`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PropertyDescriptor {
+    pub name: &'static str,
+    pub values: PropertyValues,
+}
+
+/// A typed handle to a block state property.
+///
+/// Properties are matched by name and value count, so the same handle (e.g.
+/// [`FLUID_LEVEL`](super::fluids::FLUID_LEVEL)) works for every block that declares it.
+pub trait Property {
+    type Value;
+
+    fn descriptor(&self) -> PropertyDescriptor;
+
+    /// Index of `value` among the property's values, or `None` if it's out of range.
+    fn index_of(&self, value: Self::Value) -> Option<u16>;
+
+    fn value_at(&self, index: u16) -> Self::Value;
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct IntProperty {
+    pub name: &'static str,
+    pub min: u16,
+    pub max: u16,
+}
+
+impl IntProperty {
+    pub const fn new(name: &'static str, min: u16, max: u16) -> Self {
+        assert!(
+            min <= max && max - min < u16::MAX,
+            "property range must hold 1..=65535 values"
+        );
+        Self { name, min, max }
+    }
+}
+
+impl Property for IntProperty {
+    type Value = u16;
+
+    fn descriptor(&self) -> PropertyDescriptor {
+        PropertyDescriptor {
+            name: self.name,
+            values: PropertyValues::Int {
+                min: self.min,
+                max: self.max,
+            },
+        }
+    }
+
+    fn index_of(&self, value: u16) -> Option<u16> {
+        (self.min..=self.max)
+            .contains(&value)
+            .then(|| value - self.min)
+    }
+
+    fn value_at(&self, index: u16) -> u16 {
+        self.min + index
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BoolProperty {
+    pub name: &'static str,
+}
+
+impl BoolProperty {
+    pub const fn new(name: &'static str) -> Self {
+        Self { name }
+    }
+}
+
+impl Property for BoolProperty {
+    type Value = bool;
+
+    fn descriptor(&self) -> PropertyDescriptor {
+        PropertyDescriptor {
+            name: self.name,
+            values: PropertyValues::Bool,
+        }
+    }
+
+    fn index_of(&self, value: bool) -> Option<u16> {
+        Some(value as u16)
+    }
+
+    fn value_at(&self, index: u16) -> bool {
+        index != 0
+    }
+}
+
+/// A Rust enum usable as a block state property through [`EnumProperty`].
+pub trait PropertyEnum: Copy + Eq + 'static {
+    /// Every variant, in index order.
+    const VALUES: &'static [Self];
+    /// Names matching `VALUES`.
+    const NAMES: &'static [&'static str];
+}
+
+pub struct EnumProperty<T: PropertyEnum> {
+    pub name: &'static str,
+    _marker: PhantomData<T>,
+}
+
+impl<T: PropertyEnum> EnumProperty<T> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: PropertyEnum> Property for EnumProperty<T> {
+    type Value = T;
+
+    fn descriptor(&self) -> PropertyDescriptor {
+        PropertyDescriptor {
+            name: self.name,
+            values: PropertyValues::Enum(T::NAMES),
+        }
+    }
+
+    fn index_of(&self, value: T) -> Option<u16> {
+        T::VALUES
+            .iter()
+            .position(|v| *v == value)
+            .map(|idx| idx as u16)
+    }
+
+    fn value_at(&self, index: u16) -> T {
+        T::VALUES[index as usize]
+    }
+}
+
+/// The properties a block type declares, and how they pack into a state id.
+///
+/// State ids store the value index of each property in mixed radix (the first property
+/// varies fastest), so every combination has a unique id below [`Self::state_count`] and
+/// the default state, with every property at its first value, is `0`.
+#[derive(Clone, Debug, Default)]
+pub struct BlockStateLayout {
+    /// Each property with its cardinality and stride.
+    properties: Vec<(PropertyDescriptor, u16, u16)>,
+    state_count: u32,
+}
+
+impl BlockStateLayout {
+    pub fn new(properties: impl IntoIterator<Item = PropertyDescriptor>) -> Self {
+        let mut stride = 1u32;
+        let properties = properties
+            .into_iter()
+            .map(|descriptor| {
+                let cardinality = descriptor.values.cardinality();
+                assert!(
+                    cardinality >= 1,
+                    "state property `{}` has no values",
+                    descriptor.name
+                );
+                let entry_stride = stride;
+                stride = stride.saturating_mul(cardinality);
+                assert!(
+                    stride <= Voxel::STATES as u32,
+                    "too many block states, ids must fit in a voxel"
+                );
+                // Both fit: they are at most `stride`, which is at most `Voxel::STATES`.
+                (descriptor, cardinality as u16, entry_stride as u16)
+            })
+            .collect();
+
+        Self {
+            properties,
+            state_count: stride,
+        }
+    }
+
+    pub fn properties(&self) -> impl Iterator<Item = &PropertyDescriptor> {
+        self.properties.iter().map(|(descriptor, ..)| descriptor)
+    }
+
+    pub fn state_count(&self) -> u32 {
+        self.state_count
+    }
+
+    pub fn is_valid(&self, state: u16) -> bool {
+        (state as u32) < self.state_count
+    }
+
+    fn find(&self, descriptor: &PropertyDescriptor) -> Option<(u16, u16)> {
+        self.properties
+            .iter()
+            .find(|(declared, cardinality, _)| {
+                declared.name == descriptor.name
+                    && *cardinality as u32 == descriptor.values.cardinality()
+            })
+            .map(|(_, cardinality, stride)| (*cardinality, *stride))
+    }
+
+    /// Value of `property` in `state`, or `None` if this block doesn't declare it.
+    pub fn get<P: Property>(&self, state: u16, property: &P) -> Option<P::Value> {
+        let (cardinality, stride) = self.find(&property.descriptor())?;
+        Some(property.value_at(state / stride % cardinality))
+    }
+
+    /// `state` with `property` set to `value`.
+    pub fn with<P: Property>(
+        &self,
+        state: u16,
+        property: &P,
+        value: P::Value,
+    ) -> Result<u16, BlockStateError> {
+        let descriptor = property.descriptor();
+        let (cardinality, stride) = self
+            .find(&descriptor)
+            .ok_or(BlockStateError::UnknownProperty(descriptor.name))?;
+        let index = property
+            .index_of(value)
+            .filter(|index| *index < cardinality)
+            .ok_or(BlockStateError::InvalidValue(descriptor.name))?;
+
+        let current = state / stride % cardinality;
+        Ok(state - current * stride + index * stride)
+    }
+
+    /// `name=value` pairs for every property in `state`.
+    pub fn describe(&self, state: u16) -> Vec<(&'static str, String)> {
+        self.properties
+            .iter()
+            .map(|(descriptor, cardinality, stride)| {
+                let index = state / stride % cardinality;
+                (descriptor.name, descriptor.values.label(index))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BlockStateError {
+    UnknownProperty(&'static str),
+    InvalidValue(&'static str),
+}
+
+impl fmt::Display for BlockStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockStateError::UnknownProperty(name) => {
+                write!(f, "block has no state property `{name}`")
+            }
+            BlockStateError::InvalidValue(name) => {
+                write!(f, "value out of range for state property `{name}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BlockStateError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    enum Axis {
+        X,
+        Y,
+        Z,
+    }
+
+    impl PropertyEnum for Axis {
+        const VALUES: &'static [Self] = &[Axis::X, Axis::Y, Axis::Z];
+        const NAMES: &'static [&'static str] = &["x", "y", "z"];
+    }
+
+    const AXIS: EnumProperty<Axis> = EnumProperty::new("axis");
+    const AGE: IntProperty = IntProperty::new("age", 2, 6);
+    const LIT: BoolProperty = BoolProperty::new("lit");
+
+    fn layout() -> BlockStateLayout {
+        BlockStateLayout::new([AXIS.descriptor(), AGE.descriptor(), LIT.descriptor()])
+    }
+
+    #[test]
+    fn packs_every_combination() {
+        let layout = layout();
+        assert_eq!(layout.state_count(), 30);
+        assert_eq!(layout.get(0, &AXIS), Some(Axis::X));
+        assert_eq!(layout.get(0, &AGE), Some(2));
+        assert_eq!(layout.get(0, &LIT), Some(false));
+
+        let state = layout.with(0, &AGE, 5).unwrap();
+        let state = layout.with(state, &AXIS, Axis::Z).unwrap();
+        let state = layout.with(state, &LIT, true).unwrap();
+        assert!(layout.is_valid(state));
+        assert_eq!(
+            (
+                layout.get(state, &AXIS),
+                layout.get(state, &AGE),
+                layout.get(state, &LIT)
+            ),
+            (Some(Axis::Z), Some(5), Some(true))
+        );
+        assert_eq!(
+            layout.describe(state),
+            [
+                ("axis", "z".into()),
+                ("age", "5".into()),
+                ("lit", "true".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_properties_and_values() {
+        let layout = layout();
+        assert_eq!(
+            layout.with(0, &AGE, 7),
+            Err(BlockStateError::InvalidValue("age"))
+        );
+        assert_eq!(
+            layout.with(0, &IntProperty::new("nope", 0, 1), 1),
+            Err(BlockStateError::UnknownProperty("nope"))
+        );
+        // Same name, different value count.
+        assert_eq!(layout.get(0, &IntProperty::new("age", 0, 1)), None);
+    }
+
+    #[test]
+    fn empty_ranges_have_no_values() {
+        assert_eq!(PropertyValues::Int { min: 5, max: 4 }.cardinality(), 0);
+        assert_eq!(
+            PropertyValues::Int {
+                min: 0,
+                max: u16::MAX
+            }
+            .cardinality(),
+            65536
+        );
+        assert_eq!(PropertyValues::Enum(&[]).cardinality(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "has no values")]
+    fn layout_refuses_properties_without_values() {
+        BlockStateLayout::new([PropertyDescriptor {
+            name: "broken",
+            values: PropertyValues::Int { min: 5, max: 4 },
+        }]);
+    }
+
+    #[test]
+    #[should_panic(expected = "too many block states")]
+    fn layout_refuses_states_that_do_not_fit_a_voxel() {
+        BlockStateLayout::new([
+            AGE.descriptor(),
+            PropertyDescriptor {
+                name: "wide",
+                values: PropertyValues::Int {
+                    min: 0,
+                    max: u16::MAX,
+                },
+            },
+        ]);
+    }
+}
@@ -0,0 +1,525 @@
+use std::collections::BTreeMap;
+
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    ChunkEntityMap,
+    blocks::{BLOCK_DIRT, BLOCK_GRASS, BlockId, BlockRegistry, BlockRegistryRes},
+    chunk::CHUNK_SIZE,
+    config::WorldConfig,
+    events::VoxelChanged,
+    noise::FeatureRng,
+    voxel::Voxel,
+    voxel_world::{VoxelAccess, VoxelWorld},
+};
+use crate::state::LoadingState;
+
+const NEIGHBOURS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+pub struct BlockUpdatePlugin;
+
+impl Plugin for BlockUpdatePlugin {
+    fn build(&self, app: &mut App) {
+        // A `BlockTickRng` inserted before the plugin, e.g. by a test, is kept.
+        if !app.world().contains_resource::<BlockTickRng>() {
+            let seed = app.world_mut().get_resource_or_init::<WorldConfig>().seed;
+            app.insert_resource(BlockTickRng::seeded(seed));
+        }
+
+        app.init_resource::<BlockTickSettings>()
+            .init_resource::<BlockUpdateScheduler>()
+            .init_resource::<BlockBehaviours>()
+            .register_block_behaviour(BLOCK_GRASS, GrassBehaviour)
+            .add_systems(
+                Update,
+                queue_neighbour_updates.run_if(in_state(LoadingState::Initialized)),
+            )
+            // One block tick per fixed step, so ticks don't speed up with the frame rate.
+            .add_systems(
+                FixedUpdate,
+                run_block_updates.run_if(in_state(LoadingState::Initialized)),
+            );
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct BlockTickSettings {
+    /// Random ticks given to each loaded chunk per block tick.
+    pub random_ticks_per_chunk: u32,
+}
+
+impl Default for BlockTickSettings {
+    fn default() -> Self {
+        Self {
+            random_ticks_per_chunk: 3,
+        }
+    }
+}
+
+/// Random numbers for random ticks and behaviours.
+///
+/// [`BlockUpdatePlugin`] seeds it from the [`WorldConfig`] seed, so a world ticks the
+/// same way on every run.
+#[derive(Resource, Clone, Debug, Eq, PartialEq)]
+pub struct BlockTickRng(pub FeatureRng);
+
+impl BlockTickRng {
+    pub fn seeded(seed: u64) -> Self {
+        Self(FeatureRng::for_cell(seed, IVec3::ZERO))
+    }
+}
+
+/// Game logic attached to a block type. Every method defaults to doing nothing.
+pub trait BlockBehaviour: Send + Sync + 'static {
+    /// Called for voxels picked at random each tick, see [`BlockTickSettings`].
+    fn random_tick(&self, _ctx: &mut BlockUpdateContext, _world: IVec3, _voxel: Voxel) {}
+
+    /// Called when an update scheduled with [`BlockUpdateContext::schedule`] or
+    /// [`BlockUpdateScheduler::schedule`] comes due and the block is still there.
+    fn scheduled_tick(&self, _ctx: &mut BlockUpdateContext, _world: IVec3, _voxel: Voxel) {}
+
+    /// Called the tick after the voxel at `neighbour`, adjacent to `world`, changed.
+    fn neighbour_changed(
+        &self,
+        _ctx: &mut BlockUpdateContext,
+        _world: IVec3,
+        _voxel: Voxel,
+        _neighbour: IVec3,
+    ) {
+    }
+}
+
+/// Behaviours by block, see [`RegisterBlockBehaviourExt`].
+#[derive(Resource, Default)]
+pub struct BlockBehaviours {
+    behaviours: HashMap<BlockId, Box<dyn BlockBehaviour>>,
+}
+
+impl BlockBehaviours {
+    pub fn get(&self, block_id: BlockId) -> Option<&dyn BlockBehaviour> {
+        self.behaviours.get(&block_id).map(|behaviour| &**behaviour)
+    }
+
+    pub fn insert(&mut self, block_id: BlockId, behaviour: impl BlockBehaviour) {
+        self.behaviours.insert(block_id, Box::new(behaviour));
+    }
+}
+
+pub trait RegisterBlockBehaviourExt {
+    /// Run `behaviour` for `block_id` voxels, replacing any behaviour registered before.
+    fn register_block_behaviour(
+        &mut self,
+        block_id: BlockId,
+        behaviour: impl BlockBehaviour,
+    ) -> &mut Self;
+}
+
+impl RegisterBlockBehaviourExt for App {
+    fn register_block_behaviour(
+        &mut self,
+        block_id: BlockId,
+        behaviour: impl BlockBehaviour,
+    ) -> &mut Self {
+        self.init_resource::<BlockBehaviours>();
+        self.world_mut()
+            .resource_mut::<BlockBehaviours>()
+            .insert(block_id, behaviour);
+        self
+    }
+}
+
+/// Updates due on a future tick, ordered by due tick then by scheduling order.
+#[derive(Debug, Default)]
+pub struct ScheduledUpdates {
+    next_seq: u64,
+    updates: BTreeMap<(u64, u64), (IVec3, BlockId)>,
+}
+
+impl ScheduledUpdates {
+    fn push(&mut self, due: u64, world: IVec3, block_id: BlockId) {
+        self.updates.insert((due, self.next_seq), (world, block_id));
+        self.next_seq += 1;
+    }
+
+    fn pop_due(&mut self, tick: u64) -> Vec<(IVec3, BlockId)> {
+        let later = self.updates.split_off(&(tick + 1, 0));
+        std::mem::replace(&mut self.updates, later)
+            .into_values()
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.updates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+}
+
+/// What a [`BlockBehaviour`] can see and do while it runs.
+pub struct BlockUpdateContext<'a> {
+    world: &'a mut dyn VoxelAccess,
+    pub registry: &'a BlockRegistry,
+    pub rng: &'a mut FeatureRng,
+    scheduled: &'a mut ScheduledUpdates,
+    tick: u64,
+}
+
+impl BlockUpdateContext<'_> {
+    pub fn get(&self, world: IVec3) -> Option<Voxel> {
+        self.world.get_voxel(world)
+    }
+
+    pub fn set(&mut self, world: IVec3, voxel: Voxel) -> Option<Voxel> {
+        self.world.set_voxel(world, voxel)
+    }
+
+    /// Run the `scheduled_tick` of the block at `world` in `delay` ticks (at least one),
+    /// if `block_id` is still there then.
+    pub fn schedule(&mut self, world: IVec3, block_id: BlockId, delay: u64) {
+        self.scheduled
+            .push(self.tick + delay.max(1), world, block_id);
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+}
+
+/// Drives random ticks, scheduled updates and neighbour notifications.
+///
+/// Each tick first delivers neighbour notifications queued since the last tick, then due
+/// scheduled updates, then random ticks. Given the same world, seed and inputs the
+/// result is the same on every run.
+#[derive(Resource, Debug, Default)]
+pub struct BlockUpdateScheduler {
+    tick: u64,
+    scheduled: ScheduledUpdates,
+    neighbour_updates: Vec<(IVec3, IVec3)>,
+}
+
+impl BlockUpdateScheduler {
+    pub fn tick_count(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn scheduled(&self) -> &ScheduledUpdates {
+        &self.scheduled
+    }
+
+    /// Run the `scheduled_tick` of the block at `world` in `delay` ticks (at least one),
+    /// if `block_id` is still there then.
+    pub fn schedule(&mut self, world: IVec3, block_id: BlockId, delay: u64) {
+        self.scheduled
+            .push(self.tick + delay.max(1), world, block_id);
+    }
+
+    /// Notify the six neighbours of `changed` on the next tick.
+    pub fn notify_neighbours(&mut self, changed: IVec3) {
+        self.neighbour_updates
+            .extend(NEIGHBOURS.iter().map(|offset| (changed + *offset, changed)));
+    }
+
+    /// Advance one block tick. `chunks` are the chunk coordinates that get random ticks.
+    pub fn tick(
+        &mut self,
+        world: &mut dyn VoxelAccess,
+        registry: &BlockRegistry,
+        behaviours: &BlockBehaviours,
+        rng: &mut FeatureRng,
+        chunks: &[IVec3],
+        random_ticks_per_chunk: u32,
+    ) {
+        self.tick += 1;
+        let notifications = std::mem::take(&mut self.neighbour_updates);
+        let due = self.scheduled.pop_due(self.tick);
+
+        let mut ctx = BlockUpdateContext {
+            world,
+            registry,
+            rng,
+            scheduled: &mut self.scheduled,
+            tick: self.tick,
+        };
+
+        for (position, neighbour) in notifications {
+            if let Some(voxel) = ctx.get(position)
+                && let Some(behaviour) = behaviours.get(voxel.block_id())
+            {
+                behaviour.neighbour_changed(&mut ctx, position, voxel, neighbour);
+            }
+        }
+
+        for (position, block_id) in due {
+            if let Some(voxel) = ctx.get(position)
+                && voxel.block_id() == block_id
+                && let Some(behaviour) = behaviours.get(block_id)
+            {
+                behaviour.scheduled_tick(&mut ctx, position, voxel);
+            }
+        }
+
+        let size = CHUNK_SIZE as u32;
+        for chunk in chunks {
+            for _ in 0..random_ticks_per_chunk {
+                let local = IVec3::new(
+                    ctx.rng.below(size) as i32,
+                    ctx.rng.below(size) as i32,
+                    ctx.rng.below(size) as i32,
+                );
+                let position = *chunk * CHUNK_SIZE as i32 + local;
+                if let Some(voxel) = ctx.get(position)
+                    && let Some(behaviour) = behaviours.get(voxel.block_id())
+                {
+                    behaviour.random_tick(&mut ctx, position, voxel);
+                }
+            }
+        }
+    }
+}
+
+fn queue_neighbour_updates(
+    mut changes: MessageReader<VoxelChanged>,
+    mut scheduler: ResMut<BlockUpdateScheduler>,
+) {
+    for change in changes.read() {
+        scheduler.notify_neighbours(change.world);
+    }
+}
+
+fn run_block_updates(
+    settings: Res<BlockTickSettings>,
+    mut scheduler: ResMut<BlockUpdateScheduler>,
+    mut rng: ResMut<BlockTickRng>,
+    behaviours: Res<BlockBehaviours>,
+    block_registry: Res<BlockRegistryRes>,
+    chunk_map: Res<ChunkEntityMap>,
+    mut voxel_world: VoxelWorld,
+) {
+    let mut chunks: Vec<IVec3> = chunk_map.coords().collect();
+    // Hash order differs between runs; keep random ticks reproducible.
+    chunks.sort_unstable_by_key(|coord| (coord.x, coord.y, coord.z));
+
+    scheduler.tick(
+        &mut voxel_world,
+        &block_registry.0,
+        &behaviours,
+        &mut rng.0,
+        &chunks,
+        settings.random_ticks_per_chunk,
+    );
+}
+
+/// Grass spreads to nearby dirt that has air above it, and dies back to dirt when covered.
+pub struct GrassBehaviour;
+
+impl GrassBehaviour {
+    fn is_covered(ctx: &BlockUpdateContext, world: IVec3) -> bool {
+        ctx.get(world + IVec3::Y)
+            .is_some_and(|above| ctx.registry.is_opaque(above.block_id()))
+    }
+}
+
+impl BlockBehaviour for GrassBehaviour {
+    fn random_tick(&self, ctx: &mut BlockUpdateContext, world: IVec3, _voxel: Voxel) {
+        if Self::is_covered(ctx, world) {
+            ctx.set(world, Voxel::new(BLOCK_DIRT));
+            return;
+        }
+
+        let target = world
+            + IVec3::new(
+                ctx.rng.range(-1, 1),
+                ctx.rng.range(-3, 1),
+                ctx.rng.range(-1, 1),
+            );
+        if ctx
+            .get(target)
+            .is_some_and(|voxel| voxel.block_id() == BLOCK_DIRT)
+            && ctx.get(target + IVec3::Y).is_some_and(Voxel::is_air)
+        {
+            ctx.set(target, Voxel::new(BLOCK_GRASS));
+        }
+    }
+
+    fn scheduled_tick(&self, ctx: &mut BlockUpdateContext, world: IVec3, _voxel: Voxel) {
+        if Self::is_covered(ctx, world) {
+            ctx.set(world, Voxel::new(BLOCK_DIRT));
+        }
+    }
+
+    fn neighbour_changed(
+        &self,
+        ctx: &mut BlockUpdateContext,
+        world: IVec3,
+        _voxel: Voxel,
+        neighbour: IVec3,
+    ) {
+        // Give covering a moment to be undone before the grass dies.
+        if neighbour == world + IVec3::Y && Self::is_covered(ctx, world) {
+            ctx.schedule(world, BLOCK_GRASS, 40);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::plugins::world::{
+        Chunks,
+        blocks::BLOCK_STONE,
+        chunk::Chunk,
+        test_support::{headless_app, spawn_chunks},
+        voxel_world::get_voxel,
+    };
+
+    fn behaviours() -> BlockBehaviours {
+        let mut behaviours = BlockBehaviours::default();
+        behaviours.insert(BLOCK_GRASS, GrassBehaviour);
+        behaviours
+    }
+
+    /// A dirt floor with grass in the middle, after `ticks` block ticks.
+    fn spread_grass(seed: u64, ticks: u32) -> Vec<([i32; 3], Voxel)> {
+        let registry = BlockRegistryRes::default().0;
+        let behaviours = behaviours();
+        let mut world: HashMap<IVec3, Voxel> = HashMap::new();
+        for x in 0..CHUNK_SIZE as i32 {
+            for z in 0..CHUNK_SIZE as i32 {
+                world.insert(IVec3::new(x, 0, z), Voxel::new(BLOCK_DIRT));
+            }
+        }
+        world.insert(IVec3::new(16, 0, 16), Voxel::new(BLOCK_GRASS));
+
+        let mut scheduler = BlockUpdateScheduler::default();
+        let mut rng = BlockTickRng::seeded(seed).0;
+        for _ in 0..ticks {
+            scheduler.tick(
+                &mut world,
+                &registry,
+                &behaviours,
+                &mut rng,
+                &[IVec3::ZERO],
+                20_000,
+            );
+        }
+        let mut voxels: Vec<_> = world.into_iter().map(|(p, v)| (p.to_array(), v)).collect();
+        voxels.sort_by_key(|(p, _)| *p);
+        voxels
+    }
+
+    fn grass(voxels: &[([i32; 3], Voxel)]) -> usize {
+        voxels
+            .iter()
+            .filter(|(_, voxel)| voxel.block_id() == BLOCK_GRASS)
+            .count()
+    }
+
+    #[test]
+    fn grass_spreads_the_same_way_for_a_seed() {
+        let spread = spread_grass(42, 30);
+        assert!((2..CHUNK_SIZE * CHUNK_SIZE).contains(&grass(&spread)));
+        assert_eq!(spread, spread_grass(42, 30));
+        assert_ne!(spread, spread_grass(43, 30));
+    }
+
+    #[test]
+    fn covered_grass_dies_after_its_scheduled_tick() {
+        let registry = BlockRegistryRes::default().0;
+        let behaviours = behaviours();
+        let mut world: HashMap<IVec3, Voxel> = HashMap::new();
+        world.insert(IVec3::ZERO, Voxel::new(BLOCK_GRASS));
+        world.insert(IVec3::Y, Voxel::new(BLOCK_STONE));
+        let mut scheduler = BlockUpdateScheduler::default();
+        let mut rng = BlockTickRng::seeded(1).0;
+
+        scheduler.notify_neighbours(IVec3::Y);
+        for _ in 0..40 {
+            scheduler.tick(&mut world, &registry, &behaviours, &mut rng, &[], 0);
+        }
+        assert_eq!(scheduler.scheduled().len(), 1);
+        assert_eq!(world[&IVec3::ZERO].block_id(), BLOCK_GRASS);
+        scheduler.tick(&mut world, &registry, &behaviours, &mut rng, &[], 0);
+        assert_eq!(world[&IVec3::ZERO].block_id(), BLOCK_DIRT);
+        assert!(scheduler.scheduled().is_empty());
+    }
+
+    /// A chunk of dirt with grass in the middle, ticked by the plugin seeded from `config`.
+    fn grass_app(config: WorldConfig) -> App {
+        let mut app = headless_app(0);
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_micros(
+            15_625,
+        )))
+        .insert_resource(config)
+        .insert_resource(BlockTickSettings {
+            random_ticks_per_chunk: 300,
+        })
+        .add_plugins(BlockUpdatePlugin);
+
+        let mut chunk = Chunk::new();
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set(x, 0, z, Voxel::new(BLOCK_DIRT));
+            }
+        }
+        chunk.set(16, 0, 16, Voxel::new(BLOCK_GRASS));
+        spawn_chunks(app.world_mut(), [(IVec3::ZERO, chunk)]);
+        app
+    }
+
+    fn ticked_grass(app: &mut App, updates: u32) -> Vec<IVec3> {
+        for _ in 0..updates {
+            app.update();
+        }
+        let world = app.world();
+        let (chunk_map, chunks) = (
+            world.resource::<ChunkEntityMap>(),
+            world.resource::<Chunks>(),
+        );
+        (0..CHUNK_SIZE as i32)
+            .flat_map(|x| (0..CHUNK_SIZE as i32).map(move |z| IVec3::new(x, 0, z)))
+            .filter(|at| {
+                get_voxel(chunk_map, chunks, *at).is_some_and(|v| v.block_id() == BLOCK_GRASS)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plugin_ticks_from_the_world_seed() {
+        let config = |seed| WorldConfig { seed, ..default() };
+        let mut app = grass_app(config(7));
+        assert_eq!(
+            *app.world().resource::<BlockTickRng>(),
+            BlockTickRng::seeded(7)
+        );
+
+        let grass = ticked_grass(&mut app, 100);
+        assert!(app.world().resource::<BlockUpdateScheduler>().tick_count() > 0);
+        assert!(grass.len() > 1);
+        assert_eq!(grass, ticked_grass(&mut grass_app(config(7)), 100));
+    }
+
+    #[test]
+    fn inserted_rng_overrides_the_world_seed() {
+        let mut app = App::new();
+        app.insert_resource(BlockTickRng::seeded(3))
+            .add_plugins(BlockUpdatePlugin);
+        assert_eq!(
+            *app.world().resource::<BlockTickRng>(),
+            BlockTickRng::seeded(3)
+        );
+    }
+}
@@ -1,12 +1,27 @@
 use bevy::platform::collections::HashMap;
 use bevy::prelude::Resource;
 
+use crate::plugins::world::{
+    block_state::{BlockStateError, BlockStateLayout, Property, PropertyDescriptor},
+    fluids::{FLUID_FALLING, FLUID_LEVEL},
+    voxel::Voxel,
+};
+
 pub type BlockId = u16;
 pub type TileId = u16;
 
 pub const BLOCK_GRASS: BlockId = 1;
 pub const BLOCK_DIRT: BlockId = 2;
 pub const BLOCK_STONE: BlockId = 3;
+pub const BLOCK_WATER: BlockId = 4;
+pub const BLOCK_LAVA: BlockId = 5;
+pub const BLOCK_SAND: BlockId = 6;
+pub const BLOCK_GRAVEL: BlockId = 7;
+pub const BLOCK_BEDROCK: BlockId = 8;
+pub const BLOCK_COAL_ORE: BlockId = 9;
+pub const BLOCK_IRON_ORE: BlockId = 10;
+pub const BLOCK_LOG: BlockId = 11;
+pub const BLOCK_LEAVES: BlockId = 12;
 
 #[derive(Copy, Clone)]
 pub struct BlockTiles {
@@ -16,8 +31,9 @@ pub struct BlockTiles {
 }
 
 impl BlockTiles {
+    /// The three atlas tiles after those of `block_id - 1`. Air has none and gets block 1's.
     pub fn new(block_id: BlockId) -> Self {
-        let idx_start = (block_id - 1) * 3;
+        let idx_start = block_id.saturating_sub(1) * 3;
         Self {
             top: idx_start,
             side: idx_start + 1,
@@ -26,8 +42,53 @@ impl BlockTiles {
     }
 }
 
+/// How a fluid block spreads.
+#[derive(Copy, Clone, Debug)]
+pub struct FluidProperties {
+    /// Level lost per voxel of horizontal spread.
+    pub decay: u8,
+    /// Whether two adjacent sources turn a flowing cell between them into a source.
+    pub infinite: bool,
+}
+
+#[derive(Clone)]
+pub struct BlockInfo {
+    pub tiles: BlockTiles,
+    /// Tiles replacing `tiles` for specific state ids.
+    pub state_tiles: HashMap<u16, BlockTiles>,
+    pub states: BlockStateLayout,
+    /// `Some` for fluids, which are meshed in the translucent pass and can be replaced by placing.
+    pub fluid: Option<FluidProperties>,
+    /// Falls when there's nothing solid below it.
+    pub gravity: bool,
+    /// Holds up everything connected to it, see [`StructuralIntegritySettings`](super::structural_integrity::StructuralIntegritySettings).
+    pub anchor: bool,
+    /// How much explosion strength it takes to destroy the block.
+    pub resistance: f32,
+    /// Leaves and the like: solid, but not ground to build or spawn on.
+    pub foliage: bool,
+    /// How long the block takes to mine, see [`MiningSettings`](super::mining::MiningSettings).
+    pub hardness: f32,
+}
+
+impl BlockInfo {
+    fn new(block_id: BlockId, properties: Vec<PropertyDescriptor>) -> Self {
+        Self {
+            tiles: BlockTiles::new(block_id),
+            state_tiles: HashMap::new(),
+            states: BlockStateLayout::new(properties),
+            fluid: None,
+            gravity: false,
+            anchor: false,
+            resistance: 1.0,
+            foliage: false,
+            hardness: 1.0,
+        }
+    }
+}
+
 pub struct BlockRegistry {
-    blocks: HashMap<BlockId, BlockTiles>,
+    blocks: HashMap<BlockId, BlockInfo>,
 }
 
 impl BlockRegistry {
@@ -37,14 +98,181 @@ impl BlockRegistry {
         }
     }
 
+    /// Tiles for `id`, or the tiles an unregistered block would get.
     #[inline]
     pub fn tiles(&self, id: BlockId) -> BlockTiles {
-        self.blocks[&id]
+        self.blocks
+            .get(&id)
+            .map_or_else(|| BlockTiles::new(id), |info| info.tiles)
+    }
+
+    /// Tiles for `voxel`, taking per-state overrides into account.
+    #[inline]
+    pub fn tiles_for(&self, voxel: Voxel) -> BlockTiles {
+        let Some(info) = self.blocks.get(&voxel.block_id()) else {
+            return BlockTiles::new(voxel.block_id());
+        };
+        info.state_tiles
+            .get(&voxel.state())
+            .copied()
+            .unwrap_or(info.tiles)
+    }
+
+    /// Highest atlas tile any block or block state is drawn with.
+    pub fn last_tile(&self) -> TileId {
+        self.blocks
+            .values()
+            .flat_map(|info| core::iter::once(info.tiles).chain(info.state_tiles.values().copied()))
+            .flat_map(|tiles| [tiles.top, tiles.side, tiles.bottom])
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Property layout of `id`'s states, or `None` for unknown blocks and air.
+    pub fn states(&self, id: BlockId) -> Option<&BlockStateLayout> {
+        self.blocks.get(&id).map(|info| &info.states)
+    }
+
+    /// Value of `property` on `voxel`, or `None` if its block doesn't declare it.
+    pub fn property<P: Property>(&self, voxel: Voxel, property: &P) -> Option<P::Value> {
+        self.states(voxel.block_id())?.get(voxel.state(), property)
+    }
+
+    /// `voxel` with `property` set to `value`.
+    pub fn with_property<P: Property>(
+        &self,
+        voxel: Voxel,
+        property: &P,
+        value: P::Value,
+    ) -> Result<Voxel, BlockStateError> {
+        let descriptor = property.descriptor();
+        let states = self
+            .states(voxel.block_id())
+            .ok_or(BlockStateError::UnknownProperty(descriptor.name))?;
+        let state = states.with(voxel.state(), property, value)?;
+        Ok(Voxel::with_state(voxel.block_id(), state))
+    }
+
+    #[inline]
+    pub fn fluid(&self, id: BlockId) -> Option<FluidProperties> {
+        self.blocks.get(&id).and_then(|info| info.fluid)
+    }
+
+    #[inline]
+    pub fn is_fluid(&self, id: BlockId) -> bool {
+        self.fluid(id).is_some()
+    }
+
+    #[inline]
+    pub fn has_gravity(&self, id: BlockId) -> bool {
+        self.blocks.get(&id).is_some_and(|info| info.gravity)
+    }
+
+    /// Whether `id` anchors the voxels connected to it, like bedrock.
+    #[inline]
+    pub fn is_anchor(&self, id: BlockId) -> bool {
+        self.blocks.get(&id).is_some_and(|info| info.anchor)
+    }
+
+    /// Explosion resistance of `id`; air offers none.
+    #[inline]
+    pub fn resistance(&self, id: BlockId) -> f32 {
+        self.blocks.get(&id).map_or(0.0, |info| info.resistance)
+    }
+
+    /// Mining hardness of `id`; air and unknown blocks can't be mined.
+    #[inline]
+    pub fn hardness(&self, id: BlockId) -> f32 {
+        self.blocks
+            .get(&id)
+            .map_or(f32::INFINITY, |info| info.hardness)
     }
 
+    /// Whether `id` is foliage, like leaves.
     #[inline]
-    pub fn insert(&mut self, block_id: BlockId) -> Option<BlockTiles> {
-        self.blocks.insert(block_id, BlockTiles::new(block_id))
+    pub fn is_foliage(&self, id: BlockId) -> bool {
+        self.blocks.get(&id).is_some_and(|info| info.foliage)
+    }
+
+    /// Whether `id` is a block that hides what's behind it: not air and not a fluid.
+    #[inline]
+    pub fn is_opaque(&self, id: BlockId) -> bool {
+        id != 0 && !self.is_fluid(id)
+    }
+
+    #[inline]
+    pub fn insert(&mut self, block_id: BlockId) -> Option<BlockInfo> {
+        self.blocks
+            .insert(block_id, BlockInfo::new(block_id, Vec::new()))
+    }
+
+    /// Register a block whose voxels carry the given state properties.
+    pub fn insert_with_properties(
+        &mut self,
+        block_id: BlockId,
+        properties: impl IntoIterator<Item = PropertyDescriptor>,
+    ) -> Option<BlockInfo> {
+        self.blocks.insert(
+            block_id,
+            BlockInfo::new(block_id, properties.into_iter().collect()),
+        )
+    }
+
+    /// Register a fluid; its states carry [`FLUID_LEVEL`] and [`FLUID_FALLING`].
+    pub fn insert_fluid(
+        &mut self,
+        block_id: BlockId,
+        properties: FluidProperties,
+    ) -> Option<BlockInfo> {
+        let mut info = BlockInfo::new(
+            block_id,
+            vec![FLUID_LEVEL.descriptor(), FLUID_FALLING.descriptor()],
+        );
+        info.fluid = Some(properties);
+        self.blocks.insert(block_id, info)
+    }
+
+    /// Register a block that falls when unsupported, like sand.
+    pub fn insert_falling(&mut self, block_id: BlockId) -> Option<BlockInfo> {
+        let mut info = BlockInfo::new(block_id, Vec::new());
+        info.gravity = true;
+        self.blocks.insert(block_id, info)
+    }
+
+    /// Register a block that holds up the terrain connected to it, like bedrock.
+    pub fn insert_anchor(&mut self, block_id: BlockId) -> Option<BlockInfo> {
+        let mut info = BlockInfo::new(block_id, Vec::new());
+        info.anchor = true;
+        self.blocks.insert(block_id, info)
+    }
+
+    /// Set the explosion resistance of `block_id`. Does nothing if it isn't registered.
+    pub fn set_resistance(&mut self, block_id: BlockId, resistance: f32) {
+        if let Some(info) = self.blocks.get_mut(&block_id) {
+            info.resistance = resistance;
+        }
+    }
+
+    /// Set the mining hardness of `block_id`. Does nothing if it isn't registered.
+    pub fn set_hardness(&mut self, block_id: BlockId, hardness: f32) {
+        if let Some(info) = self.blocks.get_mut(&block_id) {
+            info.hardness = hardness;
+        }
+    }
+
+    /// Mark `block_id` as foliage. Does nothing if it isn't registered.
+    pub fn set_foliage(&mut self, block_id: BlockId, foliage: bool) {
+        if let Some(info) = self.blocks.get_mut(&block_id) {
+            info.foliage = foliage;
+        }
+    }
+
+    /// Use `tiles` instead of the block's default tiles for voxels in `state`.
+    /// Does nothing if `block_id` isn't registered.
+    pub fn insert_state_tiles(&mut self, block_id: BlockId, state: u16, tiles: BlockTiles) {
+        if let Some(info) = self.blocks.get_mut(&block_id) {
+            info.state_tiles.insert(state, tiles);
+        }
     }
 }
 
@@ -58,7 +286,103 @@ impl Default for BlockRegistryRes {
         registry.insert(BLOCK_GRASS);
         registry.insert(BLOCK_DIRT);
         registry.insert(BLOCK_STONE);
+        registry.insert_fluid(
+            BLOCK_WATER,
+            FluidProperties {
+                decay: 1,
+                infinite: true,
+            },
+        );
+        registry.insert_fluid(
+            BLOCK_LAVA,
+            FluidProperties {
+                decay: 2,
+                infinite: false,
+            },
+        );
+        registry.insert_falling(BLOCK_SAND);
+        registry.insert_falling(BLOCK_GRAVEL);
+        registry.insert_anchor(BLOCK_BEDROCK);
+        registry.insert(BLOCK_COAL_ORE);
+        registry.insert(BLOCK_IRON_ORE);
+        registry.insert(BLOCK_LOG);
+        registry.insert(BLOCK_LEAVES);
+        registry.set_foliage(BLOCK_LEAVES, true);
+
+        for soft in [
+            BLOCK_GRASS,
+            BLOCK_DIRT,
+            BLOCK_SAND,
+            BLOCK_GRAVEL,
+            BLOCK_LEAVES,
+        ] {
+            registry.set_resistance(soft, 0.5);
+        }
+        for hard in [BLOCK_STONE, BLOCK_COAL_ORE, BLOCK_IRON_ORE] {
+            registry.set_resistance(hard, 1.5);
+        }
+        registry.set_resistance(BLOCK_WATER, 100.0);
+        registry.set_resistance(BLOCK_LAVA, 100.0);
+        registry.set_resistance(BLOCK_BEDROCK, f32::INFINITY);
+
+        for (block_id, hardness) in [
+            (BLOCK_LEAVES, 0.2),
+            (BLOCK_GRASS, 0.6),
+            (BLOCK_DIRT, 0.5),
+            (BLOCK_SAND, 0.5),
+            (BLOCK_GRAVEL, 0.6),
+            (BLOCK_LOG, 2.0),
+            (BLOCK_STONE, 1.5),
+            (BLOCK_COAL_ORE, 3.0),
+            (BLOCK_IRON_ORE, 3.0),
+        ] {
+            registry.set_hardness(block_id, hardness);
+        }
+        for unmineable in [BLOCK_WATER, BLOCK_LAVA, BLOCK_BEDROCK] {
+            registry.set_hardness(unmineable, f32::INFINITY);
+        }
 
         BlockRegistryRes(registry)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::world::fluids::FluidState;
+
+    #[test]
+    fn unknown_blocks_get_default_tiles() {
+        let registry = BlockRegistryRes::default().0;
+        let unknown = BLOCK_LEAVES + 20;
+        assert_eq!(registry.tiles(unknown).top, BlockTiles::new(unknown).top);
+        assert_eq!(
+            registry.tiles_for(Voxel::with_state(unknown, 3)).bottom,
+            BlockTiles::new(unknown).bottom
+        );
+        assert_eq!(registry.tiles_for(Voxel::AIR).side, 1);
+    }
+
+    #[test]
+    fn state_tiles_override_the_block_tiles() {
+        let mut registry = BlockRegistryRes::default().0;
+        let falling = FluidState::FALLING.voxel(&registry, BLOCK_WATER);
+        registry.insert_state_tiles(
+            BLOCK_WATER,
+            falling.state(),
+            BlockTiles::new(BLOCK_LEAVES + 1),
+        );
+        assert_eq!(
+            registry.tiles_for(falling).top,
+            BlockTiles::new(BLOCK_LEAVES + 1).top
+        );
+        assert_eq!(
+            registry.tiles_for(Voxel::new(BLOCK_WATER)).top,
+            BlockTiles::new(BLOCK_WATER).top
+        );
+        assert_eq!(
+            registry.last_tile(),
+            BlockTiles::new(BLOCK_LEAVES + 1).bottom
+        );
+    }
+}
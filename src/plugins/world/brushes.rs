@@ -0,0 +1,740 @@
+use bevy::color::palettes::basic::{AQUA, YELLOW};
+use bevy::math::I64Vec3;
+use bevy::prelude::*;
+
+use crate::plugins::{
+    player::{PlayerCollider, inventory::Inventory},
+    world::{
+        blocks::BlockRegistryRes,
+        edit_history::{EditHistory, EditTransaction},
+        items::{ItemRegistryRes, ItemStack, LootRng},
+        voxel::Voxel,
+        voxel_picking::{HoveredVoxel, VoxelHit},
+        voxel_tools::VoxelToolBindings,
+        voxel_world::VoxelWorld,
+    },
+};
+use crate::state::LoadingState;
+
+const MAX_BRUSH_RADIUS: u32 = 32;
+/// Most voxels a brush box may span, the same as the largest radius brush.
+pub const MAX_BRUSH_VOLUME: u64 = (2 * MAX_BRUSH_RADIUS as u64 + 1).pow(3);
+/// Most voxels a line brush may cover.
+pub const MAX_BRUSH_LINE_LENGTH: u64 = 8 * MAX_BRUSH_RADIUS as u64;
+
+pub struct BrushToolPlugin;
+
+impl Plugin for BrushToolPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BrushTool>().add_systems(
+            Update,
+            (
+                configure_brush_tool,
+                apply_brush_tool.run_if(brush_active),
+                draw_brush_preview_gizmos.run_if(brush_active),
+            )
+                .chain()
+                .run_if(in_state(LoadingState::Initialized)),
+        );
+    }
+}
+
+/// A bulk edit resolved to world voxel coords. All bounds are inclusive.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Brush {
+    /// Every voxel in the box spanned by two corners.
+    Cuboid { from: IVec3, to: IVec3 },
+    /// Voxels inside the ellipsoid with the given per-axis radii.
+    Ellipsoid { center: IVec3, radii: UVec3 },
+    /// Y-aligned cylinder centered on `center`.
+    Cylinder {
+        center: IVec3,
+        radius: u32,
+        half_height: u32,
+    },
+    /// Voxels equal to `target` in the box spanned by two corners.
+    Replace {
+        from: IVec3,
+        to: IVec3,
+        target: Voxel,
+    },
+    /// Voxels along the line between two voxel centers.
+    Line { from: IVec3, to: IVec3 },
+}
+
+impl Brush {
+    /// Inclusive (min, max) bounds of every voxel the brush can touch.
+    pub fn bounds(&self) -> (IVec3, IVec3) {
+        match *self {
+            Brush::Cuboid { from, to }
+            | Brush::Replace { from, to, .. }
+            | Brush::Line { from, to } => (from.min(to), from.max(to)),
+            Brush::Ellipsoid { center, radii } => {
+                let radii = radii.as_ivec3();
+                (center - radii, center + radii)
+            }
+            Brush::Cylinder {
+                center,
+                radius,
+                half_height,
+            } => {
+                let extent = IVec3::new(radius as i32, half_height as i32, radius as i32);
+                (center - extent, center + extent)
+            }
+        }
+    }
+
+    /// World voxel coords covered by the brush shape, listed lazily. [`Brush::Replace`]
+    /// yields its whole box; filtering by target happens when the brush is applied.
+    pub fn positions(&self) -> Box<dyn Iterator<Item = IVec3>> {
+        let (min, max) = self.bounds();
+        match *self {
+            Brush::Cuboid { .. } | Brush::Replace { .. } => Box::new(box_positions(min, max)),
+            Brush::Ellipsoid { center, radii } => {
+                // Half a voxel of slack so small radii still produce round-ish shapes.
+                let r = radii.as_vec3() + Vec3::splat(0.5);
+                Box::new(
+                    box_positions(min, max)
+                        .filter(move |p| ((*p - center).as_vec3() / r).length_squared() <= 1.0),
+                )
+            }
+            Brush::Cylinder { center, radius, .. } => {
+                let r = radius as f32 + 0.5;
+                Box::new(box_positions(min, max).filter(move |p| {
+                    let d = *p - center;
+                    ((d.x * d.x + d.z * d.z) as f32) <= r * r
+                }))
+            }
+            Brush::Line { from, to } => {
+                let delta = to - from;
+                let steps = delta.abs().max_element();
+                Box::new((0..=steps).map(move |i| {
+                    let t = i as f32 / steps.max(1) as f32;
+                    from + (delta.as_vec3() * t).round().as_ivec3()
+                }))
+            }
+        }
+    }
+
+    /// Whether the brush stays within [`MAX_BRUSH_VOLUME`] and, for lines,
+    /// [`MAX_BRUSH_LINE_LENGTH`].
+    pub fn within_limits(&self) -> bool {
+        let (min, max) = self.bounds();
+        let extent = (max.as_i64vec3() - min.as_i64vec3() + I64Vec3::ONE).as_u64vec3();
+        match *self {
+            Brush::Line { .. } => extent.max_element() <= MAX_BRUSH_LINE_LENGTH,
+            _ => extent
+                .x
+                .checked_mul(extent.y)
+                .and_then(|area| area.checked_mul(extent.z))
+                .is_some_and(|volume| volume <= MAX_BRUSH_VOLUME),
+        }
+    }
+}
+
+fn box_positions(min: IVec3, max: IVec3) -> impl Iterator<Item = IVec3> {
+    (min.z..=max.z).flat_map(move |z| {
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec3::new(x, y, z)))
+    })
+}
+
+/// Fill `brush` with `voxel` as a single batch, so each affected chunk is dirtied once.
+pub fn apply_brush(voxel_world: &mut VoxelWorld, brush: &Brush, voxel: Voxel) -> EditTransaction {
+    apply_brush_up_to(voxel_world, brush, voxel, usize::MAX, |_, _| true)
+}
+
+/// Like [`apply_brush`], but change at most `limit` voxels, the first ones the brush
+/// lists, and only those `allowed` accepts given their position and current voxel.
+pub fn apply_brush_up_to(
+    voxel_world: &mut VoxelWorld,
+    brush: &Brush,
+    voxel: Voxel,
+    limit: usize,
+    allowed: impl Fn(IVec3, Voxel) -> bool,
+) -> EditTransaction {
+    let positions: Vec<IVec3> = brush
+        .positions()
+        .filter(|p| {
+            let Some(current) = voxel_world.get(*p) else {
+                return false;
+            };
+            let changes = match *brush {
+                Brush::Replace { target, .. } => current == target && current != voxel,
+                _ => current != voxel,
+            };
+            changes && allowed(*p, current)
+        })
+        .take(limit)
+        .collect();
+
+    let mut transaction = EditTransaction::new();
+    transaction.set_batch(voxel_world, positions.into_iter().map(|p| (p, voxel)));
+    transaction
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BrushShape {
+    Cuboid,
+    Ellipsoid,
+    Cylinder,
+    Replace,
+    Line,
+}
+
+impl BrushShape {
+    pub const ALL: [Self; 5] = [
+        Self::Cuboid,
+        Self::Ellipsoid,
+        Self::Cylinder,
+        Self::Replace,
+        Self::Line,
+    ];
+
+    /// Shapes spanned between an anchor and a second picked point.
+    pub fn is_two_point(self) -> bool {
+        matches!(self, Self::Cuboid | Self::Replace | Self::Line)
+    }
+}
+
+/// Brush editing state. While a shape is selected the brush replaces the
+/// single-voxel tools, using the same place/break bindings.
+#[derive(Resource, Clone, Debug)]
+pub struct BrushTool {
+    pub shape: Option<BrushShape>,
+    /// First picked point of a two-point shape.
+    pub anchor: Option<IVec3>,
+    /// Radii for ellipsoids; cylinders use `x` as radius and `y` as half height.
+    pub radius: UVec3,
+}
+
+impl Default for BrushTool {
+    fn default() -> Self {
+        Self {
+            shape: None,
+            anchor: None,
+            radius: UVec3::splat(3),
+        }
+    }
+}
+
+impl BrushTool {
+    pub fn is_active(&self) -> bool {
+        self.shape.is_some()
+    }
+
+    /// The brush the current shape would produce ending at `target`, or `None` if it
+    /// would be too large. Two-point shapes without an anchor span just `target`.
+    pub fn brush_at(&self, target: IVec3, replace_target: Voxel) -> Option<Brush> {
+        let from = self.anchor.unwrap_or(target);
+        let brush = match self.shape? {
+            BrushShape::Cuboid => Brush::Cuboid { from, to: target },
+            BrushShape::Ellipsoid => Brush::Ellipsoid {
+                center: target,
+                radii: self.radius,
+            },
+            BrushShape::Cylinder => Brush::Cylinder {
+                center: target,
+                radius: self.radius.x,
+                half_height: self.radius.y,
+            },
+            BrushShape::Replace => Brush::Replace {
+                from,
+                to: target,
+                target: replace_target,
+            },
+            BrushShape::Line => Brush::Line { from, to: target },
+        };
+        brush.within_limits().then_some(brush)
+    }
+
+    /// Voxel a brush targets for `hit`: the cell in front of the face when
+    /// placing, the hovered voxel itself when breaking or replacing.
+    fn target(&self, hit: VoxelHit, placing: bool) -> IVec3 {
+        if placing && self.shape != Some(BrushShape::Replace) {
+            hit.adjacent()
+        } else {
+            hit.world
+        }
+    }
+}
+
+pub fn brush_active(brush: Res<BrushTool>) -> bool {
+    brush.is_active()
+}
+
+pub fn brush_inactive(brush: Res<BrushTool>) -> bool {
+    !brush.is_active()
+}
+
+/// B cycles shapes (and off), [ and ] shrink/grow the radius, Escape drops the anchor.
+fn configure_brush_tool(keys: Res<ButtonInput<KeyCode>>, mut brush: ResMut<BrushTool>) {
+    if keys.just_pressed(KeyCode::KeyB) {
+        brush.shape = match brush.shape {
+            None => Some(BrushShape::ALL[0]),
+            Some(shape) => {
+                let idx = BrushShape::ALL.iter().position(|s| *s == shape).unwrap();
+                BrushShape::ALL.get(idx + 1).copied()
+            }
+        };
+        brush.anchor = None;
+        info!("Brush: {:?}", brush.shape);
+    }
+
+    if keys.just_pressed(KeyCode::BracketLeft) {
+        brush.radius = brush.radius.saturating_sub(UVec3::ONE);
+    }
+    if keys.just_pressed(KeyCode::BracketRight) {
+        brush.radius = (brush.radius + UVec3::ONE).min(UVec3::splat(MAX_BRUSH_RADIUS));
+    }
+
+    if keys.just_pressed(KeyCode::Escape) {
+        brush.anchor = None;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn apply_brush_tool(
+    mouse: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<VoxelToolBindings>,
+    colliders: Query<(&PlayerCollider, &GlobalTransform)>,
+    mut inventory: Single<&mut Inventory, With<PlayerCollider>>,
+    block_registry: Res<BlockRegistryRes>,
+    item_registry: Res<ItemRegistryRes>,
+    mut loot_rng: ResMut<LootRng>,
+    hovered: Res<HoveredVoxel>,
+    mut brush: ResMut<BrushTool>,
+    mut history: ResMut<EditHistory>,
+    mut voxel_world: VoxelWorld,
+) {
+    let Some(hit) = hovered.hit else {
+        return;
+    };
+    let Some(shape) = brush.shape else {
+        return;
+    };
+
+    let placing = bindings.place_block.just_pressed(&mouse, &keys);
+    let breaking = bindings.break_block.just_pressed(&mouse, &keys);
+    if !placing && !breaking {
+        return;
+    }
+
+    let target = brush.target(hit, placing);
+    if shape.is_two_point() && brush.anchor.is_none() {
+        brush.anchor = Some(target);
+        return;
+    }
+
+    // Placing uses up one of the selected item per voxel, and stops when it runs out.
+    let (fill, stack) = if placing {
+        let Some((stack, block_id)) = inventory.selected_stack().and_then(|stack| {
+            item_registry
+                .0
+                .block(stack.item)
+                .map(|block_id| (stack, block_id))
+        }) else {
+            return;
+        };
+        (Voxel::new(block_id), Some(stack))
+    } else {
+        (Voxel::AIR, None)
+    };
+    let replace_target = brush
+        .anchor
+        .and_then(|anchor| voxel_world.get(anchor))
+        .unwrap_or(Voxel::AIR);
+
+    let Some(op) = brush.brush_at(target, replace_target) else {
+        warn!("Brush ending at {target} is too large, pick a closer point");
+        return;
+    };
+    let limit = stack.map_or(usize::MAX, |stack| stack.count as usize);
+    // Like single placement, solid voxels aren't placed inside the player.
+    let encloses_player = |p: IVec3| {
+        !fill.is_air()
+            && !block_registry.0.is_fluid(fill.block_id())
+            && colliders
+                .iter()
+                .any(|(collider, gt)| collider.intersects_voxel(gt.translation(), p))
+    };
+    // Like mining, breaking leaves blocks that can't be mined alone.
+    let unbreakable = |current: Voxel| {
+        fill.is_air() && !block_registry.0.hardness(current.block_id()).is_finite()
+    };
+    let mut transaction = apply_brush_up_to(&mut voxel_world, &op, fill, limit, |p, current| {
+        !encloses_player(p) && !unbreakable(current)
+    });
+    if let Some(stack) = stack {
+        let used = transaction.edits().len();
+        let selected = inventory.selected;
+        inventory.take(selected, used as u32);
+        for edit in 0..used {
+            transaction.spend(edit, ItemStack::new(stack.item, 1));
+        }
+    } else {
+        // Broken voxels drop their loot, as when mined one at a time.
+        let broken: Vec<Voxel> = transaction
+            .edits()
+            .iter()
+            .map(|edit| edit.previous)
+            .collect();
+        for (edit, voxel) in broken.into_iter().enumerate() {
+            for stack in item_registry.0.drops(voxel.block_id(), &mut loot_rng.0) {
+                let lost = inventory.insert(stack, &item_registry.0);
+                if let Some(lost) = lost {
+                    debug!("Inventory full, dropped {lost:?}");
+                }
+                transaction.gain(
+                    edit,
+                    ItemStack::new(stack.item, stack.count - lost.map_or(0, |lost| lost.count)),
+                );
+            }
+        }
+    }
+    history.push(transaction);
+    brush.anchor = None;
+}
+
+/// Outline the volume the active brush would edit at the hovered voxel.
+fn draw_brush_preview_gizmos(
+    mut gizmos: Gizmos,
+    brush: Res<BrushTool>,
+    hovered: Res<HoveredVoxel>,
+) {
+    let Some(hit) = hovered.hit else {
+        return;
+    };
+    let Some(op) = brush.brush_at(brush.target(hit, true), Voxel::AIR) else {
+        return;
+    };
+
+    if let Some(anchor) = brush.anchor {
+        draw_voxel_outline(&mut gizmos, anchor, anchor, YELLOW);
+    }
+
+    match op {
+        Brush::Cuboid { from, to } | Brush::Replace { from, to, .. } => {
+            draw_voxel_outline(&mut gizmos, from.min(to), from.max(to), AQUA);
+        }
+        Brush::Ellipsoid { center, radii } => {
+            let c = voxel_center(center);
+            let r = radii.as_vec3() + Vec3::splat(0.5);
+            gizmos.ellipse(Isometry3d::from_translation(c), r.xy(), AQUA);
+            gizmos.ellipse(
+                Isometry3d::new(c, Quat::from_rotation_x(std::f32::consts::FRAC_PI_2)),
+                r.xz(),
+                AQUA,
+            );
+            gizmos.ellipse(
+                Isometry3d::new(c, Quat::from_rotation_y(std::f32::consts::FRAC_PI_2)),
+                r.zy(),
+                AQUA,
+            );
+        }
+        Brush::Cylinder {
+            center,
+            radius,
+            half_height,
+        } => {
+            let c = voxel_center(center);
+            let r = radius as f32 + 0.5;
+            let h = half_height as f32 + 0.5;
+            let flat = Quat::from_rotation_x(std::f32::consts::FRAC_PI_2);
+            gizmos.circle(Isometry3d::new(c + Vec3::Y * h, flat), r, AQUA);
+            gizmos.circle(Isometry3d::new(c - Vec3::Y * h, flat), r, AQUA);
+            for dir in [Vec3::X, Vec3::NEG_X, Vec3::Z, Vec3::NEG_Z] {
+                gizmos.line(c + dir * r - Vec3::Y * h, c + dir * r + Vec3::Y * h, AQUA);
+            }
+        }
+        Brush::Line { from, to } => {
+            gizmos.line(voxel_center(from), voxel_center(to), AQUA);
+            draw_voxel_outline(&mut gizmos, to, to, AQUA);
+        }
+    }
+}
+
+fn voxel_center(world: IVec3) -> Vec3 {
+    world.as_vec3() + Vec3::splat(0.5)
+}
+
+/// Box outline around the inclusive voxel range `min..=max`.
+fn draw_voxel_outline(gizmos: &mut Gizmos, min: IVec3, max: IVec3, color: impl Into<Color>) {
+    let lo = min.as_vec3();
+    let hi = max.as_vec3() + Vec3::ONE;
+    gizmos.cube(
+        Transform::from_translation((lo + hi) * 0.5).with_scale(hi - lo),
+        color,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::gizmos::GizmoPlugin;
+
+    use super::*;
+    use crate::plugins::world::{
+        ChunkEntityMap, Chunks,
+        blocks::{BLOCK_BEDROCK, BLOCK_STONE, BLOCK_WATER},
+        chunk::Chunk,
+        test_support::{headless_app, spawn_chunks},
+        voxel_picking::VoxelFace,
+        voxel_world::get_voxel,
+    };
+
+    #[test]
+    fn boxes_span_both_corners_in_any_order() {
+        let brush = Brush::Cuboid {
+            from: IVec3::new(2, 0, -1),
+            to: IVec3::new(0, 1, -1),
+        };
+        assert_eq!(brush.bounds(), (IVec3::new(0, 0, -1), IVec3::new(2, 1, -1)));
+        assert_eq!(brush.positions().count(), 6);
+
+        // Replace lists its whole box; the target is only checked when applied.
+        let replace = Brush::Replace {
+            from: IVec3::new(2, 0, -1),
+            to: IVec3::new(0, 1, -1),
+            target: Voxel::new(BLOCK_STONE),
+        };
+        assert_eq!(replace.bounds(), brush.bounds());
+        assert!(replace.positions().eq(brush.positions()));
+    }
+
+    #[test]
+    fn ellipsoids_stay_inside_their_radii() {
+        let center = IVec3::new(5, 5, 5);
+        let brush = Brush::Ellipsoid {
+            center,
+            radii: UVec3::new(3, 1, 2),
+        };
+        let (min, max) = brush.bounds();
+        assert_eq!((min, max), (IVec3::new(2, 4, 3), IVec3::new(8, 6, 7)));
+
+        let positions: Vec<IVec3> = brush.positions().collect();
+        assert!(
+            positions
+                .iter()
+                .all(|p| p.cmpge(min).all() && p.cmple(max).all())
+        );
+        for tip in [IVec3::X * 3, IVec3::Y, IVec3::Z * 2, -IVec3::X * 3] {
+            assert!(positions.contains(&(center + tip)), "missing {tip}");
+        }
+        assert!(!positions.contains(&(center + IVec3::new(3, 1, 0))));
+        assert!(!positions.contains(&max));
+    }
+
+    #[test]
+    fn cylinders_are_round_across_and_straight_up() {
+        let center = IVec3::new(0, 10, 0);
+        let brush = Brush::Cylinder {
+            center,
+            radius: 2,
+            half_height: 1,
+        };
+        assert_eq!(
+            brush.bounds(),
+            (IVec3::new(-2, 9, -2), IVec3::new(2, 11, 2))
+        );
+
+        // Every layer is the same 21-voxel disc: the 5x5 square without its corners.
+        let positions: Vec<IVec3> = brush.positions().collect();
+        assert_eq!(positions.len(), 3 * 21);
+        for y in 9..=11 {
+            assert!(positions.contains(&IVec3::new(2, y, 0)));
+            assert!(positions.contains(&IVec3::new(1, y, -1)));
+            assert!(!positions.contains(&IVec3::new(2, y, 2)));
+        }
+    }
+
+    #[test]
+    fn lines_step_once_per_voxel_along_their_longest_axis() {
+        let from = IVec3::new(0, 0, 0);
+        let to = IVec3::new(4, -2, 1);
+        let brush = Brush::Line { from, to };
+        assert_eq!(brush.bounds(), (IVec3::new(0, -2, 0), IVec3::new(4, 0, 1)));
+
+        let positions: Vec<IVec3> = brush.positions().collect();
+        assert_eq!(positions.len(), 5);
+        assert_eq!(positions.first(), Some(&from));
+        assert_eq!(positions.last(), Some(&to));
+        assert!(
+            positions
+                .windows(2)
+                .all(|w| (w[1] - w[0]).abs().max_element() == 1)
+        );
+        assert!(Brush::Line { from: to, to }.positions().eq([to]));
+    }
+
+    #[test]
+    fn brushes_refuse_boxes_and_lines_past_the_limits() {
+        let mut tool = BrushTool {
+            shape: Some(BrushShape::Cuboid),
+            anchor: Some(IVec3::ZERO),
+            ..default()
+        };
+        let edge = 2 * MAX_BRUSH_RADIUS as i32;
+        assert!(tool.brush_at(IVec3::splat(edge), Voxel::AIR).is_some());
+        assert!(tool.brush_at(IVec3::splat(edge + 1), Voxel::AIR).is_none());
+        // Far apart picks don't overflow the volume.
+        assert!(tool.brush_at(IVec3::splat(i32::MAX), Voxel::AIR).is_none());
+
+        tool.shape = Some(BrushShape::Line);
+        let length = MAX_BRUSH_LINE_LENGTH as i32;
+        assert!(tool.brush_at(IVec3::X * (length - 1), Voxel::AIR).is_some());
+        assert!(tool.brush_at(IVec3::X * length, Voxel::AIR).is_none());
+    }
+
+    /// An app running the brush tool on a stone floor at y 0, with `brush` selected
+    /// and the player carrying `carried`.
+    fn brush_app(brush: BrushTool, carried: Option<ItemStack>) -> (App, Entity) {
+        let mut app = headless_app(1);
+        app.add_plugins((AssetPlugin::default(), GizmoPlugin))
+            .init_resource::<ItemRegistryRes>()
+            .insert_resource(LootRng::new(0))
+            .init_resource::<EditHistory>()
+            .init_resource::<HoveredVoxel>()
+            .init_resource::<VoxelToolBindings>()
+            .init_resource::<ButtonInput<MouseButton>>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .insert_resource(brush)
+            .add_plugins(BrushToolPlugin);
+
+        let mut chunk = Chunk::new();
+        for x in 0..32 {
+            for z in 0..32 {
+                chunk.set(x, 0, z, Voxel::new(BLOCK_STONE));
+            }
+        }
+        spawn_chunks(app.world_mut(), [(IVec3::ZERO, chunk)]);
+
+        let mut inventory = Inventory::default();
+        inventory.slots[0] = carried;
+        let player = app
+            .world_mut()
+            .spawn((
+                PlayerCollider {
+                    half_extents: Vec3::splat(0.3),
+                    offset: Vec3::ZERO,
+                },
+                inventory,
+            ))
+            .id();
+        (app, player)
+    }
+
+    /// Hover the top of the floor at `floor` and click `button`.
+    fn click_floor(app: &mut App, floor: IVec3, button: MouseButton) {
+        app.world_mut().resource_mut::<HoveredVoxel>().hit = Some(VoxelHit {
+            chunk: IVec3::ZERO,
+            local: floor,
+            world: floor,
+            face: VoxelFace::PosY,
+        });
+        let mut mouse = app.world_mut().resource_mut::<ButtonInput<MouseButton>>();
+        mouse.release_all();
+        mouse.clear();
+        mouse.press(button);
+        app.update();
+    }
+
+    fn voxel_at(app: &App, p: IVec3) -> Option<Voxel> {
+        get_voxel(
+            app.world().resource::<ChunkEntityMap>(),
+            app.world().resource::<Chunks>(),
+            p,
+        )
+    }
+
+    #[test]
+    fn placing_uses_up_the_selected_stack() {
+        let cylinder = BrushTool {
+            shape: Some(BrushShape::Cylinder),
+            radius: UVec3::ONE,
+            ..default()
+        };
+        let (mut app, player) = brush_app(cylinder, Some(ItemStack::new(BLOCK_STONE, 4)));
+        click_floor(&mut app, IVec3::new(8, 0, 8), MouseButton::Right);
+
+        // The cylinder has room for 18 voxels above the floor, but only 4 are carried.
+        let history = app.world().resource::<EditHistory>();
+        assert!(history.can_undo());
+        assert_eq!(app.world().get::<Inventory>(player).unwrap().slots[0], None);
+        let placed = box_positions(IVec3::new(6, 1, 6), IVec3::new(10, 2, 10))
+            .filter(|&p| voxel_at(&app, p) == Some(Voxel::new(BLOCK_STONE)))
+            .count();
+        assert_eq!(placed, 4);
+    }
+
+    #[test]
+    fn placing_leaves_room_for_the_player() {
+        let cylinder = BrushTool {
+            shape: Some(BrushShape::Cylinder),
+            radius: UVec3::ONE,
+            ..default()
+        };
+        let (mut app, player) = brush_app(cylinder, Some(ItemStack::new(BLOCK_STONE, 64)));
+        app.world_mut()
+            .entity_mut(player)
+            .insert(GlobalTransform::from_translation(Vec3::new(8.5, 1.5, 8.5)));
+        click_floor(&mut app, IVec3::new(8, 0, 8), MouseButton::Right);
+
+        assert_eq!(voxel_at(&app, IVec3::new(8, 1, 8)), Some(Voxel::AIR));
+        let placed = box_positions(IVec3::new(6, 1, 6), IVec3::new(10, 2, 10))
+            .filter(|&p| voxel_at(&app, p) == Some(Voxel::new(BLOCK_STONE)))
+            .count();
+        // Two layers of 3x3, less the voxel the player stands in.
+        assert_eq!(placed, 17);
+        let inventory = app.world().get::<Inventory>(player).unwrap();
+        assert_eq!(inventory.slots[0], Some(ItemStack::new(BLOCK_STONE, 47)));
+    }
+
+    #[test]
+    fn breaking_collects_what_the_voxels_drop() {
+        let cuboid = BrushTool {
+            shape: Some(BrushShape::Cuboid),
+            anchor: Some(IVec3::new(2, 0, 2)),
+            ..default()
+        };
+        let (mut app, player) = brush_app(cuboid, None);
+        click_floor(&mut app, IVec3::new(4, 0, 2), MouseButton::Left);
+
+        for x in 2..=4 {
+            assert_eq!(voxel_at(&app, IVec3::new(x, 0, 2)), Some(Voxel::AIR));
+        }
+        let inventory = app.world().get::<Inventory>(player).unwrap();
+        assert_eq!(inventory.count(BLOCK_STONE), 3);
+        assert!(app.world().resource::<EditHistory>().can_undo());
+    }
+
+    #[test]
+    fn breaking_leaves_blocks_that_cannot_be_mined() {
+        let cuboid = BrushTool {
+            shape: Some(BrushShape::Cuboid),
+            anchor: Some(IVec3::new(2, 0, 2)),
+            ..default()
+        };
+        let (mut app, player) = brush_app(cuboid, None);
+        let kept = [
+            (IVec3::new(3, 0, 2), Voxel::new(BLOCK_BEDROCK)),
+            (IVec3::new(4, 0, 2), Voxel::new(BLOCK_WATER)),
+        ];
+        app.world_mut()
+            .run_system_once(move |mut voxel_world: VoxelWorld| {
+                for (p, voxel) in kept {
+                    voxel_world.set(p, voxel);
+                }
+            })
+            .unwrap();
+        click_floor(&mut app, IVec3::new(4, 0, 2), MouseButton::Left);
+
+        assert_eq!(voxel_at(&app, IVec3::new(2, 0, 2)), Some(Voxel::AIR));
+        for (p, voxel) in kept {
+            assert_eq!(voxel_at(&app, p), Some(voxel));
+        }
+        let inventory = app.world().get::<Inventory>(player).unwrap();
+        assert_eq!(inventory.count(BLOCK_STONE), 1);
+        assert_eq!(inventory.count(BLOCK_BEDROCK), 0);
+    }
+}
@@ -0,0 +1,99 @@
+use std::{fmt, io};
+
+/// Reads the little endian binary formats: schematics, `.vox` files and chunk saves.
+pub struct ByteReader<'a>(&'a [u8]);
+
+impl<'a> ByteReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self(bytes)
+    }
+
+    /// Bytes left to read.
+    pub fn remaining(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn take(&mut self, n: usize) -> Result<&'a [u8], BinaryError> {
+        if self.0.len() < n {
+            return Err(BinaryError::Truncated);
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    /// Read `magic`, failing with [`BinaryError::InvalidMagic`] if something else is there.
+    pub fn magic(&mut self, magic: &'static [u8; 4]) -> Result<(), BinaryError> {
+        if self.take(4)? != magic {
+            return Err(BinaryError::InvalidMagic(magic));
+        }
+        Ok(())
+    }
+
+    pub fn u8(&mut self) -> Result<u8, BinaryError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, BinaryError> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, BinaryError> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, BinaryError> {
+        Ok(self.u32()? as i32)
+    }
+
+    /// `len` bytes of UTF-8.
+    pub fn str(&mut self, len: usize) -> Result<&'a str, BinaryError> {
+        std::str::from_utf8(self.take(len)?)
+            .map_err(|_| BinaryError::InvalidData("string is not UTF-8"))
+    }
+
+    /// Capacity to reserve for `count` items of at least `item_size` bytes each: no more
+    /// than the rest of the input could hold, however large a count it claims.
+    pub fn capacity(&self, count: usize, item_size: usize) -> usize {
+        count.min(self.0.len() / item_size.max(1))
+    }
+}
+
+/// Why reading or writing one of the binary formats failed.
+#[derive(Debug)]
+pub enum BinaryError {
+    Io(io::Error),
+    /// The data doesn't start with the format's magic bytes.
+    InvalidMagic(&'static [u8; 4]),
+    UnsupportedVersion(u8),
+    Truncated,
+    InvalidData(&'static str),
+}
+
+impl fmt::Display for BinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinaryError::Io(err) => write!(f, "io error: {err}"),
+            BinaryError::InvalidMagic(magic) => {
+                write!(f, "missing {:?} magic", String::from_utf8_lossy(*magic))
+            }
+            BinaryError::UnsupportedVersion(v) => write!(f, "unsupported version {v}"),
+            BinaryError::Truncated => write!(f, "unexpected end of data"),
+            BinaryError::InvalidData(msg) => write!(f, "invalid data: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for BinaryError {}
+
+impl From<io::Error> for BinaryError {
+    fn from(err: io::Error) -> Self {
+        BinaryError::Io(err)
+    }
+}
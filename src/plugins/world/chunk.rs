@@ -28,6 +28,19 @@ impl Chunk {
         }
     }
 
+    /// A chunk holding `voxels`, laid out as by [`Chunk::index`].
+    pub fn from_voxels(voxels: Box<[Voxel; CHUNK_VOLUME]>) -> Self {
+        Self {
+            voxels,
+            dirty: true,
+        }
+    }
+
+    /// All voxels, laid out as by [`Chunk::index`].
+    pub fn voxels(&self) -> &[Voxel; CHUNK_VOLUME] {
+        &self.voxels
+    }
+
     #[inline]
     pub fn index(x: usize, y: usize, z: usize) -> usize {
         debug_assert!(x < CHUNK_SIZE);
@@ -52,7 +65,31 @@ impl Chunk {
         self.dirty
     }
 
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
     pub fn clear_dirty(&mut self) {
         self.dirty = false;
     }
 }
+
+/// Convert world voxel coord -> (chunk coord, local voxel coord).
+pub fn world_to_chunk_local(world: IVec3) -> (IVec3, IVec3) {
+    let cs = CHUNK_SIZE as i32;
+
+    // Euclidean division so negatives work the way you expect.
+    let chunk = IVec3::new(
+        world.x.div_euclid(cs),
+        world.y.div_euclid(cs),
+        world.z.div_euclid(cs),
+    );
+
+    let local = IVec3::new(
+        world.x.rem_euclid(cs),
+        world.y.rem_euclid(cs),
+        world.z.rem_euclid(cs),
+    );
+
+    (chunk, local)
+}
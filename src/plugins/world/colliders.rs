@@ -0,0 +1,349 @@
+use bevy::platform::collections::HashSet;
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    ChunkComponent, Chunks,
+    blocks::{BlockRegistry, BlockRegistryRes},
+    chunk::{CHUNK_SIZE, CHUNK_VOLUME, Chunk, world_to_chunk_local},
+    events::VoxelChanged,
+};
+use crate::state::LoadingState;
+
+/// Generates collision shapes for chunks near [`TerrainCollider`] entities, for use with
+/// external physics engines. The shapes are handed to the [`ColliderBackendResource`];
+/// by default they are inserted as [`ChunkColliderShape`] components.
+///
+/// Added by the world plugin with the `colliders` feature.
+pub struct ChunkColliderPlugin;
+
+impl Plugin for ChunkColliderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkColliderSettings>()
+            .insert_resource(ColliderBackendResource(Box::new(BoxListBackend)))
+            .add_systems(
+                Update,
+                update_chunk_colliders.run_if(in_state(LoadingState::Initialized)),
+            );
+    }
+}
+
+/// Marks an entity that collides with the terrain, like a dynamic rigid body or a
+/// character. Chunks near it get colliders. The player and mobs have it.
+#[derive(Component, Copy, Clone, Debug, Default)]
+pub struct TerrainCollider;
+
+#[derive(Resource, Clone, Debug)]
+pub struct ChunkColliderSettings {
+    /// Chunks within this many chunks (on every axis) of a [`TerrainCollider`] get colliders.
+    pub radius: i32,
+}
+
+impl Default for ChunkColliderSettings {
+    fn default() -> Self {
+        Self { radius: 1 }
+    }
+}
+
+/// An axis-aligned box of solid voxels in chunk-local voxel coords. `max` is exclusive.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ColliderBox {
+    pub min: UVec3,
+    pub max: UVec3,
+}
+
+impl ColliderBox {
+    pub fn size(&self) -> UVec3 {
+        self.max - self.min
+    }
+
+    pub fn volume(&self) -> u32 {
+        self.size().element_product()
+    }
+
+    /// Center relative to the chunk's min corner.
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max).as_vec3() * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        self.size().as_vec3() * 0.5
+    }
+}
+
+/// A chunk's solid voxels merged into as few non-overlapping boxes as a greedy sweep finds.
+#[derive(Component, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ChunkColliderShape {
+    pub boxes: Vec<ColliderBox>,
+}
+
+impl ChunkColliderShape {
+    /// Merge the solid (opaque) voxels of `chunk`. Fluids and air don't collide.
+    ///
+    /// Boxes grow along x, then y, then z from the first uncovered voxel in index order.
+    pub fn build(chunk: &Chunk, registry: &BlockRegistry) -> Self {
+        let size = CHUNK_SIZE;
+        let solid =
+            |x: usize, y: usize, z: usize| registry.is_opaque(chunk.get(x, y, z).block_id());
+        let mut covered = vec![false; CHUNK_VOLUME];
+        let open = |covered: &[bool], x: usize, y: usize, z: usize| {
+            solid(x, y, z) && !covered[Chunk::index(x, y, z)]
+        };
+
+        let mut boxes = Vec::new();
+        for z in 0..size {
+            for y in 0..size {
+                for x in 0..size {
+                    if !open(&covered, x, y, z) {
+                        continue;
+                    }
+
+                    let mut max_x = x + 1;
+                    while max_x < size && open(&covered, max_x, y, z) {
+                        max_x += 1;
+                    }
+                    let mut max_y = y + 1;
+                    while max_y < size && (x..max_x).all(|x| open(&covered, x, max_y, z)) {
+                        max_y += 1;
+                    }
+                    let mut max_z = z + 1;
+                    while max_z < size
+                        && (y..max_y).all(|y| (x..max_x).all(|x| open(&covered, x, y, max_z)))
+                    {
+                        max_z += 1;
+                    }
+
+                    for cz in z..max_z {
+                        for cy in y..max_y {
+                            for cx in x..max_x {
+                                covered[Chunk::index(cx, cy, cz)] = true;
+                            }
+                        }
+                    }
+                    boxes.push(ColliderBox {
+                        min: UVec3::new(x as u32, y as u32, z as u32),
+                        max: UVec3::new(max_x as u32, max_y as u32, max_z as u32),
+                    });
+                }
+            }
+        }
+
+        Self { boxes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boxes.is_empty()
+    }
+
+    /// Whether the chunk-local voxel `local` is inside one of the boxes.
+    pub fn contains(&self, local: UVec3) -> bool {
+        self.boxes
+            .iter()
+            .any(|b| local.cmpge(b.min).all() && local.cmplt(b.max).all())
+    }
+}
+
+/// Turns chunk collision shapes into a physics engine's components.
+///
+/// Implement this to attach, for example, a compound collider of cuboids built from
+/// [`ColliderBox::center`] and [`ColliderBox::half_extents`].
+pub trait ColliderBackend: Send + Sync + 'static {
+    /// Attach the collider for `shape` to the chunk entity, replacing any previous one.
+    fn attach(&self, chunk: &mut EntityCommands, shape: ChunkColliderShape);
+
+    /// Remove the chunk entity's collider.
+    fn detach(&self, chunk: &mut EntityCommands);
+}
+
+#[derive(Resource)]
+pub struct ColliderBackendResource(pub Box<dyn ColliderBackend>);
+
+/// Stores the box list itself as a [`ChunkColliderShape`] component.
+pub struct BoxListBackend;
+
+impl ColliderBackend for BoxListBackend {
+    fn attach(&self, chunk: &mut EntityCommands, shape: ChunkColliderShape) {
+        chunk.insert(shape);
+    }
+
+    fn detach(&self, chunk: &mut EntityCommands) {
+        chunk.remove::<ChunkColliderShape>();
+    }
+}
+
+/// Marks chunks whose collider is attached, whatever the backend.
+#[derive(Component, Copy, Clone, Debug, Default)]
+pub struct ChunkColliderAttached;
+
+/// Chunks in range are rebuilt when they first come in range and after any
+/// [`VoxelChanged`] in them, whichever system made the edit and whenever it ran. Chunks
+/// out of range lose their collider, so none is ever left stale.
+#[allow(clippy::too_many_arguments)]
+fn update_chunk_colliders(
+    mut commands: Commands,
+    mut changes: MessageReader<VoxelChanged>,
+    settings: Res<ChunkColliderSettings>,
+    backend: Res<ColliderBackendResource>,
+    block_registry: Res<BlockRegistryRes>,
+    chunks: Res<Chunks>,
+    bodies: Query<&GlobalTransform, With<TerrainCollider>>,
+    chunk_query: Query<(Entity, &ChunkComponent, Has<ChunkColliderAttached>)>,
+) {
+    let edited: HashSet<IVec3> = changes
+        .read()
+        .map(|change| world_to_chunk_local(change.world).0)
+        .collect();
+
+    let radius = IVec3::splat(settings.radius);
+    let mut in_range = HashSet::new();
+    for transform in bodies.iter() {
+        let center = (transform.translation() / CHUNK_SIZE as f32)
+            .floor()
+            .as_ivec3();
+        for z in -radius.z..=radius.z {
+            for y in -radius.y..=radius.y {
+                for x in -radius.x..=radius.x {
+                    in_range.insert(center + IVec3::new(x, y, z));
+                }
+            }
+        }
+    }
+
+    for (entity, chunk_cmp, attached) in chunk_query.iter() {
+        let Some(chunk) = chunks.0.get(&entity) else {
+            continue;
+        };
+
+        if !in_range.contains(&chunk_cmp.coord) {
+            if attached {
+                let mut entity_commands = commands.entity(entity);
+                backend.0.detach(&mut entity_commands);
+                entity_commands.remove::<ChunkColliderAttached>();
+            }
+            continue;
+        }
+        if attached && !edited.contains(&chunk_cmp.coord) {
+            continue;
+        }
+
+        let shape = ChunkColliderShape::build(chunk, &block_registry.0);
+        let mut entity_commands = commands.entity(entity);
+        backend.0.attach(&mut entity_commands, shape);
+        entity_commands.insert(ChunkColliderAttached);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::plugins::world::{
+        ChunkEntityMap,
+        blocks::{BLOCK_DIRT, BLOCK_STONE, BLOCK_WATER},
+        test_support::{headless_app, spawn_chunks},
+        voxel::Voxel,
+        voxel_world::VoxelWorld,
+    };
+
+    /// A chunk with stone in its four bottom layers.
+    fn floor() -> Chunk {
+        let mut chunk = Chunk::new();
+        for x in 0..CHUNK_SIZE {
+            for y in 0..4 {
+                for z in 0..CHUNK_SIZE {
+                    chunk.set(x, y, z, Voxel::new(BLOCK_STONE));
+                }
+            }
+        }
+        chunk
+    }
+
+    #[test]
+    fn merges_a_floor_into_one_box() {
+        let registry = BlockRegistryRes::default().0;
+        assert!(ChunkColliderShape::build(&Chunk::new(), &registry).is_empty());
+        assert_eq!(
+            ChunkColliderShape::build(&floor(), &registry).boxes,
+            [ColliderBox {
+                min: UVec3::ZERO,
+                max: UVec3::new(32, 4, 32),
+            }]
+        );
+    }
+
+    #[test]
+    fn boxes_cover_solid_voxels_without_overlapping() {
+        let registry = BlockRegistryRes::default().0;
+        let mut chunk = floor();
+        chunk.set(5, 10, 5, Voxel::new(BLOCK_DIRT));
+        chunk.set(6, 10, 5, Voxel::new(BLOCK_DIRT));
+        chunk.set(7, 10, 5, Voxel::new(BLOCK_WATER));
+        chunk.set(3, 2, 3, Voxel::AIR);
+
+        let shape = ChunkColliderShape::build(&chunk, &registry);
+        let volume: u32 = shape.boxes.iter().map(ColliderBox::volume).sum();
+        assert_eq!(volume, 32 * 4 * 32 - 1 + 2);
+        assert!(shape.contains(UVec3::new(6, 10, 5)));
+        assert!(!shape.contains(UVec3::new(7, 10, 5)));
+        assert!(!shape.contains(UVec3::new(3, 2, 3)));
+        assert!(shape.boxes.len() < 10, "{} boxes", shape.boxes.len());
+        for (i, a) in shape.boxes.iter().enumerate() {
+            for b in &shape.boxes[i + 1..] {
+                assert!(!(a.min.cmplt(b.max).all() && b.min.cmplt(a.max).all()));
+            }
+        }
+    }
+
+    /// A floor chunk at the origin and an empty one above it.
+    fn collider_app() -> App {
+        let mut app = headless_app(1);
+        app.add_plugins(ChunkColliderPlugin)
+            .insert_resource(ChunkColliderSettings { radius: 0 });
+        spawn_chunks(
+            app.world_mut(),
+            [(IVec3::ZERO, floor()), (IVec3::Y, Chunk::new())],
+        );
+        app
+    }
+
+    fn shape(app: &App, coord: IVec3) -> Option<&ChunkColliderShape> {
+        let chunk = app.world().resource::<ChunkEntityMap>().get(&coord)?;
+        app.world().get::<ChunkColliderShape>(chunk)
+    }
+
+    fn set(app: &mut App, world: IVec3, voxel: Voxel) {
+        app.world_mut()
+            .run_system_once(move |mut voxel_world: VoxelWorld| {
+                voxel_world.set(world, voxel);
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn follows_terrain_colliders_and_edits() {
+        let mut app = collider_app();
+        let body = app
+            .world_mut()
+            .spawn((TerrainCollider, GlobalTransform::from_xyz(8.0, 8.0, 8.0)))
+            .id();
+        app.update();
+        assert_eq!(shape(&app, IVec3::ZERO).unwrap().boxes.len(), 1);
+        assert!(shape(&app, IVec3::Y).is_none());
+
+        // An edit made after the colliders were updated is picked up on the next frame.
+        set(&mut app, IVec3::new(8, 4, 8), Voxel::new(BLOCK_DIRT));
+        app.update();
+        assert!(
+            shape(&app, IVec3::ZERO)
+                .unwrap()
+                .contains(UVec3::new(8, 4, 8))
+        );
+
+        app.world_mut()
+            .entity_mut(body)
+            .insert(GlobalTransform::from_xyz(8.0, 40.0, 8.0));
+        app.update();
+        assert!(shape(&app, IVec3::ZERO).is_none());
+        assert!(shape(&app, IVec3::Y).unwrap().is_empty());
+    }
+}
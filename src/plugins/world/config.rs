@@ -0,0 +1,435 @@
+use core::fmt;
+use core::str::FromStr;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::plugins::world::{chunk::CHUNK_SIZE, worldgen::DEFAULT_WORLD_SEED};
+
+/// Where the config of the world is saved, next to its chunks.
+pub const WORLD_CONFIG_PATH: &str = "saves/world/world.ron";
+/// Furthest chunk y from zero a world may reach, keeping voxel y well inside `i32`.
+pub const MAX_WORLD_CHUNK_Y: i32 = 1 << 16;
+
+/// How a new world's terrain is generated.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum WorldPreset {
+    /// A slab of dirt and grass.
+    Flat,
+    /// Biomes, caves, ores and trees.
+    #[default]
+    Default,
+    /// The default terrain, stretched into taller mountains and deeper overhangs.
+    Amplified,
+    /// Nothing at all.
+    Void,
+}
+
+impl WorldPreset {
+    pub const ALL: [WorldPreset; 4] = [Self::Flat, Self::Default, Self::Amplified, Self::Void];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Flat => "flat",
+            Self::Default => "default",
+            Self::Amplified => "amplified",
+            Self::Void => "void",
+        }
+    }
+}
+
+impl FromStr for WorldPreset {
+    type Err = WorldConfigError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| WorldConfigError::UnknownPreset(name.to_string()))
+    }
+}
+
+/// Everything needed to generate a world again: its seed and preset, with the limits and
+/// spawn point chosen when it was created.
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldConfig {
+    pub seed: u64,
+    pub preset: WorldPreset,
+    /// Horizontal distance, in chunks, generated around each viewer.
+    pub view_distance: i32,
+    /// Inclusive range of chunk y coords the world has.
+    pub min_chunk_y: i32,
+    pub max_chunk_y: i32,
+    pub spawn: Vec3,
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self::for_preset(WorldPreset::default())
+    }
+}
+
+impl WorldConfig {
+    /// The defaults of `preset`, with the default seed.
+    pub fn for_preset(preset: WorldPreset) -> Self {
+        let (max_chunk_y, spawn) = match preset {
+            WorldPreset::Flat => (1, Vec3::new(16.0, 24.0, 16.0)),
+            WorldPreset::Default => (2, Vec3::new(20.0, 56.0, 20.0)),
+            WorldPreset::Amplified => (4, Vec3::new(20.0, 120.0, 20.0)),
+            WorldPreset::Void => (2, Vec3::new(0.0, 32.0, 0.0)),
+        };
+        Self {
+            seed: DEFAULT_WORLD_SEED,
+            preset,
+            view_distance: 3,
+            min_chunk_y: 0,
+            max_chunk_y,
+            spawn,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, WorldConfigError> {
+        let text = std::fs::read_to_string(path)?;
+        ron::from_str(&text).map_err(|err| WorldConfigError::Parse(err.to_string()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), WorldConfigError> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| WorldConfigError::Parse(err.to_string()))?;
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// The config of the world saved at `saved`, or of a new world made from `args` if
+    /// there is none. A saved world keeps its seed, preset and limits; only the view
+    /// distance can be changed. Runs before logging is set up, so a seed or preset that is
+    /// ignored for a saved world is reported on stderr.
+    ///
+    /// Height limits that are upside down or further than [`MAX_WORLD_CHUNK_Y`] from zero
+    /// are rejected as [`WorldConfigError::InvalidValue`].
+    pub fn resolve(
+        args: &WorldConfigArgs,
+        saved: impl AsRef<Path>,
+    ) -> Result<Self, WorldConfigError> {
+        let saved = saved.as_ref();
+        let mut config = if saved.exists() {
+            let config = Self::load(saved)?;
+            if args.seed.is_some_and(|seed| seed != config.seed)
+                || args.preset.is_some_and(|preset| preset != config.preset)
+            {
+                eprintln!(
+                    "keeping seed {} and preset {} of the saved world at {}",
+                    config.seed,
+                    config.preset.name(),
+                    saved.display()
+                );
+            }
+            config
+        } else {
+            let mut config = match &args.config {
+                Some(path) => Self::load(path)?,
+                None => Self::for_preset(args.preset.unwrap_or_default()),
+            };
+            if let Some(preset) = args.preset {
+                config.preset = preset;
+            }
+            if let Some(seed) = args.seed {
+                config.seed = seed;
+            }
+            config
+        };
+        if let Some(view_distance) = args.view_distance {
+            config.view_distance = view_distance;
+        }
+        if config.view_distance <= 0 {
+            return Err(WorldConfigError::Parse(format!(
+                "view_distance must be positive, not {}",
+                config.view_distance
+            )));
+        }
+        let limit = -MAX_WORLD_CHUNK_Y..=MAX_WORLD_CHUNK_Y;
+        if !limit.contains(&config.min_chunk_y) {
+            return Err(WorldConfigError::InvalidValue(
+                "min_chunk_y".to_string(),
+                config.min_chunk_y.to_string(),
+            ));
+        }
+        if !limit.contains(&config.max_chunk_y) || config.max_chunk_y < config.min_chunk_y {
+            return Err(WorldConfigError::InvalidValue(
+                "max_chunk_y".to_string(),
+                config.max_chunk_y.to_string(),
+            ));
+        }
+        Ok(config)
+    }
+}
+
+/// Floor and ceiling of the world. Chunks outside are never generated or loaded, edits
+/// outside are rejected and rays stop at them.
+#[derive(Resource, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct WorldHeightLimits {
+    /// Inclusive range of chunk y coords.
+    pub min_chunk_y: i32,
+    pub max_chunk_y: i32,
+}
+
+impl Default for WorldHeightLimits {
+    fn default() -> Self {
+        Self::from(&WorldConfig::default())
+    }
+}
+
+impl From<&WorldConfig> for WorldHeightLimits {
+    fn from(config: &WorldConfig) -> Self {
+        Self {
+            min_chunk_y: config.min_chunk_y,
+            max_chunk_y: config.max_chunk_y,
+        }
+    }
+}
+
+impl WorldHeightLimits {
+    /// Lowest voxel y in the world.
+    pub fn min_y(&self) -> i32 {
+        self.min_chunk_y * CHUNK_SIZE as i32
+    }
+
+    /// Highest voxel y in the world.
+    pub fn max_y(&self) -> i32 {
+        (self.max_chunk_y + 1) * CHUNK_SIZE as i32 - 1
+    }
+
+    pub fn contains_chunk(&self, chunk_y: i32) -> bool {
+        (self.min_chunk_y..=self.max_chunk_y).contains(&chunk_y)
+    }
+
+    pub fn contains(&self, world_y: i32) -> bool {
+        (self.min_y()..=self.max_y()).contains(&world_y)
+    }
+}
+
+/// World options given on the command line.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WorldConfigArgs {
+    pub seed: Option<u64>,
+    pub preset: Option<WorldPreset>,
+    pub view_distance: Option<i32>,
+    /// A RON [`WorldConfig`] to create the world from.
+    pub config: Option<PathBuf>,
+}
+
+impl WorldConfigArgs {
+    pub const USAGE: &str = "usage: aettesaga [--seed <number>] \
+        [--preset flat|default|amplified|void] [--view-distance <chunks>] [--config <file.ron>]";
+
+    /// Parse `--seed`, `--preset`, `--view-distance` and `--config`, each followed by its
+    /// value, from `args` (without the program name).
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, WorldConfigError> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| WorldConfigError::MissingValue(flag.clone()))
+            };
+            match flag.as_str() {
+                "--seed" => {
+                    let value = value()?;
+                    let seed = value
+                        .parse()
+                        .map_err(|_| WorldConfigError::InvalidValue(flag.clone(), value))?;
+                    parsed.seed = Some(seed);
+                }
+                "--preset" => parsed.preset = Some(value()?.parse()?),
+                "--view-distance" => {
+                    let value = value()?;
+                    let distance = value.parse().ok().filter(|&distance: &i32| distance > 0);
+                    let distance = distance
+                        .ok_or_else(|| WorldConfigError::InvalidValue(flag.clone(), value))?;
+                    parsed.view_distance = Some(distance);
+                }
+                "--config" => parsed.config = Some(PathBuf::from(value()?)),
+                _ => return Err(WorldConfigError::UnknownArgument(flag)),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+#[derive(Debug)]
+pub enum WorldConfigError {
+    Io(io::Error),
+    Parse(String),
+    UnknownPreset(String),
+    UnknownArgument(String),
+    MissingValue(String),
+    InvalidValue(String, String),
+}
+
+impl fmt::Display for WorldConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorldConfigError::Io(err) => write!(f, "io error: {err}"),
+            WorldConfigError::Parse(msg) => write!(f, "invalid world config: {msg}"),
+            WorldConfigError::UnknownPreset(name) => write!(f, "unknown world preset {name:?}"),
+            WorldConfigError::UnknownArgument(arg) => write!(f, "unknown argument {arg:?}"),
+            WorldConfigError::MissingValue(flag) => write!(f, "{flag} needs a value"),
+            WorldConfigError::InvalidValue(flag, value) => {
+                write!(f, "invalid value {value:?} for {flag}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WorldConfigError {}
+
+impl From<io::Error> for WorldConfigError {
+    fn from(err: io::Error) -> Self {
+        WorldConfigError::Io(err)
+    }
+}
+
+/// Save the config with the world, the first time it is started.
+pub(crate) fn save_world_config(config: Res<WorldConfig>) {
+    if Path::new(WORLD_CONFIG_PATH).exists() {
+        return;
+    }
+    if let Err(err) = config.save(WORLD_CONFIG_PATH) {
+        error!("failed to save world config to {WORLD_CONFIG_PATH}: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<WorldConfigArgs, WorldConfigError> {
+        WorldConfigArgs::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn temp_config(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("aettesaga-config-{name}"));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.join("world.ron")
+    }
+
+    #[test]
+    fn parses_every_flag() {
+        let parsed = args(&[
+            "--seed",
+            "7",
+            "--preset",
+            "Amplified",
+            "--view-distance",
+            "5",
+        ]);
+        let parsed = parsed.unwrap();
+        assert_eq!(parsed.seed, Some(7));
+        assert_eq!(parsed.preset, Some(WorldPreset::Amplified));
+        assert_eq!(parsed.view_distance, Some(5));
+        assert!(args(&["--seed"]).is_err());
+        assert!(args(&["--preset", "nope"]).is_err());
+        assert!(args(&["--size", "3"]).is_err());
+    }
+
+    #[test]
+    fn rejects_view_distances_below_one() {
+        for distance in ["0", "-2", "far"] {
+            assert!(matches!(
+                args(&["--view-distance", distance]),
+                Err(WorldConfigError::InvalidValue(..))
+            ));
+        }
+        let path = temp_config("view-distance");
+        let config = WorldConfig {
+            view_distance: 0,
+            ..default()
+        };
+        config.save(&path).unwrap();
+        assert!(WorldConfig::resolve(&WorldConfigArgs::default(), &path).is_err());
+        let fixed = args(&["--view-distance", "2"]).unwrap();
+        assert_eq!(
+            WorldConfig::resolve(&fixed, &path).unwrap().view_distance,
+            2
+        );
+    }
+
+    #[test]
+    fn rejects_upside_down_and_out_of_range_height_limits() {
+        let saved = temp_config("limits-saved");
+        let given = temp_config("limits-given");
+        let with_config = WorldConfigArgs {
+            config: Some(given.clone()),
+            ..default()
+        };
+        for (min_chunk_y, max_chunk_y) in [(2, 1), (0, i32::MAX), (i32::MIN, 0)] {
+            let config = WorldConfig {
+                min_chunk_y,
+                max_chunk_y,
+                ..default()
+            };
+            config.save(&saved).unwrap();
+            config.save(&given).unwrap();
+            assert!(matches!(
+                WorldConfig::resolve(&WorldConfigArgs::default(), &saved),
+                Err(WorldConfigError::InvalidValue(..))
+            ));
+            let _ = std::fs::remove_file(&saved);
+            assert!(matches!(
+                WorldConfig::resolve(&with_config, &saved),
+                Err(WorldConfigError::InvalidValue(..))
+            ));
+        }
+
+        let tall = WorldConfig {
+            min_chunk_y: -MAX_WORLD_CHUNK_Y,
+            max_chunk_y: MAX_WORLD_CHUNK_Y,
+            ..default()
+        };
+        tall.save(&given).unwrap();
+        let resolved = WorldConfig::resolve(&with_config, &saved).unwrap();
+        let limits = WorldHeightLimits::from(&resolved);
+        assert!(limits.min_y() < 0 && limits.max_y() > 0);
+    }
+
+    #[test]
+    fn saved_worlds_keep_their_seed_and_preset() {
+        let path = temp_config("saved");
+        let new = args(&[
+            "--seed",
+            "7",
+            "--preset",
+            "amplified",
+            "--view-distance",
+            "5",
+        ]);
+        let config = WorldConfig::resolve(&new.unwrap(), &path).unwrap();
+        assert_eq!(
+            (
+                config.seed,
+                config.preset,
+                config.view_distance,
+                config.max_chunk_y
+            ),
+            (7, WorldPreset::Amplified, 5, 4)
+        );
+        config.save(&path).unwrap();
+
+        let other = args(&["--seed", "9", "--preset", "flat", "--view-distance", "2"]);
+        let reloaded = WorldConfig::resolve(&other.unwrap(), &path).unwrap();
+        assert_eq!(
+            reloaded,
+            WorldConfig {
+                view_distance: 2,
+                ..config
+            }
+        );
+    }
+}
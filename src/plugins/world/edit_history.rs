@@ -0,0 +1,554 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::plugins::{
+    player::{PlayerCollider, inventory::Inventory},
+    world::{
+        items::{ItemRegistry, ItemRegistryRes, ItemStack},
+        voxel::Voxel,
+        voxel_world::VoxelWorld,
+    },
+};
+use crate::state::LoadingState;
+
+pub struct EditHistoryPlugin;
+
+impl Plugin for EditHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditHistory>().add_systems(
+            Update,
+            undo_redo_edits.run_if(in_state(LoadingState::Initialized)),
+        );
+    }
+}
+
+/// A single voxel change. Keyed by world voxel coord rather than chunk entity,
+/// so it can still be replayed after the chunk holding it has been reloaded.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct VoxelEdit {
+    pub world: IVec3,
+    pub previous: Voxel,
+    pub new: Voxel,
+}
+
+/// Voxel edits that are undone and redone as one step, with the items the player got or
+/// used up making each of them.
+#[derive(Clone, Debug, Default)]
+pub struct EditTransaction {
+    edits: Vec<VoxelEdit>,
+    /// Taken back out of the inventory on undo, by index into `edits`.
+    gained: Vec<(usize, ItemStack)>,
+    /// Given back to the inventory on undo, by index into `edits`.
+    spent: Vec<(usize, ItemStack)>,
+}
+
+impl EditTransaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a voxel through `voxel_world` and record the change.
+    /// Returns the replaced voxel, or `None` if its chunk isn't loaded.
+    pub fn set(
+        &mut self,
+        voxel_world: &mut VoxelWorld,
+        world: IVec3,
+        voxel: Voxel,
+    ) -> Option<Voxel> {
+        let previous = voxel_world.set(world, voxel)?;
+        if previous != voxel {
+            self.edits.push(VoxelEdit {
+                world,
+                previous,
+                new: voxel,
+            });
+        }
+        Some(previous)
+    }
+
+    /// Set many voxels through `voxel_world` as one batch and record the changes.
+    pub fn set_batch(
+        &mut self,
+        voxel_world: &mut VoxelWorld,
+        voxels: impl IntoIterator<Item = (IVec3, Voxel)>,
+    ) {
+        self.edits.extend(voxel_world.set_batch(voxels));
+    }
+
+    /// Record that the player got `stack` from the edit at index `edit` of
+    /// [`edits`](Self::edits).
+    pub fn gain(&mut self, edit: usize, stack: ItemStack) {
+        if !stack.is_empty() {
+            self.gained.push((edit, stack));
+        }
+    }
+
+    /// Record that the player used up `stack` on the edit at index `edit` of
+    /// [`edits`](Self::edits).
+    pub fn spend(&mut self, edit: usize, stack: ItemStack) {
+        if !stack.is_empty() {
+            self.spent.push((edit, stack));
+        }
+    }
+
+    pub fn edits(&self) -> &[VoxelEdit] {
+        &self.edits
+    }
+
+    pub fn gained(&self) -> impl Iterator<Item = ItemStack> + '_ {
+        self.gained.iter().map(|(_, stack)| *stack)
+    }
+
+    pub fn spent(&self) -> impl Iterator<Item = ItemStack> + '_ {
+        self.spent.iter().map(|(_, stack)| *stack)
+    }
+
+    /// Whether undoing or redoing moves items in or out of an inventory.
+    pub fn moves_items(&self) -> bool {
+        !self.gained.is_empty() || !self.spent.is_empty()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// Whether every voxel the transaction changed is in a loaded chunk, so undoing or
+    /// redoing it changes all of them.
+    pub fn is_loaded(&self, voxel_world: &VoxelWorld) -> bool {
+        self.edits
+            .iter()
+            .all(|edit| voxel_world.get(edit.world).is_some())
+    }
+
+    /// The edits whose voxel is still `then(edit)`, with only their items. Voxels changed
+    /// since by something that isn't recorded, like falling, flowing or an explosion, are
+    /// left out.
+    fn still_current(
+        &self,
+        voxel_world: &VoxelWorld,
+        then: impl Fn(&VoxelEdit) -> Voxel,
+    ) -> EditTransaction {
+        let mut current = EditTransaction::new();
+        let mut moved = vec![None; self.edits.len()];
+        for (index, edit) in self.edits.iter().enumerate() {
+            if voxel_world.get(edit.world) == Some(then(edit)) {
+                moved[index] = Some(current.edits.len());
+                current.edits.push(*edit);
+            }
+        }
+        let keep = |items: &[(usize, ItemStack)]| {
+            items
+                .iter()
+                .filter_map(|(edit, stack)| Some((moved[*edit]?, *stack)))
+                .collect()
+        };
+        current.gained = keep(&self.gained);
+        current.spent = keep(&self.spent);
+        current
+    }
+
+    fn undo(&self, voxel_world: &mut VoxelWorld) {
+        voxel_world.set_batch(
+            self.edits
+                .iter()
+                .rev()
+                .map(|edit| (edit.world, edit.previous)),
+        );
+    }
+
+    fn redo(&self, voxel_world: &mut VoxelWorld) {
+        voxel_world.set_batch(self.edits.iter().map(|edit| (edit.world, edit.new)));
+    }
+}
+
+/// Bounded undo/redo stacks of committed [`EditTransaction`]s.
+#[derive(Resource, Debug)]
+pub struct EditHistory {
+    undo: VecDeque<EditTransaction>,
+    redo: Vec<EditTransaction>,
+    capacity: usize,
+}
+
+impl Default for EditHistory {
+    fn default() -> Self {
+        Self::with_capacity(256)
+    }
+}
+
+impl EditHistory {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            undo: VecDeque::with_capacity(capacity),
+            redo: Vec::new(),
+            capacity,
+        }
+    }
+
+    /// Commit an applied transaction. Clears the redo stack and drops the
+    /// oldest entry once `capacity` is exceeded.
+    pub fn push(&mut self, transaction: EditTransaction) {
+        if transaction.is_empty() {
+            return;
+        }
+
+        self.redo.clear();
+        self.undo.push_back(transaction);
+        while self.undo.len() > self.capacity {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Revert the most recent transaction, taking back the items it gave and returning
+    /// the ones it used up. Only edits whose voxel is still what they set are reverted,
+    /// and only their items moved; if none are, the transaction is dropped.
+    ///
+    /// Returns `false` and keeps the transaction if there was nothing to undo, some of its
+    /// chunks aren't loaded, or its items can't be moved: the inventory no longer holds
+    /// what it gave, has no room for what it used, or there's no inventory at all.
+    pub fn undo(
+        &mut self,
+        voxel_world: &mut VoxelWorld,
+        inventory: Option<&mut Inventory>,
+        registry: &ItemRegistry,
+    ) -> bool {
+        let Some(transaction) = self.undo.back() else {
+            return false;
+        };
+        if !transaction.is_loaded(voxel_world) {
+            debug!("Can't undo an edit of a chunk that isn't loaded");
+            return false;
+        }
+        let current = transaction.still_current(voxel_world, |edit| edit.new);
+        if current.is_empty() {
+            debug!("Dropped an edit whose voxels have all changed since");
+            self.undo.pop_back();
+            return false;
+        }
+        let (give, take): (Vec<_>, Vec<_>) =
+            (current.spent().collect(), current.gained().collect());
+        if !exchange(inventory, &give, &take, registry) {
+            return false;
+        }
+        self.undo.pop_back();
+        current.undo(voxel_world);
+        self.redo.push(current);
+        true
+    }
+
+    /// Re-apply the most recently undone transaction, moving its items again. Like
+    /// [`undo`](Self::undo), only edits whose voxel hasn't changed since are re-applied.
+    /// Returns `false` and keeps the transaction if there was nothing to redo, some of its
+    /// chunks aren't loaded, or its items can't be moved.
+    pub fn redo(
+        &mut self,
+        voxel_world: &mut VoxelWorld,
+        inventory: Option<&mut Inventory>,
+        registry: &ItemRegistry,
+    ) -> bool {
+        let Some(transaction) = self.redo.last() else {
+            return false;
+        };
+        if !transaction.is_loaded(voxel_world) {
+            debug!("Can't redo an edit of a chunk that isn't loaded");
+            return false;
+        }
+        let current = transaction.still_current(voxel_world, |edit| edit.previous);
+        if current.is_empty() {
+            debug!("Dropped an undone edit whose voxels have all changed since");
+            self.redo.pop();
+            return false;
+        }
+        let (give, take): (Vec<_>, Vec<_>) =
+            (current.gained().collect(), current.spent().collect());
+        if !exchange(inventory, &give, &take, registry) {
+            return false;
+        }
+        self.redo.pop();
+        current.redo(voxel_world);
+        self.undo.push_back(current);
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+/// Take every stack of `take` out of `inventory` and put every stack of `give` in, or
+/// leave it as it was and return `false` if that can't be done in full.
+fn exchange(
+    inventory: Option<&mut Inventory>,
+    give: &[ItemStack],
+    take: &[ItemStack],
+    registry: &ItemRegistry,
+) -> bool {
+    if give.is_empty() && take.is_empty() {
+        return true;
+    }
+    let Some(inventory) = inventory else {
+        return false;
+    };
+
+    let mut after = inventory.clone();
+    if !take.iter().all(|stack| after.remove(*stack))
+        || give
+            .iter()
+            .any(|stack| after.insert(*stack, registry).is_some())
+    {
+        return false;
+    }
+    *inventory = after;
+    true
+}
+
+/// Ctrl+Z undoes, Ctrl+Y or Ctrl+Shift+Z redoes.
+fn undo_redo_edits(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut history: ResMut<EditHistory>,
+    mut inventories: Query<&mut Inventory, With<PlayerCollider>>,
+    item_registry: Res<ItemRegistryRes>,
+    mut voxel_world: VoxelWorld,
+) {
+    if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let inventory = inventories.single_mut().ok();
+    let inventory = inventory.map(Mut::into_inner);
+
+    if keys.just_pressed(KeyCode::KeyY) || (shift && keys.just_pressed(KeyCode::KeyZ)) {
+        history.redo(&mut voxel_world, inventory, &item_registry.0);
+    } else if keys.just_pressed(KeyCode::KeyZ) {
+        history.undo(&mut voxel_world, inventory, &item_registry.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::plugins::asset_loader::assets::VoxelAtlasHandles;
+    use crate::plugins::world::{
+        ChunkEntityMap, MesherResource,
+        blocks::{BLOCK_SAND, BLOCK_STONE},
+        chunk::Chunk,
+        falling_blocks::FallingBlockPlugin,
+        meshers::NaiveMesher,
+        test_support::{headless_app, spawn_chunks},
+    };
+
+    fn history_app() -> App {
+        let mut app = headless_app(1);
+        app.init_resource::<ItemRegistryRes>()
+            .add_plugins(EditHistoryPlugin);
+        spawn_chunks(
+            app.world_mut(),
+            (0..2).map(|x| (IVec3::new(x, 0, 0), Chunk::new())),
+        );
+        app
+    }
+
+    /// Put stone at each of `positions` as one transaction.
+    fn place(app: &mut App, positions: Vec<IVec3>) {
+        app.world_mut()
+            .run_system_once(
+                move |mut voxel_world: VoxelWorld, mut history: ResMut<EditHistory>| {
+                    let mut transaction = EditTransaction::new();
+                    transaction.set_batch(
+                        &mut voxel_world,
+                        positions.iter().map(|p| (*p, Voxel::new(BLOCK_STONE))),
+                    );
+                    history.push(transaction);
+                },
+            )
+            .unwrap();
+    }
+
+    fn undo(app: &mut App) -> bool {
+        app.world_mut()
+            .run_system_once(
+                |mut voxel_world: VoxelWorld,
+                 mut history: ResMut<EditHistory>,
+                 items: Res<ItemRegistryRes>| {
+                    history.undo(&mut voxel_world, None, &items.0)
+                },
+            )
+            .unwrap()
+    }
+
+    fn redo(app: &mut App) -> bool {
+        app.world_mut()
+            .run_system_once(
+                |mut voxel_world: VoxelWorld,
+                 mut history: ResMut<EditHistory>,
+                 items: Res<ItemRegistryRes>| {
+                    history.redo(&mut voxel_world, None, &items.0)
+                },
+            )
+            .unwrap()
+    }
+
+    fn voxel(app: &mut App, world: IVec3) -> Option<Voxel> {
+        app.world_mut()
+            .run_system_once(move |voxel_world: VoxelWorld| voxel_world.get(world))
+            .unwrap()
+    }
+
+    #[test]
+    fn undo_and_redo_replay_a_transaction() {
+        let mut app = history_app();
+        let positions = vec![IVec3::new(1, 1, 1), IVec3::new(40, 1, 1)];
+        place(&mut app, positions.clone());
+        assert!(undo(&mut app));
+        assert!(!undo(&mut app));
+        for p in &positions {
+            assert_eq!(voxel(&mut app, *p), Some(Voxel::AIR));
+        }
+        assert!(redo(&mut app));
+        assert!(!redo(&mut app));
+        for p in &positions {
+            assert_eq!(voxel(&mut app, *p), Some(Voxel::new(BLOCK_STONE)));
+        }
+    }
+
+    #[test]
+    fn edits_of_unloaded_chunks_are_kept_for_later() {
+        let mut app = history_app();
+        let (near, far) = (IVec3::new(1, 1, 1), IVec3::new(40, 1, 1));
+        place(&mut app, vec![near, far]);
+
+        let far_chunk = app
+            .world_mut()
+            .resource_mut::<ChunkEntityMap>()
+            .get(&IVec3::X)
+            .unwrap();
+        app.world_mut().despawn(far_chunk);
+        assert!(!undo(&mut app));
+        assert_eq!(voxel(&mut app, near), Some(Voxel::new(BLOCK_STONE)));
+        let history = app.world().resource::<EditHistory>();
+        assert!(history.can_undo() && !history.can_redo());
+    }
+
+    #[test]
+    fn item_moves_need_an_inventory() {
+        let registry = ItemRegistryRes::default().0;
+        let stone = ItemStack::new(BLOCK_STONE, 2);
+        assert!(exchange(None, &[], &[], &registry));
+        assert!(!exchange(None, &[stone], &[], &registry));
+
+        let mut inventory = Inventory::default();
+        assert!(!exchange(Some(&mut inventory), &[], &[stone], &registry));
+        assert!(exchange(Some(&mut inventory), &[stone], &[], &registry));
+        assert_eq!(inventory.count(BLOCK_STONE), 2);
+        assert!(exchange(Some(&mut inventory), &[], &[stone], &registry));
+        assert_eq!(inventory.count(BLOCK_STONE), 0);
+    }
+
+    #[test]
+    fn undo_leaves_voxels_that_changed_since() {
+        let mut app = history_app();
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+            1.0 / 30.0,
+        )))
+        .init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<Assets<Mesh>>()
+        .insert_resource(MesherResource(Box::new(NaiveMesher)))
+        .insert_resource(VoxelAtlasHandles {
+            material: Handle::default(),
+            translucent_material: Handle::default(),
+        })
+        .add_plugins(FallingBlockPlugin);
+        let (floor, placed) = (IVec3::new(3, 0, 3), IVec3::new(3, 5, 3));
+        let landed = floor + IVec3::Y;
+
+        // Place sand in the air, using up the only one carried.
+        app.world_mut()
+            .run_system_once(
+                move |mut voxel_world: VoxelWorld, mut history: ResMut<EditHistory>| {
+                    voxel_world.set(floor, Voxel::new(BLOCK_STONE));
+                    let mut transaction = EditTransaction::new();
+                    transaction.set(&mut voxel_world, placed, Voxel::new(BLOCK_SAND));
+                    transaction.spend(0, ItemStack::new(BLOCK_SAND, 1));
+                    history.push(transaction);
+                },
+            )
+            .unwrap();
+        for _ in 0..60 {
+            app.update();
+        }
+        assert_eq!(voxel(&mut app, placed), Some(Voxel::AIR));
+        assert_eq!(voxel(&mut app, landed), Some(Voxel::new(BLOCK_SAND)));
+
+        // The sand fell, so there's nothing to undo and no sand to give back.
+        let (undone, inventory) = app
+            .world_mut()
+            .run_system_once(
+                |mut voxel_world: VoxelWorld,
+                 mut history: ResMut<EditHistory>,
+                 items: Res<ItemRegistryRes>| {
+                    let mut inventory = Inventory::default();
+                    let undone = history.undo(&mut voxel_world, Some(&mut inventory), &items.0);
+                    (undone, inventory)
+                },
+            )
+            .unwrap();
+        assert!(!undone);
+        assert_eq!(inventory.count(BLOCK_SAND), 0);
+        assert_eq!(voxel(&mut app, landed), Some(Voxel::new(BLOCK_SAND)));
+        let history = app.world().resource::<EditHistory>();
+        assert!(!history.can_undo() && !history.can_redo());
+    }
+
+    #[test]
+    fn undo_reverts_only_the_edits_still_in_place() {
+        let mut app = history_app();
+        let (kept, changed) = (IVec3::new(1, 1, 1), IVec3::new(2, 1, 1));
+        app.world_mut()
+            .run_system_once(
+                move |mut voxel_world: VoxelWorld, mut history: ResMut<EditHistory>| {
+                    let mut transaction = EditTransaction::new();
+                    transaction.set_batch(
+                        &mut voxel_world,
+                        [kept, changed].map(|p| (p, Voxel::new(BLOCK_STONE))),
+                    );
+                    for edit in 0..2 {
+                        transaction.spend(edit, ItemStack::new(BLOCK_STONE, 1));
+                    }
+                    history.push(transaction);
+                    // Blown away, without going through the history.
+                    voxel_world.set(changed, Voxel::AIR);
+                },
+            )
+            .unwrap();
+
+        let inventory = app
+            .world_mut()
+            .run_system_once(
+                |mut voxel_world: VoxelWorld,
+                 mut history: ResMut<EditHistory>,
+                 items: Res<ItemRegistryRes>| {
+                    let mut inventory = Inventory::default();
+                    assert!(history.undo(&mut voxel_world, Some(&mut inventory), &items.0));
+                    inventory
+                },
+            )
+            .unwrap();
+        assert_eq!(inventory.count(BLOCK_STONE), 1);
+        assert_eq!(voxel(&mut app, kept), Some(Voxel::AIR));
+        assert_eq!(voxel(&mut app, changed), Some(Voxel::AIR));
+    }
+}
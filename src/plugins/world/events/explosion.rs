@@ -0,0 +1,20 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use crate::plugins::world::blocks::BlockId;
+
+/// Triggered after an explosion carved its crater, listing the destroyed voxels by block.
+#[derive(Event, Debug, Clone)]
+pub struct ExplosionCarved {
+    pub center: Vec3,
+    pub radius: f32,
+    pub destroyed: BTreeMap<BlockId, Vec<IVec3>>,
+}
+
+impl ExplosionCarved {
+    /// Number of destroyed `block_id` voxels.
+    pub fn count(&self, block_id: BlockId) -> usize {
+        self.destroyed.get(&block_id).map_or(0, Vec::len)
+    }
+}
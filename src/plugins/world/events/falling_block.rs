@@ -0,0 +1,17 @@
+use bevy::prelude::*;
+
+use crate::plugins::world::voxel::Voxel;
+
+/// Triggered when a falling block settled back into the world at `world`.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct FallingBlockLanded {
+    pub world: IVec3,
+    pub voxel: Voxel,
+}
+
+/// Triggered when a falling block landed in an occupied cell at `world` and was discarded.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct FallingBlockDropped {
+    pub world: IVec3,
+    pub voxel: Voxel,
+}
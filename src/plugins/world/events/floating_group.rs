@@ -0,0 +1,10 @@
+use bevy::prelude::*;
+
+/// Triggered when an edit leaves solid voxels with no connection to an anchor, see
+/// [`StructuralIntegritySettings`](crate::plugins::world::structural_integrity::StructuralIntegritySettings).
+#[derive(Event, Debug, Clone)]
+pub struct FloatingGroupDetected {
+    pub voxels: Vec<IVec3>,
+    /// Whether the group was removed from the world and turned into a falling body.
+    pub detached: bool,
+}
@@ -1,3 +1,15 @@
-pub mod voxel_clicked;
+pub mod explosion;
+pub mod falling_block;
+pub mod floating_group;
+pub mod path_failed;
+pub mod voxel_broken;
+pub mod voxel_changed;
+pub mod voxel_placed;
 
-pub use voxel_clicked::*;
+pub use explosion::*;
+pub use falling_block::*;
+pub use floating_group::*;
+pub use path_failed::*;
+pub use voxel_broken::*;
+pub use voxel_changed::*;
+pub use voxel_placed::*;
@@ -0,0 +1,11 @@
+use bevy::prelude::*;
+
+use crate::plugins::world::pathfinding::{PathRequest, astar::PathError};
+
+/// Triggered when no path was found for the [`PathRequest`] of `entity`.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PathFailed {
+    pub entity: Entity,
+    pub request: PathRequest,
+    pub error: PathError,
+}
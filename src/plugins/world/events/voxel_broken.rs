@@ -0,0 +1,10 @@
+use bevy::prelude::*;
+
+use crate::plugins::world::voxel::Voxel;
+
+/// Triggered after a voxel tool broke `voxel` at the world voxel coord `world`.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct VoxelBroken {
+    pub world: IVec3,
+    pub voxel: Voxel,
+}
@@ -0,0 +1,12 @@
+use bevy::prelude::*;
+
+use crate::plugins::world::voxel::Voxel;
+
+/// Written by [`VoxelWorld`](crate::plugins::world::voxel_world::VoxelWorld) for every voxel
+/// it changes, whatever caused the change (tools, brushes, undo, simulations).
+#[derive(Message, Debug, Clone, Copy)]
+pub struct VoxelChanged {
+    pub world: IVec3,
+    pub previous: Voxel,
+    pub new: Voxel,
+}
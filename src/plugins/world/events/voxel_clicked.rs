@@ -1,108 +0,0 @@
-use bevy::prelude::*;
-
-use crate::plugins::world::{
-    ChunkEntityMap, Chunks,
-    blocks::BLOCK_STONE,
-    chunk::CHUNK_SIZE,
-    voxel::Voxel,
-    voxel_picking::{VoxelFace, VoxelHit},
-};
-
-#[derive(Event, Debug, Clone, Copy)]
-pub struct VoxelClicked {
-    pub hit: VoxelHit,
-    pub button: MouseButton,
-}
-
-pub fn on_voxel_clicked(
-    event: On<VoxelClicked>,
-    chunk_map: Res<ChunkEntityMap>,
-    mut chunks: ResMut<Chunks>,
-) {
-    let VoxelClicked { hit, button } = *event.event();
-
-    let local = hit.local.as_uvec3();
-    if local.x >= CHUNK_SIZE as u32 || local.y >= CHUNK_SIZE as u32 || local.z >= CHUNK_SIZE as u32
-    {
-        warn!("[event] VoxelClicked: local out of bounds");
-        return;
-    }
-
-    match button {
-        MouseButton::Left => {
-            let Some((chunk, local)) = adjacent_voxel(hit) else {
-                return;
-            };
-
-            let Some(entity) = chunk_map.get(&chunk) else {
-                return;
-            };
-
-            chunks.0.entry(entity).and_modify(move |chunk| {
-                chunk.set(
-                    local.x as usize,
-                    local.y as usize,
-                    local.z as usize,
-                    Voxel::new(BLOCK_STONE),
-                );
-            });
-        }
-        MouseButton::Right => {
-            let Some(entity) = chunk_map.get(&hit.chunk) else {
-                return;
-            };
-
-            chunks.0.entry(entity).and_modify(move |chunk| {
-                chunk.set(
-                    local.x as usize,
-                    local.y as usize,
-                    local.z as usize,
-                    Voxel::AIR,
-                );
-            });
-        }
-        _ => {}
-    }
-}
-
-fn adjacent_voxel(hit: VoxelHit) -> Option<(IVec3, IVec3)> {
-    let max = CHUNK_SIZE as i32 - 1;
-
-    let normal = match hit.face {
-        VoxelFace::PosX => IVec3::X,
-        VoxelFace::NegX => -IVec3::X,
-        VoxelFace::PosY => IVec3::Y,
-        VoxelFace::NegY => -IVec3::Y,
-        VoxelFace::PosZ => IVec3::Z,
-        VoxelFace::NegZ => -IVec3::Z,
-    };
-
-    let mut chunk = hit.chunk;
-    let mut local = hit.local + normal;
-
-    if local.x < 0 {
-        local.x = max;
-        chunk.x -= 1;
-    } else if local.x > max {
-        local.x = 0;
-        chunk.x += 1;
-    }
-
-    if local.y < 0 {
-        local.y = max;
-        chunk.y -= 1;
-    } else if local.y > max {
-        local.y = 0;
-        chunk.y += 1;
-    }
-
-    if local.z < 0 {
-        local.z = max;
-        chunk.z -= 1;
-    } else if local.z > max {
-        local.z = 0;
-        chunk.z += 1;
-    }
-
-    Some((chunk, local))
-}
@@ -0,0 +1,10 @@
+use bevy::prelude::*;
+
+use crate::plugins::world::voxel::Voxel;
+
+/// Triggered after a voxel tool placed `voxel` at the world voxel coord `world`.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct VoxelPlaced {
+    pub world: IVec3,
+    pub voxel: Voxel,
+}
@@ -0,0 +1,318 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    blocks::{BlockRegistry, BlockRegistryRes},
+    brushes::brush_inactive,
+    edit_history::{EditHistory, EditTransaction},
+    events::ExplosionCarved,
+    noise::value_noise_3d,
+    voxel::Voxel,
+    voxel_picking::HoveredVoxel,
+    voxel_tools::ToolBinding,
+    voxel_world::{VoxelAccess, VoxelWorld},
+};
+use crate::state::LoadingState;
+
+/// Size in voxels of the bumps along a crater's edge.
+const CRATER_NOISE_SCALE: f32 = 2.0;
+
+pub struct ExplosionPlugin;
+
+impl Plugin for ExplosionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ExplosionTool>().add_systems(
+            Update,
+            detonate_at_hovered_voxel
+                .run_if(brush_inactive)
+                .run_if(in_state(LoadingState::Initialized)),
+        );
+    }
+}
+
+/// A blast that destroys every voxel whose block resistance is below the blast strength
+/// there. Strength falls off linearly from `power` at the center to zero at a noisy edge
+/// around `radius`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Explosion {
+    pub center: Vec3,
+    pub radius: f32,
+    pub power: f32,
+    /// How far the crater edge strays from a sphere, as a fraction of `radius`.
+    pub roughness: f32,
+    pub seed: u64,
+}
+
+impl Explosion {
+    pub fn new(center: Vec3, radius: f32) -> Self {
+        Self {
+            center,
+            radius,
+            power: radius,
+            roughness: 0.3,
+            seed: 0,
+        }
+    }
+
+    pub fn with_power(mut self, power: f32) -> Self {
+        self.power = power;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Blast strength at the center of the voxel `world`.
+    pub fn strength_at(&self, world: IVec3) -> f32 {
+        let offset = world.as_vec3() + Vec3::splat(0.5) - self.center;
+        let noise = value_noise_3d(self.seed, offset / CRATER_NOISE_SCALE);
+        let edge = self.radius * (1.0 + self.roughness * (noise * 2.0 - 1.0));
+        if edge <= 0.0 {
+            return 0.0;
+        }
+        self.power * (1.0 - offset.length() / edge).max(0.0)
+    }
+
+    /// Voxels the blast destroys, in z, y, x order.
+    pub fn carve<W: VoxelAccess + ?Sized>(
+        &self,
+        world: &W,
+        registry: &BlockRegistry,
+    ) -> Vec<(IVec3, Voxel)> {
+        let reach = (self.radius * (1.0 + self.roughness)).ceil() as i32;
+        let center = self.center.floor().as_ivec3();
+        let mut destroyed = Vec::new();
+        for z in -reach..=reach {
+            for y in -reach..=reach {
+                for x in -reach..=reach {
+                    let position = center + IVec3::new(x, y, z);
+                    let Some(voxel) = world.get_voxel(position) else {
+                        continue;
+                    };
+                    if voxel.is_air() {
+                        continue;
+                    }
+                    if self.strength_at(position) > registry.resistance(voxel.block_id()) {
+                        destroyed.push((position, voxel));
+                    }
+                }
+            }
+        }
+        destroyed
+    }
+}
+
+/// Carve `explosion` into the world as one batch, so each affected chunk and neighbour is
+/// remeshed once. Returns the edit, for undo, and the summary to trigger.
+pub fn detonate(
+    voxel_world: &mut VoxelWorld,
+    registry: &BlockRegistry,
+    explosion: &Explosion,
+) -> (EditTransaction, ExplosionCarved) {
+    let destroyed_voxels = explosion.carve(&*voxel_world, registry);
+
+    let mut destroyed: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for (position, voxel) in &destroyed_voxels {
+        destroyed
+            .entry(voxel.block_id())
+            .or_default()
+            .push(*position);
+    }
+
+    let mut transaction = EditTransaction::new();
+    transaction.set_batch(
+        voxel_world,
+        destroyed_voxels
+            .into_iter()
+            .map(|(position, _)| (position, Voxel::AIR)),
+    );
+
+    let carved = ExplosionCarved {
+        center: explosion.center,
+        radius: explosion.radius,
+        destroyed,
+    };
+    (transaction, carved)
+}
+
+pub trait ExplodeCommandExt {
+    /// Detonate `explosion` and trigger [`ExplosionCarved`]. Not recorded in the edit history.
+    fn explode(&mut self, explosion: Explosion);
+}
+
+impl<'w, 's> ExplodeCommandExt for Commands<'w, 's> {
+    fn explode(&mut self, explosion: Explosion) {
+        self.queue(move |world: &mut World| {
+            if let Err(err) = world.run_system_cached_with(run_explosion, explosion) {
+                warn!("failed to run explosion: {err}");
+            }
+        })
+    }
+}
+
+fn run_explosion(
+    In(explosion): In<Explosion>,
+    mut commands: Commands,
+    block_registry: Res<BlockRegistryRes>,
+    mut voxel_world: VoxelWorld,
+) {
+    let (_, carved) = detonate(&mut voxel_world, &block_registry.0, &explosion);
+    commands.trigger(carved);
+}
+
+/// Debug tool that blows up the hovered voxel.
+#[derive(Resource, Clone, Debug)]
+pub struct ExplosionTool {
+    pub binding: ToolBinding,
+    pub radius: f32,
+    pub power: f32,
+    detonations: u64,
+}
+
+impl Default for ExplosionTool {
+    fn default() -> Self {
+        Self {
+            binding: ToolBinding::Key(KeyCode::KeyX),
+            radius: 4.0,
+            power: 4.0,
+            detonations: 0,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn detonate_at_hovered_voxel(
+    mut commands: Commands,
+    mouse: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut tool: ResMut<ExplosionTool>,
+    hovered: Res<HoveredVoxel>,
+    mut history: ResMut<EditHistory>,
+    block_registry: Res<BlockRegistryRes>,
+    mut voxel_world: VoxelWorld,
+) {
+    if !tool.binding.just_pressed(&mouse, &keys) {
+        return;
+    }
+    let Some(hit) = hovered.hit else {
+        return;
+    };
+
+    // Vary the crater shape between detonations.
+    tool.detonations += 1;
+    let explosion = Explosion::new(hit.world.as_vec3() + Vec3::splat(0.5), tool.radius)
+        .with_power(tool.power)
+        .with_seed(tool.detonations);
+    let (transaction, carved) = detonate(&mut voxel_world, &block_registry.0, &explosion);
+    history.push(transaction);
+    commands.trigger(carved);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::platform::collections::HashMap;
+
+    use super::*;
+    use crate::plugins::world::blocks::{BLOCK_BEDROCK, BLOCK_DIRT, BLOCK_STONE};
+
+    /// A 16-voxel cube around the origin: stone for x < 0, dirt for x >= 0, and a
+    /// bedrock layer at y -1.
+    fn stone_and_dirt() -> HashMap<IVec3, Voxel> {
+        let mut world = HashMap::new();
+        for z in -8..8 {
+            for y in -8..8 {
+                for x in -8..8 {
+                    let block = match (x, y) {
+                        (_, -1) => BLOCK_BEDROCK,
+                        (..0, _) => BLOCK_STONE,
+                        _ => BLOCK_DIRT,
+                    };
+                    world.insert(IVec3::new(x, y, z), Voxel::new(block));
+                }
+            }
+        }
+        world
+    }
+
+    #[test]
+    fn strength_falls_off_to_the_edge() {
+        let mut explosion = Explosion::new(Vec3::splat(0.5), 4.0).with_power(8.0);
+        explosion.roughness = 0.0;
+        assert_eq!(explosion.strength_at(IVec3::ZERO), 8.0);
+        assert_eq!(explosion.strength_at(IVec3::new(2, 0, 0)), 4.0);
+        assert_eq!(explosion.strength_at(IVec3::new(0, -3, 0)), 2.0);
+        assert_eq!(explosion.strength_at(IVec3::new(0, 0, 4)), 0.0);
+        assert_eq!(explosion.strength_at(IVec3::new(9, 9, 9)), 0.0);
+
+        // A rough edge strays from the sphere, but never past its reach.
+        let rough = Explosion::new(Vec3::splat(0.5), 4.0);
+        let strengths: Vec<f32> = (0..8)
+            .map(|x| rough.strength_at(IVec3::new(x, 0, 0)))
+            .collect();
+        assert!(strengths[3] > 0.0);
+        assert_eq!(rough.strength_at(IVec3::new(6, 0, 0)), 0.0);
+    }
+
+    #[test]
+    fn bedrock_survives_any_blast() {
+        let registry = BlockRegistryRes::default().0;
+        let world = stone_and_dirt();
+        let destroyed = Explosion::new(Vec3::ZERO, 6.0)
+            .with_power(1.0e6)
+            .carve(&world, &registry);
+        assert!(!destroyed.is_empty());
+        assert!(
+            destroyed
+                .iter()
+                .all(|(_, voxel)| voxel.block_id() != BLOCK_BEDROCK)
+        );
+        assert!(destroyed.iter().all(|(p, voxel)| world[p] == *voxel));
+    }
+
+    #[test]
+    fn resistance_shrinks_the_crater() {
+        let registry = BlockRegistryRes::default().0;
+        let world = stone_and_dirt();
+        let mut explosion = Explosion::new(Vec3::new(0.0, 3.0, 0.0), 5.0);
+        explosion.roughness = 0.0;
+
+        // Strength 5 falls to stone's 1.5 at 3.5 voxels out, and to dirt's 0.5 at 4.5.
+        let expected: Vec<IVec3> = box_around(explosion.center, 6)
+            .filter(|p| {
+                let reach = if p.x < 0 { 3.5 } else { 4.5 };
+                world
+                    .get(p)
+                    .is_some_and(|voxel| voxel.block_id() != BLOCK_BEDROCK)
+                    && (p.as_vec3() + Vec3::splat(0.5)).distance(explosion.center) < reach
+            })
+            .collect();
+        let destroyed: Vec<IVec3> = explosion
+            .carve(&world, &registry)
+            .into_iter()
+            .map(|(p, _)| p)
+            .collect();
+        assert_eq!(destroyed, expected);
+    }
+
+    /// Voxels within `reach` of `center` on each axis, in z, y, x order.
+    fn box_around(center: Vec3, reach: i32) -> impl Iterator<Item = IVec3> {
+        let center = center.floor().as_ivec3();
+        (-reach..=reach).flat_map(move |z| {
+            (-reach..=reach)
+                .flat_map(move |y| (-reach..=reach).map(move |x| center + IVec3::new(x, y, z)))
+        })
+    }
+
+    #[test]
+    fn the_same_seed_carves_the_same_crater() {
+        let registry = BlockRegistryRes::default().0;
+        let world = stone_and_dirt();
+        let explosion = Explosion::new(Vec3::ZERO, 5.0).with_seed(7);
+        let crater = explosion.carve(&world, &registry);
+        assert_eq!(crater, explosion.carve(&world, &registry));
+        assert_ne!(crater, explosion.with_seed(8).carve(&world, &registry));
+    }
+}
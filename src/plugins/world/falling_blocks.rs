@@ -0,0 +1,508 @@
+use bevy::ecs::system::RunSystemOnce;
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+
+use crate::plugins::asset_loader::assets::VoxelAtlasHandles;
+use crate::plugins::world::{
+    MesherResource,
+    blocks::{BlockRegistry, BlockRegistryRes},
+    chunk::Chunk,
+    events::{FallingBlockDropped, FallingBlockLanded, VoxelChanged},
+    meshers::Neighbors,
+    schematic::{Schematic, build_schematic_meshes},
+    voxel::Voxel,
+    voxel_world::{VoxelAccess, VoxelWorld},
+};
+use crate::state::LoadingState;
+
+pub struct FallingBlockPlugin;
+
+impl Plugin for FallingBlockPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FallingBlockSettings>()
+            .init_resource::<FallingBlockCandidates>()
+            .init_resource::<FallingBlockMeshes>()
+            .add_systems(
+                Update,
+                (
+                    collect_falling_block_candidates,
+                    release_unsupported_blocks,
+                    simulate_falling_blocks,
+                )
+                    .chain()
+                    .run_if(in_state(LoadingState::Initialized)),
+            );
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct FallingBlockSettings {
+    /// Downward acceleration in voxels per second squared.
+    pub gravity: f32,
+    /// Maximum fall speed in voxels per second.
+    pub terminal_velocity: f32,
+    /// Falling blocks below this height are discarded.
+    pub kill_height: f32,
+}
+
+impl Default for FallingBlockSettings {
+    fn default() -> Self {
+        Self {
+            gravity: 30.0,
+            terminal_velocity: 40.0,
+            kill_height: -1024.0,
+        }
+    }
+}
+
+/// Voxels that left the grid and fall as one rigid body: a single gravity block, or a
+/// group cut loose from the terrain. Its `Transform` holds the min corner of the body,
+/// which `voxels` are offset from.
+#[derive(Component, Clone, Debug)]
+pub struct FallingBody {
+    voxels: Vec<(IVec3, Voxel)>,
+    /// Offsets with no voxel of the body directly below them; these are what lands.
+    bottom: Vec<IVec3>,
+    /// Vertical velocity in voxels per second; negative is down.
+    pub velocity: f32,
+}
+
+impl FallingBody {
+    pub fn new(voxels: Vec<(IVec3, Voxel)>) -> Self {
+        let offsets: HashSet<IVec3> = voxels.iter().map(|(offset, _)| *offset).collect();
+        let bottom = offsets
+            .iter()
+            .copied()
+            .filter(|offset| !offsets.contains(&(offset + IVec3::NEG_Y)))
+            .collect();
+        Self {
+            voxels,
+            bottom,
+            velocity: 0.0,
+        }
+    }
+
+    pub fn single(voxel: Voxel) -> Self {
+        Self::new(vec![(IVec3::ZERO, voxel)])
+    }
+
+    pub fn voxels(&self) -> &[(IVec3, Voxel)] {
+        &self.voxels
+    }
+
+    /// Fall for `dt` seconds from `translation`, the body's min corner.
+    ///
+    /// Every cell passed below each bottom voxel is checked, so fast bodies can't tunnel
+    /// through floors. The body rests on whichever support it reaches first, and waits
+    /// without falling if it would pass into voxels that aren't loaded.
+    pub fn step<W: VoxelAccess + ?Sized>(
+        &mut self,
+        translation: Vec3,
+        dt: f32,
+        settings: &FallingBlockSettings,
+        registry: &BlockRegistry,
+        world: &W,
+    ) -> FallStep {
+        let velocity = (self.velocity - settings.gravity * dt).max(-settings.terminal_velocity);
+        let from_y = translation.y;
+        let to_y = from_y + velocity * dt;
+        let origin = translation.round().as_ivec3();
+
+        let mut landing_y: Option<i32> = None;
+        for offset in &self.bottom {
+            let mut cell_y = from_y.ceil() as i32 - 1;
+            while (cell_y + 1) as f32 > to_y {
+                let cell = IVec3::new(origin.x, cell_y, origin.z) + offset;
+                match world.get_voxel(cell) {
+                    None => {
+                        self.velocity = 0.0;
+                        return FallStep::Waiting;
+                    }
+                    Some(voxel) if registry.is_opaque(voxel.block_id()) => {
+                        landing_y = landing_y.max(Some(cell_y + 1));
+                        break;
+                    }
+                    Some(_) => cell_y -= 1,
+                }
+            }
+        }
+
+        self.velocity = velocity;
+        match landing_y {
+            Some(y) => FallStep::Landed(y),
+            None => FallStep::Falling(to_y),
+        }
+    }
+
+    /// Where the voxels go when the body comes to rest with its min corner at `landing`:
+    /// those that fit into non-opaque voxels, and those that are dropped because the
+    /// space is taken. `None` if any of them would land outside the loaded voxels.
+    pub fn land<W: VoxelAccess + ?Sized>(
+        &self,
+        landing: IVec3,
+        registry: &BlockRegistry,
+        world: &W,
+    ) -> Option<Landing> {
+        let mut outcome = Landing::default();
+        for &(offset, voxel) in &self.voxels {
+            let position = landing + offset;
+            let existing = world.get_voxel(position)?;
+            if registry.is_opaque(existing.block_id()) {
+                outcome.dropped.push((position, voxel));
+            } else {
+                outcome.placed.push((position, voxel));
+            }
+        }
+        Some(outcome)
+    }
+}
+
+/// Outcome of [`FallingBody::step`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FallStep {
+    /// Still falling, with its min corner now at this height.
+    Falling(f32),
+    /// Comes to rest with its min corner at this height.
+    Landed(i32),
+    /// Stays where it is until the voxels below it are loaded.
+    Waiting,
+}
+
+/// Outcome of [`FallingBody::land`], in world voxel coords.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Landing {
+    pub placed: Vec<(IVec3, Voxel)>,
+    pub dropped: Vec<(IVec3, Voxel)>,
+}
+
+/// Voxels to check for support on the next update.
+#[derive(Resource, Debug, Default)]
+pub struct FallingBlockCandidates(Vec<IVec3>);
+
+impl FallingBlockCandidates {
+    pub fn push(&mut self, world: IVec3) {
+        self.0.push(world);
+    }
+}
+
+/// Single-cube meshes, built once per voxel.
+#[derive(Resource, Default)]
+struct FallingBlockMeshes(HashMap<Voxel, Handle<Mesh>>);
+
+/// Whether `voxel` is held up by whatever is below it. Unloaded voxels count as support.
+fn is_supported(registry: &BlockRegistry, below: Option<Voxel>) -> bool {
+    below.is_none_or(|below| registry.is_opaque(below.block_id()))
+}
+
+/// An edit can place an unsupported gravity block, or remove the support of the one above.
+fn collect_falling_block_candidates(
+    mut changes: MessageReader<VoxelChanged>,
+    mut candidates: ResMut<FallingBlockCandidates>,
+) {
+    for change in changes.read() {
+        candidates.push(change.world);
+        candidates.push(change.world + IVec3::Y);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn release_unsupported_blocks(
+    mut commands: Commands,
+    mut candidates: ResMut<FallingBlockCandidates>,
+    mut falling_meshes: ResMut<FallingBlockMeshes>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mesher: Res<MesherResource>,
+    handles: Option<Res<VoxelAtlasHandles>>,
+    block_registry: Res<BlockRegistryRes>,
+    mut voxel_world: VoxelWorld,
+) {
+    // Blocks only fall once they can be drawn; until then the candidates wait.
+    let Some(handles) = handles else {
+        return;
+    };
+    if candidates.0.is_empty() {
+        return;
+    }
+    let registry = &block_registry.0;
+
+    let mut seen = HashSet::new();
+    let mut positions = std::mem::take(&mut candidates.0);
+    positions.retain(|position| seen.insert(*position));
+
+    for position in positions {
+        let Some(voxel) = voxel_world.get(position) else {
+            continue;
+        };
+        if !registry.has_gravity(voxel.block_id())
+            || is_supported(registry, voxel_world.get(position + IVec3::NEG_Y))
+        {
+            continue;
+        }
+
+        voxel_world.set(position, Voxel::AIR);
+
+        let mesh = falling_meshes
+            .0
+            .entry(voxel)
+            .or_insert_with(|| {
+                let mut chunk = Chunk::new();
+                chunk.set(0, 0, 0, voxel);
+                meshes.add(mesher.0.build_mesh(&chunk, Neighbors::default(), registry))
+            })
+            .clone();
+
+        commands.spawn((
+            FallingBody::single(voxel),
+            Mesh3d(mesh),
+            MeshMaterial3d(handles.material.clone()),
+            Transform::from_translation(position.as_vec3()),
+        ));
+    }
+}
+
+fn simulate_falling_blocks(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<FallingBlockSettings>,
+    block_registry: Res<BlockRegistryRes>,
+    mut falling: Query<(Entity, &mut FallingBody, &mut Transform)>,
+    mut voxel_world: VoxelWorld,
+) {
+    let registry = &block_registry.0;
+    let dt = time.delta_secs();
+
+    for (entity, mut body, mut transform) in falling.iter_mut() {
+        let origin = transform.translation.round().as_ivec3();
+        let landing_y =
+            match body.step(transform.translation, dt, &settings, registry, &voxel_world) {
+                FallStep::Falling(y) => {
+                    transform.translation.y = y;
+                    if y < settings.kill_height {
+                        commands.entity(entity).despawn();
+                    }
+                    continue;
+                }
+                FallStep::Waiting => continue,
+                FallStep::Landed(y) => y,
+            };
+
+        let landing = IVec3::new(origin.x, landing_y, origin.z);
+        let Some(outcome) = body.land(landing, registry, &voxel_world) else {
+            body.velocity = 0.0;
+            continue;
+        };
+        commands.entity(entity).despawn();
+        for &(world, voxel) in &outcome.placed {
+            commands.trigger(FallingBlockLanded { world, voxel });
+        }
+        for &(world, voxel) in &outcome.dropped {
+            commands.trigger(FallingBlockDropped { world, voxel });
+        }
+        voxel_world.set_batch(outcome.placed);
+    }
+}
+
+pub trait SpawnFallingBodyCommandExt {
+    /// Spawn a falling body whose min corner is at `origin`, with `voxels` offset from it.
+    /// The voxels should already have been removed from the world; they are put back if
+    /// the voxel atlas to draw the body with isn't loaded.
+    fn spawn_falling_body(&mut self, origin: IVec3, voxels: Vec<(IVec3, Voxel)>);
+}
+
+impl<'w, 's> SpawnFallingBodyCommandExt for Commands<'w, 's> {
+    fn spawn_falling_body(&mut self, origin: IVec3, voxels: Vec<(IVec3, Voxel)>) {
+        self.queue(move |world: &mut World| {
+            let size = voxels.iter().fold(IVec3::ZERO, |size, (offset, _)| {
+                size.max(offset + IVec3::ONE)
+            });
+            let lookup: HashMap<IVec3, Voxel> = voxels.iter().copied().collect();
+            let schematic = Schematic::from_fn(size.as_uvec3(), |local| {
+                lookup.get(&local.as_ivec3()).copied().unwrap_or(Voxel::AIR)
+            });
+            let Some(children) = build_schematic_meshes(world, &schematic) else {
+                warn!("spawn_falling_body called before the voxel atlas was loaded");
+                let restored: Vec<(IVec3, Voxel)> = voxels
+                    .into_iter()
+                    .map(|(offset, voxel)| (origin + offset, voxel))
+                    .collect();
+                world
+                    .run_system_once(move |mut voxel_world: VoxelWorld| {
+                        voxel_world.set_batch(restored.iter().copied());
+                    })
+                    .expect("voxel world is available");
+                return;
+            };
+
+            world
+                .spawn((
+                    FallingBody::new(voxels),
+                    Transform::from_translation(origin.as_vec3()),
+                    Visibility::default(),
+                ))
+                .with_children(|parent| {
+                    for child in children {
+                        parent.spawn(child);
+                    }
+                });
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::world::{
+        blocks::{BLOCK_SAND, BLOCK_STONE, BLOCK_WATER},
+        test_support::{headless_world, spawn_chunks},
+    };
+
+    /// A stone floor at y 0.
+    fn floor() -> HashMap<IVec3, Voxel> {
+        let mut world = HashMap::new();
+        for x in -4..=4 {
+            for z in -4..=4 {
+                world.insert(IVec3::new(x, 0, z), Voxel::new(BLOCK_STONE));
+            }
+        }
+        world
+    }
+
+    /// Step `body` from `translation` until it stops falling, at most `steps` times.
+    fn fall<W: VoxelAccess + ?Sized>(
+        body: &mut FallingBody,
+        mut translation: Vec3,
+        dt: f32,
+        world: &W,
+        steps: usize,
+    ) -> (FallStep, Vec3) {
+        let registry = BlockRegistryRes::default().0;
+        let settings = FallingBlockSettings::default();
+        for _ in 0..steps {
+            match body.step(translation, dt, &settings, &registry, world) {
+                FallStep::Falling(y) => translation.y = y,
+                stopped => return (stopped, translation),
+            }
+        }
+        (FallStep::Falling(translation.y), translation)
+    }
+
+    #[test]
+    fn falls_onto_the_floor() {
+        let world = floor();
+        let mut body = FallingBody::single(Voxel::new(BLOCK_SAND));
+        let (step, _) = fall(
+            &mut body,
+            Vec3::new(1.0, 10.0, 2.0),
+            1.0 / 64.0,
+            &world,
+            200,
+        );
+        assert_eq!(step, FallStep::Landed(1));
+
+        let registry = BlockRegistryRes::default().0;
+        let landing = body.land(IVec3::new(1, 1, 2), &registry, &world).unwrap();
+        assert_eq!(
+            landing.placed,
+            [(IVec3::new(1, 1, 2), Voxel::new(BLOCK_SAND))]
+        );
+        assert!(landing.dropped.is_empty());
+    }
+
+    #[test]
+    fn fast_bodies_do_not_tunnel_through_the_floor() {
+        let world = floor();
+        let mut body = FallingBody::single(Voxel::new(BLOCK_SAND));
+        body.velocity = -40.0;
+        let (step, _) = fall(&mut body, Vec3::new(0.0, 20.0, 0.0), 1.0, &world, 1);
+        assert_eq!(step, FallStep::Landed(1));
+    }
+
+    #[test]
+    fn groups_rest_on_their_highest_support() {
+        let mut world = floor();
+        world.insert(IVec3::new(2, 1, 0), Voxel::new(BLOCK_STONE));
+        world.insert(IVec3::new(2, 2, 0), Voxel::new(BLOCK_STONE));
+        world.insert(IVec3::new(0, 1, 0), Voxel::new(BLOCK_WATER));
+        // A bar along x from 0 to 2, with a voxel hanging under its middle.
+        let sand = Voxel::new(BLOCK_SAND);
+        let mut body = FallingBody::new(vec![
+            (IVec3::new(0, 1, 0), sand),
+            (IVec3::new(1, 1, 0), sand),
+            (IVec3::new(2, 1, 0), sand),
+            (IVec3::new(1, 0, 0), sand),
+        ]);
+        let (step, _) = fall(
+            &mut body,
+            Vec3::new(0.0, 12.0, 0.0),
+            1.0 / 64.0,
+            &world,
+            500,
+        );
+        assert_eq!(step, FallStep::Landed(2));
+
+        let registry = BlockRegistryRes::default().0;
+        let landing = body.land(IVec3::new(0, 2, 0), &registry, &world).unwrap();
+        assert_eq!(landing.placed.len(), 4);
+
+        // Landing into the pillar drops what doesn't fit; water is replaced.
+        let landing = body.land(IVec3::new(0, 0, 0), &registry, &world).unwrap();
+        assert_eq!(
+            landing.dropped,
+            [(IVec3::new(2, 1, 0), sand), (IVec3::new(1, 0, 0), sand)]
+        );
+        assert!(landing.placed.contains(&(IVec3::new(0, 1, 0), sand)));
+    }
+
+    /// `floor`, with only `x < 2` loaded.
+    struct HalfLoaded(HashMap<IVec3, Voxel>);
+
+    impl VoxelAccess for HalfLoaded {
+        fn get_voxel(&self, world: IVec3) -> Option<Voxel> {
+            (world.x < 2).then(|| self.0.get_voxel(world)).flatten()
+        }
+
+        fn set_voxel(&mut self, world: IVec3, voxel: Voxel) -> Option<Voxel> {
+            (world.x < 2)
+                .then(|| self.0.set_voxel(world, voxel))
+                .flatten()
+        }
+    }
+
+    #[test]
+    fn waits_above_voxels_that_are_not_loaded() {
+        let world = HalfLoaded(floor());
+        let mut body = FallingBody::single(Voxel::new(BLOCK_SAND));
+        let start = Vec3::new(3.0, 6.0, 0.0);
+        let (step, at) = fall(&mut body, start, 1.0 / 64.0, &world, 50);
+        assert_eq!((step, at), (FallStep::Waiting, start));
+        assert_eq!(body.velocity, 0.0);
+
+        // A bar lands on the loaded side, but would stick out into the rest.
+        let sand = Voxel::new(BLOCK_SAND);
+        let bar = FallingBody::new(vec![(IVec3::ZERO, sand), (IVec3::X * 2, sand)]);
+        let registry = BlockRegistryRes::default().0;
+        assert_eq!(bar.land(IVec3::new(0, 1, 0), &registry, &world), None);
+        assert!(bar.land(IVec3::new(-2, 1, 0), &registry, &world).is_some());
+    }
+
+    #[test]
+    fn bodies_without_an_atlas_put_their_voxels_back() {
+        let mut world = headless_world(0);
+        spawn_chunks(&mut world, [(IVec3::ZERO, Chunk::new())]);
+
+        let sand = Voxel::new(BLOCK_SAND);
+        world.commands().spawn_falling_body(
+            IVec3::new(3, 4, 5),
+            vec![(IVec3::ZERO, sand), (IVec3::Y, sand)],
+        );
+        world.flush();
+
+        assert!(world.query::<&FallingBody>().iter(&world).next().is_none());
+        let restored = world
+            .run_system_once(|voxel_world: VoxelWorld| {
+                [IVec3::new(3, 4, 5), IVec3::new(3, 5, 5)].map(|p| voxel_world.get(p))
+            })
+            .unwrap();
+        assert_eq!(restored, [Some(sand); 2]);
+    }
+}
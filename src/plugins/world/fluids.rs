@@ -0,0 +1,527 @@
+use bevy::platform::collections::HashSet;
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    ChunkEntityMap, Chunks,
+    block_state::{BoolProperty, IntProperty},
+    blocks::{BlockId, BlockRegistry, BlockRegistryRes},
+    events::VoxelChanged,
+    voxel::Voxel,
+    voxel_world::{VoxelAccess, VoxelWorld, get_voxel},
+};
+use crate::state::LoadingState;
+
+/// Height of a fluid surface that has no fluid of the same kind above it.
+pub const FLUID_SURFACE_HEIGHT: f32 = 0.875;
+
+const NEIGHBOURS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+const HORIZONTAL_NEIGHBOURS: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+
+pub struct FluidPlugin;
+
+impl Plugin for FluidPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FluidSettings>()
+            .init_resource::<FluidSimulation>()
+            .add_systems(
+                Update,
+                schedule_changed_fluids.run_if(in_state(LoadingState::Initialized)),
+            )
+            // Counted in fixed steps, so fluids flow at the same pace at any frame rate.
+            .add_systems(
+                FixedUpdate,
+                tick_fluids.run_if(in_state(LoadingState::Initialized)),
+            );
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct FluidSettings {
+    /// Fixed steps between simulation steps.
+    pub fixed_steps_per_tick: u32,
+    /// Cells evaluated per step; the rest wait for the next step.
+    pub max_updates_per_tick: usize,
+}
+
+impl Default for FluidSettings {
+    fn default() -> Self {
+        Self {
+            // A quarter of a second at the default 64 Hz.
+            fixed_steps_per_tick: 16,
+            max_updates_per_tick: 4096,
+        }
+    }
+}
+
+/// Distance from the nearest source or fall; `0` on sources and falling fluid.
+pub const FLUID_LEVEL: IntProperty = IntProperty::new("level", 0, FluidState::MAX_DISTANCE as u16);
+/// Set on fluid fed from directly above.
+pub const FLUID_FALLING: BoolProperty = BoolProperty::new("falling");
+
+/// Typed view of a fluid voxel's [`FLUID_LEVEL`] and [`FLUID_FALLING`] properties.
+///
+/// The default state is a source, so `Voxel::new(BLOCK_WATER)` places a source block.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FluidState {
+    pub distance: u8,
+    pub falling: bool,
+}
+
+impl FluidState {
+    pub const MAX_DISTANCE: u8 = 7;
+    pub const SOURCE: Self = Self {
+        distance: 0,
+        falling: false,
+    };
+    pub const FALLING: Self = Self {
+        distance: 0,
+        falling: true,
+    };
+
+    pub fn flowing(distance: u8) -> Self {
+        Self {
+            distance: distance.clamp(1, Self::MAX_DISTANCE),
+            falling: false,
+        }
+    }
+
+    pub fn of(registry: &BlockRegistry, voxel: Voxel) -> Self {
+        Self {
+            distance: registry.property(voxel, &FLUID_LEVEL).unwrap_or(0) as u8,
+            falling: registry.property(voxel, &FLUID_FALLING).unwrap_or(false),
+        }
+    }
+
+    pub fn is_source(self) -> bool {
+        self == Self::SOURCE
+    }
+
+    pub fn is_falling(self) -> bool {
+        self.falling
+    }
+
+    pub fn distance(self) -> u8 {
+        self.distance
+    }
+
+    /// Surface height within the voxel, ignoring fluid stacked above.
+    pub fn height(self) -> f32 {
+        if self.is_source() || self.is_falling() {
+            return FLUID_SURFACE_HEIGHT;
+        }
+        let levels = Self::MAX_DISTANCE as f32 + 1.0;
+        FLUID_SURFACE_HEIGHT * (levels - self.distance as f32) / levels
+    }
+
+    /// A `block_id` voxel in this state. `block_id` must be registered as a fluid.
+    pub fn voxel(self, registry: &BlockRegistry, block_id: BlockId) -> Voxel {
+        let voxel = Voxel::new(block_id);
+        registry
+            .with_property(voxel, &FLUID_LEVEL, self.distance as u16)
+            .and_then(|voxel| registry.with_property(voxel, &FLUID_FALLING, self.falling))
+            .expect("fluid blocks declare level and falling")
+    }
+}
+
+/// Cellular automaton for water, lava and any other fluid in the [`BlockRegistry`].
+///
+/// Only scheduled cells are evaluated. Each step computes the next state of every
+/// evaluated cell from the same snapshot before writing any of them, and visits cells in
+/// coordinate order, so the result is deterministic for a given world and schedule.
+#[derive(Resource, Default, Debug)]
+pub struct FluidSimulation {
+    pending: HashSet<IVec3>,
+    ticks: u64,
+}
+
+impl FluidSimulation {
+    /// Evaluate `world` and its six neighbours on the next step.
+    pub fn schedule(&mut self, world: IVec3) {
+        self.pending.insert(world);
+        self.pending
+            .extend(NEIGHBOURS.iter().map(|offset| world + *offset));
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_settled(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Number of steps run so far.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Advance the simulation one step, evaluating at most `max_updates` cells.
+    /// Returns the voxels that changed, in the order they were written.
+    pub fn step(
+        &mut self,
+        world: &mut impl VoxelAccess,
+        registry: &BlockRegistry,
+        max_updates: usize,
+    ) -> Vec<(IVec3, Voxel)> {
+        let mut cells: Vec<IVec3> = self.pending.iter().copied().collect();
+        cells.sort_unstable_by_key(|cell| (cell.y, cell.x, cell.z));
+        cells.truncate(max_updates);
+        for cell in &cells {
+            self.pending.remove(cell);
+        }
+
+        let updates: Vec<(IVec3, Voxel)> = cells
+            .into_iter()
+            .filter_map(|cell| {
+                let current = world.get_voxel(cell)?;
+                let next = next_voxel(world, registry, cell, current)?;
+                (next != current).then_some((cell, next))
+            })
+            .collect();
+
+        for &(cell, voxel) in &updates {
+            world.set_voxel(cell, voxel);
+            self.schedule(cell);
+        }
+
+        self.ticks += 1;
+        updates
+    }
+
+    /// Step until nothing is pending or `max_steps` is reached. Returns the steps taken.
+    pub fn run_until_settled(
+        &mut self,
+        world: &mut impl VoxelAccess,
+        registry: &BlockRegistry,
+        max_steps: usize,
+    ) -> usize {
+        let mut steps = 0;
+        while !self.is_settled() && steps < max_steps {
+            self.step(world, registry, usize::MAX);
+            steps += 1;
+        }
+        steps
+    }
+}
+
+fn fluid_at(
+    world: &impl VoxelAccess,
+    registry: &BlockRegistry,
+    cell: IVec3,
+) -> Option<(BlockId, FluidState)> {
+    let voxel = world.get_voxel(cell)?;
+    registry
+        .is_fluid(voxel.block_id())
+        .then(|| (voxel.block_id(), FluidState::of(registry, voxel)))
+}
+
+/// Fluid only spreads sideways when it can't fall: it rests on something that isn't air
+/// or flowing fluid of its own kind. Unloaded voxels count as support.
+fn can_spread_sideways(
+    world: &impl VoxelAccess,
+    registry: &BlockRegistry,
+    cell: IVec3,
+    block_id: BlockId,
+) -> bool {
+    match world.get_voxel(cell + IVec3::NEG_Y) {
+        None => true,
+        Some(below) if below.is_air() => false,
+        Some(below) if below.block_id() == block_id => FluidState::of(registry, below).is_source(),
+        Some(_) => true,
+    }
+}
+
+/// The voxel `cell` should hold next step, or `None` if it isn't affected by fluids.
+fn next_voxel(
+    world: &impl VoxelAccess,
+    registry: &BlockRegistry,
+    cell: IVec3,
+    current: Voxel,
+) -> Option<Voxel> {
+    let current_is_fluid = registry.is_fluid(current.block_id());
+    if !current.is_air() && !current_is_fluid {
+        return None;
+    }
+    if current_is_fluid && FluidState::of(registry, current).is_source() {
+        return None;
+    }
+
+    if let Some((block_id, _)) = fluid_at(world, registry, cell + IVec3::Y) {
+        return Some(FluidState::FALLING.voxel(registry, block_id));
+    }
+
+    let neighbours = HORIZONTAL_NEIGHBOURS.map(|offset| {
+        let neighbour = cell + offset;
+        fluid_at(world, registry, neighbour).map(|(block_id, state)| (neighbour, block_id, state))
+    });
+
+    // Two sources of an infinite fluid refill the supported cell between them.
+    let supported = |block_id: BlockId| match world.get_voxel(cell + IVec3::NEG_Y) {
+        Some(below) if below.is_air() => false,
+        Some(below) if registry.is_fluid(below.block_id()) => {
+            below.block_id() == block_id && FluidState::of(registry, below).is_source()
+        }
+        _ => true,
+    };
+    for (_, block_id, state) in neighbours.iter().flatten() {
+        let sources = neighbours
+            .iter()
+            .flatten()
+            .filter(|(_, other, other_state)| other == block_id && other_state.is_source())
+            .count();
+        if state.is_source()
+            && sources >= 2
+            && registry
+                .fluid(*block_id)
+                .is_some_and(|fluid| fluid.infinite)
+            && supported(*block_id)
+        {
+            return Some(FluidState::SOURCE.voxel(registry, *block_id));
+        }
+    }
+
+    // Closest feeding neighbour wins; ties go to the lower block id.
+    let flow = neighbours
+        .iter()
+        .flatten()
+        .filter(|(neighbour, block_id, _)| {
+            can_spread_sideways(world, registry, *neighbour, *block_id)
+        })
+        .filter_map(|(_, block_id, state)| {
+            let decay = registry.fluid(*block_id)?.decay;
+            let distance = if state.is_source() || state.is_falling() {
+                decay
+            } else {
+                state.distance().saturating_add(decay)
+            };
+            (distance <= FluidState::MAX_DISTANCE).then_some((distance, *block_id))
+        })
+        .min();
+
+    match flow {
+        Some((distance, block_id)) => Some(FluidState::flowing(distance).voxel(registry, block_id)),
+        None if current_is_fluid => Some(Voxel::AIR),
+        None => None,
+    }
+}
+
+/// Wake the simulation around edits that touch or border a fluid.
+fn schedule_changed_fluids(
+    mut changes: MessageReader<VoxelChanged>,
+    mut simulation: ResMut<FluidSimulation>,
+    block_registry: Res<BlockRegistryRes>,
+    chunk_map: Res<ChunkEntityMap>,
+    chunks: Res<Chunks>,
+) {
+    let registry = &block_registry.0;
+    for change in changes.read() {
+        let near_fluid = registry.is_fluid(change.previous.block_id())
+            || registry.is_fluid(change.new.block_id())
+            || NEIGHBOURS.iter().any(|offset| {
+                get_voxel(&chunk_map, &chunks, change.world + *offset)
+                    .is_some_and(|voxel| registry.is_fluid(voxel.block_id()))
+            });
+        if near_fluid {
+            simulation.schedule(change.world);
+        }
+    }
+}
+
+fn tick_fluids(
+    settings: Res<FluidSettings>,
+    mut steps: Local<u32>,
+    mut simulation: ResMut<FluidSimulation>,
+    block_registry: Res<BlockRegistryRes>,
+    mut voxel_world: VoxelWorld,
+) {
+    *steps += 1;
+    if *steps < settings.fixed_steps_per_tick {
+        return;
+    }
+    *steps = 0;
+
+    if !simulation.is_settled() {
+        simulation.step(
+            &mut voxel_world,
+            &block_registry.0,
+            settings.max_updates_per_tick,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::platform::collections::HashMap;
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::plugins::world::{
+        blocks::{BLOCK_LAVA, BLOCK_STONE, BLOCK_WATER, BlockRegistryRes},
+        chunk::{CHUNK_SIZE, Chunk},
+        test_support::{headless_app, spawn_chunks},
+    };
+
+    /// A stone floor at y 0, 25 voxels across.
+    fn floor() -> HashMap<IVec3, Voxel> {
+        let mut world = HashMap::new();
+        for x in -12..=12 {
+            for z in -12..=12 {
+                world.insert(IVec3::new(x, 0, z), Voxel::new(BLOCK_STONE));
+            }
+        }
+        world
+    }
+
+    /// `floor` with `block` poured at each of `sources`, run until it settles.
+    fn pour(block: BlockId, sources: &[IVec3]) -> (HashMap<IVec3, Voxel>, FluidSimulation) {
+        let registry = BlockRegistryRes::default().0;
+        let mut world = floor();
+        let mut simulation = FluidSimulation::default();
+        for source in sources {
+            world.set_voxel(*source, Voxel::new(block));
+            simulation.schedule(*source);
+        }
+        simulation.run_until_settled(&mut world, &registry, 200);
+        assert!(simulation.is_settled());
+        (world, simulation)
+    }
+
+    fn state(world: &HashMap<IVec3, Voxel>, at: IVec3) -> Option<FluidState> {
+        let registry = BlockRegistryRes::default().0;
+        world
+            .get(&at)
+            .filter(|voxel| registry.is_fluid(voxel.block_id()))
+            .map(|voxel| FluidState::of(&registry, *voxel))
+    }
+
+    #[test]
+    fn water_spreads_seven_voxels() {
+        let (world, _) = pour(BLOCK_WATER, &[IVec3::new(0, 1, 0)]);
+        assert!(state(&world, IVec3::new(0, 1, 0)).unwrap().is_source());
+        assert_eq!(state(&world, IVec3::new(7, 1, 0)).unwrap().distance(), 7);
+        assert_eq!(state(&world, IVec3::new(3, 1, 2)).unwrap().distance(), 5);
+        assert_eq!(state(&world, IVec3::new(8, 1, 0)), None);
+        assert_eq!(state(&world, IVec3::new(0, 2, 0)), None);
+    }
+
+    #[test]
+    fn spreading_is_deterministic() {
+        let sorted = |world: HashMap<IVec3, Voxel>| {
+            let mut voxels: Vec<_> = world.into_iter().map(|(p, v)| (p.to_array(), v)).collect();
+            voxels.sort_by_key(|(p, _)| *p);
+            voxels
+        };
+        let sources = [IVec3::new(0, 1, 0), IVec3::new(4, 3, -2)];
+        assert_eq!(
+            sorted(pour(BLOCK_WATER, &sources).0),
+            sorted(pour(BLOCK_WATER, &sources).0)
+        );
+    }
+
+    #[test]
+    fn drains_when_the_source_is_removed() {
+        let registry = BlockRegistryRes::default().0;
+        let source = IVec3::new(0, 1, 0);
+        let (mut world, mut simulation) = pour(BLOCK_WATER, &[source]);
+        world.set_voxel(source, Voxel::AIR);
+        simulation.schedule(source);
+        simulation.run_until_settled(&mut world, &registry, 200);
+        assert!(
+            world
+                .values()
+                .all(|voxel| !registry.is_fluid(voxel.block_id()))
+        );
+    }
+
+    #[test]
+    fn falls_then_spreads_lava_less_far() {
+        let (world, _) = pour(BLOCK_LAVA, &[IVec3::new(0, 6, 0)]);
+        for y in 1..6 {
+            assert!(state(&world, IVec3::new(0, y, 0)).unwrap().is_falling());
+        }
+        assert_eq!(state(&world, IVec3::new(1, 6, 0)), None);
+        assert_eq!(state(&world, IVec3::new(3, 1, 0)).unwrap().distance(), 6);
+        assert_eq!(state(&world, IVec3::new(4, 1, 0)), None);
+    }
+
+    #[test]
+    fn two_sources_make_a_third() {
+        let (world, _) = pour(BLOCK_WATER, &[IVec3::new(-1, 1, 0), IVec3::new(1, 1, 0)]);
+        assert!(state(&world, IVec3::new(0, 1, 0)).unwrap().is_source());
+    }
+
+    #[test]
+    fn states_fit_in_a_voxel() {
+        let registry = BlockRegistryRes::default().0;
+        for distance in 0..=FluidState::MAX_DISTANCE {
+            for state in [FluidState::flowing(distance), FluidState::FALLING] {
+                let voxel = state.voxel(&registry, BLOCK_WATER);
+                assert_eq!(voxel.block_id(), BLOCK_WATER);
+                assert_eq!(FluidState::of(&registry, voxel), state);
+            }
+        }
+    }
+
+    /// A chunk with a stone floor at y 0, simulated by the plugin one fixed step per update.
+    fn fluid_app() -> App {
+        let mut app = headless_app(0);
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_micros(
+            15_625,
+        )))
+        .add_plugins(FluidPlugin);
+
+        let mut chunk = Chunk::new();
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set(x, 0, z, Voxel::new(BLOCK_STONE));
+            }
+        }
+        spawn_chunks(app.world_mut(), [(IVec3::ZERO, chunk)]);
+        app
+    }
+
+    #[test]
+    fn ticks_every_sixteen_fixed_steps() {
+        let mut app = fluid_app();
+        let source = IVec3::new(16, 1, 16);
+        app.world_mut()
+            .run_system_once(move |mut voxel_world: VoxelWorld| {
+                voxel_world.set(source, Voxel::new(BLOCK_WATER));
+            })
+            .unwrap();
+        let ticks = |app: &App| app.world().resource::<FluidSimulation>().ticks();
+
+        for _ in 0..8 {
+            app.update();
+        }
+        let start = ticks(&app);
+        for _ in 0..16 {
+            app.update();
+        }
+        assert_eq!(ticks(&app), start + 1);
+        for _ in 0..32 {
+            app.update();
+        }
+        assert_eq!(ticks(&app), start + 3);
+
+        let world = app.world();
+        let flowed = get_voxel(
+            world.resource::<ChunkEntityMap>(),
+            world.resource::<Chunks>(),
+            source + IVec3::X,
+        )
+        .unwrap();
+        assert_eq!(flowed.block_id(), BLOCK_WATER);
+    }
+}
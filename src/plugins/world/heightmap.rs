@@ -0,0 +1,346 @@
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    ChunkEntityMap, Chunks,
+    blocks::{BlockId, BlockRegistry, BlockRegistryRes},
+    chunk::{CHUNK_SIZE, Chunk},
+    config::WorldHeightLimits,
+    voxel::Voxel,
+};
+
+const CHUNK_AREA: usize = CHUNK_SIZE * CHUNK_SIZE;
+
+/// Which voxels count towards a heightmap.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum HeightmapKind {
+    /// Ground: blocks that aren't fluids or foliage.
+    Solid,
+    /// Any block that isn't a fluid, so tree tops and the floor under water.
+    NonFluid,
+    /// Anything that stops or slows movement: every block, fluids included.
+    MotionBlocking,
+}
+
+impl HeightmapKind {
+    pub const ALL: [HeightmapKind; 3] = [Self::Solid, Self::NonFluid, Self::MotionBlocking];
+
+    /// Whether voxels of `id` count towards this heightmap.
+    pub fn matches(self, registry: &BlockRegistry, id: BlockId) -> bool {
+        match self {
+            Self::Solid => registry.is_opaque(id) && !registry.is_foliage(id),
+            Self::NonFluid => registry.is_opaque(id),
+            Self::MotionBlocking => id != 0,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Highest voxel of each [`HeightmapKind`] in each voxel column of one chunk column, in
+/// world y.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ColumnHeightmap(Box<[[Option<i32>; HeightmapKind::ALL.len()]; CHUNK_AREA]>);
+
+impl Default for ColumnHeightmap {
+    fn default() -> Self {
+        Self(Box::new([[None; HeightmapKind::ALL.len()]; CHUNK_AREA]))
+    }
+}
+
+impl ColumnHeightmap {
+    /// Highest voxel of `kind` at local `(x, z)`, or `None` if the column has none.
+    pub fn get(&self, kind: HeightmapKind, x: usize, z: usize) -> Option<i32> {
+        self.0[x + z * CHUNK_SIZE][kind.index()]
+    }
+
+    fn set(&mut self, kind: HeightmapKind, x: usize, z: usize, top: Option<i32>) {
+        self.0[x + z * CHUNK_SIZE][kind.index()] = top;
+    }
+}
+
+/// Heightmaps of the chunk columns with loaded chunks, kept up to date as chunks load and
+/// unload and as voxels are set through [`VoxelWorld`](super::voxel_world::VoxelWorld).
+#[derive(Resource, Debug, Default)]
+pub struct Heightmaps {
+    columns: HashMap<IVec2, ColumnHeightmap>,
+}
+
+impl Heightmaps {
+    pub fn column(&self, column: IVec2) -> Option<&ColumnHeightmap> {
+        self.columns.get(&column)
+    }
+
+    /// World y of the highest voxel of `kind` at `(world_x, world_z)`, if its column has
+    /// any loaded.
+    pub fn top(&self, kind: HeightmapKind, world_x: i32, world_z: i32) -> Option<i32> {
+        let (column, x, z) = split_column(world_x, world_z);
+        self.column(column)?.get(kind, x, z)
+    }
+
+    /// The highest voxel of `kind` at `(world_x, world_z)`.
+    pub fn top_voxel(&self, kind: HeightmapKind, world_x: i32, world_z: i32) -> Option<IVec3> {
+        self.top(kind, world_x, world_z)
+            .map(|y| IVec3::new(world_x, y, world_z))
+    }
+
+    /// The highest ground voxel at `(world_x, world_z)`, ignoring fluids and foliage.
+    pub fn top_solid(&self, world_x: i32, world_z: i32) -> Option<IVec3> {
+        self.top_voxel(HeightmapKind::Solid, world_x, world_z)
+    }
+
+    /// The highest block at `(world_x, world_z)` that isn't a fluid.
+    pub fn top_non_fluid(&self, world_x: i32, world_z: i32) -> Option<IVec3> {
+        self.top_voxel(HeightmapKind::NonFluid, world_x, world_z)
+    }
+
+    /// The highest voxel at `(world_x, world_z)` that isn't air.
+    pub fn top_motion_blocking(&self, world_x: i32, world_z: i32) -> Option<IVec3> {
+        self.top_voxel(HeightmapKind::MotionBlocking, world_x, world_z)
+    }
+
+    /// Recompute the chunk column `column` from its loaded chunks, or forget it if none
+    /// are loaded.
+    pub fn rebuild_column<'a>(
+        &mut self,
+        column: IVec2,
+        chunk_at: impl Fn(IVec3) -> Option<&'a Chunk>,
+        registry: &BlockRegistry,
+        limits: &WorldHeightLimits,
+    ) {
+        let chunks: Vec<(i32, &Chunk)> = (limits.min_chunk_y..=limits.max_chunk_y)
+            .rev()
+            .filter_map(|y| chunk_at(IVec3::new(column.x, y, column.y)).map(|chunk| (y, chunk)))
+            .collect();
+        if chunks.is_empty() {
+            self.columns.remove(&column);
+            return;
+        }
+
+        let mut heightmap = ColumnHeightmap::default();
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                for kind in HeightmapKind::ALL {
+                    let top = chunks.iter().find_map(|(chunk_y, chunk)| {
+                        (0..CHUNK_SIZE)
+                            .rev()
+                            .find(|y| kind.matches(registry, chunk.get(x, *y, z).block_id()))
+                            .map(|y| chunk_y * CHUNK_SIZE as i32 + y as i32)
+                    });
+                    heightmap.set(kind, x, z, top);
+                }
+            }
+        }
+        self.columns.insert(column, heightmap);
+    }
+
+    /// Update the column at `world` after its voxel was set to `voxel`. When the top voxel
+    /// of a kind is removed, the column is searched downwards through `get` for the next
+    /// one.
+    pub fn voxel_changed(
+        &mut self,
+        world: IVec3,
+        voxel: Voxel,
+        registry: &BlockRegistry,
+        limits: &WorldHeightLimits,
+        get: impl Fn(IVec3) -> Option<Voxel>,
+    ) {
+        let (column, x, z) = split_column(world.x, world.z);
+        let Some(heightmap) = self.columns.get_mut(&column) else {
+            return;
+        };
+
+        for kind in HeightmapKind::ALL {
+            let top = heightmap.get(kind, x, z);
+            if kind.matches(registry, voxel.block_id()) {
+                if top.is_none_or(|top| world.y > top) {
+                    heightmap.set(kind, x, z, Some(world.y));
+                }
+            } else if top == Some(world.y) {
+                let below = (limits.min_y()..world.y).rev().find(|y| {
+                    get(IVec3::new(world.x, *y, world.z))
+                        .is_some_and(|voxel| kind.matches(registry, voxel.block_id()))
+                });
+                heightmap.set(kind, x, z, below);
+            }
+        }
+    }
+}
+
+/// Chunk column of `(world_x, world_z)`, and the local x and z within it.
+fn split_column(world_x: i32, world_z: i32) -> (IVec2, usize, usize) {
+    let size = CHUNK_SIZE as i32;
+    (
+        IVec2::new(world_x.div_euclid(size), world_z.div_euclid(size)),
+        world_x.rem_euclid(size) as usize,
+        world_z.rem_euclid(size) as usize,
+    )
+}
+
+/// Recompute the heightmap of the chunk column holding `coord`, after a chunk there was
+/// loaded or unloaded.
+pub fn rebuild_column_heightmap(world: &mut World, coord: IVec3) {
+    world.resource_scope(|world, mut heightmaps: Mut<Heightmaps>| {
+        let chunk_map = world.resource::<ChunkEntityMap>();
+        let chunks = world.resource::<Chunks>();
+        heightmaps.rebuild_column(
+            coord.xz(),
+            |coord| {
+                chunk_map
+                    .get(&coord)
+                    .and_then(|entity| chunks.0.get(&entity))
+            },
+            &world.resource::<BlockRegistryRes>().0,
+            world.resource::<WorldHeightLimits>(),
+        );
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::plugins::world::{
+        blocks::{BLOCK_DIRT, BLOCK_LEAVES, BLOCK_STONE, BLOCK_WATER},
+        test_support::{headless_world, spawn_chunks},
+        voxel_world::VoxelWorld,
+    };
+
+    /// Two chunks high, with stone up to y 4, a water voxel on it at (1, 1) and a leaves
+    /// voxel floating at (2, 8, 2).
+    fn heightmap_world() -> World {
+        let mut world = headless_world(1);
+        let mut ground = Chunk::new();
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                for y in 0..5 {
+                    ground.set(x, y, z, Voxel::new(BLOCK_STONE));
+                }
+            }
+        }
+        ground.set(1, 5, 1, Voxel::new(BLOCK_WATER));
+        ground.set(2, 8, 2, Voxel::new(BLOCK_LEAVES));
+        spawn_chunks(
+            &mut world,
+            [(IVec3::ZERO, ground), (IVec3::Y, Chunk::new())],
+        );
+        world
+    }
+
+    fn set(world: &mut World, voxels: &[(IVec3, Voxel)]) {
+        let voxels = voxels.to_vec();
+        world
+            .run_system_once(move |mut voxel_world: VoxelWorld| {
+                for &(pos, voxel) in &voxels {
+                    voxel_world.set(pos, voxel);
+                }
+            })
+            .unwrap();
+    }
+
+    /// Solid, non-fluid and motion blocking tops at `(x, z)`.
+    fn tops(world: &World, x: i32, z: i32) -> [Option<i32>; 3] {
+        HeightmapKind::ALL.map(|kind| world.resource::<Heightmaps>().top(kind, x, z))
+    }
+
+    #[test]
+    fn kinds_skip_fluids_and_foliage() {
+        let world = heightmap_world();
+        assert_eq!(tops(&world, 1, 1), [Some(4), Some(4), Some(5)]);
+        assert_eq!(tops(&world, 2, 2), [Some(4), Some(8), Some(8)]);
+        assert_eq!(tops(&world, 3, 3), [Some(4); 3]);
+        assert_eq!(tops(&world, 40, 3), [None; 3]);
+        let heightmaps = world.resource::<Heightmaps>();
+        assert_eq!(heightmaps.top_solid(2, 2), Some(IVec3::new(2, 4, 2)));
+    }
+
+    #[test]
+    fn placing_above_the_top_raises_it() {
+        let mut world = heightmap_world();
+        set(
+            &mut world,
+            &[
+                (IVec3::new(1, 20, 1), Voxel::new(BLOCK_WATER)),
+                (IVec3::new(2, 12, 2), Voxel::new(BLOCK_DIRT)),
+                (IVec3::new(3, 40, 3), Voxel::new(BLOCK_DIRT)),
+                (IVec3::new(4, 2, 4), Voxel::new(BLOCK_DIRT)),
+            ],
+        );
+        assert_eq!(tops(&world, 1, 1), [Some(4), Some(4), Some(20)]);
+        assert_eq!(tops(&world, 2, 2), [Some(12); 3]);
+        assert_eq!(tops(&world, 3, 3), [Some(40); 3]);
+        assert_eq!(tops(&world, 4, 4), [Some(4); 3]);
+    }
+
+    #[test]
+    fn removing_the_top_finds_the_next_one_down() {
+        let mut world = heightmap_world();
+        set(
+            &mut world,
+            &[
+                (IVec3::new(1, 20, 1), Voxel::new(BLOCK_WATER)),
+                (IVec3::new(3, 40, 3), Voxel::new(BLOCK_DIRT)),
+            ],
+        );
+        set(
+            &mut world,
+            &[
+                (IVec3::new(1, 20, 1), Voxel::AIR),
+                (IVec3::new(3, 40, 3), Voxel::AIR),
+                (IVec3::new(2, 8, 2), Voxel::AIR),
+                (IVec3::new(1, 4, 1), Voxel::AIR),
+                (IVec3::new(5, 3, 5), Voxel::AIR),
+            ],
+        );
+        assert_eq!(tops(&world, 1, 1), [Some(3), Some(3), Some(5)]);
+        assert_eq!(tops(&world, 2, 2), [Some(4); 3]);
+        assert_eq!(tops(&world, 3, 3), [Some(4); 3]);
+        assert_eq!(tops(&world, 5, 5), [Some(4); 3]);
+    }
+
+    #[test]
+    fn despawning_a_chunk_forgets_it() {
+        let mut world = heightmap_world();
+        let chunk_map = world.resource::<ChunkEntityMap>();
+        let (ground, above) = (
+            chunk_map.get(&IVec3::ZERO).unwrap(),
+            chunk_map.get(&IVec3::Y).unwrap(),
+        );
+        world
+            .resource_mut::<Chunks>()
+            .0
+            .get_mut(&above)
+            .unwrap()
+            .clear_dirty();
+
+        world.despawn(ground);
+        assert!(
+            world
+                .resource::<ChunkEntityMap>()
+                .get(&IVec3::ZERO)
+                .is_none()
+        );
+        assert!(!world.resource::<Chunks>().0.contains_key(&ground));
+        assert!(world.resource::<Chunks>().0[&above].is_dirty());
+        assert_eq!(tops(&world, 3, 3), [None; 3]);
+    }
+
+    #[test]
+    fn columns_without_chunks_have_no_heightmap() {
+        let mut world = heightmap_world();
+        let mut heightmaps = Heightmaps::default();
+        let registry = &world.resource::<BlockRegistryRes>().0;
+        let limits = world.resource::<WorldHeightLimits>();
+        heightmaps.rebuild_column(IVec2::ZERO, |_| None, registry, limits);
+        assert!(heightmaps.column(IVec2::ZERO).is_none());
+        world.resource_mut::<Heightmaps>().columns.clear();
+        set(
+            &mut world,
+            &[(IVec3::new(1, 20, 1), Voxel::new(BLOCK_DIRT))],
+        );
+        assert_eq!(tops(&world, 1, 1), [None; 3]);
+    }
+}
@@ -0,0 +1,444 @@
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    blocks::{
+        BLOCK_COAL_ORE, BLOCK_DIRT, BLOCK_GRASS, BLOCK_GRAVEL, BLOCK_IRON_ORE, BLOCK_LEAVES,
+        BLOCK_LOG, BLOCK_SAND, BLOCK_STONE, BlockId, BlockTiles, TileId,
+    },
+    noise::FeatureRng,
+    voxel::Voxel,
+};
+
+/// Block items share their block's id; items that aren't blocks start at
+/// [`Voxel::BLOCKS`], past every block id.
+pub type ItemId = u16;
+
+pub const ITEM_COAL: ItemId = Voxel::BLOCKS;
+pub const ITEM_RAW_IRON: ItemId = Voxel::BLOCKS + 1;
+pub const ITEM_FLINT: ItemId = Voxel::BLOCKS + 2;
+pub const ITEM_STICK: ItemId = Voxel::BLOCKS + 3;
+
+/// Stack size for items that don't set their own.
+pub const DEFAULT_MAX_STACK: u32 = 64;
+
+/// A number of the same item, held in one inventory slot.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ItemStack {
+    pub item: ItemId,
+    pub count: u32,
+}
+
+impl ItemStack {
+    pub fn new(item: ItemId, count: u32) -> Self {
+        Self { item, count }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Move as much of `other` onto this stack as fits under `max_stack`. Returns what's
+    /// left of `other`, or `None` if all of it fit.
+    pub fn merge(&mut self, other: ItemStack, max_stack: u32) -> Option<ItemStack> {
+        if other.item != self.item {
+            return Some(other);
+        }
+        let moved = other.count.min(max_stack.saturating_sub(self.count));
+        self.count += moved;
+        let left = other.count - moved;
+        (left > 0).then_some(ItemStack::new(other.item, left))
+    }
+
+    /// Take up to `count` items off this stack. Returns `None` if nothing was taken.
+    pub fn split(&mut self, count: u32) -> Option<ItemStack> {
+        let taken = count.min(self.count);
+        self.count -= taken;
+        (taken > 0).then_some(ItemStack::new(self.item, taken))
+    }
+}
+
+/// How an item is drawn in the hotbar.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ItemIcon {
+    /// A tile of the block atlas.
+    Tile(TileId),
+    /// A flat swatch, for items without a tile.
+    Color(Color),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ItemInfo {
+    pub name: &'static str,
+    pub max_stack: u32,
+    /// Block placed by using the item.
+    pub block: Option<BlockId>,
+    pub icon: ItemIcon,
+}
+
+/// One roll of a [`LootTable`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LootEntry {
+    pub item: ItemId,
+    pub min: u32,
+    pub max: u32,
+    /// Chance of dropping anything at all, from 0 to 1.
+    pub chance: f32,
+}
+
+impl LootEntry {
+    /// Always exactly `count` of `item`.
+    pub fn always(item: ItemId, count: u32) -> Self {
+        Self {
+            item,
+            min: count,
+            max: count,
+            chance: 1.0,
+        }
+    }
+
+    /// One `item`, `chance` of the time.
+    pub fn sometimes(item: ItemId, chance: f32) -> Self {
+        Self {
+            item,
+            min: 1,
+            max: 1,
+            chance,
+        }
+    }
+}
+
+/// What a block drops when broken. Every entry is rolled on its own.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LootTable(pub Vec<LootEntry>);
+
+impl LootTable {
+    pub fn roll(&self, rng: &mut FeatureRng) -> Vec<ItemStack> {
+        let mut drops = Vec::new();
+        for entry in &self.0 {
+            if entry.chance < 1.0 && rng.next_f32() >= entry.chance {
+                continue;
+            }
+            // A `max` below `min` always drops `min`.
+            let span = entry.max.saturating_sub(entry.min).saturating_add(1);
+            let count = entry.min + rng.below(span);
+            if count > 0 {
+                drops.push(ItemStack::new(entry.item, count));
+            }
+        }
+        drops
+    }
+}
+
+pub struct ItemRegistry {
+    items: HashMap<ItemId, ItemInfo>,
+    loot: HashMap<BlockId, LootTable>,
+}
+
+impl ItemRegistry {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            items: HashMap::with_capacity(capacity),
+            loot: HashMap::with_capacity(capacity),
+        }
+    }
+
+    pub fn get(&self, id: ItemId) -> Option<&ItemInfo> {
+        self.items.get(&id)
+    }
+
+    /// Most of `id` one slot holds; unknown items don't stack.
+    #[inline]
+    pub fn max_stack(&self, id: ItemId) -> u32 {
+        self.items.get(&id).map_or(1, |info| info.max_stack)
+    }
+
+    /// Block placed by using `id`, if it's a block item.
+    #[inline]
+    pub fn block(&self, id: ItemId) -> Option<BlockId> {
+        self.items.get(&id).and_then(|info| info.block)
+    }
+
+    /// Item that places `block_id`, if it has one.
+    #[inline]
+    pub fn block_item(&self, block_id: BlockId) -> Option<ItemId> {
+        self.items
+            .get(&block_id)
+            .is_some_and(|info| info.block == Some(block_id))
+            .then_some(block_id)
+    }
+
+    /// Register `id`, returning what was registered there before.
+    ///
+    /// # Panics
+    /// If `id` is in the range of the other kind of item, so that a block item and one
+    /// that isn't never replace each other.
+    pub fn insert(&mut self, id: ItemId, info: ItemInfo) -> Option<ItemInfo> {
+        assert_eq!(
+            info.block.is_some(),
+            id < Voxel::BLOCKS,
+            "item {id} ({}) is in the id range of the other kind of item",
+            info.name
+        );
+        self.items.insert(id, info)
+    }
+
+    /// Register the item for `block_id`, shown with the block's side tile and dropping
+    /// itself when broken.
+    pub fn insert_block(&mut self, block_id: BlockId, name: &'static str) -> Option<ItemInfo> {
+        self.loot
+            .insert(block_id, LootTable(vec![LootEntry::always(block_id, 1)]));
+        self.insert(
+            block_id,
+            ItemInfo {
+                name,
+                max_stack: DEFAULT_MAX_STACK,
+                block: Some(block_id),
+                icon: ItemIcon::Tile(BlockTiles::new(block_id).side),
+            },
+        )
+    }
+
+    /// Replace what `block_id` drops when broken.
+    pub fn set_loot(&mut self, block_id: BlockId, loot: LootTable) {
+        self.loot.insert(block_id, loot);
+    }
+
+    pub fn loot(&self, block_id: BlockId) -> Option<&LootTable> {
+        self.loot.get(&block_id)
+    }
+
+    /// Roll what breaking `block_id` drops. Blocks without loot drop nothing.
+    pub fn drops(&self, block_id: BlockId, rng: &mut FeatureRng) -> Vec<ItemStack> {
+        self.loot
+            .get(&block_id)
+            .map(|loot| loot.roll(rng))
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Resource)]
+pub struct ItemRegistryRes(pub ItemRegistry);
+
+impl Default for ItemRegistryRes {
+    fn default() -> Self {
+        let mut registry = ItemRegistry::with_capacity(32);
+
+        registry.insert_block(BLOCK_GRASS, "Grass");
+        registry.insert_block(BLOCK_DIRT, "Dirt");
+        registry.insert_block(BLOCK_STONE, "Stone");
+        registry.insert_block(BLOCK_SAND, "Sand");
+        registry.insert_block(BLOCK_GRAVEL, "Gravel");
+        registry.insert_block(BLOCK_COAL_ORE, "Coal Ore");
+        registry.insert_block(BLOCK_IRON_ORE, "Iron Ore");
+        registry.insert_block(BLOCK_LOG, "Log");
+        registry.insert_block(BLOCK_LEAVES, "Leaves");
+
+        for (id, name, color) in [
+            (ITEM_COAL, "Coal", Color::srgb(0.1, 0.1, 0.1)),
+            (ITEM_RAW_IRON, "Raw Iron", Color::srgb(0.75, 0.6, 0.5)),
+            (ITEM_FLINT, "Flint", Color::srgb(0.3, 0.3, 0.32)),
+            (ITEM_STICK, "Stick", Color::srgb(0.5, 0.35, 0.2)),
+        ] {
+            registry.insert(
+                id,
+                ItemInfo {
+                    name,
+                    max_stack: DEFAULT_MAX_STACK,
+                    block: None,
+                    icon: ItemIcon::Color(color),
+                },
+            );
+        }
+
+        registry.set_loot(
+            BLOCK_GRASS,
+            LootTable(vec![LootEntry::always(BLOCK_DIRT, 1)]),
+        );
+        registry.set_loot(
+            BLOCK_GRAVEL,
+            LootTable(vec![
+                LootEntry::always(BLOCK_GRAVEL, 1),
+                LootEntry::sometimes(ITEM_FLINT, 0.1),
+            ]),
+        );
+        registry.set_loot(
+            BLOCK_COAL_ORE,
+            LootTable(vec![LootEntry::always(ITEM_COAL, 1)]),
+        );
+        registry.set_loot(
+            BLOCK_IRON_ORE,
+            LootTable(vec![LootEntry::always(ITEM_RAW_IRON, 1)]),
+        );
+        registry.set_loot(
+            BLOCK_LEAVES,
+            LootTable(vec![LootEntry {
+                item: ITEM_STICK,
+                min: 1,
+                max: 2,
+                chance: 0.2,
+            }]),
+        );
+
+        ItemRegistryRes(registry)
+    }
+}
+
+/// Random numbers for loot rolls, seeded from the world seed.
+#[derive(Resource, Clone, Debug)]
+pub struct LootRng(pub FeatureRng);
+
+impl LootRng {
+    pub fn new(seed: u64) -> Self {
+        Self(FeatureRng::for_cell(seed, IVec3::ZERO))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::world::blocks::{BLOCK_BEDROCK, BLOCK_WATER};
+
+    #[test]
+    fn merge_fills_up_to_max_stack() {
+        let mut stack = ItemStack::new(BLOCK_STONE, 60);
+        assert_eq!(
+            stack.merge(ItemStack::new(BLOCK_STONE, 10), 64),
+            Some(ItemStack::new(BLOCK_STONE, 6))
+        );
+        assert_eq!(stack.count, 64);
+        assert_eq!(
+            stack.merge(ItemStack::new(BLOCK_STONE, 1), 64),
+            Some(ItemStack::new(BLOCK_STONE, 1))
+        );
+
+        let mut stack = ItemStack::new(BLOCK_DIRT, 3);
+        assert_eq!(stack.merge(ItemStack::new(BLOCK_DIRT, 3), 64), None);
+        assert_eq!(stack.count, 6);
+    }
+
+    #[test]
+    fn merge_leaves_other_items_alone() {
+        let mut stack = ItemStack::new(BLOCK_STONE, 1);
+        assert_eq!(
+            stack.merge(ItemStack::new(BLOCK_DIRT, 5), 64),
+            Some(ItemStack::new(BLOCK_DIRT, 5))
+        );
+        assert_eq!(stack, ItemStack::new(BLOCK_STONE, 1));
+    }
+
+    #[test]
+    fn split_takes_at_most_what_is_there() {
+        let mut stack = ItemStack::new(BLOCK_DIRT, 6);
+        assert_eq!(stack.split(4), Some(ItemStack::new(BLOCK_DIRT, 4)));
+        assert_eq!(stack.split(10), Some(ItemStack::new(BLOCK_DIRT, 2)));
+        assert!(stack.is_empty());
+        assert_eq!(stack.split(1), None);
+    }
+
+    #[test]
+    fn blocks_drop_their_loot() {
+        let registry = ItemRegistryRes::default().0;
+        let mut rng = FeatureRng::for_cell(1, IVec3::ZERO);
+        assert_eq!(
+            registry.drops(BLOCK_STONE, &mut rng),
+            vec![ItemStack::new(BLOCK_STONE, 1)]
+        );
+        assert_eq!(
+            registry.drops(BLOCK_GRASS, &mut rng),
+            vec![ItemStack::new(BLOCK_DIRT, 1)]
+        );
+        assert_eq!(
+            registry.drops(BLOCK_COAL_ORE, &mut rng),
+            vec![ItemStack::new(ITEM_COAL, 1)]
+        );
+        assert!(registry.drops(BLOCK_BEDROCK, &mut rng).is_empty());
+        assert!(registry.drops(BLOCK_WATER, &mut rng).is_empty());
+
+        let flint = (0..1000)
+            .filter(|_| {
+                registry
+                    .drops(BLOCK_GRAVEL, &mut rng)
+                    .iter()
+                    .any(|stack| stack.item == ITEM_FLINT)
+            })
+            .count();
+        assert!((60..140).contains(&flint), "{flint} flint in 1000");
+    }
+
+    #[test]
+    fn loot_counts_stay_in_range() {
+        let mut rng = FeatureRng::for_cell(2, IVec3::ZERO);
+        let table = |min, max| {
+            LootTable(vec![LootEntry {
+                item: ITEM_STICK,
+                min,
+                max,
+                chance: 1.0,
+            }])
+        };
+
+        for _ in 0..100 {
+            let count = table(1, 3).roll(&mut rng)[0].count;
+            assert!((1..=3).contains(&count));
+        }
+        assert_eq!(
+            table(4, 2).roll(&mut rng),
+            vec![ItemStack::new(ITEM_STICK, 4)]
+        );
+        assert!(table(0, u32::MAX).roll(&mut rng).len() <= 1);
+        assert_eq!(
+            table(u32::MAX, u32::MAX).roll(&mut rng),
+            vec![ItemStack::new(ITEM_STICK, u32::MAX)]
+        );
+    }
+
+    #[test]
+    fn block_items_place_their_block() {
+        let registry = ItemRegistryRes::default().0;
+        assert_eq!(registry.block_item(BLOCK_STONE), Some(BLOCK_STONE));
+        assert_eq!(registry.block(BLOCK_STONE), Some(BLOCK_STONE));
+        assert_eq!(registry.block_item(BLOCK_WATER), None);
+        assert_eq!(registry.block(ITEM_COAL), None);
+        assert_eq!(registry.max_stack(ITEM_COAL), DEFAULT_MAX_STACK);
+        assert_eq!(registry.max_stack(9999), 1);
+    }
+
+    fn item(block: Option<BlockId>) -> ItemInfo {
+        ItemInfo {
+            name: "Test",
+            max_stack: DEFAULT_MAX_STACK,
+            block,
+            icon: ItemIcon::Color(Color::WHITE),
+        }
+    }
+
+    #[test]
+    fn items_never_share_an_id_with_a_block() {
+        let registry = ItemRegistryRes::default().0;
+        for id in [ITEM_COAL, ITEM_RAW_IRON, ITEM_FLINT, ITEM_STICK] {
+            assert!(id >= Voxel::BLOCKS);
+            assert_eq!(registry.block(id), None);
+        }
+        let mut registry = ItemRegistry::with_capacity(2);
+        let last_block = Voxel::BLOCKS - 1;
+        assert!(registry.insert_block(last_block, "Last").is_none());
+        assert!(registry.insert(ITEM_COAL, item(None)).is_none());
+        assert_eq!(registry.block_item(last_block), Some(last_block));
+        assert!(registry.insert(ITEM_COAL, item(None)).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "id range of the other kind")]
+    fn items_that_are_not_blocks_stay_out_of_the_block_range() {
+        let mut registry = ItemRegistry::with_capacity(1);
+        registry.insert(300, item(None));
+    }
+
+    #[test]
+    #[should_panic(expected = "id range of the other kind")]
+    fn block_items_cannot_take_an_item_id() {
+        let mut registry = ItemRegistryRes::default().0;
+        registry.insert(ITEM_COAL, item(Some(BLOCK_STONE)));
+    }
+}
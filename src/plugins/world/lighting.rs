@@ -0,0 +1,291 @@
+use std::collections::VecDeque;
+
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    ChunkComponent, ChunkEntityMap, Chunks,
+    blocks::{BlockRegistry, BlockRegistryRes},
+    chunk::{CHUNK_SIZE, CHUNK_VOLUME, Chunk, world_to_chunk_local},
+    config::WorldHeightLimits,
+    events::VoxelChanged,
+    heightmap::{HeightmapKind, Heightmaps},
+};
+use crate::state::LoadingState;
+
+/// Light of a voxel open to the sky.
+pub const MAX_LIGHT: u8 = 15;
+
+pub struct LightingPlugin;
+
+impl Plugin for LightingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LightMap>()
+            .init_resource::<RelightQueue>()
+            .add_systems(
+                Update,
+                (queue_changed_chunks, relight_chunks)
+                    .chain()
+                    .run_if(in_state(LoadingState::Initialized)),
+            );
+    }
+}
+
+/// Sky light of every voxel of one chunk, from 0 (dark) to [`MAX_LIGHT`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChunkLight(Box<[u8; CHUNK_VOLUME]>);
+
+impl ChunkLight {
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.0[Chunk::index(x, y, z)]
+    }
+}
+
+/// Sky light of the loaded chunks.
+#[derive(Resource, Debug, Default)]
+pub struct LightMap {
+    chunks: HashMap<IVec3, ChunkLight>,
+}
+
+impl LightMap {
+    pub fn insert(&mut self, coord: IVec3, light: ChunkLight) {
+        self.chunks.insert(coord, light);
+    }
+
+    pub fn get(&self, coord: IVec3) -> Option<&ChunkLight> {
+        self.chunks.get(&coord)
+    }
+
+    pub fn contains(&self, coord: IVec3) -> bool {
+        self.chunks.contains_key(&coord)
+    }
+
+    pub fn remove(&mut self, coord: IVec3) -> Option<ChunkLight> {
+        self.chunks.remove(&coord)
+    }
+
+    /// Sky light at `world`, if its chunk has been lit.
+    pub fn sky_light_at(&self, world: IVec3) -> Option<u8> {
+        let (coord, local) = world_to_chunk_local(world);
+        let local = local.as_uvec3();
+        self.get(coord)
+            .map(|light| light.get(local.x as usize, local.y as usize, local.z as usize))
+    }
+}
+
+/// Chunks whose light is recomputed on the next update.
+#[derive(Resource, Debug, Default)]
+pub struct RelightQueue(HashSet<IVec3>);
+
+impl RelightQueue {
+    pub fn push(&mut self, coord: IVec3) {
+        self.0.insert(coord);
+    }
+}
+
+/// Whether every voxel of the column `(x, z)` of `chunk`, from `from_y` up, lets light
+/// through.
+fn column_open(chunk: &Chunk, registry: &BlockRegistry, x: usize, z: usize, from_y: usize) -> bool {
+    (from_y..CHUNK_SIZE).all(|y| !registry.is_opaque(chunk.get(x, y, z).block_id()))
+}
+
+/// Where the sky shines straight down to.
+#[derive(Copy, Clone, Debug)]
+pub enum SkySource<'a> {
+    /// Look through the chunk above only; above `top_chunk_y`, or where that chunk is
+    /// missing, is open sky. Used while generating, before the column is complete.
+    ChunkAbove { top_chunk_y: i32 },
+    /// Above the highest solid voxel of each column.
+    Heightmaps(&'a Heightmaps),
+}
+
+/// Lowest local y of each column of the chunk at `coord` that the sky shines straight
+/// down to, or `CHUNK_SIZE` if none does.
+fn sky_floor<'a>(
+    coord: IVec3,
+    chunk: &Chunk,
+    chunk_at: &dyn Fn(IVec3) -> Option<&'a Chunk>,
+    registry: &BlockRegistry,
+    sky: SkySource,
+) -> [usize; CHUNK_SIZE * CHUNK_SIZE] {
+    let mut floor = [CHUNK_SIZE; CHUNK_SIZE * CHUNK_SIZE];
+    match sky {
+        SkySource::ChunkAbove { top_chunk_y } => {
+            let above = (coord.y < top_chunk_y)
+                .then(|| chunk_at(coord + IVec3::Y))
+                .flatten();
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    if above.is_some_and(|above| !column_open(above, registry, x, z, 0)) {
+                        continue;
+                    }
+                    let mut y = CHUNK_SIZE;
+                    while y > 0 && !registry.is_opaque(chunk.get(x, y - 1, z).block_id()) {
+                        y -= 1;
+                    }
+                    floor[x + z * CHUNK_SIZE] = y;
+                }
+            }
+        }
+        SkySource::Heightmaps(heightmaps) => {
+            let min_y = coord.y * CHUNK_SIZE as i32;
+            let heightmap = heightmaps.column(coord.xz());
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let top = heightmap
+                        .and_then(|heightmap| heightmap.get(HeightmapKind::NonFluid, x, z));
+                    floor[x + z * CHUNK_SIZE] = top.map_or(0, |top| {
+                        (top + 1 - min_y).clamp(0, CHUNK_SIZE as i32) as usize
+                    });
+                }
+            }
+        }
+    }
+    floor
+}
+
+/// Sky light of the chunk at `coord`: [`MAX_LIGHT`] where the sky shines straight down,
+/// spreading sideways and down into caves one level per voxel.
+///
+/// Light only spreads within the chunk, seeded from the sky shining straight down in the
+/// four chunks beside it, so lighting a chunk needs nothing but its neighbours.
+pub fn compute_sky_light<'a>(
+    coord: IVec3,
+    chunk_at: &dyn Fn(IVec3) -> Option<&'a Chunk>,
+    registry: &BlockRegistry,
+    sky: SkySource,
+) -> Option<ChunkLight> {
+    let chunk = chunk_at(coord)?;
+    let mut light = Box::new([0; CHUNK_VOLUME]);
+    let mut frontier = VecDeque::new();
+    let size = CHUNK_SIZE as i32;
+
+    let floor = sky_floor(coord, chunk, chunk_at, registry, sky);
+    for z in 0..CHUNK_SIZE {
+        for x in 0..CHUNK_SIZE {
+            for y in floor[x + z * CHUNK_SIZE]..CHUNK_SIZE {
+                light[Chunk::index(x, y, z)] = MAX_LIGHT;
+                frontier.push_back(IVec3::new(x as i32, y as i32, z as i32));
+            }
+        }
+    }
+
+    for offset in [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z] {
+        let Some(neighbour) = chunk_at(coord + offset) else {
+            continue;
+        };
+        let neighbour_floor = sky_floor(coord + offset, neighbour, chunk_at, registry, sky);
+        for along in 0..size {
+            // The border column of this chunk, and the facing column of the neighbour.
+            let (inside, outside) = match offset {
+                IVec3::X => (IVec2::new(size - 1, along), IVec2::new(0, along)),
+                IVec3::NEG_X => (IVec2::new(0, along), IVec2::new(size - 1, along)),
+                IVec3::Z => (IVec2::new(along, size - 1), IVec2::new(along, 0)),
+                _ => (IVec2::new(along, 0), IVec2::new(along, size - 1)),
+            };
+            let from = neighbour_floor[(outside.x + outside.y * size) as usize];
+            for y in from..CHUNK_SIZE {
+                let (x, z) = (inside.x as usize, inside.y as usize);
+                let index = Chunk::index(x, y, z);
+                if registry.is_opaque(chunk.get(x, y, z).block_id())
+                    || light[index] >= MAX_LIGHT - 1
+                {
+                    continue;
+                }
+                light[index] = MAX_LIGHT - 1;
+                frontier.push_back(IVec3::new(inside.x, y as i32, inside.y));
+            }
+        }
+    }
+
+    while let Some(position) = frontier.pop_front() {
+        let level = light[Chunk::index(
+            position.x as usize,
+            position.y as usize,
+            position.z as usize,
+        )];
+        if level <= 1 {
+            continue;
+        }
+        for offset in [
+            IVec3::X,
+            IVec3::NEG_X,
+            IVec3::Y,
+            IVec3::NEG_Y,
+            IVec3::Z,
+            IVec3::NEG_Z,
+        ] {
+            let next = position + offset;
+            if next.cmplt(IVec3::ZERO).any() || next.cmpge(IVec3::splat(size)).any() {
+                continue;
+            }
+            let (x, y, z) = (next.x as usize, next.y as usize, next.z as usize);
+            let index = Chunk::index(x, y, z);
+            if light[index] >= level - 1 || registry.is_opaque(chunk.get(x, y, z).block_id()) {
+                continue;
+            }
+            light[index] = level - 1;
+            frontier.push_back(next);
+        }
+    }
+
+    Some(ChunkLight(light))
+}
+
+/// Relight chunks whose voxels changed, and loaded chunks that were never lit.
+fn queue_changed_chunks(
+    mut changes: MessageReader<VoxelChanged>,
+    added: Query<&ChunkComponent, Added<ChunkComponent>>,
+    light_map: Res<LightMap>,
+    limits: Res<WorldHeightLimits>,
+    mut queue: ResMut<RelightQueue>,
+) {
+    for change in changes.read() {
+        let (coord, local) = world_to_chunk_local(change.world);
+        queue.push(coord);
+        // Light spreads in from the sides and shines down the whole column.
+        for (axis, offset) in [(0, IVec3::X), (2, IVec3::Z)] {
+            if local[axis] == 0 {
+                queue.push(coord - offset);
+            } else if local[axis] == CHUNK_SIZE as i32 - 1 {
+                queue.push(coord + offset);
+            }
+        }
+        for y in limits.min_chunk_y..coord.y {
+            queue.push(coord.with_y(y));
+        }
+    }
+    for chunk in &added {
+        if !light_map.contains(chunk.coord) {
+            queue.push(chunk.coord);
+        }
+    }
+}
+
+fn relight_chunks(
+    registry: Res<BlockRegistryRes>,
+    heightmaps: Res<Heightmaps>,
+    chunk_map: Res<ChunkEntityMap>,
+    chunks: Res<Chunks>,
+    mut queue: ResMut<RelightQueue>,
+    mut light_map: ResMut<LightMap>,
+) {
+    if queue.0.is_empty() {
+        return;
+    }
+    let chunk_at = |coord: IVec3| {
+        chunk_map
+            .get(&coord)
+            .and_then(|entity| chunks.0.get(&entity))
+    };
+    for coord in queue.0.drain() {
+        if let Some(light) = compute_sky_light(
+            coord,
+            &chunk_at,
+            &registry.0,
+            SkySource::Heightmaps(&heightmaps),
+        ) {
+            light_map.insert(coord, light);
+        }
+    }
+}
@@ -65,6 +65,16 @@ impl<'a> Neighbors<'a> {
 
 pub trait ChunkMesher: Send + Sync + 'static {
     fn build_mesh(&self, chunk: &Chunk, neighbours: Neighbors, registry: &BlockRegistry) -> Mesh;
+
+    /// Mesh for the translucent pass (fluids), or `None` if the chunk has nothing to draw in it.
+    fn build_translucent_mesh(
+        &self,
+        _chunk: &Chunk,
+        _neighbours: Neighbors,
+        _registry: &BlockRegistry,
+    ) -> Option<Mesh> {
+        None
+    }
 }
 
 pub use naive_mesher::NaiveMesher;
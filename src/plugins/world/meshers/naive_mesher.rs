@@ -2,9 +2,11 @@ use bevy::mesh::{Indices, MeshVertexAttribute, PrimitiveTopology, VertexFormat};
 use bevy::prelude::*;
 
 use crate::plugins::world::{
-    blocks::{BlockId, BlockRegistry, TileId},
+    blocks::{BlockRegistry, TileId},
     chunk::{CHUNK_SIZE, Chunk},
+    fluids::FluidState,
     meshers::{ChunkMesher, Neighbors},
+    voxel::Voxel,
 };
 
 pub const ATTRIBUTE_TILE_ID: MeshVertexAttribute =
@@ -45,6 +47,10 @@ impl VoxelMeshBuilder {
             .extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
     }
 
+    fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
     fn build(self) -> Mesh {
         let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, Default::default());
 
@@ -165,8 +171,8 @@ pub struct TileResolver<'a> {
 
 impl<'a> TileResolver<'a> {
     #[inline]
-    pub fn resolve(&self, block_id: BlockId, face: FaceKind) -> TileId {
-        let t = self.registry.tiles(block_id);
+    pub fn resolve(&self, voxel: Voxel, face: FaceKind) -> TileId {
+        let t = self.registry.tiles_for(voxel);
         match face {
             FaceKind::Top => t.top,
             FaceKind::Bottom => t.bottom,
@@ -186,19 +192,23 @@ impl ChunkMesher for NaiveMesher {
             for y in 0..CHUNK_SIZE {
                 for x in 0..CHUNK_SIZE {
                     let voxel = chunk.get(x, y, z);
-                    if voxel.is_air() {
+                    if voxel.is_air() || registry.is_fluid(voxel.block_id()) {
                         continue;
                     }
 
                     let base = Vec3::new(x as f32, y as f32, z as f32);
+                    let local = IVec3::new(x as i32, y as i32, z as i32);
 
                     for face in &FACES {
-                        if !neighbor_is_air(chunk, &neighbors, x, y, z, face.neighbor_offset) {
+                        // Unloaded neighbours count as air; fluids don't hide opaque faces.
+                        let exposed = voxel_at(chunk, &neighbors, local + face.neighbor_offset)
+                            .is_none_or(|n| n.is_air() || registry.is_fluid(n.block_id()));
+                        if !exposed {
                             continue;
                         }
 
                         let face_kind = face_kind_from_normal(face.normal);
-                        let tile_id = resolver.resolve(voxel.block_id(), face_kind);
+                        let tile_id = resolver.resolve(voxel, face_kind);
 
                         let verts = face.vertices.map(|v| base + v);
                         builder.add_quad(verts, face.uvs, tile_id, face.normal);
@@ -209,34 +219,286 @@ impl ChunkMesher for NaiveMesher {
 
         builder.build()
     }
+
+    fn build_translucent_mesh(
+        &self,
+        chunk: &Chunk,
+        neighbors: Neighbors,
+        registry: &BlockRegistry,
+    ) -> Option<Mesh> {
+        let resolver = TileResolver { registry };
+        let mut builder = VoxelMeshBuilder::new();
+
+        for z in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let voxel = chunk.get(x, y, z);
+                    if !registry.is_fluid(voxel.block_id()) {
+                        continue;
+                    }
+
+                    let base = Vec3::new(x as f32, y as f32, z as f32);
+                    let local = IVec3::new(x as i32, y as i32, z as i32);
+                    let height = fluid_height(chunk, &neighbors, registry, local, voxel);
+
+                    for face in &FACES {
+                        let neighbour = voxel_at(chunk, &neighbors, local + face.neighbor_offset);
+
+                        // Visible slice of the face, as (bottom, top) heights within the voxel.
+                        let span = match neighbour {
+                            Some(n) if n.block_id() == voxel.block_id() => {
+                                if face.normal.y != 0.0 {
+                                    continue;
+                                }
+                                // Only the part above a lower neighbouring surface shows.
+                                let neighbour_height = fluid_height(
+                                    chunk,
+                                    &neighbors,
+                                    registry,
+                                    local + face.neighbor_offset,
+                                    n,
+                                );
+                                if neighbour_height >= height {
+                                    continue;
+                                }
+                                (neighbour_height, height)
+                            }
+                            Some(n) if !n.is_air() && !registry.is_fluid(n.block_id()) => {
+                                // A partial surface under an opaque block is still visible.
+                                if face.normal != Vec3::Y || height >= 1.0 {
+                                    continue;
+                                }
+                                (0.0, height)
+                            }
+                            _ => (0.0, height),
+                        };
+
+                        let face_kind = face_kind_from_normal(face.normal);
+                        let tile_id = resolver.resolve(voxel, face_kind);
+
+                        let (verts, uvs) = if face.normal.y != 0.0 {
+                            (
+                                face.vertices
+                                    .map(|v| base + Vec3::new(v.x, v.y * height, v.z)),
+                                face.uvs,
+                            )
+                        } else {
+                            let (bottom, top) = span;
+                            let y_at = |v: Vec3| if v.y > 0.0 { top } else { bottom };
+                            (
+                                face.vertices.map(|v| base + Vec3::new(v.x, y_at(v), v.z)),
+                                std::array::from_fn(|i| {
+                                    [face.uvs[i][0], 1.0 - y_at(face.vertices[i])]
+                                }),
+                            )
+                        };
+                        builder.add_quad(verts, uvs, tile_id, face.normal);
+                    }
+                }
+            }
+        }
+
+        (!builder.is_empty()).then(|| builder.build())
+    }
 }
 
-fn neighbor_is_air(
+/// Surface height of the fluid `voxel` at `local`; full if the same fluid continues above.
+fn fluid_height(
     chunk: &Chunk,
     neighbours: &Neighbors,
-    x: usize,
-    y: usize,
-    z: usize,
-    offset: IVec3,
-) -> bool {
-    let nx = x as i32 + offset.x;
-    let ny = y as i32 + offset.y;
-    let nz = z as i32 + offset.z;
-
-    if (0..CHUNK_SIZE as i32).contains(&nx)
-        && (0..CHUNK_SIZE as i32).contains(&ny)
-        && (0..CHUNK_SIZE as i32).contains(&nz)
-    {
-        return chunk.get(nx as usize, ny as usize, nz as usize).is_air();
-    }
-
-    let Some(nchunk) = neighbours.get_from_normal(offset) else {
-        return true;
+    registry: &BlockRegistry,
+    local: IVec3,
+    voxel: Voxel,
+) -> f32 {
+    let covered = voxel_at(chunk, neighbours, local + IVec3::Y)
+        .is_some_and(|above| above.block_id() == voxel.block_id());
+    if covered {
+        1.0
+    } else {
+        FluidState::of(registry, voxel).height()
+    }
+}
+
+/// Voxel at a chunk-local position that may lie in one of the face neighbours.
+/// `None` if that neighbour isn't loaded or the position is outside more than one axis.
+fn voxel_at(chunk: &Chunk, neighbours: &Neighbors, local: IVec3) -> Option<Voxel> {
+    let size = CHUNK_SIZE as i32;
+    let outside = local.cmplt(IVec3::ZERO) | local.cmpge(IVec3::splat(size));
+
+    let source = match outside.bitmask() {
+        0 => chunk,
+        0b001 | 0b010 | 0b100 => {
+            neighbours.get_from_normal(IVec3::select(outside, local.signum(), IVec3::ZERO))?
+        }
+        _ => return None,
     };
 
-    let lx = ((nx % CHUNK_SIZE as i32) + CHUNK_SIZE as i32) % CHUNK_SIZE as i32;
-    let ly = ((ny % CHUNK_SIZE as i32) + CHUNK_SIZE as i32) % CHUNK_SIZE as i32;
-    let lz = ((nz % CHUNK_SIZE as i32) + CHUNK_SIZE as i32) % CHUNK_SIZE as i32;
+    let wrapped = local.rem_euclid(IVec3::splat(size));
+    Some(source.get(wrapped.x as usize, wrapped.y as usize, wrapped.z as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::world::{
+        blocks::{BLOCK_STONE, BLOCK_WATER, BlockRegistryRes},
+        fluids::FLUID_SURFACE_HEIGHT,
+        meshers::Neighbour,
+    };
 
-    nchunk.get(lx as usize, ly as usize, lz as usize).is_air()
+    /// Each quad of `mesh` as its normal and its min and max corners.
+    fn quads(mesh: &Mesh) -> Vec<(Vec3, Vec3, Vec3)> {
+        let positions = mesh
+            .attribute(Mesh::ATTRIBUTE_POSITION)
+            .and_then(|positions| positions.as_float3())
+            .unwrap();
+        let normals = mesh
+            .attribute(Mesh::ATTRIBUTE_NORMAL)
+            .and_then(|normals| normals.as_float3())
+            .unwrap();
+        positions
+            .chunks(4)
+            .zip(normals.chunks(4))
+            .map(|(quad, normals)| {
+                let corners = quad.iter().copied().map(Vec3::from);
+                let min = corners.clone().reduce(Vec3::min).unwrap();
+                let max = corners.reduce(Vec3::max).unwrap();
+                (Vec3::from(normals[0]), min, max)
+            })
+            .collect()
+    }
+
+    fn translucent_quads(chunk: &Chunk, neighbours: Neighbors) -> Vec<(Vec3, Vec3, Vec3)> {
+        let registry = BlockRegistryRes::default().0;
+        NaiveMesher
+            .build_translucent_mesh(chunk, neighbours, &registry)
+            .map_or_else(Vec::new, |mesh| quads(&mesh))
+    }
+
+    fn with(voxels: &[(IVec3, Voxel)]) -> Chunk {
+        let mut chunk = Chunk::new();
+        for (at, voxel) in voxels {
+            chunk.set(at.x as usize, at.y as usize, at.z as usize, *voxel);
+        }
+        chunk
+    }
+
+    fn water() -> Voxel {
+        Voxel::new(BLOCK_WATER)
+    }
+
+    #[test]
+    fn fluids_are_meshed_only_in_the_translucent_pass() {
+        let registry = BlockRegistryRes::default().0;
+        let stone = IVec3::new(1, 1, 1);
+        let chunk = with(&[
+            (stone, Voxel::new(BLOCK_STONE)),
+            (IVec3::new(5, 1, 5), water()),
+        ]);
+
+        let opaque = quads(&NaiveMesher.build_mesh(&chunk, Neighbors::default(), &registry));
+        assert_eq!(opaque.len(), 6);
+        assert!(
+            opaque
+                .iter()
+                .all(|(_, min, max)| min.cmpge(stone.as_vec3()).all()
+                    && max.cmple(stone.as_vec3() + 1.0).all())
+        );
+
+        let translucent = translucent_quads(&chunk, Neighbors::default());
+        assert_eq!(translucent.len(), 6);
+        let top = 1.0 + FLUID_SURFACE_HEIGHT;
+        assert!(
+            translucent
+                .iter()
+                .all(|(normal, _, max)| max.y == if *normal == Vec3::NEG_Y { 1.0 } else { top })
+        );
+
+        let dry = with(&[(stone, Voxel::new(BLOCK_STONE))]);
+        assert!(
+            NaiveMesher
+                .build_translucent_mesh(&dry, Neighbors::default(), &registry)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn faces_between_the_same_fluid_are_hidden() {
+        let beside = with(&[
+            (IVec3::new(5, 1, 5), water()),
+            (IVec3::new(6, 1, 5), water()),
+        ]);
+        assert_eq!(translucent_quads(&beside, Neighbors::default()).len(), 10);
+
+        // The lower voxel of a column fills up to the one above.
+        let stacked = with(&[
+            (IVec3::new(5, 1, 5), water()),
+            (IVec3::new(5, 2, 5), water()),
+        ]);
+        let quads = translucent_quads(&stacked, Neighbors::default());
+        assert_eq!(quads.len(), 10);
+        let lower_sides = quads
+            .iter()
+            .filter(|(normal, min, _)| normal.y == 0.0 && min.y == 1.0);
+        assert!(lower_sides.clone().all(|(_, _, max)| max.y == 2.0));
+        assert_eq!(lower_sides.count(), 4);
+    }
+
+    #[test]
+    fn sides_show_above_a_lower_surface() {
+        let registry = BlockRegistryRes::default().0;
+        let flowing = FluidState::flowing(4);
+        let chunk = with(&[
+            (IVec3::new(5, 1, 5), water()),
+            (IVec3::new(6, 1, 5), flowing.voxel(&registry, BLOCK_WATER)),
+        ]);
+        let quads = translucent_quads(&chunk, Neighbors::default());
+        assert_eq!(quads.len(), 11);
+
+        let facing = |normal: Vec3| {
+            quads
+                .iter()
+                .filter(|(n, min, _)| *n == normal && min.x == 6.0)
+                .map(|(_, min, max)| (min.y, max.y))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            facing(Vec3::X),
+            [(1.0 + flowing.height(), 1.0 + FLUID_SURFACE_HEIGHT)]
+        );
+        assert!(facing(Vec3::NEG_X).is_empty());
+    }
+
+    #[test]
+    fn surfaces_under_opaque_blocks_stay_visible() {
+        let chunk = with(&[
+            (IVec3::new(5, 1, 5), water()),
+            (IVec3::new(5, 2, 5), Voxel::new(BLOCK_STONE)),
+        ]);
+        let quads = translucent_quads(&chunk, Neighbors::default());
+        assert_eq!(quads.len(), 6);
+        assert!(
+            quads
+                .iter()
+                .any(|(normal, min, _)| *normal == Vec3::Y && min.y == 1.0 + FLUID_SURFACE_HEIGHT)
+        );
+    }
+
+    #[test]
+    fn fluid_continues_into_a_loaded_neighbour() {
+        let edge = IVec3::new(CHUNK_SIZE as i32 - 1, 1, 5);
+        let chunk = with(&[(edge, water())]);
+        let neighbour = with(&[(IVec3::new(0, 1, 5), water())]);
+        let has_east_face = |neighbours| {
+            translucent_quads(&chunk, neighbours)
+                .iter()
+                .any(|(normal, _, _)| *normal == Vec3::X)
+        };
+
+        let mut loaded = [None; 6];
+        loaded[Neighbour::X as usize] = Some(&neighbour);
+        assert!(!has_east_face(Neighbors::from_array(loaded)));
+        // Unloaded neighbours count as air.
+        assert!(has_east_face(Neighbors::default()));
+    }
 }
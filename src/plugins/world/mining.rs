@@ -0,0 +1,264 @@
+use bevy::light::NotShadowCaster;
+use bevy::mesh::VertexAttributeValues;
+use bevy::prelude::*;
+
+use crate::plugins::{
+    asset_loader::assets::{ATLAS_ROWS, ATLAS_TILE_SIZE, GameAssets, atlas_cell},
+    world::{
+        blocks::{BlockRegistry, BlockRegistryRes, TileId},
+        voxel::Voxel,
+        voxel_picking::{VoxelFace, VoxelHit},
+    },
+};
+use crate::state::LoadingState;
+
+/// Crack stages drawn over a block as it's mined.
+pub const CRACK_STAGES: usize = 9;
+
+/// Gap between a mined face and the cracks drawn over it.
+const CRACK_OFFSET: f32 = 0.002;
+
+/// Atlas tile of the first crack stage. The stages fill the columns after the last one
+/// `registry` draws blocks with.
+pub fn crack_tiles(registry: &BlockRegistry) -> TileId {
+    let rows = ATLAS_ROWS as TileId;
+    (registry.last_tile() / rows + 1) * rows
+}
+
+pub struct MiningPlugin;
+
+impl Plugin for MiningPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MiningSettings>()
+            .init_resource::<MiningProgress>()
+            .add_systems(OnEnter(LoadingState::Initialized), spawn_crack_overlay)
+            .add_systems(
+                Update,
+                update_crack_overlay.run_if(in_state(LoadingState::Initialized)),
+            );
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct MiningSettings {
+    /// Hardness mined per second: a block takes `hardness / speed` seconds to break.
+    pub speed: f32,
+}
+
+impl Default for MiningSettings {
+    fn default() -> Self {
+        Self { speed: 1.0 }
+    }
+}
+
+/// The voxel being mined.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MiningTarget {
+    pub world: IVec3,
+    /// Face the cracks are drawn on.
+    pub face: VoxelFace,
+    /// What was there when mining started.
+    pub voxel: Voxel,
+}
+
+/// How far the player has got mining the voxel under the cursor.
+#[derive(Resource, Clone, Debug, Default, PartialEq)]
+pub struct MiningProgress {
+    pub target: Option<MiningTarget>,
+    /// From 0 to 1; the voxel breaks at 1.
+    pub progress: f32,
+}
+
+impl MiningProgress {
+    /// Keep mining `voxel` at `hit` for `dt` seconds, where breaking it takes `seconds`.
+    /// Starts over if that's not what was being mined. Returns whether it broke.
+    pub fn mine(&mut self, hit: VoxelHit, voxel: Voxel, seconds: f32, dt: f32) -> bool {
+        let target = MiningTarget {
+            world: hit.world,
+            face: hit.face,
+            voxel,
+        };
+        match self.target {
+            Some(current) if current.world == target.world && current.voxel == target.voxel => {}
+            _ => self.progress = 0.0,
+        }
+        self.target = Some(target);
+
+        self.progress = if seconds > 0.0 {
+            (self.progress + dt / seconds).min(1.0)
+        } else {
+            1.0
+        };
+        self.progress >= 1.0
+    }
+
+    pub fn reset(&mut self) {
+        self.target = None;
+        self.progress = 0.0;
+    }
+
+    /// Crack stage to draw, or `None` when nothing is being mined.
+    pub fn stage(&self) -> Option<usize> {
+        self.target?;
+        Some(((self.progress * CRACK_STAGES as f32) as usize).min(CRACK_STAGES - 1))
+    }
+}
+
+#[derive(Component)]
+struct CrackOverlay;
+
+/// A quad showing each crack stage, in order.
+#[derive(Resource)]
+struct CrackMeshes(Vec<Handle<Mesh>>);
+
+/// A unit quad facing +Z, textured with atlas `tile`.
+fn crack_mesh(tile: TileId, columns: u32) -> Mesh {
+    let cell = atlas_cell(tile).as_vec2();
+    let grid = Vec2::new(columns as f32, ATLAS_ROWS as f32);
+    let mut mesh = Mesh::from(Rectangle::new(1.0, 1.0));
+    if let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0) {
+        for uv in uvs {
+            // Inset a little so neighbouring tiles don't bleed in.
+            let inset = Vec2::from(*uv).clamp(Vec2::splat(0.001), Vec2::splat(0.999));
+            *uv = ((cell + inset) / grid).to_array();
+        }
+    }
+    mesh
+}
+
+fn spawn_crack_overlay(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    images: Res<Assets<Image>>,
+    registry: Res<BlockRegistryRes>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let columns = images
+        .get(&assets.block_atlas)
+        .map_or(1, |image| image.width() / ATLAS_TILE_SIZE);
+    let first = crack_tiles(&registry.0);
+    let stages: Vec<Handle<Mesh>> = (0..CRACK_STAGES)
+        .map(|stage| meshes.add(crack_mesh(first + stage as TileId, columns)))
+        .collect();
+    let material = materials.add(StandardMaterial {
+        base_color_texture: Some(assets.block_atlas.clone()),
+        alpha_mode: AlphaMode::Blend,
+        depth_bias: 1.0,
+        ..default()
+    });
+
+    commands.spawn((
+        CrackOverlay,
+        Mesh3d(stages[0].clone()),
+        MeshMaterial3d(material),
+        Transform::default(),
+        Visibility::Hidden,
+        NotShadowCaster,
+    ));
+    commands.insert_resource(CrackMeshes(stages));
+}
+
+fn update_crack_overlay(
+    progress: Res<MiningProgress>,
+    meshes: Res<CrackMeshes>,
+    overlay: Single<(&mut Mesh3d, &mut Transform, &mut Visibility), With<CrackOverlay>>,
+) {
+    let (mut mesh, mut transform, mut visibility) = overlay.into_inner();
+    let (Some(target), Some(stage)) = (progress.target, progress.stage()) else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    let normal = target.face.normal_f();
+    transform.translation =
+        target.world.as_vec3() + Vec3::splat(0.5) + normal * (0.5 + CRACK_OFFSET);
+    transform.rotation = Quat::from_rotation_arc(Vec3::Z, normal);
+    if mesh.0 != meshes.0[stage] {
+        mesh.0 = meshes.0[stage].clone();
+    }
+    *visibility = Visibility::Visible;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::world::blocks::{BLOCK_DIRT, BLOCK_LEAVES, BLOCK_STONE, BlockTiles};
+
+    fn hit(world: IVec3) -> VoxelHit {
+        VoxelHit {
+            chunk: IVec3::ZERO,
+            local: world,
+            world,
+            face: VoxelFace::PosY,
+        }
+    }
+
+    #[test]
+    fn breaks_after_hardness_over_speed_seconds() {
+        let mut progress = MiningProgress::default();
+        let stone = Voxel::new(BLOCK_STONE);
+        // 1.5 hardness at speed 1, in 0.1 second frames.
+        for _ in 0..14 {
+            assert!(!progress.mine(hit(IVec3::ZERO), stone, 1.5, 0.1));
+        }
+        assert!((progress.progress - 14.0 / 15.0).abs() < 1e-4);
+        assert!(progress.mine(hit(IVec3::ZERO), stone, 1.5, 0.1 + 1e-4));
+        assert_eq!(progress.progress, 1.0);
+
+        let mut instant = MiningProgress::default();
+        assert!(instant.mine(hit(IVec3::ZERO), stone, 0.0, 0.0));
+    }
+
+    #[test]
+    fn starts_over_on_another_voxel() {
+        let mut progress = MiningProgress::default();
+        let stone = Voxel::new(BLOCK_STONE);
+        progress.mine(hit(IVec3::ZERO), stone, 1.0, 0.5);
+        assert_eq!(progress.progress, 0.5);
+
+        progress.mine(hit(IVec3::X), stone, 1.0, 0.25);
+        assert_eq!(progress.progress, 0.25);
+        assert_eq!(progress.target.unwrap().world, IVec3::X);
+
+        progress.mine(hit(IVec3::X), Voxel::new(BLOCK_DIRT), 1.0, 0.25);
+        assert_eq!(progress.progress, 0.25);
+
+        // Another face of the same voxel keeps going.
+        let mut side = hit(IVec3::X);
+        side.face = VoxelFace::PosX;
+        progress.mine(side, Voxel::new(BLOCK_DIRT), 1.0, 0.25);
+        assert_eq!(progress.progress, 0.5);
+        assert_eq!(progress.target.unwrap().face, VoxelFace::PosX);
+
+        progress.reset();
+        assert_eq!(progress, MiningProgress::default());
+    }
+
+    #[test]
+    fn stages_span_progress() {
+        let mut progress = MiningProgress::default();
+        assert_eq!(progress.stage(), None);
+
+        progress.mine(hit(IVec3::ZERO), Voxel::new(BLOCK_STONE), 1.0, 0.0);
+        assert_eq!(progress.stage(), Some(0));
+        for (value, stage) in [(0.11, 0), (0.12, 1), (0.5, 4), (0.99, 8), (1.0, 8)] {
+            progress.progress = value;
+            assert_eq!(progress.stage(), Some(stage), "at {value}");
+        }
+    }
+
+    #[test]
+    fn cracks_follow_the_last_block() {
+        let mut registry = BlockRegistryRes::default().0;
+        let leaves = BlockTiles::new(BLOCK_LEAVES);
+        assert_eq!(registry.last_tile(), leaves.bottom);
+        assert_eq!(crack_tiles(&registry), leaves.bottom + 1);
+
+        registry.insert(BLOCK_LEAVES + 1);
+        assert_eq!(
+            crack_tiles(&registry),
+            BlockTiles::new(BLOCK_LEAVES + 1).bottom + 1
+        );
+    }
+}
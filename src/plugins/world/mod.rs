@@ -1,21 +1,65 @@
+pub mod block_entity;
+pub mod block_state;
+pub mod block_updates;
 pub mod blocks;
+pub mod brushes;
+pub mod byte_reader;
 pub mod chunk;
+pub mod colliders;
+pub mod config;
+pub mod edit_history;
 pub mod events;
+pub mod explosion;
+pub mod falling_blocks;
+pub mod fluids;
+pub mod heightmap;
+pub mod items;
+pub mod lighting;
 pub mod material;
 pub mod meshers;
+pub mod mining;
+pub mod noise;
+pub mod pathfinding;
+pub mod persistence;
+pub mod schematic;
+pub mod structural_integrity;
+#[cfg(test)]
+pub(crate) mod test_support;
+pub mod vox;
 pub mod voxel;
 pub mod voxel_picking;
+pub mod voxel_tools;
+pub mod voxel_world;
+pub mod worldgen;
 
 use bevy::ecs::{entity::MapEntities, lifecycle::HookContext, world::DeferredWorld};
 use bevy::platform::collections::HashMap;
 use bevy::prelude::*;
 
+use block_entity::BlockEntityPlugin;
+use block_updates::BlockUpdatePlugin;
 use blocks::BlockRegistryRes;
+use brushes::BrushToolPlugin;
 use chunk::{CHUNK_SIZE, Chunk};
-use events::on_voxel_clicked;
+use config::{WorldConfig, WorldHeightLimits};
+use edit_history::EditHistoryPlugin;
+use events::VoxelChanged;
+use explosion::ExplosionPlugin;
+use falling_blocks::FallingBlockPlugin;
+use fluids::FluidPlugin;
+use heightmap::{Heightmaps, rebuild_column_heightmap};
+use items::{ItemRegistryRes, LootRng};
+use lighting::{LightMap, LightingPlugin};
 use material::VoxelAtlasMaterialPlugin;
 use meshers::{ChunkMesher, NaiveMesher, Neighbors, Neighbour};
+use mining::MiningPlugin;
+use pathfinding::PathfindingPlugin;
+use persistence::ChunkPersistence;
+use schematic::SchematicPlugin;
+use structural_integrity::StructuralIntegrityPlugin;
 use voxel_picking::VoxelPickingPlugin;
+use voxel_tools::VoxelToolsPlugin;
+use worldgen::{WorldGenPlugin, biomes::BiomeMap};
 
 use crate::{plugins::asset_loader::assets::VoxelAtlasHandles, state::LoadingState};
 
@@ -33,7 +77,7 @@ impl Default for Chunks {
 
 #[derive(Component, Copy, Clone, Eq, PartialEq, Default, Hash, MapEntities, Debug)]
 #[require(Transform)]
-#[component(on_add = on_add_chunk_component)]
+#[component(on_add = on_add_chunk_component, on_remove = on_remove_chunk_component)]
 pub struct ChunkComponent {
     pub coord: IVec3,
 }
@@ -74,6 +118,37 @@ fn on_add_chunk_component(mut world: DeferredWorld, context: HookContext) {
         .insert(chunk_cmp.coord, context.entity);
 }
 
+fn on_remove_chunk_component(mut world: DeferredWorld, context: HookContext) {
+    let coord = world.get::<ChunkComponent>(context.entity).unwrap().coord;
+    let mut chunk_map = world.resource_mut::<ChunkEntityMap>();
+    if chunk_map.get(&coord) == Some(context.entity) {
+        chunk_map.remove(&coord);
+    }
+    world.resource_mut::<Chunks>().0.remove(&context.entity);
+    world
+        .commands()
+        .queue(move |world: &mut World| forget_chunk(world, coord));
+}
+
+/// Update what was derived from the removed chunk at `coord`: the faces and heightmap
+/// around it, its light, and the biomes of its column once no chunk of it is loaded.
+fn forget_chunk(world: &mut World, coord: IVec3) {
+    mark_neighbours_dirty(world, coord);
+    rebuild_column_heightmap(world, coord);
+
+    let chunk_map = world.resource::<ChunkEntityMap>();
+    // Another chunk may have been spawned in its place already.
+    let replaced = chunk_map.get(&coord).is_some();
+    let column_loaded = chunk_map.coords().any(|loaded| loaded.xz() == coord.xz());
+    // A chunk loaded back is relit, as it has no light.
+    if !replaced && let Some(mut light_map) = world.get_resource_mut::<LightMap>() {
+        light_map.remove(coord);
+    }
+    if !column_loaded && let Some(mut biome_map) = world.get_resource_mut::<BiomeMap>() {
+        biome_map.remove(coord.xz());
+    }
+}
+
 pub trait SpawnChunkCommandExt {
     fn spawn_chunk(&mut self, chunk: Chunk, coord: IVec3);
 }
@@ -83,10 +158,40 @@ impl<'w, 's> SpawnChunkCommandExt for Commands<'w, 's> {
         self.queue(move |world: &mut World| {
             let entity = world.spawn(ChunkComponent { coord }).id();
             world.resource_mut::<Chunks>().0.insert(entity, chunk);
+            mark_neighbours_dirty(world, coord);
+            rebuild_column_heightmap(world, coord);
         })
     }
 }
 
+/// Faces along the border with `coord` have been exposed or hidden.
+pub fn mark_neighbours_dirty(world: &mut World, coord: IVec3) {
+    let neighbours: Vec<Entity> = [
+        IVec3::X,
+        IVec3::NEG_X,
+        IVec3::Y,
+        IVec3::NEG_Y,
+        IVec3::Z,
+        IVec3::NEG_Z,
+    ]
+    .into_iter()
+    .filter_map(|offset| world.resource::<ChunkEntityMap>().get(&(coord + offset)))
+    .collect();
+
+    let mut chunks = world.resource_mut::<Chunks>();
+    for entity in neighbours {
+        if let Some(chunk) = chunks.0.get_mut(&entity) {
+            chunk.mark_dirty();
+        }
+    }
+}
+
+/// Child entity of a chunk holding its translucent (fluid) mesh.
+#[derive(Component, Copy, Clone, Debug)]
+pub struct ChunkTranslucentMesh {
+    pub entity: Entity,
+}
+
 #[derive(Resource, Debug, Default)]
 pub struct ChunkEntityMap {
     chunks: HashMap<IVec3, Entity>,
@@ -100,24 +205,64 @@ impl ChunkEntityMap {
     pub fn get(&self, chunk_coord: &IVec3) -> Option<Entity> {
         self.chunks.get(chunk_coord).copied()
     }
+
+    pub fn coords(&self) -> impl Iterator<Item = IVec3> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    pub fn remove(&mut self, chunk_coord: &IVec3) -> Option<Entity> {
+        self.chunks.remove(chunk_coord)
+    }
 }
 
 pub struct WorldPlugin;
 
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<WorldConfig>();
+        let limits = WorldHeightLimits::from(app.world().resource::<WorldConfig>());
+        let loot_rng = LootRng::new(app.world().resource::<WorldConfig>().seed);
+
         app.init_resource::<BlockRegistryRes>()
+            .init_resource::<ItemRegistryRes>()
+            .insert_resource(loot_rng)
+            .insert_resource(limits)
+            .init_resource::<Heightmaps>()
             .init_resource::<Chunks>()
+            .init_resource::<ChunkPersistence>()
             .insert_resource(MesherResource(Box::new(NaiveMesher)))
             .insert_resource(ChunkEntityMap {
                 chunks: HashMap::with_capacity(128),
             })
-            .add_plugins((VoxelAtlasMaterialPlugin, VoxelPickingPlugin))
+            .add_message::<VoxelChanged>()
+            .add_plugins((
+                VoxelAtlasMaterialPlugin,
+                VoxelPickingPlugin,
+                VoxelToolsPlugin,
+                MiningPlugin,
+                EditHistoryPlugin,
+                BrushToolPlugin,
+                SchematicPlugin,
+            ))
+            .add_plugins((
+                FluidPlugin,
+                BlockEntityPlugin,
+                BlockUpdatePlugin,
+                FallingBlockPlugin,
+                StructuralIntegrityPlugin,
+                ExplosionPlugin,
+                WorldGenPlugin,
+                LightingPlugin,
+                PathfindingPlugin,
+            ))
+            .add_systems(Startup, config::save_world_config)
             .add_systems(
                 Update,
                 rebuild_dirty_chunks.run_if(in_state(LoadingState::Initialized)),
-            )
-            .add_observer(on_voxel_clicked);
+            );
+
+        #[cfg(feature = "colliders")]
+        app.add_plugins(colliders::ChunkColliderPlugin);
     }
 }
 
@@ -128,11 +273,17 @@ fn rebuild_dirty_chunks(
     block_registry: Res<BlockRegistryRes>,
     mesher: Res<MesherResource>,
     handles: Res<VoxelAtlasHandles>,
-    mut chunk_query: Query<(Entity, &ChunkComponent, Option<&mut Mesh3d>)>,
+    mut chunk_query: Query<(
+        Entity,
+        &ChunkComponent,
+        Option<&mut Mesh3d>,
+        Option<&ChunkTranslucentMesh>,
+    )>,
+    mut translucent_query: Query<&mut Mesh3d, Without<ChunkComponent>>,
     mut chunks: ResMut<Chunks>,
     chunk_map: Res<ChunkEntityMap>,
 ) {
-    for (entity, chunk_cmp, mesh3d_opt) in chunk_query.iter_mut() {
+    for (entity, chunk_cmp, mesh3d_opt, translucent_opt) in chunk_query.iter_mut() {
         // Fast check: if we don't have it, skip
         let Some(is_dirty) = chunks.0.get(&entity).map(|c| c.is_dirty()) else {
             continue;
@@ -148,6 +299,10 @@ fn rebuild_dirty_chunks(
 
         let neighbours = get_neighbours(&chunk_cmp.coord, &chunk_map, &chunks);
         let mesh = mesher.0.build_mesh(&chunk, neighbours, &block_registry.0);
+        let translucent_mesh =
+            mesher
+                .0
+                .build_translucent_mesh(&chunk, neighbours, &block_registry.0);
         let handle = meshes.add(mesh);
 
         match mesh3d_opt {
@@ -159,6 +314,32 @@ fn rebuild_dirty_chunks(
             }
         }
 
+        match (translucent_mesh, translucent_opt) {
+            (Some(mesh), Some(translucent)) => {
+                if let Ok(mut mesh3d) = translucent_query.get_mut(translucent.entity) {
+                    mesh3d.0 = meshes.add(mesh);
+                }
+            }
+            (Some(mesh), None) => {
+                let child = commands
+                    .spawn((
+                        Mesh3d(meshes.add(mesh)),
+                        MeshMaterial3d(handles.translucent_material.clone()),
+                        Transform::default(),
+                        ChildOf(entity),
+                    ))
+                    .id();
+                commands
+                    .entity(entity)
+                    .insert(ChunkTranslucentMesh { entity: child });
+            }
+            (None, Some(translucent)) => {
+                commands.entity(translucent.entity).despawn();
+                commands.entity(entity).remove::<ChunkTranslucentMesh>();
+            }
+            (None, None) => {}
+        }
+
         chunk.clear_dirty();
 
         chunks.0.insert(entity, chunk);
@@ -0,0 +1,121 @@
+use bevy::prelude::*;
+
+/// 64-bit hash of a lattice point. The same seed and point always give the same value.
+pub fn hash_cell(seed: u64, cell: IVec3) -> u64 {
+    let mut hash = seed
+        ^ (cell.x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (cell.y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (cell.z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    hash ^ (hash >> 31)
+}
+
+/// Hash of a lattice point, uniform in `[0, 1)`.
+pub fn hash_unit(seed: u64, cell: IVec3) -> f32 {
+    (hash_cell(seed, cell) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Smooth value noise in `[0, 1]`, with lattice points one unit apart.
+pub fn value_noise_3d(seed: u64, point: Vec3) -> f32 {
+    let cell = point.floor();
+    let base = cell.as_ivec3();
+    let t = point - cell;
+    let t = t * t * (Vec3::splat(3.0) - 2.0 * t);
+
+    let corner = |x: i32, y: i32, z: i32| hash_unit(seed, base + IVec3::new(x, y, z));
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), t.x);
+    let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), t.x);
+    let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), t.x);
+    let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), t.x);
+    lerp(lerp(x00, x10, t.y), lerp(x01, x11, t.y), t.z)
+}
+
+/// Smooth value noise in `[0, 1]` on the plane, with lattice points one unit apart.
+pub fn value_noise_2d(seed: u64, point: Vec2) -> f32 {
+    let cell = point.floor();
+    let base = cell.as_ivec2();
+    let t = point - cell;
+    let t = t * t * (Vec2::splat(3.0) - 2.0 * t);
+
+    let corner = |x: i32, z: i32| hash_unit(seed, IVec3::new(base.x + x, 0, base.y + z));
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let z0 = lerp(corner(0, 0), corner(1, 0), t.x);
+    let z1 = lerp(corner(0, 1), corner(1, 1), t.x);
+    lerp(z0, z1, t.y)
+}
+
+/// Fractal sum of `octaves` layers of [`value_noise_2d`], each at twice the frequency and
+/// half the amplitude of the last. Normalised to `[0, 1]`.
+pub fn fbm_2d(seed: u64, point: Vec2, octaves: u32) -> f32 {
+    let mut sum = 0.0;
+    let mut amplitude = 1.0;
+    let mut total = 0.0;
+    let mut point = point;
+    for octave in 0..octaves {
+        sum += amplitude * value_noise_2d(seed.wrapping_add(octave as u64), point);
+        total += amplitude;
+        amplitude *= 0.5;
+        point *= 2.0;
+    }
+    sum / total
+}
+
+/// Fractal sum of [`value_noise_3d`] layers, like [`fbm_2d`].
+pub fn fbm_3d(seed: u64, point: Vec3, octaves: u32) -> f32 {
+    let mut sum = 0.0;
+    let mut amplitude = 1.0;
+    let mut total = 0.0;
+    let mut point = point;
+    for octave in 0..octaves {
+        sum += amplitude * value_noise_3d(seed.wrapping_add(octave as u64), point);
+        total += amplitude;
+        amplitude *= 0.5;
+        point *= 2.0;
+    }
+    sum / total
+}
+
+/// Deterministic random numbers for placing generated features, seeded per lattice cell so
+/// any chunk can reproduce the features of its neighbours.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeatureRng {
+    state: u64,
+}
+
+impl FeatureRng {
+    pub fn for_cell(seed: u64, cell: IVec3) -> Self {
+        Self {
+            state: hash_cell(seed, cell),
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform in `0..n`. `n` must be non-zero.
+    pub fn below(&mut self, n: u32) -> u32 {
+        (((self.next_u64() >> 32) * n as u64) >> 32) as u32
+    }
+
+    /// Uniform in `min..=max`.
+    pub fn range(&mut self, min: i32, max: i32) -> i32 {
+        min + self.below((max - min + 1) as u32) as i32
+    }
+
+    /// Uniform in `min..max`.
+    pub fn range_f32(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
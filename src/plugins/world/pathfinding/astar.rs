@@ -0,0 +1,563 @@
+use core::cmp::Ordering;
+use core::fmt;
+use std::collections::BinaryHeap;
+
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+
+use crate::plugins::world::{chunk::CHUNK_SIZE, pathfinding::grid::NavGrid};
+
+const CARDINALS: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+const DIAGONALS: [IVec3; 4] = [
+    IVec3::new(1, 0, 1),
+    IVec3::new(1, 0, -1),
+    IVec3::new(-1, 0, 1),
+    IVec3::new(-1, 0, -1),
+];
+
+/// How an agent moves, and so which paths it can take.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NavAgent {
+    /// Open voxels it needs to stand in.
+    pub height: i32,
+    /// Highest ledge it can step or jump up onto.
+    pub max_step_up: i32,
+    /// Furthest it is willing to fall.
+    pub max_drop: i32,
+    /// Widest gap it can jump across, onto the same level.
+    pub max_jump_gap: i32,
+}
+
+impl Default for NavAgent {
+    fn default() -> Self {
+        Self {
+            height: 2,
+            max_step_up: 1,
+            max_drop: 3,
+            max_jump_gap: 1,
+        }
+    }
+}
+
+/// How a [`PathStep`] is reached from the one before.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Movement {
+    /// The first step, where the agent already stands.
+    Start,
+    Walk,
+    StepUp,
+    Drop,
+    Jump,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PathStep {
+    /// Voxel the agent's feet are in.
+    pub cell: IVec3,
+    pub movement: Movement,
+}
+
+impl PathStep {
+    /// Where the agent stands in `cell`: the middle of its floor.
+    pub fn waypoint(&self) -> Vec3 {
+        self.cell.as_vec3() + Vec3::new(0.5, 0.0, 0.5)
+    }
+}
+
+/// A path found by [`find_path`], from the start cell to the goal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoxelPath {
+    pub steps: Vec<PathStep>,
+    pub cost: f32,
+}
+
+impl VoxelPath {
+    pub fn start(&self) -> IVec3 {
+        self.steps[0].cell
+    }
+
+    pub fn goal(&self) -> IVec3 {
+        self.steps[self.steps.len() - 1].cell
+    }
+
+    /// Where the agent stands at each step, in world space.
+    pub fn waypoints(&self) -> impl Iterator<Item = Vec3> + '_ {
+        self.steps.iter().map(PathStep::waypoint)
+    }
+
+    /// Chunks holding a voxel the path relies on: the floor of each step and the space
+    /// above it an agent `height` tall moves through, and the corners each diagonal step
+    /// passes between.
+    pub fn chunks(&self, height: i32) -> HashSet<IVec3> {
+        let size = IVec3::splat(CHUNK_SIZE as i32);
+        let corners = self.steps.windows(2).flat_map(|pair| {
+            let (from, dir) = (pair[0].cell, pair[1].cell - pair[0].cell);
+            let diagonal = pair[1].movement == Movement::Walk && dir.x != 0 && dir.z != 0;
+            diagonal
+                .then(|| [from + dir.with_z(0), from + dir.with_x(0)])
+                .into_iter()
+                .flatten()
+        });
+        self.steps
+            .iter()
+            .map(|step| step.cell)
+            .chain(corners)
+            .flat_map(|cell| (-1..=height).map(move |y| cell + IVec3::Y * y))
+            .map(|world| world.div_euclid(size))
+            .collect()
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PathError {
+    /// The agent can't stand at the start.
+    InvalidStart,
+    /// The agent can't stand at the goal.
+    InvalidGoal,
+    /// Every reachable cell was searched without finding the goal.
+    Unreachable,
+    /// The search gave up after visiting its budget of cells.
+    BudgetExhausted,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathError::InvalidStart => write!(f, "can't stand at the start"),
+            PathError::InvalidGoal => write!(f, "can't stand at the goal"),
+            PathError::Unreachable => write!(f, "goal is unreachable"),
+            PathError::BudgetExhausted => write!(f, "search budget exhausted"),
+        }
+    }
+}
+
+impl std::error::Error for PathError {}
+
+/// The cells an agent can reach in one move from standing at `from`, with the cost of
+/// each move.
+pub fn neighbours(grid: &NavGrid, agent: &NavAgent, from: IVec3) -> Vec<(IVec3, Movement, f32)> {
+    let mut moves = Vec::new();
+    let height = agent.height;
+
+    for dir in CARDINALS {
+        let ahead = from + dir;
+        if grid.is_walkable(ahead, height) {
+            moves.push((ahead, Movement::Walk, 1.0));
+            continue;
+        }
+
+        // Up onto a ledge, with room overhead to climb.
+        for rise in 1..=agent.max_step_up {
+            let ledge = ahead + IVec3::Y * rise;
+            if !grid.is_open(from + IVec3::Y * (height + rise - 1)) {
+                break;
+            }
+            if grid.is_walkable(ledge, height) {
+                moves.push((ledge, Movement::StepUp, 1.0 + 0.5 * rise as f32));
+                break;
+            }
+        }
+
+        if !grid.is_clear(ahead, height) {
+            continue;
+        }
+
+        // Off an edge, landing on the first floor below.
+        for fall in 1..=agent.max_drop {
+            let below = ahead - IVec3::Y * fall;
+            if !grid.is_open(below) {
+                break;
+            }
+            if grid.is_solid(below - IVec3::Y) {
+                moves.push((below, Movement::Drop, 1.0 + 0.25 * fall as f32));
+                break;
+            }
+        }
+
+        // Across a gap, with room for the arc of the jump.
+        if grid.is_solid(ahead - IVec3::Y) || !grid.is_open(from + IVec3::Y * height) {
+            continue;
+        }
+        for width in 1..=agent.max_jump_gap {
+            let over = from + dir * width;
+            if !grid.is_clear(over, height + 1) || grid.is_solid(over - IVec3::Y) {
+                break;
+            }
+            let landing = over + dir;
+            if grid.is_walkable(landing, height) && grid.is_open(landing + IVec3::Y * height) {
+                moves.push((landing, Movement::Jump, width as f32 + 2.0));
+                break;
+            }
+        }
+    }
+
+    // Diagonally, only where both cardinal neighbours are free so corners aren't cut.
+    for dir in DIAGONALS {
+        let cell = from + dir;
+        if grid.is_walkable(cell, height)
+            && grid.is_clear(from + dir.with_z(0), height)
+            && grid.is_clear(from + dir.with_x(0), height)
+        {
+            moves.push((cell, Movement::Walk, core::f32::consts::SQRT_2));
+        }
+    }
+
+    moves
+}
+
+/// Octile distance across the ground, never more than the cost of getting there.
+pub(super) fn heuristic(from: IVec3, to: IVec3) -> f32 {
+    let dx = (from.x - to.x).abs() as f32;
+    let dz = (from.z - to.z).abs() as f32;
+    dx.max(dz) + (core::f32::consts::SQRT_2 - 1.0) * dx.min(dz)
+}
+
+pub(super) struct Open {
+    pub estimate: f32,
+    pub cost: f32,
+    pub cell: IVec3,
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    /// Lowest estimate first, then the cell furthest along, then by coordinate so ties
+    /// always break the same way.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then(self.cost.total_cmp(&other.cost))
+            .then_with(|| other.cell.to_array().cmp(&self.cell.to_array()))
+    }
+}
+
+/// Find the cheapest path for `agent` from standing at `start` to standing at `goal`,
+/// visiting at most `max_nodes` cells.
+pub fn find_path(
+    grid: &NavGrid,
+    agent: &NavAgent,
+    start: IVec3,
+    goal: IVec3,
+    max_nodes: usize,
+) -> Result<VoxelPath, PathError> {
+    find_path_within(grid, agent, start, goal, max_nodes, &|_| true)
+}
+
+/// [`find_path`] through only the cells `within` accepts.
+pub fn find_path_within(
+    grid: &NavGrid,
+    agent: &NavAgent,
+    start: IVec3,
+    goal: IVec3,
+    max_nodes: usize,
+    within: &dyn Fn(IVec3) -> bool,
+) -> Result<VoxelPath, PathError> {
+    if !grid.is_walkable(start, agent.height) {
+        return Err(PathError::InvalidStart);
+    }
+    if !grid.is_walkable(goal, agent.height) {
+        return Err(PathError::InvalidGoal);
+    }
+
+    let mut open = BinaryHeap::new();
+    let mut best: HashMap<IVec3, (f32, IVec3, Movement)> = HashMap::new();
+    let mut closed = HashSet::new();
+    best.insert(start, (0.0, start, Movement::Start));
+    open.push(Open {
+        estimate: heuristic(start, goal),
+        cost: 0.0,
+        cell: start,
+    });
+
+    while let Some(Open { cost, cell, .. }) = open.pop() {
+        if cell == goal {
+            let mut steps = Vec::new();
+            let mut at = goal;
+            loop {
+                let (_, previous, movement) = best[&at];
+                steps.push(PathStep { cell: at, movement });
+                if movement == Movement::Start {
+                    break;
+                }
+                at = previous;
+            }
+            steps.reverse();
+            return Ok(VoxelPath { steps, cost });
+        }
+        if !closed.insert(cell) {
+            continue;
+        }
+        if closed.len() > max_nodes {
+            return Err(PathError::BudgetExhausted);
+        }
+
+        for (next, movement, step_cost) in neighbours(grid, agent, cell) {
+            let next_cost = cost + step_cost;
+            if !within(next)
+                || closed.contains(&next)
+                || best
+                    .get(&next)
+                    .is_some_and(|(known, ..)| *known <= next_cost)
+            {
+                continue;
+            }
+            best.insert(next, (next_cost, cell, movement));
+            open.push(Open {
+                estimate: next_cost + heuristic(next, goal),
+                cost: next_cost,
+                cell: next,
+            });
+        }
+    }
+
+    Err(PathError::Unreachable)
+}
+
+/// Cost of the cheapest path from standing at `start` to every cell `within` accepts that
+/// can be reached through only such cells, visiting at most `max_nodes` cells.
+pub fn costs_within(
+    grid: &NavGrid,
+    agent: &NavAgent,
+    start: IVec3,
+    max_nodes: usize,
+    within: &dyn Fn(IVec3) -> bool,
+) -> HashMap<IVec3, f32> {
+    let mut costs = HashMap::new();
+    if !grid.is_walkable(start, agent.height) {
+        return costs;
+    }
+
+    let mut open = BinaryHeap::new();
+    open.push(Open {
+        estimate: 0.0,
+        cost: 0.0,
+        cell: start,
+    });
+    while let Some(Open { cost, cell, .. }) = open.pop() {
+        if costs.contains_key(&cell) {
+            continue;
+        }
+        costs.insert(cell, cost);
+        if costs.len() >= max_nodes {
+            break;
+        }
+        for (next, _, step_cost) in neighbours(grid, agent, cell) {
+            if within(next) && !costs.contains_key(&next) {
+                open.push(Open {
+                    estimate: cost + step_cost,
+                    cost: cost + step_cost,
+                    cell: next,
+                });
+            }
+        }
+    }
+    costs
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::plugins::world::{
+        blocks::{BLOCK_STONE, BlockRegistryRes},
+        chunk::Chunk,
+        voxel::Voxel,
+    };
+
+    /// A chunk with one row of `rows` along x for each z: `.` is floor, `#` a wall two
+    /// voxels high on the floor and ` ` a hole. `S` and `G` are floor, with the start and
+    /// goal cells above them.
+    pub(crate) fn maze(rows: &[&str]) -> (Chunk, IVec3, IVec3) {
+        let mut chunk = Chunk::new();
+        let (mut start, mut goal) = (IVec3::ZERO, IVec3::ZERO);
+        for (z, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c != ' ' {
+                    chunk.set(x, 0, z, Voxel::new(BLOCK_STONE));
+                }
+                if c == '#' {
+                    chunk.set(x, 1, z, Voxel::new(BLOCK_STONE));
+                    chunk.set(x, 2, z, Voxel::new(BLOCK_STONE));
+                }
+                let cell = IVec3::new(x as i32, 1, z as i32);
+                match c {
+                    'S' => start = cell,
+                    'G' => goal = cell,
+                    _ => {}
+                }
+            }
+        }
+        (chunk, start, goal)
+    }
+
+    fn grid(chunk: &Chunk) -> NavGrid {
+        let mut grid = NavGrid::default();
+        grid.insert_chunk(IVec3::ZERO, chunk, &BlockRegistryRes::default().0);
+        grid
+    }
+
+    fn path(rows: &[&str], agent: &NavAgent) -> Result<VoxelPath, PathError> {
+        let (chunk, start, goal) = maze(rows);
+        find_path(&grid(&chunk), agent, start, goal, 10_000)
+    }
+
+    #[test]
+    fn finds_the_way_through_a_maze() {
+        let rows = [
+            "#########",
+            "#S..#...#",
+            "###.#.#.#",
+            "#...#.#.#",
+            "#.###.#.#",
+            "#.....#G#",
+            "#########",
+        ];
+        let (chunk, start, goal) = maze(&rows);
+        let grid = grid(&chunk);
+        let agent = NavAgent::default();
+        let path = find_path(&grid, &agent, start, goal, 10_000).unwrap();
+        assert_eq!((path.start(), path.goal()), (start, goal));
+        assert_eq!(path.waypoints().next(), Some(Vec3::new(1.5, 1.0, 1.5)));
+        let mut cost = 0.0;
+        for pair in path.steps.windows(2) {
+            let (_, _, step_cost) = neighbours(&grid, &agent, pair[0].cell)
+                .into_iter()
+                .find(|(cell, movement, _)| *cell == pair[1].cell && *movement == pair[1].movement)
+                .unwrap_or_else(|| panic!("{pair:?} is not a move"));
+            assert!(grid.is_walkable(pair[1].cell, agent.height));
+            cost += step_cost;
+        }
+        assert!((cost - path.cost).abs() < 1e-3);
+        // Down the left corridor and up the right one, cutting no corners.
+        assert_eq!(path.steps.len(), 23);
+    }
+
+    #[test]
+    fn reports_why_there_is_no_path() {
+        let agent = NavAgent::default();
+        assert_eq!(
+            path(&["#####", "#S#G#", "#####"], &agent),
+            Err(PathError::Unreachable)
+        );
+        let (chunk, start, _) = maze(&["#####", "#S#G#", "#####"]);
+        let grid = grid(&chunk);
+        let wall = IVec3::new(2, 1, 1);
+        assert_eq!(
+            find_path(&grid, &agent, start, wall, 10_000),
+            Err(PathError::InvalidGoal)
+        );
+        assert_eq!(
+            find_path(&grid, &agent, wall, start, 10_000),
+            Err(PathError::InvalidStart)
+        );
+
+        let (chunk, start, goal) = maze(&[
+            "S..............................",
+            "..............................G",
+        ]);
+        let grid = self::grid(&chunk);
+        assert_eq!(
+            find_path(&grid, &agent, start, goal, 5),
+            Err(PathError::BudgetExhausted)
+        );
+        assert!(find_path(&grid, &agent, start, goal, 10_000).is_ok());
+    }
+
+    #[test]
+    fn diagonals_do_not_cut_corners() {
+        let agent = NavAgent::default();
+        let open = path(&["S.", ".G"], &agent).unwrap();
+        assert_eq!(open.steps.len(), 2);
+        let blocked = path(&["S#", ".G"], &agent).unwrap();
+        assert_eq!(blocked.steps.len(), 3);
+    }
+
+    #[test]
+    fn jumps_gaps_as_wide_as_the_agent_can() {
+        let agent = NavAgent::default();
+        let narrow = path(&["S. .G"], &agent).unwrap();
+        assert!(
+            narrow
+                .steps
+                .iter()
+                .any(|step| step.movement == Movement::Jump)
+        );
+        assert_eq!(path(&["S.  .G"], &agent), Err(PathError::Unreachable));
+        let jumper = NavAgent {
+            max_jump_gap: 2,
+            ..agent
+        };
+        assert!(path(&["S.  .G"], &jumper).is_ok());
+    }
+
+    #[test]
+    fn steps_up_and_drops_within_the_agent_limits() {
+        // Floors at y 0, 1, 2, 4 and 1 along x.
+        let mut chunk = Chunk::new();
+        for (x, top) in [0, 1, 2, 4, 1].into_iter().enumerate() {
+            for y in 0..=top {
+                chunk.set(x, y, 0, Voxel::new(BLOCK_STONE));
+            }
+        }
+        let grid = grid(&chunk);
+        let agent = NavAgent::default();
+        let (bottom, top, below) = (
+            IVec3::new(0, 1, 0),
+            IVec3::new(3, 5, 0),
+            IVec3::new(4, 2, 0),
+        );
+
+        let moves = neighbours(&grid, &agent, bottom);
+        assert!(moves.contains(&(IVec3::new(1, 2, 0), Movement::StepUp, 1.5)));
+        assert!(find_path(&grid, &agent, bottom, top, 1000).is_err());
+        let climber = NavAgent {
+            max_step_up: 2,
+            ..agent
+        };
+        assert!(find_path(&grid, &climber, bottom, top, 1000).is_ok());
+
+        let drops = |agent: &NavAgent| {
+            neighbours(&grid, agent, top)
+                .into_iter()
+                .any(|(cell, movement, _)| cell == below && movement == Movement::Drop)
+        };
+        assert!(drops(&agent));
+        assert!(!drops(&NavAgent {
+            max_drop: 2,
+            ..agent
+        }));
+
+        let mut low_ceiling = chunk.clone();
+        low_ceiling.set(0, 3, 0, Voxel::new(BLOCK_STONE));
+        assert!(neighbours(&self::grid(&low_ceiling), &agent, bottom).is_empty());
+    }
+
+    #[test]
+    fn chunks_include_the_corners_of_diagonal_steps() {
+        let step = |x, z, movement| PathStep {
+            cell: IVec3::new(x, 1, z),
+            movement,
+        };
+        let path = VoxelPath {
+            steps: vec![step(31, 31, Movement::Start), step(32, 32, Movement::Walk)],
+            cost: core::f32::consts::SQRT_2,
+        };
+        let chunks = path.chunks(2);
+        for coord in [IVec3::ZERO, IVec3::X, IVec3::Z, IVec3::new(1, 0, 1)] {
+            assert!(chunks.contains(&coord), "{coord} missing from {chunks:?}");
+        }
+        assert_eq!(chunks.len(), 4);
+    }
+}
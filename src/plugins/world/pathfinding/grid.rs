@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    blocks::BlockRegistry,
+    chunk::{CHUNK_SIZE, CHUNK_VOLUME, Chunk, world_to_chunk_local},
+};
+
+const WORDS: usize = CHUNK_VOLUME / 64;
+
+/// What agents need to know about the voxels of one chunk, a bit per voxel.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NavChunk {
+    /// Blocks that can be stood on.
+    solid: Box<[u64; WORDS]>,
+    /// Air, which agents can move through. Fluids are neither solid nor open, so agents
+    /// keep out of them.
+    open: Box<[u64; WORDS]>,
+}
+
+impl NavChunk {
+    pub fn from_chunk(chunk: &Chunk, registry: &BlockRegistry) -> Self {
+        let mut solid = Box::new([0; WORDS]);
+        let mut open = Box::new([0; WORDS]);
+        for (index, voxel) in chunk.voxels().iter().enumerate() {
+            let bit = 1 << (index % 64);
+            if voxel.is_air() {
+                open[index / 64] |= bit;
+            } else if registry.is_opaque(voxel.block_id()) {
+                solid[index / 64] |= bit;
+            }
+        }
+        Self { solid, open }
+    }
+
+    pub fn is_solid(&self, local: IVec3) -> bool {
+        test(&self.solid, local)
+    }
+
+    pub fn is_open(&self, local: IVec3) -> bool {
+        test(&self.open, local)
+    }
+}
+
+fn test(bits: &[u64; WORDS], local: IVec3) -> bool {
+    let index = Chunk::index(local.x as usize, local.y as usize, local.z as usize);
+    bits[index / 64] & (1 << (index % 64)) != 0
+}
+
+/// The [`NavChunk`]s a search runs over. Voxels in chunks that aren't in the grid are
+/// neither solid nor open, so paths never leave it.
+#[derive(Clone, Debug, Default)]
+pub struct NavGrid {
+    chunks: HashMap<IVec3, Arc<NavChunk>>,
+}
+
+impl NavGrid {
+    pub fn get(&self, coord: IVec3) -> Option<&Arc<NavChunk>> {
+        self.chunks.get(&coord)
+    }
+
+    pub fn insert(&mut self, coord: IVec3, chunk: Arc<NavChunk>) {
+        self.chunks.insert(coord, chunk);
+    }
+
+    /// Add the chunk at `coord`, converting it with `registry`.
+    pub fn insert_chunk(&mut self, coord: IVec3, chunk: &Chunk, registry: &BlockRegistry) {
+        self.insert(coord, Arc::new(NavChunk::from_chunk(chunk, registry)));
+    }
+
+    pub fn is_solid(&self, world: IVec3) -> bool {
+        let (coord, local) = world_to_chunk_local(world);
+        self.get(coord).is_some_and(|chunk| chunk.is_solid(local))
+    }
+
+    pub fn is_open(&self, world: IVec3) -> bool {
+        let (coord, local) = world_to_chunk_local(world);
+        self.get(coord).is_some_and(|chunk| chunk.is_open(local))
+    }
+
+    /// Whether the `height` voxels from `world` up are all open.
+    pub fn is_clear(&self, world: IVec3, height: i32) -> bool {
+        (0..height).all(|y| self.is_open(world + IVec3::Y * y))
+    }
+
+    /// Whether an agent `height` voxels tall can stand with its feet in `world`: on
+    /// something solid, with open space above.
+    pub fn is_walkable(&self, world: IVec3, height: i32) -> bool {
+        self.is_solid(world - IVec3::Y) && self.is_clear(world, height)
+    }
+}
+
+/// Chunk coords of the box around `a` and `b`, grown by `margin` chunks on every side.
+pub fn chunks_around(a: IVec3, b: IVec3, margin: i32) -> impl Iterator<Item = IVec3> {
+    let size = CHUNK_SIZE as i32;
+    let min = a.min(b).div_euclid(IVec3::splat(size)) - IVec3::splat(margin);
+    let max = a.max(b).div_euclid(IVec3::splat(size)) + IVec3::splat(margin);
+    (min.z..=max.z).flat_map(move |z| {
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec3::new(x, y, z)))
+    })
+}
@@ -0,0 +1,496 @@
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    chunk::{CHUNK_SIZE, world_to_chunk_local},
+    pathfinding::{
+        astar::{
+            Movement, NavAgent, Open, PathError, PathStep, VoxelPath, costs_within,
+            find_path_within, heuristic, neighbours,
+        },
+        grid::{NavChunk, NavGrid},
+    },
+};
+
+/// Most cells of a border that share a portal.
+pub const PORTAL_SPACING: usize = 8;
+
+/// How an edge of a [`ChunkNavGraph`] is travelled.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NavEdgeKind {
+    /// A path inside the chunk, found again when the edge is taken.
+    Within,
+    /// A single move into another chunk.
+    Cross(Movement),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NavEdge {
+    pub to: IVec3,
+    pub cost: f32,
+    pub kind: NavEdgeKind,
+}
+
+/// Portals of one chunk for one kind of [`NavAgent`]: cells on its borders where agents
+/// cross into the chunks around it, with the cost of getting between them inside the
+/// chunk.
+///
+/// Portals are picked the same way whichever of the two chunks on a border is built, so
+/// the graphs of neighbouring chunks meet up.
+#[derive(Clone, Debug)]
+pub struct ChunkNavGraph {
+    pub coord: IVec3,
+    pub portals: Vec<IVec3>,
+    edges: HashMap<IVec3, Vec<NavEdge>>,
+    /// The nav chunks of the chunk and those around it it was built from.
+    sources: Vec<(IVec3, Option<Arc<NavChunk>>)>,
+}
+
+impl ChunkNavGraph {
+    /// Build the graph of the chunk at `coord` from `grid`, which should hold the chunk
+    /// and those around it.
+    pub fn build(coord: IVec3, grid: &NavGrid, agent: &NavAgent) -> Self {
+        let around: Vec<IVec3> = neighbourhood(coord).collect();
+
+        let mut exits = Vec::new();
+        let mut portals = HashSet::new();
+        for neighbour in &around {
+            for (from, to) in [(coord, *neighbour), (*neighbour, coord)] {
+                for (cell, target, movement, cost) in border_portals(grid, agent, from, to) {
+                    if from == coord {
+                        portals.insert(cell);
+                        exits.push((cell, target, movement, cost));
+                    } else {
+                        portals.insert(target);
+                    }
+                }
+            }
+        }
+
+        let mut portals: Vec<IVec3> = portals.into_iter().collect();
+        portals.sort_by_key(|cell| (cell.y, cell.z, cell.x));
+        let in_chunk = |cell: IVec3| world_to_chunk_local(cell).0 == coord;
+        let mut edges: HashMap<IVec3, Vec<NavEdge>> = HashMap::new();
+        for portal in &portals {
+            let costs = costs_within(grid, agent, *portal, usize::MAX, &in_chunk);
+            let reachable = portals.iter().filter(|other| *other != portal);
+            edges
+                .entry(*portal)
+                .or_default()
+                .extend(reachable.filter_map(|other| {
+                    costs.get(other).map(|cost| NavEdge {
+                        to: *other,
+                        cost: *cost,
+                        kind: NavEdgeKind::Within,
+                    })
+                }));
+        }
+        for (cell, target, movement, cost) in exits {
+            edges.entry(cell).or_default().push(NavEdge {
+                to: target,
+                cost,
+                kind: NavEdgeKind::Cross(movement),
+            });
+        }
+
+        let sources = std::iter::once(coord)
+            .chain(around)
+            .map(|source| (source, grid.get(source).cloned()))
+            .collect();
+        Self {
+            coord,
+            portals,
+            edges,
+            sources,
+        }
+    }
+
+    pub fn edges(&self, portal: IVec3) -> &[NavEdge] {
+        self.edges.get(&portal).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether the graph was built from the nav chunks `current` returns now, and from no
+    /// chunk where `loaded` says there is one now.
+    pub fn is_current(
+        &self,
+        loaded: impl Fn(IVec3) -> bool,
+        current: impl Fn(IVec3) -> Option<Arc<NavChunk>>,
+    ) -> bool {
+        self.sources.iter().all(|(coord, source)| match source {
+            Some(source) => current(*coord).is_some_and(|current| Arc::ptr_eq(source, &current)),
+            None => !loaded(*coord),
+        })
+    }
+}
+
+/// The 26 chunks around `coord`.
+pub fn neighbourhood(coord: IVec3) -> impl Iterator<Item = IVec3> {
+    (-1..=1)
+        .flat_map(|z| (-1..=1).flat_map(move |y| (-1..=1).map(move |x| IVec3::new(x, y, z))))
+        .filter(|offset| *offset != IVec3::ZERO)
+        .map(move |offset| coord + offset)
+}
+
+/// The portal moves from chunk `from` into chunk `to`: runs of neighbouring cells that
+/// cross are split into pieces of [`PORTAL_SPACING`] cells, and the middle cell of each
+/// piece, by y, z, x, crosses the cheapest way it can.
+fn border_portals(
+    grid: &NavGrid,
+    agent: &NavAgent,
+    from: IVec3,
+    to: IVec3,
+) -> Vec<(IVec3, IVec3, Movement, f32)> {
+    let size = CHUNK_SIZE as i32;
+    let reach = (agent.max_jump_gap + 1)
+        .max(agent.max_step_up)
+        .max(agent.max_drop + 1)
+        .min(size);
+    // Local cells of `from` close enough to `to` along every axis they differ on.
+    let side = |axis: i32| match axis {
+        1 => size - reach..size,
+        -1 => 0..reach,
+        _ => 0..size,
+    };
+    let offset = to - from;
+    let origin = from * size;
+
+    let mut crossings: Vec<(IVec3, IVec3, Movement, f32)> = Vec::new();
+    for z in side(offset.z) {
+        for y in side(offset.y) {
+            for x in side(offset.x) {
+                let cell = origin + IVec3::new(x, y, z);
+                if !grid.is_walkable(cell, agent.height) {
+                    continue;
+                }
+                crossings.extend(
+                    neighbours(grid, agent, cell)
+                        .into_iter()
+                        .filter(|(target, ..)| world_to_chunk_local(*target).0 == to)
+                        .map(|(target, movement, cost)| (cell, target, movement, cost)),
+                );
+            }
+        }
+    }
+    crossings
+        .sort_by_key(|(cell, target, ..)| (cell.y, cell.z, cell.x, target.y, target.z, target.x));
+
+    // Runs of crossings whose cells touch.
+    let mut run_of = vec![usize::MAX; crossings.len()];
+    let mut runs = 0;
+    for start in 0..crossings.len() {
+        if run_of[start] != usize::MAX {
+            continue;
+        }
+        run_of[start] = runs;
+        let mut stack = vec![start];
+        while let Some(at) = stack.pop() {
+            for other in 0..crossings.len() {
+                if run_of[other] == usize::MAX
+                    && (crossings[other].0 - crossings[at].0).abs().max_element() <= 1
+                {
+                    run_of[other] = runs;
+                    stack.push(other);
+                }
+            }
+        }
+        runs += 1;
+    }
+
+    let mut portals = Vec::new();
+    for run in 0..runs {
+        let mut cells: Vec<IVec3> = (0..crossings.len())
+            .filter(|index| run_of[*index] == run)
+            .map(|index| crossings[index].0)
+            .collect();
+        cells.dedup();
+        for piece in cells.chunks(PORTAL_SPACING) {
+            let cell = piece[piece.len() / 2];
+            let cheapest = crossings
+                .iter()
+                .filter(|crossing| crossing.0 == cell)
+                .min_by(|a, b| a.3.total_cmp(&b.3))
+                .copied();
+            portals.extend(cheapest);
+        }
+    }
+    portals
+}
+
+/// Find a path across many chunks: search the portals of the chunk graphs in `graphs`,
+/// then find the path along each portal edge in `grid`. Paths come out a little longer
+/// than [`find_path`](super::astar::find_path) finds, but the search only visits
+/// portals.
+pub fn find_path_hierarchical(
+    grid: &NavGrid,
+    graphs: &HashMap<IVec3, Arc<ChunkNavGraph>>,
+    agent: &NavAgent,
+    start: IVec3,
+    goal: IVec3,
+    max_nodes: usize,
+) -> Result<VoxelPath, PathError> {
+    if !grid.is_walkable(start, agent.height) {
+        return Err(PathError::InvalidStart);
+    }
+    if !grid.is_walkable(goal, agent.height) {
+        return Err(PathError::InvalidGoal);
+    }
+    let chunk_of = |cell: IVec3| world_to_chunk_local(cell).0;
+    let (start_chunk, goal_chunk) = (chunk_of(start), chunk_of(goal));
+    let in_start_chunk = |cell: IVec3| chunk_of(cell) == start_chunk;
+    let in_goal_chunk = |cell: IVec3| chunk_of(cell) == goal_chunk;
+
+    // Edges from the start to the portals of its chunk, and from the portals of the goal
+    // chunk to the goal.
+    let from_start = costs_within(grid, agent, start, max_nodes, &in_start_chunk);
+    let mut start_edges: Vec<NavEdge> = graphs
+        .get(&start_chunk)
+        .map(|graph| graph.portals.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .chain((start_chunk == goal_chunk).then_some(&goal))
+        .filter_map(|portal| {
+            from_start.get(portal).map(|cost| NavEdge {
+                to: *portal,
+                cost: *cost,
+                kind: NavEdgeKind::Within,
+            })
+        })
+        .collect();
+    start_edges.sort_by_key(|edge| (edge.to.y, edge.to.z, edge.to.x));
+    let mut to_goal: HashMap<IVec3, f32> = HashMap::new();
+    if let Some(graph) = graphs.get(&goal_chunk) {
+        for portal in &graph.portals {
+            if let Some(cost) =
+                costs_within(grid, agent, *portal, max_nodes, &in_goal_chunk).get(&goal)
+            {
+                to_goal.insert(*portal, *cost);
+            }
+        }
+    }
+
+    let mut open = BinaryHeap::new();
+    let mut best: HashMap<IVec3, (f32, IVec3, NavEdgeKind)> = HashMap::new();
+    let mut closed = HashSet::new();
+    best.insert(start, (0.0, start, NavEdgeKind::Within));
+    open.push(Open {
+        estimate: heuristic(start, goal),
+        cost: 0.0,
+        cell: start,
+    });
+
+    let mut route = None;
+    while let Some(Open { cost, cell, .. }) = open.pop() {
+        if cell == goal {
+            route = Some(cost);
+            break;
+        }
+        if !closed.insert(cell) {
+            continue;
+        }
+        if closed.len() > max_nodes {
+            return Err(PathError::BudgetExhausted);
+        }
+
+        let mut edges: Vec<NavEdge> = graphs
+            .get(&chunk_of(cell))
+            .map(|graph| graph.edges(cell).to_vec())
+            .unwrap_or_default();
+        if cell == start {
+            edges.extend_from_slice(&start_edges);
+        }
+        if let Some(cost) = to_goal.get(&cell) {
+            edges.push(NavEdge {
+                to: goal,
+                cost: *cost,
+                kind: NavEdgeKind::Within,
+            });
+        }
+        for edge in edges {
+            let next_cost = cost + edge.cost;
+            if closed.contains(&edge.to)
+                || best
+                    .get(&edge.to)
+                    .is_some_and(|(known, ..)| *known <= next_cost)
+            {
+                continue;
+            }
+            best.insert(edge.to, (next_cost, cell, edge.kind));
+            open.push(Open {
+                estimate: next_cost + heuristic(edge.to, goal),
+                cost: next_cost,
+                cell: edge.to,
+            });
+        }
+    }
+    if route.is_none() {
+        return Err(PathError::Unreachable);
+    }
+
+    let mut hops = Vec::new();
+    let mut at = goal;
+    while at != start {
+        let (_, previous, kind) = best[&at];
+        hops.push((previous, at, kind));
+        at = previous;
+    }
+    hops.reverse();
+
+    let mut path = VoxelPath {
+        steps: vec![PathStep {
+            cell: start,
+            movement: Movement::Start,
+        }],
+        cost: 0.0,
+    };
+    for (from, to, kind) in hops {
+        match kind {
+            NavEdgeKind::Cross(movement) => {
+                let cost = neighbours(grid, agent, from)
+                    .into_iter()
+                    .find(|(cell, crossing, _)| *cell == to && *crossing == movement)
+                    .map_or(1.0, |(.., cost)| cost);
+                path.steps.push(PathStep { cell: to, movement });
+                path.cost += cost;
+            }
+            NavEdgeKind::Within => {
+                let chunk = chunk_of(from);
+                let leg = find_path_within(grid, agent, from, to, max_nodes, &|cell| {
+                    chunk_of(cell) == chunk
+                })?;
+                path.steps.extend_from_slice(&leg.steps[1..]);
+                path.cost += leg.cost;
+            }
+        }
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::world::{
+        blocks::{BLOCK_STONE, BlockRegistryRes},
+        chunk::Chunk,
+        voxel::Voxel,
+    };
+
+    /// Coords of the chunks [`terrain`] is made of.
+    fn terrain_coords() -> impl Iterator<Item = IVec3> {
+        (0..3).flat_map(|x| (0..2).flat_map(move |y| (0..3).map(move |z| IVec3::new(x, y, z))))
+    }
+
+    /// Chunks `0..3` across and `0..2` up: ground at `height(x, z)`, with a wall two
+    /// voxels high on it where `wall(x, z)`.
+    fn terrain(height: impl Fn(i32, i32) -> i32, wall: impl Fn(i32, i32) -> bool) -> NavGrid {
+        let mut chunks: HashMap<IVec3, Chunk> = terrain_coords()
+            .map(|coord| (coord, Chunk::new()))
+            .collect();
+        let mut set = |world: IVec3| {
+            let (coord, local) = world_to_chunk_local(world);
+            let chunk = chunks.get_mut(&coord).unwrap();
+            chunk.set(
+                local.x as usize,
+                local.y as usize,
+                local.z as usize,
+                Voxel::new(BLOCK_STONE),
+            );
+        };
+        for x in 0..3 * CHUNK_SIZE as i32 {
+            for z in 0..3 * CHUNK_SIZE as i32 {
+                let top = height(x, z);
+                let wall_top = if wall(x, z) { top + 2 } else { top };
+                for y in 0..=wall_top {
+                    set(IVec3::new(x, y, z));
+                }
+            }
+        }
+
+        let registry = BlockRegistryRes::default().0;
+        let mut grid = NavGrid::default();
+        for (coord, chunk) in &chunks {
+            grid.insert_chunk(*coord, chunk, &registry);
+        }
+        grid
+    }
+
+    /// Deterministic noise for picking walls and endpoints.
+    fn hash(i: u64) -> u64 {
+        let mut x = i.wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ 0x0123_4567;
+        x ^= x >> 31;
+        x = x.wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        x ^ (x >> 29)
+    }
+
+    /// Search between `pairs` random cells on the ground both ways, checking the
+    /// hierarchical paths are real paths found whenever flat A* finds one. Returns how
+    /// many were found and the worst ratio of their cost to the flat path's.
+    fn compare(
+        height: impl Fn(i32, i32) -> i32,
+        wall: impl Fn(i32, i32) -> bool + Copy,
+        pairs: u64,
+    ) -> (usize, f32) {
+        let grid = terrain(&height, wall);
+        let agent = NavAgent::default();
+        let graphs: HashMap<IVec3, Arc<ChunkNavGraph>> = terrain_coords()
+            .map(|coord| (coord, Arc::new(ChunkNavGraph::build(coord, &grid, &agent))))
+            .collect();
+
+        let size = 3 * CHUNK_SIZE as u64;
+        let (mut found, mut worst) = (0, 1.0f32);
+        for i in 0..pairs {
+            let [ax, az, bx, bz] = [0, 1, 2, 3].map(|j| (hash(i * 4 + j) % size) as i32);
+            if wall(ax, az) || wall(bx, bz) {
+                continue;
+            }
+            let start = IVec3::new(ax, height(ax, az) + 1, az);
+            let goal = IVec3::new(bx, height(bx, bz) + 1, bz);
+            let flat = find_path_within(&grid, &agent, start, goal, 1_000_000, &|_| true);
+            let hierarchical =
+                find_path_hierarchical(&grid, &graphs, &agent, start, goal, 1_000_000);
+            let (flat, path) = match (flat, hierarchical) {
+                (Ok(flat), Ok(path)) => (flat, path),
+                (Err(_), Err(_)) => continue,
+                (flat, path) => panic!("{start} to {goal}: flat {flat:?}, hierarchical {path:?}"),
+            };
+
+            assert_eq!((path.start(), path.goal()), (start, goal));
+            let mut cost = 0.0;
+            for pair in path.steps.windows(2) {
+                let (_, _, step_cost) = neighbours(&grid, &agent, pair[0].cell)
+                    .into_iter()
+                    .find(|(cell, movement, _)| {
+                        *cell == pair[1].cell && *movement == pair[1].movement
+                    })
+                    .unwrap_or_else(|| panic!("{pair:?} is not a move"));
+                cost += step_cost;
+            }
+            assert!((cost - path.cost).abs() < 1e-2, "{cost} != {}", path.cost);
+            assert!(path.cost >= flat.cost - 1e-3);
+            worst = worst.max(path.cost / flat.cost.max(1.0));
+            found += 1;
+        }
+        (found, worst)
+    }
+
+    #[test]
+    fn paths_through_a_maze_are_nearly_as_short_as_flat_ones() {
+        let wall = |x: i32, z: i32| hash((x * 1000 + z) as u64) % 100 < 20;
+        let (found, worst) = compare(|_, _| 0, wall, 40);
+        assert!(found > 20, "only {found} paths found");
+        assert!(worst < 1.3, "a path is {worst} times longer");
+    }
+
+    #[test]
+    fn paths_over_hills_across_chunk_layers_are_nearly_as_short_as_flat_ones() {
+        let height = |x: i32, z: i32| {
+            31 + ((x as f32 * 0.15).sin() * 3.0 + (z as f32 * 0.11).cos() * 3.0) as i32
+        };
+        let wall = |x: i32, z: i32| hash((x * 7777 + z) as u64) % 100 < 10;
+        let (found, worst) = compare(height, wall, 40);
+        assert!(found > 20, "only {found} paths found");
+        assert!(worst < 1.3, "a path is {worst} times longer");
+    }
+}
@@ -0,0 +1,595 @@
+pub mod astar;
+pub mod grid;
+pub mod hierarchy;
+
+use std::sync::Arc;
+
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task, futures::check_ready};
+
+use crate::plugins::world::{
+    ChunkEntityMap, Chunks,
+    blocks::BlockRegistryRes,
+    chunk::world_to_chunk_local,
+    config::WorldHeightLimits,
+    events::{PathFailed, VoxelChanged},
+};
+use crate::state::LoadingState;
+use astar::{NavAgent, PathError, VoxelPath, find_path};
+use grid::{NavChunk, NavGrid, chunks_around};
+use hierarchy::{ChunkNavGraph, find_path_hierarchical, neighbourhood};
+
+/// Finds paths for entities with a [`PathRequest`] on the async compute pool, giving them
+/// a [`NavPath`] or triggering [`PathFailed`]. Paths through chunks that are edited are
+/// searched again from where the agent got to.
+///
+/// Long paths are found over the [`NavGraphs`] of the chunks between, which are built by
+/// the searches that need them and rebuilt as chunks are edited.
+pub struct PathfindingPlugin;
+
+impl Plugin for PathfindingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PathfindingSettings>()
+            .init_resource::<NavChunks>()
+            .init_resource::<NavGraphs>()
+            .add_systems(
+                Update,
+                (
+                    invalidate_edited_paths,
+                    rebuild_stale_nav_graphs,
+                    dispatch_path_requests,
+                    collect_found_paths,
+                )
+                    .chain()
+                    .run_if(in_state(LoadingState::Initialized)),
+            );
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct PathfindingSettings {
+    /// Most cells a single search visits before giving up.
+    pub max_nodes: usize,
+    /// Chunks around the start and goal that a search may leave their box by.
+    pub search_margin: i32,
+    /// Most searches running at once.
+    pub max_in_flight: usize,
+    /// Requests whose start and goal are further apart than this across the ground, in
+    /// voxels, search the chunk graphs instead of every voxel.
+    pub hierarchical_distance: i32,
+    /// Most stale chunk graphs rebuilt together.
+    pub max_graph_rebuilds: usize,
+}
+
+impl Default for PathfindingSettings {
+    fn default() -> Self {
+        Self {
+            max_nodes: 20_000,
+            search_margin: 1,
+            max_in_flight: 8,
+            hierarchical_distance: 64,
+            max_graph_rebuilds: 8,
+        }
+    }
+}
+
+/// Ask for a path from `start` to `goal`, both the voxel the agent's feet are in. The
+/// entity's [`NavAgent`], or the default one, decides where it can go.
+#[derive(Component, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PathRequest {
+    pub start: IVec3,
+    pub goal: IVec3,
+}
+
+/// A path being followed. `next` is the step the agent is heading for.
+#[derive(Component, Clone, Debug)]
+pub struct NavPath {
+    pub path: VoxelPath,
+    pub next: usize,
+}
+
+impl NavPath {
+    pub fn new(path: VoxelPath) -> Self {
+        Self { path, next: 1 }
+    }
+
+    /// Where the agent is heading, or `None` once it has arrived.
+    pub fn next_waypoint(&self) -> Option<Vec3> {
+        self.path.steps.get(self.next).map(|step| step.waypoint())
+    }
+
+    pub fn advance(&mut self) {
+        self.next += 1;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.path.steps.len()
+    }
+
+    /// The last cell the agent reached.
+    pub fn current_cell(&self) -> IVec3 {
+        let reached = self.next.saturating_sub(1).min(self.path.steps.len() - 1);
+        self.path.steps[reached].cell
+    }
+}
+
+/// A search running on the task pool, over the chunks in `chunks`.
+#[derive(Component)]
+struct PathTask {
+    request: PathRequest,
+    agent: NavAgent,
+    chunks: HashSet<IVec3>,
+    task: Task<SearchOutcome>,
+}
+
+struct SearchOutcome {
+    result: Result<VoxelPath, PathError>,
+    /// Chunk graphs the search had to build.
+    graphs: Vec<ChunkNavGraph>,
+}
+
+/// The [`NavChunk`] of each loaded chunk a search has needed, and the chunk entity it
+/// was made from. Edited and reloaded chunks are converted again when next needed.
+#[derive(Resource, Default)]
+pub struct NavChunks {
+    chunks: HashMap<IVec3, (Entity, Arc<NavChunk>)>,
+}
+
+impl NavChunks {
+    /// The nav chunk of the loaded chunk at `coord`, converting it if it has changed.
+    pub fn get_or_build(
+        &mut self,
+        coord: IVec3,
+        chunk_map: &ChunkEntityMap,
+        chunks: &Chunks,
+        registry: &BlockRegistryRes,
+    ) -> Option<Arc<NavChunk>> {
+        let entity = chunk_map.get(&coord)?;
+        if let Some((built_from, nav)) = self.chunks.get(&coord)
+            && *built_from == entity
+        {
+            return Some(nav.clone());
+        }
+        let nav = Arc::new(NavChunk::from_chunk(chunks.0.get(&entity)?, &registry.0));
+        self.chunks.insert(coord, (entity, nav.clone()));
+        Some(nav)
+    }
+
+    /// The nav chunk of `coord` as last converted, if it is still current.
+    pub fn current(&self, coord: IVec3) -> Option<Arc<NavChunk>> {
+        self.chunks.get(&coord).map(|(_, nav)| nav.clone())
+    }
+
+    pub fn invalidate(&mut self, coord: IVec3) {
+        self.chunks.remove(&coord);
+    }
+}
+
+/// The [`ChunkNavGraph`] of each loaded chunk for each kind of agent that has searched
+/// across it. Graphs of unloaded chunks are dropped.
+#[derive(Resource, Default)]
+pub struct NavGraphs {
+    graphs: HashMap<(NavAgent, IVec3), Arc<ChunkNavGraph>>,
+    /// Graphs to rebuild, after their chunk or one around it changed.
+    stale: HashSet<(NavAgent, IVec3)>,
+    rebuilding: Option<Task<Vec<(NavAgent, ChunkNavGraph)>>>,
+}
+
+impl NavGraphs {
+    pub fn get(&self, agent: &NavAgent, coord: IVec3) -> Option<&Arc<ChunkNavGraph>> {
+        self.graphs.get(&(*agent, coord))
+    }
+
+    /// The graph of `coord` for `agent`, if it was built from the chunks loaded now.
+    pub fn current(
+        &self,
+        agent: &NavAgent,
+        coord: IVec3,
+        chunk_map: &ChunkEntityMap,
+        nav_chunks: &NavChunks,
+    ) -> Option<&Arc<ChunkNavGraph>> {
+        self.get(agent, coord)
+            .filter(|graph| is_current(graph, chunk_map, nav_chunks))
+    }
+
+    /// Keep `graph` if it was built from the chunks loaded now.
+    fn insert_if_current(
+        &mut self,
+        agent: NavAgent,
+        graph: ChunkNavGraph,
+        chunk_map: &ChunkEntityMap,
+        nav_chunks: &NavChunks,
+    ) {
+        let key = (agent, graph.coord);
+        if is_current(&graph, chunk_map, nav_chunks) {
+            self.stale.remove(&key);
+            self.graphs.insert(key, Arc::new(graph));
+        }
+    }
+
+    /// Mark the graphs of `coord` and the chunks around it for rebuilding.
+    pub fn invalidate_around(&mut self, coord: IVec3) {
+        for coord in std::iter::once(coord).chain(neighbourhood(coord)) {
+            let agents: Vec<NavAgent> = self
+                .graphs
+                .keys()
+                .filter(|(_, graph)| *graph == coord)
+                .map(|(agent, _)| *agent)
+                .collect();
+            for agent in agents {
+                self.graphs.remove(&(agent, coord));
+                self.stale.insert((agent, coord));
+            }
+        }
+    }
+}
+
+fn is_current(graph: &ChunkNavGraph, chunk_map: &ChunkEntityMap, nav_chunks: &NavChunks) -> bool {
+    graph.is_current(
+        |coord| chunk_map.get(&coord).is_some(),
+        |coord| nav_chunks.current(coord),
+    )
+}
+
+/// Search again for paths, finished or still running, that rely on an edited chunk.
+fn invalidate_edited_paths(
+    mut commands: Commands,
+    mut changes: MessageReader<VoxelChanged>,
+    mut nav_chunks: ResMut<NavChunks>,
+    mut nav_graphs: ResMut<NavGraphs>,
+    paths: Query<(Entity, &NavPath, Option<&NavAgent>)>,
+    tasks: Query<(Entity, &PathTask)>,
+) {
+    let edited: HashSet<IVec3> = changes
+        .read()
+        .map(|change| world_to_chunk_local(change.world).0)
+        .collect();
+    if edited.is_empty() {
+        return;
+    }
+    for coord in &edited {
+        nav_chunks.invalidate(*coord);
+        nav_graphs.invalidate_around(*coord);
+    }
+
+    for (entity, path, agent) in &paths {
+        let height = agent.copied().unwrap_or_default().height;
+        if path.path.chunks(height).is_disjoint(&edited) {
+            continue;
+        }
+        commands
+            .entity(entity)
+            .remove::<NavPath>()
+            .insert(PathRequest {
+                start: path.current_cell(),
+                goal: path.path.goal(),
+            });
+    }
+    for (entity, task) in &tasks {
+        if !task.chunks.is_disjoint(&edited) {
+            commands
+                .entity(entity)
+                .remove::<PathTask>()
+                .insert(task.request);
+        }
+    }
+}
+
+/// Drop the graphs of unloaded chunks, and rebuild those invalidated by edits on the task
+/// pool, a batch at a time.
+fn rebuild_stale_nav_graphs(
+    settings: Res<PathfindingSettings>,
+    chunk_map: Res<ChunkEntityMap>,
+    chunks: Res<Chunks>,
+    registry: Res<BlockRegistryRes>,
+    mut nav_chunks: ResMut<NavChunks>,
+    mut nav_graphs: ResMut<NavGraphs>,
+) {
+    nav_graphs
+        .graphs
+        .retain(|(_, coord), _| chunk_map.get(coord).is_some());
+
+    if let Some(task) = &mut nav_graphs.rebuilding {
+        let Some(rebuilt) = check_ready(task) else {
+            return;
+        };
+        nav_graphs.rebuilding = None;
+        for (agent, graph) in rebuilt {
+            nav_graphs.insert_if_current(agent, graph, &chunk_map, &nav_chunks);
+        }
+    }
+
+    nav_graphs
+        .stale
+        .retain(|(_, coord)| chunk_map.get(coord).is_some());
+    let batch: Vec<(NavAgent, IVec3)> = nav_graphs
+        .stale
+        .iter()
+        .take(settings.max_graph_rebuilds)
+        .copied()
+        .collect();
+    if batch.is_empty() {
+        return;
+    }
+
+    let mut grid = NavGrid::default();
+    for (_, coord) in &batch {
+        for coord in std::iter::once(*coord).chain(neighbourhood(*coord)) {
+            if grid.get(coord).is_none()
+                && let Some(nav) = nav_chunks.get_or_build(coord, &chunk_map, &chunks, &registry)
+            {
+                grid.insert(coord, nav);
+            }
+        }
+    }
+    nav_graphs.rebuilding = Some(AsyncComputeTaskPool::get().spawn(async move {
+        batch
+            .into_iter()
+            .map(|(agent, coord)| (agent, ChunkNavGraph::build(coord, &grid, &agent)))
+            .collect()
+    }));
+}
+
+/// Start searches for new requests, as long as there are free slots.
+#[allow(clippy::too_many_arguments)]
+fn dispatch_path_requests(
+    mut commands: Commands,
+    settings: Res<PathfindingSettings>,
+    chunk_map: Res<ChunkEntityMap>,
+    chunks: Res<Chunks>,
+    registry: Res<BlockRegistryRes>,
+    limits: Res<WorldHeightLimits>,
+    mut nav_chunks: ResMut<NavChunks>,
+    nav_graphs: Res<NavGraphs>,
+    requests: Query<(Entity, &PathRequest, Option<&NavAgent>), Without<PathTask>>,
+    running: Query<(), With<PathTask>>,
+) {
+    nav_chunks
+        .chunks
+        .retain(|coord, (entity, _)| chunk_map.get(coord) == Some(*entity));
+
+    let free = settings.max_in_flight.saturating_sub(running.iter().len());
+    let pool = AsyncComputeTaskPool::get();
+    for (entity, request, agent) in requests.iter().take(free) {
+        let mut grid = NavGrid::default();
+        let mut region = HashSet::new();
+        for coord in chunks_around(request.start, request.goal, settings.search_margin)
+            .filter(|coord| limits.contains_chunk(coord.y))
+        {
+            if let Some(nav) = nav_chunks.get_or_build(coord, &chunk_map, &chunks, &registry) {
+                grid.insert(coord, nav);
+                region.insert(coord);
+            }
+        }
+
+        let request = *request;
+        let agent = agent.copied().unwrap_or_default();
+        let max_nodes = settings.max_nodes;
+        let across = (request.goal - request.start).xz().abs().max_element();
+        let task = if across > settings.hierarchical_distance {
+            let mut graphs = HashMap::new();
+            let mut missing = Vec::new();
+            for coord in &region {
+                match nav_graphs.current(&agent, *coord, &chunk_map, &nav_chunks) {
+                    Some(graph) => {
+                        graphs.insert(*coord, graph.clone());
+                    }
+                    None => missing.push(*coord),
+                }
+            }
+            pool.spawn(async move {
+                let built: Vec<ChunkNavGraph> = missing
+                    .into_iter()
+                    .map(|coord| ChunkNavGraph::build(coord, &grid, &agent))
+                    .collect();
+                for graph in &built {
+                    graphs.insert(graph.coord, Arc::new(graph.clone()));
+                }
+                SearchOutcome {
+                    result: find_path_hierarchical(
+                        &grid,
+                        &graphs,
+                        &agent,
+                        request.start,
+                        request.goal,
+                        max_nodes,
+                    ),
+                    graphs: built,
+                }
+            })
+        } else {
+            pool.spawn(async move {
+                SearchOutcome {
+                    result: find_path(&grid, &agent, request.start, request.goal, max_nodes),
+                    graphs: Vec::new(),
+                }
+            })
+        };
+        commands
+            .entity(entity)
+            .remove::<PathRequest>()
+            .insert(PathTask {
+                request,
+                agent,
+                chunks: region,
+                task,
+            });
+    }
+}
+
+fn collect_found_paths(
+    mut commands: Commands,
+    chunk_map: Res<ChunkEntityMap>,
+    nav_chunks: Res<NavChunks>,
+    mut nav_graphs: ResMut<NavGraphs>,
+    mut tasks: Query<(Entity, &mut PathTask)>,
+) {
+    for (entity, mut task) in &mut tasks {
+        let Some(outcome) = check_ready(&mut task.task) else {
+            continue;
+        };
+        commands.entity(entity).remove::<PathTask>();
+        for graph in outcome.graphs {
+            nav_graphs.insert_if_current(task.agent, graph, &chunk_map, &nav_chunks);
+        }
+        match outcome.result {
+            Ok(path) => {
+                commands.entity(entity).insert(NavPath::new(path));
+            }
+            Err(error) => commands.trigger(PathFailed {
+                entity,
+                request: task.request,
+                error,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::plugins::world::{
+        blocks::BLOCK_STONE,
+        chunk::{CHUNK_SIZE, Chunk},
+        test_support::{headless_app_in, spawn_chunks, update_until},
+        voxel::Voxel,
+        voxel_world::VoxelWorld,
+    };
+
+    /// The chunks in `chunks` loaded, in `state`.
+    fn pathfinding_app(
+        state: LoadingState,
+        chunks: impl IntoIterator<Item = (IVec3, Chunk)>,
+    ) -> App {
+        let mut app = headless_app_in(state, 1);
+        app.add_plugins(PathfindingPlugin);
+        spawn_chunks(app.world_mut(), chunks);
+        app
+    }
+
+    fn set_voxels(app: &mut App, voxels: &[(IVec3, Voxel)]) {
+        let voxels = voxels.to_vec();
+        app.world_mut()
+            .run_system_once(move |mut voxel_world: VoxelWorld| {
+                voxel_world.set_batch(voxels.iter().copied());
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn walls_across_a_path_search_it_again() {
+        let (chunk, start, goal) = astar::tests::maze(&["S..........G"]);
+        let mut app = pathfinding_app(LoadingState::Initialized, [(IVec3::ZERO, chunk)]);
+        let entity = app.world_mut().spawn(PathRequest { start, goal }).id();
+        assert!(update_until(&mut app, |world| world
+            .get::<NavPath>(entity)
+            .is_some()));
+        let path = &app.world().get::<NavPath>(entity).unwrap().path;
+        assert_eq!(path.steps.len(), 12);
+
+        let failed = Arc::new(Mutex::new(None));
+        let observed = failed.clone();
+        app.add_observer(move |failure: On<PathFailed>| {
+            *observed.lock().unwrap() = Some(failure.error);
+        });
+        let wall = Voxel::new(BLOCK_STONE);
+        set_voxels(
+            &mut app,
+            &(1..=3)
+                .map(|y| (IVec3::new(5, y, 0), wall))
+                .collect::<Vec<_>>(),
+        );
+        assert!(update_until(&mut app, |_| failed.lock().unwrap().is_some()));
+        assert!(app.world().get::<NavPath>(entity).is_none());
+        assert_eq!(*failed.lock().unwrap(), Some(PathError::Unreachable));
+    }
+
+    #[test]
+    fn waits_for_the_world_to_load() {
+        let (chunk, start, goal) = astar::tests::maze(&["S..G"]);
+        let mut app = pathfinding_app(LoadingState::Loading, [(IVec3::ZERO, chunk)]);
+        let entity = app.world_mut().spawn(PathRequest { start, goal }).id();
+        for _ in 0..5 {
+            app.update();
+        }
+        assert!(app.world().get::<PathRequest>(entity).is_some());
+        assert!(
+            app.world()
+                .resource::<NavChunks>()
+                .current(IVec3::ZERO)
+                .is_none()
+        );
+
+        app.world_mut()
+            .resource_mut::<NextState<LoadingState>>()
+            .set(LoadingState::Initialized);
+        assert!(update_until(&mut app, |world| world
+            .get::<NavPath>(entity)
+            .is_some()));
+    }
+
+    /// Three by three chunks with a stone floor at y 0, and air above.
+    fn flat_world() -> Vec<(IVec3, Chunk)> {
+        let mut floor = Chunk::new();
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                floor.set(x, 0, z, Voxel::new(BLOCK_STONE));
+            }
+        }
+        let mut chunks = Vec::new();
+        for x in 0..3 {
+            for z in 0..3 {
+                chunks.push((IVec3::new(x, 0, z), floor.clone()));
+                chunks.push((IVec3::new(x, 1, z), Chunk::new()));
+            }
+        }
+        chunks
+    }
+
+    #[test]
+    fn long_paths_keep_graphs_of_loaded_chunks_only() {
+        let mut app = pathfinding_app(LoadingState::Initialized, flat_world());
+        let request = PathRequest {
+            start: IVec3::new(1, 1, 1),
+            goal: IVec3::new(90, 1, 90),
+        };
+        let entity = app.world_mut().spawn(request).id();
+        assert!(update_until(&mut app, |world| world
+            .get::<NavPath>(entity)
+            .is_some()));
+        let agent = NavAgent::default();
+        let middle = IVec3::new(1, 0, 1);
+        let graph = |world: &World| world.resource::<NavGraphs>().get(&agent, middle).cloned();
+        let before = graph(app.world()).expect("the search built the graphs between");
+
+        // An edit invalidates the graphs around it, which are rebuilt in the background.
+        set_voxels(
+            &mut app,
+            &[(IVec3::new(40, 1, 40), Voxel::new(BLOCK_STONE))],
+        );
+        app.update();
+        assert!(graph(app.world()).is_none());
+        assert!(update_until(&mut app, |world| graph(world).is_some()));
+        assert!(!Arc::ptr_eq(&before, &graph(app.world()).unwrap()));
+        assert!(update_until(&mut app, |world| {
+            let nav_graphs = world.resource::<NavGraphs>();
+            nav_graphs.stale.is_empty() && nav_graphs.rebuilding.is_none()
+        }));
+
+        let chunk = app
+            .world()
+            .resource::<ChunkEntityMap>()
+            .get(&middle)
+            .unwrap();
+        app.world_mut().despawn(chunk);
+        app.update();
+        let nav_graphs = app.world().resource::<NavGraphs>();
+        assert!(nav_graphs.get(&agent, middle).is_none());
+        assert!(nav_graphs.get(&agent, IVec3::ZERO).is_some());
+    }
+}
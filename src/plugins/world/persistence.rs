@@ -0,0 +1,336 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    ChunkComponent, ChunkEntityMap, Chunks,
+    block_entity::{BlockEntities, BlockEntity, BlockEntityTypes, spawn_block_entity},
+    blocks::BlockId,
+    byte_reader::{BinaryError, ByteReader},
+    chunk::{CHUNK_SIZE, CHUNK_VOLUME, Chunk, world_to_chunk_local},
+    heightmap::rebuild_column_heightmap,
+    mark_neighbours_dirty,
+    voxel::Voxel,
+};
+
+pub const CHUNK_SAVE_DIR: &str = "saves/world/chunks";
+pub const CHUNK_EXTENSION: &str = "chunk";
+
+const CHUNK_MAGIC: &[u8; 4] = b"VXCK";
+const CHUNK_VERSION: u8 = 1;
+
+/// Where unloaded chunks are written to and loaded from.
+#[derive(Resource, Clone, Debug)]
+pub struct ChunkPersistence {
+    pub directory: PathBuf,
+}
+
+impl Default for ChunkPersistence {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from(CHUNK_SAVE_DIR),
+        }
+    }
+}
+
+impl ChunkPersistence {
+    pub fn path(&self, coord: IVec3) -> PathBuf {
+        self.directory
+            .join(format!("{}_{}_{}", coord.x, coord.y, coord.z))
+            .with_extension(CHUNK_EXTENSION)
+    }
+}
+
+/// A block entity as stored in a [`ChunkSave`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SavedBlockEntity {
+    /// Voxel coordinate local to the chunk.
+    pub local: IVec3,
+    pub block_id: BlockId,
+    /// Data as written by the block's [`BlockEntityType`](super::block_entity::BlockEntityType).
+    pub data: String,
+}
+
+/// A chunk's voxels together with the block entities it owns.
+#[derive(Clone, Debug)]
+pub struct ChunkSave {
+    pub chunk: Chunk,
+    pub block_entities: Vec<SavedBlockEntity>,
+}
+
+impl ChunkSave {
+    /// Snapshot the loaded chunk at `coord`, or `None` if it isn't loaded.
+    pub fn capture(world: &World, coord: IVec3) -> Option<Self> {
+        let entity = world.resource::<ChunkEntityMap>().get(&coord)?;
+        let chunk = world.resource::<Chunks>().0.get(&entity)?.clone();
+
+        let types = world.resource::<BlockEntityTypes>();
+        let mut block_entities: Vec<SavedBlockEntity> = world
+            .resource::<BlockEntities>()
+            .in_chunk(coord)
+            .filter_map(|(position, entity)| {
+                let entity = world.get_entity(entity).ok()?;
+                let block_id = entity.get::<BlockEntity>()?.block_id;
+                let data = types.get(block_id)?.save(entity)?;
+                Some(SavedBlockEntity {
+                    local: world_to_chunk_local(position).1,
+                    block_id,
+                    data,
+                })
+            })
+            .collect();
+        block_entities.sort_by_key(|saved| (saved.local.z, saved.local.y, saved.local.x));
+
+        Some(Self {
+            chunk,
+            block_entities,
+        })
+    }
+
+    /// Serialize as:
+    /// `"VXCK"`, version `u8`, run-length encoded voxels as `(run u16, block id u16, state u16)`
+    /// in [`Chunk::index`] order, block entity count `u16`, then per block entity its local
+    /// `x, y, z` as `u8`, block id `u16`, data length `u32` and UTF-8 data. All little endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(CHUNK_MAGIC);
+        bytes.push(CHUNK_VERSION);
+
+        for run in self.chunk.voxels().chunk_by(|a, b| a == b) {
+            for part in run.chunks(u16::MAX as usize) {
+                bytes.extend_from_slice(&(part.len() as u16).to_le_bytes());
+                bytes.extend_from_slice(&part[0].block_id().to_le_bytes());
+                bytes.extend_from_slice(&part[0].state().to_le_bytes());
+            }
+        }
+
+        bytes.extend_from_slice(&(self.block_entities.len() as u16).to_le_bytes());
+        for saved in &self.block_entities {
+            bytes.extend(saved.local.to_array().map(|v| v as u8));
+            bytes.extend_from_slice(&saved.block_id.to_le_bytes());
+            bytes.extend_from_slice(&(saved.data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(saved.data.as_bytes());
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BinaryError> {
+        let mut cursor = ByteReader::new(bytes);
+
+        cursor.magic(CHUNK_MAGIC)?;
+        let version = cursor.u8()?;
+        if version != CHUNK_VERSION {
+            return Err(BinaryError::UnsupportedVersion(version));
+        }
+
+        let mut voxels = Vec::with_capacity(CHUNK_VOLUME);
+        while voxels.len() < CHUNK_VOLUME {
+            let run = cursor.u16()? as usize;
+            let voxel = Voxel::checked(cursor.u16()?, cursor.u16()?)
+                .ok_or(BinaryError::InvalidData("voxel out of range"))?;
+            voxels.extend(std::iter::repeat_n(voxel, run));
+        }
+        let voxels: Box<[Voxel; CHUNK_VOLUME]> = voxels
+            .into_boxed_slice()
+            .try_into()
+            .map_err(|_| BinaryError::InvalidData("voxel count does not match chunk size"))?;
+
+        let count = cursor.u16()?;
+        let block_entities = (0..count)
+            .map(|_| {
+                let local = cursor.take(3)?;
+                let local = IVec3::new(local[0] as i32, local[1] as i32, local[2] as i32);
+                if local.max_element() >= CHUNK_SIZE as i32 {
+                    return Err(BinaryError::InvalidData("block entity outside chunk"));
+                }
+                let block_id = cursor.u16()?;
+                let len = cursor.u32()? as usize;
+                let data = cursor.str(len)?.to_string();
+                Ok(SavedBlockEntity {
+                    local,
+                    block_id,
+                    data,
+                })
+            })
+            .collect::<Result<Vec<_>, BinaryError>>()?;
+
+        if !cursor.is_empty() {
+            return Err(BinaryError::InvalidData("trailing bytes"));
+        }
+
+        Ok(Self {
+            chunk: Chunk::from_voxels(voxels),
+            block_entities,
+        })
+    }
+}
+
+/// Write the loaded chunk at `coord` to disk. Returns `Ok(false)` if it isn't loaded.
+pub fn save_chunk(world: &World, coord: IVec3) -> Result<bool, BinaryError> {
+    let Some(save) = ChunkSave::capture(world, coord) else {
+        return Ok(false);
+    };
+    let path = world.resource::<ChunkPersistence>().path(coord);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, save.to_bytes())?;
+    Ok(true)
+}
+
+/// Save the chunk at `coord`, then despawn it together with its block entities.
+/// The chunk stays loaded if saving fails.
+pub fn unload_chunk(world: &mut World, coord: IVec3) -> Result<bool, BinaryError> {
+    if !save_chunk(world, coord)? {
+        return Ok(false);
+    }
+    let Some(entity) = world.resource::<ChunkEntityMap>().get(&coord) else {
+        return Ok(false);
+    };
+
+    // Removing the chunk component updates whatever was derived from the chunk.
+    world.despawn(entity);
+    Ok(true)
+}
+
+/// Spawn `save` as the chunk at `coord`, restoring its block entities.
+/// Does nothing if a chunk is already loaded there.
+pub fn insert_chunk_save(world: &mut World, coord: IVec3, save: ChunkSave) -> Option<Entity> {
+    if world.resource::<ChunkEntityMap>().get(&coord).is_some() {
+        return None;
+    }
+
+    let entity = world.spawn(ChunkComponent { coord }).id();
+    world.resource_mut::<Chunks>().0.insert(entity, save.chunk);
+
+    let origin = coord * CHUNK_SIZE as i32;
+    for saved in save.block_entities {
+        spawn_block_entity(
+            world,
+            origin + saved.local,
+            saved.block_id,
+            Some(&saved.data),
+        );
+    }
+
+    mark_neighbours_dirty(world, coord);
+    rebuild_column_heightmap(world, coord);
+    Some(entity)
+}
+
+/// Read the chunk saved at `path`. Returns `Ok(None)` if there is none. Needs no
+/// [`World`], so it can run on a task pool.
+pub fn read_chunk_save(path: &Path) -> Result<Option<ChunkSave>, BinaryError> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    ChunkSave::from_bytes(&bytes).map(Some)
+}
+
+/// Load the chunk at `coord` from disk. Returns `Ok(false)` if it has never been saved
+/// or is already loaded.
+pub fn load_chunk(world: &mut World, coord: IVec3) -> Result<bool, BinaryError> {
+    let path = world.resource::<ChunkPersistence>().path(coord);
+    let Some(save) = read_chunk_save(&path)? else {
+        return Ok(false);
+    };
+    Ok(insert_chunk_save(world, coord, save).is_some())
+}
+
+pub trait ChunkPersistenceCommandExt {
+    fn save_chunk(&mut self, coord: IVec3);
+    fn unload_chunk(&mut self, coord: IVec3);
+    fn load_chunk(&mut self, coord: IVec3);
+}
+
+impl<'w, 's> ChunkPersistenceCommandExt for Commands<'w, 's> {
+    fn save_chunk(&mut self, coord: IVec3) {
+        self.queue(move |world: &mut World| {
+            if let Err(err) = save_chunk(world, coord) {
+                error!("Failed to save chunk {coord}: {err}");
+            }
+        })
+    }
+
+    fn unload_chunk(&mut self, coord: IVec3) {
+        self.queue(move |world: &mut World| {
+            if let Err(err) = unload_chunk(world, coord) {
+                error!("Failed to unload chunk {coord}: {err}");
+            }
+        })
+    }
+
+    fn load_chunk(&mut self, coord: IVec3) {
+        self.queue(move |world: &mut World| {
+            if let Err(err) = load_chunk(world, coord) {
+                error!("Failed to load chunk {coord}: {err}");
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::world::blocks::{BLOCK_DIRT, BLOCK_LOG, BLOCK_STONE};
+
+    fn sample() -> ChunkSave {
+        let mut chunk = Chunk::new();
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set(x, 0, z, Voxel::new(BLOCK_STONE));
+            }
+        }
+        chunk.set(3, 1, 4, Voxel::with_state(BLOCK_LOG, 2));
+        chunk.set(31, 31, 31, Voxel::new(BLOCK_DIRT));
+        ChunkSave {
+            chunk,
+            block_entities: vec![SavedBlockEntity {
+                local: IVec3::new(3, 1, 4),
+                block_id: BLOCK_LOG,
+                data: "(items: [])".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let save = sample();
+        let loaded = ChunkSave::from_bytes(&save.to_bytes()).unwrap();
+        assert_eq!(loaded.chunk.voxels(), save.chunk.voxels());
+        assert_eq!(loaded.block_entities, save.block_entities);
+    }
+
+    #[test]
+    fn rejects_bad_data() {
+        let bytes = sample().to_bytes();
+        assert!(matches!(
+            ChunkSave::from_bytes(b"VXSC\x01"),
+            Err(BinaryError::InvalidMagic(_))
+        ));
+        assert!(matches!(
+            ChunkSave::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(BinaryError::Truncated)
+        ));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(
+            ChunkSave::from_bytes(&trailing),
+            Err(BinaryError::InvalidData(_))
+        ));
+
+        // Move the block entity out of the chunk.
+        let mut outside = bytes;
+        let entity = outside.len() - (3 + 2 + 4 + "(items: [])".len());
+        outside[entity] = CHUNK_SIZE as u8;
+        assert!(matches!(
+            ChunkSave::from_bytes(&outside),
+            Err(BinaryError::InvalidData(_))
+        ));
+    }
+}
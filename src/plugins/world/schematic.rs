@@ -0,0 +1,737 @@
+use std::path::Path;
+
+use bevy::asset::{AssetLoader, LoadContext, io::Reader};
+use bevy::color::palettes::basic::AQUA;
+use bevy::math::I64Vec3;
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+
+use crate::plugins::{
+    asset_loader::assets::VoxelAtlasHandles,
+    world::{
+        MesherResource,
+        blocks::BlockRegistryRes,
+        byte_reader::{BinaryError, ByteReader},
+        chunk::{CHUNK_SIZE, Chunk},
+        edit_history::{EditHistory, EditTransaction},
+        material::VoxelAtlasMaterial,
+        meshers::{Neighbors, Neighbour},
+        vox::VoxLoader,
+        voxel::Voxel,
+        voxel_picking::{HoveredVoxel, VoxelHit},
+        voxel_world::VoxelWorld,
+    },
+};
+use crate::state::LoadingState;
+
+/// Extension of schematic files, see [`Schematic::to_bytes`] for the layout.
+pub const SCHEMATIC_EXTENSION: &str = "vxs";
+/// Directory (relative to the working directory) that saved schematics are written to.
+pub const SCHEMATIC_SAVE_DIR: &str = "assets/schematics";
+
+/// Most voxels a schematic may hold, whether loaded from a file or captured.
+pub const MAX_SCHEMATIC_VOLUME: usize = 1 << 26;
+
+const SCHEMATIC_MAGIC: &[u8; 4] = b"VXSC";
+const SCHEMATIC_VERSION: u8 = 2;
+
+pub struct SchematicPlugin;
+
+impl Plugin for SchematicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Schematic>()
+            .register_asset_loader(SchematicLoader)
+            .register_asset_loader(VoxLoader)
+            .init_resource::<SchematicClipboard>()
+            .add_systems(
+                Update,
+                (copy_paste_schematic, draw_schematic_paste_gizmos)
+                    .chain()
+                    .run_if(in_state(LoadingState::Initialized)),
+            );
+    }
+}
+
+/// A stored multi-voxel structure. Voxels are palette indices laid out like
+/// [`Chunk`]: x fastest, then y, then z.
+#[derive(Asset, TypePath, Clone, Debug, PartialEq)]
+pub struct Schematic {
+    size: UVec3,
+    palette: Vec<Voxel>,
+    data: Vec<u16>,
+}
+
+impl Schematic {
+    /// Build a schematic of `size` by sampling `f` at every local position.
+    pub fn from_fn(size: UVec3, mut f: impl FnMut(UVec3) -> Voxel) -> Self {
+        let mut palette = Vec::new();
+        let mut lookup = HashMap::new();
+        let mut data = Vec::with_capacity(size.as_usizevec3().element_product());
+
+        for z in 0..size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let voxel = f(UVec3::new(x, y, z));
+                    let idx = *lookup.entry(voxel).or_insert_with(|| {
+                        palette.push(voxel);
+                        (palette.len() - 1) as u16
+                    });
+                    data.push(idx);
+                }
+            }
+        }
+
+        Self {
+            size,
+            palette,
+            data,
+        }
+    }
+
+    /// Capture the inclusive box between two world voxel coords, or `None` if it holds
+    /// more than [`MAX_SCHEMATIC_VOLUME`] voxels. Voxels in unloaded chunks are captured as air.
+    pub fn capture(voxel_world: &VoxelWorld, from: IVec3, to: IVec3) -> Option<Self> {
+        let min = from.min(to);
+        let size = (from.max(to).as_i64vec3() - min.as_i64vec3() + I64Vec3::ONE).as_u64vec3();
+        let volume = size
+            .x
+            .checked_mul(size.y)
+            .and_then(|area| area.checked_mul(size.z))?;
+        if volume > MAX_SCHEMATIC_VOLUME as u64 {
+            return None;
+        }
+
+        Some(Self::from_fn(size.as_uvec3(), |local| {
+            voxel_world
+                .get(min + local.as_ivec3())
+                .unwrap_or(Voxel::AIR)
+        }))
+    }
+
+    pub fn size(&self) -> UVec3 {
+        self.size
+    }
+
+    pub fn palette(&self) -> &[Voxel] {
+        &self.palette
+    }
+
+    pub fn get(&self, local: UVec3) -> Voxel {
+        let (local, size) = (local.as_usizevec3(), self.size.as_usizevec3());
+        let idx = local.x + size.x * (local.y + size.y * local.z);
+        self.palette[self.data[idx] as usize]
+    }
+
+    /// Non-air voxels as offsets from the min corner of the transformed structure.
+    pub fn voxels(&self, transform: SchematicTransform) -> impl Iterator<Item = (IVec3, Voxel)> {
+        let size = self.size;
+        (0..size.z)
+            .flat_map(move |z| {
+                (0..size.y).flat_map(move |y| (0..size.x).map(move |x| UVec3::new(x, y, z)))
+            })
+            .zip(self.data.iter())
+            .filter_map(move |(local, idx)| {
+                let voxel = self.palette[*idx as usize];
+                (!voxel.is_air()).then(|| (transform.apply(local, size).as_ivec3(), voxel))
+            })
+    }
+
+    /// Paste with the min corner at `origin`. Air in the schematic leaves the world untouched.
+    pub fn paste(
+        &self,
+        voxel_world: &mut VoxelWorld,
+        origin: IVec3,
+        transform: SchematicTransform,
+    ) -> EditTransaction {
+        let mut transaction = EditTransaction::new();
+        transaction.set_batch(
+            voxel_world,
+            self.voxels(transform)
+                .map(|(offset, voxel)| (origin + offset, voxel)),
+        );
+        transaction
+    }
+
+    /// Write the part of the structure that falls inside the chunk at `chunk_coord`,
+    /// for generators filling chunks before they are spawned. A voxel is only written
+    /// where `overwrites(current, voxel)` allows it.
+    pub fn stamp_into_chunk(
+        &self,
+        chunk: &mut Chunk,
+        chunk_coord: IVec3,
+        origin: IVec3,
+        transform: SchematicTransform,
+        overwrites: impl Fn(Voxel, Voxel) -> bool,
+    ) {
+        let chunk_origin = chunk_coord * CHUNK_SIZE as i32;
+        let range = 0..CHUNK_SIZE as i32;
+
+        for (offset, voxel) in self.voxels(transform) {
+            let local = origin + offset - chunk_origin;
+            if range.contains(&local.x) && range.contains(&local.y) && range.contains(&local.z) {
+                let (x, y, z) = (local.x as usize, local.y as usize, local.z as usize);
+                if overwrites(chunk.get(x, y, z), voxel) {
+                    chunk.set(x, y, z, voxel);
+                }
+            }
+        }
+    }
+
+    /// Min corner that centers the structure horizontally on the cell in front of the hit face.
+    pub fn origin_at_hit(&self, hit: VoxelHit, transform: SchematicTransform) -> IVec3 {
+        let size = transform.transformed_size(self.size).as_ivec3();
+        hit.adjacent() - IVec3::new(size.x / 2, 0, size.z / 2)
+    }
+
+    /// Serialize as:
+    /// `"VXSC"`, version `u8`, size `3 x u16`, palette length `u16`, palette entries as
+    /// `(block id u16, state u16)`, then run-length encoded palette indices as
+    /// `(run u16, index u16)` pairs. All little endian.
+    ///
+    /// Version 1 files, whose palette entries are block ids only, are still read.
+    ///
+    /// Fails if a dimension or the palette is too large for a `u16`.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BinaryError> {
+        let narrow =
+            |value: usize, what| u16::try_from(value).map_err(|_| BinaryError::InvalidData(what));
+        let mut bytes = Vec::with_capacity(16 + self.palette.len() * 4 + self.data.len());
+        bytes.extend_from_slice(SCHEMATIC_MAGIC);
+        bytes.push(SCHEMATIC_VERSION);
+        for dim in self.size.to_array() {
+            let dim = narrow(dim as usize, "schematic is too large")?;
+            bytes.extend_from_slice(&dim.to_le_bytes());
+        }
+        let palette_len = narrow(self.palette.len(), "schematic palette is too large")?;
+        bytes.extend_from_slice(&palette_len.to_le_bytes());
+        for voxel in &self.palette {
+            bytes.extend_from_slice(&voxel.block_id().to_le_bytes());
+            bytes.extend_from_slice(&voxel.state().to_le_bytes());
+        }
+
+        for run in self.data.chunk_by(|a, b| a == b) {
+            for part in run.chunks(u16::MAX as usize) {
+                bytes.extend_from_slice(&(part.len() as u16).to_le_bytes());
+                bytes.extend_from_slice(&part[0].to_le_bytes());
+            }
+        }
+
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BinaryError> {
+        let mut cursor = ByteReader::new(bytes);
+
+        cursor.magic(SCHEMATIC_MAGIC)?;
+        let version = cursor.u8()?;
+        if version == 0 || version > SCHEMATIC_VERSION {
+            return Err(BinaryError::UnsupportedVersion(version));
+        }
+
+        let size = UVec3::new(
+            cursor.u16()? as u32,
+            cursor.u16()? as u32,
+            cursor.u16()? as u32,
+        );
+        let palette_len = cursor.u16()? as usize;
+        let palette = (0..palette_len)
+            .map(|_| {
+                let block_id = cursor.u16()?;
+                let state = if version >= 2 { cursor.u16()? } else { 0 };
+                Voxel::checked(block_id, state)
+                    .ok_or(BinaryError::InvalidData("palette voxel out of range"))
+            })
+            .collect::<Result<Vec<_>, BinaryError>>()?;
+
+        let volume = size.as_usizevec3().element_product();
+        if volume > MAX_SCHEMATIC_VOLUME {
+            return Err(BinaryError::InvalidData("schematic is too large"));
+        }
+        // Each 4 byte run covers at most `u16::MAX` voxels, so a size the rest of the data
+        // can't fill is rejected before anything is allocated for it.
+        if volume > cursor.remaining() / 4 * u16::MAX as usize {
+            return Err(BinaryError::InvalidData(
+                "size is larger than the voxel data",
+            ));
+        }
+        let mut data = Vec::with_capacity(volume.min(cursor.remaining()));
+        while data.len() < volume {
+            let run = cursor.u16()? as usize;
+            let idx = cursor.u16()?;
+            if idx as usize >= palette.len() {
+                return Err(BinaryError::InvalidData("palette index out of range"));
+            }
+            data.extend(std::iter::repeat_n(idx, run));
+        }
+        if data.len() != volume || !cursor.is_empty() {
+            return Err(BinaryError::InvalidData("voxel count does not match size"));
+        }
+
+        Ok(Self {
+            size,
+            palette,
+            data,
+        })
+    }
+
+    /// Write to `<SCHEMATIC_SAVE_DIR>/<name>.vxs`, loadable as `schematics/<name>.vxs`.
+    pub fn save(&self, name: &str) -> Result<(), BinaryError> {
+        let dir = Path::new(SCHEMATIC_SAVE_DIR);
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(
+            dir.join(name).with_extension(SCHEMATIC_EXTENSION),
+            self.to_bytes()?,
+        )?;
+        Ok(())
+    }
+}
+
+#[derive(Default, TypePath)]
+pub struct SchematicLoader;
+
+impl AssetLoader for SchematicLoader {
+    type Asset = Schematic;
+    type Settings = ();
+    type Error = BinaryError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Schematic, BinaryError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Schematic::from_bytes(&bytes)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &[SCHEMATIC_EXTENSION]
+    }
+}
+
+pub trait SpawnSchematicModelCommandExt {
+    /// Spawn `schematic` as a standalone meshed entity, outside of [`Chunks`](super::Chunks).
+    /// The structure's min corner sits at the entity's origin.
+    fn spawn_schematic_model(&mut self, schematic: Schematic, transform: Transform);
+}
+
+impl<'w, 's> SpawnSchematicModelCommandExt for Commands<'w, 's> {
+    fn spawn_schematic_model(&mut self, schematic: Schematic, transform: Transform) {
+        self.queue(move |world: &mut World| {
+            let Some(children) = build_schematic_meshes(world, &schematic) else {
+                warn!("spawn_schematic_model called before the voxel atlas was loaded");
+                return;
+            };
+
+            world
+                .spawn((transform, Visibility::default()))
+                .with_children(|parent| {
+                    for child in children {
+                        parent.spawn(child);
+                    }
+                });
+        })
+    }
+}
+
+/// Mesh `schematic` with the chunk mesher, returning one child bundle per opaque or
+/// translucent piece, positioned relative to the schematic's min corner.
+/// Returns `None` before the voxel atlas has loaded.
+pub fn build_schematic_meshes(
+    world: &mut World,
+    schematic: &Schematic,
+) -> Option<Vec<(Mesh3d, MeshMaterial3d<VoxelAtlasMaterial>, Transform)>> {
+    let (material, translucent_material) =
+        world.get_resource::<VoxelAtlasHandles>().map(|handles| {
+            (
+                handles.material.clone(),
+                handles.translucent_material.clone(),
+            )
+        })?;
+
+    // Split into chunk-sized pieces so the regular chunk mesher can be reused.
+    let pieces_per_axis = schematic.size().as_ivec3() + IVec3::splat(CHUNK_SIZE as i32 - 1);
+    let pieces_per_axis = pieces_per_axis / CHUNK_SIZE as i32;
+    let mut pieces = HashMap::new();
+    for z in 0..pieces_per_axis.z {
+        for y in 0..pieces_per_axis.y {
+            for x in 0..pieces_per_axis.x {
+                let coord = IVec3::new(x, y, z);
+                let mut chunk = Chunk::new();
+                schematic.stamp_into_chunk(
+                    &mut chunk,
+                    coord,
+                    IVec3::ZERO,
+                    SchematicTransform::default(),
+                    |_, _| true,
+                );
+                pieces.insert(coord, chunk);
+            }
+        }
+    }
+
+    let mesher = world.resource::<MesherResource>();
+    let registry = world.resource::<BlockRegistryRes>();
+    let mut meshes: Vec<(IVec3, Mesh, bool)> = Vec::new();
+    for (coord, chunk) in &pieces {
+        let neighbours = Neighbour::ALL.map(|n| pieces.get(&(coord + n.normal())));
+        let neighbours = Neighbors::from_array(neighbours);
+        let mesh = mesher.0.build_mesh(chunk, neighbours, &registry.0);
+        meshes.push((*coord, mesh, false));
+        if let Some(mesh) = mesher
+            .0
+            .build_translucent_mesh(chunk, neighbours, &registry.0)
+        {
+            meshes.push((*coord, mesh, true));
+        }
+    }
+
+    let mut mesh_assets = world.resource_mut::<Assets<Mesh>>();
+    let children = meshes
+        .into_iter()
+        .map(|(coord, mesh, translucent)| {
+            let material = if translucent {
+                translucent_material.clone()
+            } else {
+                material.clone()
+            };
+            (
+                Mesh3d(mesh_assets.add(mesh)),
+                MeshMaterial3d(material),
+                Transform::from_translation((coord * CHUNK_SIZE as i32).as_vec3()),
+            )
+        })
+        .collect();
+    Some(children)
+}
+
+/// Mirroring followed by clockwise (seen from above) quarter turns about +Y.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SchematicTransform {
+    pub quarter_turns: u8,
+    pub mirror_x: bool,
+    pub mirror_z: bool,
+}
+
+impl SchematicTransform {
+    pub fn rotated_cw(self) -> Self {
+        Self {
+            quarter_turns: (self.quarter_turns + 1) % 4,
+            ..self
+        }
+    }
+
+    pub fn transformed_size(self, size: UVec3) -> UVec3 {
+        if self.quarter_turns % 2 == 1 {
+            UVec3::new(size.z, size.y, size.x)
+        } else {
+            size
+        }
+    }
+
+    /// Map `local` in a box of `size` to its position in the transformed box.
+    pub fn apply(self, local: UVec3, size: UVec3) -> UVec3 {
+        let mut p = local;
+        if self.mirror_x {
+            p.x = size.x - 1 - p.x;
+        }
+        if self.mirror_z {
+            p.z = size.z - 1 - p.z;
+        }
+
+        let mut size_z = size.z;
+        let mut size_x = size.x;
+        for _ in 0..self.quarter_turns % 4 {
+            p = UVec3::new(size_z - 1 - p.z, p.y, p.x);
+            std::mem::swap(&mut size_x, &mut size_z);
+        }
+        p
+    }
+}
+
+/// Copied structure and how it will be pasted.
+#[derive(Resource, Default, Debug)]
+pub struct SchematicClipboard {
+    pub schematic: Option<Schematic>,
+    pub transform: SchematicTransform,
+    /// First corner of the region being selected for copying.
+    pub corner: Option<IVec3>,
+}
+
+/// Ctrl+C twice selects two corners and copies the region between them,
+/// Ctrl+V pastes at the hovered face, R rotates, T mirrors left to right, Shift+T
+/// mirrors front to back and Ctrl+S saves the clipboard under [`SCHEMATIC_SAVE_DIR`].
+fn copy_paste_schematic(
+    keys: Res<ButtonInput<KeyCode>>,
+    hovered: Res<HoveredVoxel>,
+    mut clipboard: ResMut<SchematicClipboard>,
+    mut history: ResMut<EditHistory>,
+    mut voxel_world: VoxelWorld,
+) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+
+    if ctrl && keys.just_pressed(KeyCode::KeyS) {
+        if let Some(schematic) = &clipboard.schematic {
+            let name = format!(
+                "clipboard_{}",
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default()
+            );
+            match schematic.save(&name) {
+                Ok(()) => {
+                    info!("Saved schematic {SCHEMATIC_SAVE_DIR}/{name}.{SCHEMATIC_EXTENSION}")
+                }
+                Err(err) => warn!("Failed to save schematic: {err}"),
+            }
+        }
+        return;
+    }
+
+    if !ctrl {
+        if keys.just_pressed(KeyCode::KeyR) {
+            clipboard.transform = clipboard.transform.rotated_cw();
+        }
+        if keys.just_pressed(KeyCode::KeyT) {
+            let transform = &mut clipboard.transform;
+            if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+                transform.mirror_z = !transform.mirror_z;
+            } else {
+                transform.mirror_x = !transform.mirror_x;
+            }
+        }
+        return;
+    }
+
+    let Some(hit) = hovered.hit else {
+        return;
+    };
+
+    if keys.just_pressed(KeyCode::KeyC) {
+        match clipboard.corner.take() {
+            None => clipboard.corner = Some(hit.world),
+            Some(corner) => {
+                let Some(schematic) = Schematic::capture(&voxel_world, corner, hit.world) else {
+                    warn!(
+                        "Selection from {corner} to {} is too large to copy",
+                        hit.world
+                    );
+                    return;
+                };
+                info!("Copied {} schematic", schematic.size());
+                clipboard.schematic = Some(schematic);
+                clipboard.transform = SchematicTransform::default();
+            }
+        }
+    }
+
+    if keys.just_pressed(KeyCode::KeyV)
+        && let Some(schematic) = &clipboard.schematic
+    {
+        let origin = schematic.origin_at_hit(hit, clipboard.transform);
+        history.push(schematic.paste(&mut voxel_world, origin, clipboard.transform));
+    }
+}
+
+/// Outline the copy selection and where the clipboard would be pasted.
+fn draw_schematic_paste_gizmos(
+    mut gizmos: Gizmos,
+    clipboard: Res<SchematicClipboard>,
+    hovered: Res<HoveredVoxel>,
+) {
+    let Some(hit) = hovered.hit else {
+        return;
+    };
+
+    if let Some(corner) = clipboard.corner {
+        draw_box(
+            &mut gizmos,
+            corner.min(hit.world),
+            corner.max(hit.world) + IVec3::ONE,
+        );
+        return;
+    }
+
+    if let Some(schematic) = &clipboard.schematic {
+        let origin = schematic.origin_at_hit(hit, clipboard.transform);
+        let size = clipboard.transform.transformed_size(schematic.size());
+        draw_box(&mut gizmos, origin, origin + size.as_ivec3());
+    }
+}
+
+fn draw_box(gizmos: &mut Gizmos, min: IVec3, max: IVec3) {
+    let (lo, hi) = (min.as_vec3(), max.as_vec3());
+    gizmos.cube(
+        Transform::from_translation((lo + hi) * 0.5).with_scale(hi - lo),
+        AQUA,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::plugins::world::test_support::headless_world;
+
+    fn sample() -> Schematic {
+        Schematic::from_fn(UVec3::new(3, 2, 5), |p| {
+            if p.x == 0 {
+                Voxel::AIR
+            } else {
+                Voxel::with_state((p.z % 3) as u16 + 1, p.y as u16)
+            }
+        })
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let schematic = sample();
+        let bytes = schematic.to_bytes().unwrap();
+        assert_eq!(Schematic::from_bytes(&bytes).unwrap(), schematic);
+    }
+
+    #[test]
+    fn rejects_bad_headers_and_data() {
+        let bytes = sample().to_bytes().unwrap();
+        assert!(matches!(
+            Schematic::from_bytes(b"NOPE\x02"),
+            Err(BinaryError::InvalidMagic(_))
+        ));
+
+        let mut future = bytes.clone();
+        future[4] = SCHEMATIC_VERSION + 1;
+        assert!(matches!(
+            Schematic::from_bytes(&future),
+            Err(BinaryError::UnsupportedVersion(_))
+        ));
+
+        assert!(Schematic::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut trailing = bytes;
+        trailing.push(0);
+        assert!(Schematic::from_bytes(&trailing).is_err());
+    }
+
+    #[test]
+    fn rejects_sizes_the_data_cannot_fill() {
+        // A size under the volume cap, with one run of air.
+        let mut bytes = SCHEMATIC_MAGIC.to_vec();
+        bytes.push(SCHEMATIC_VERSION);
+        for _ in 0..3 {
+            bytes.extend_from_slice(&256u16.to_le_bytes());
+        }
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&u16::MAX.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        assert!(matches!(
+            Schematic::from_bytes(&bytes),
+            Err(BinaryError::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_sizes_over_the_volume_cap() {
+        // Enough runs to fill the size, in a few kilobytes.
+        let size = [1024u16, 1024, 128];
+        let volume = size.iter().map(|dim| *dim as usize).product::<usize>();
+        assert!(volume > MAX_SCHEMATIC_VOLUME);
+        let mut bytes = SCHEMATIC_MAGIC.to_vec();
+        bytes.push(SCHEMATIC_VERSION);
+        for dim in size {
+            bytes.extend_from_slice(&dim.to_le_bytes());
+        }
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        for _ in 0..volume.div_ceil(u16::MAX as usize) {
+            bytes.extend_from_slice(&u16::MAX.to_le_bytes());
+            bytes.extend_from_slice(&0u16.to_le_bytes());
+        }
+        assert!(matches!(
+            Schematic::from_bytes(&bytes),
+            Err(BinaryError::InvalidData("schematic is too large"))
+        ));
+    }
+
+    #[test]
+    fn refuses_to_capture_boxes_over_the_volume_cap() {
+        let mut world = headless_world(0);
+        let capture = |world: &mut World, to: IVec3| {
+            world
+                .run_system_once(move |voxel_world: VoxelWorld| {
+                    Schematic::capture(&voxel_world, IVec3::ZERO, to).map(|s| s.size())
+                })
+                .unwrap()
+        };
+
+        assert_eq!(
+            capture(&mut world, IVec3::new(-2, 1, 3)),
+            Some(UVec3::new(3, 2, 4))
+        );
+        assert_eq!(capture(&mut world, IVec3::new(1023, 1023, 127)), None);
+        assert_eq!(capture(&mut world, IVec3::MAX), None);
+    }
+
+    #[test]
+    fn refuses_to_write_oversized_structures() {
+        let wide = Schematic::from_fn(UVec3::new(u16::MAX as u32 + 1, 1, 1), |_| Voxel::AIR);
+        assert!(matches!(wide.to_bytes(), Err(BinaryError::InvalidData(_))));
+    }
+
+    #[test]
+    fn rotation_keeps_voxels_inside_the_bounds() {
+        let schematic = sample();
+        let transform = SchematicTransform {
+            quarter_turns: 1,
+            ..default()
+        };
+        let size = transform.transformed_size(schematic.size()).as_ivec3();
+        assert_eq!(size, IVec3::new(5, 2, 3));
+        for (offset, _) in schematic.voxels(transform) {
+            assert!(offset.cmpge(IVec3::ZERO).all() && offset.cmplt(size).all());
+        }
+        assert_eq!(
+            schematic.voxels(transform).count(),
+            schematic.voxels(default()).count()
+        );
+    }
+
+    /// Run the copy and paste keys once with `pressed` held down and `just` just pressed.
+    fn press_keys(world: &mut World, pressed: &[KeyCode], just: KeyCode) {
+        let mut keys = ButtonInput::<KeyCode>::default();
+        for key in pressed {
+            keys.press(*key);
+        }
+        keys.clear();
+        keys.press(just);
+        world.insert_resource(keys);
+        world.run_system_once(copy_paste_schematic).unwrap();
+    }
+
+    #[test]
+    fn t_and_shift_t_mirror_across_either_axis() {
+        let mut world = headless_world(0);
+        world.init_resource::<HoveredVoxel>();
+        world.init_resource::<EditHistory>();
+        world.init_resource::<SchematicClipboard>();
+        let transform = |world: &World| world.resource::<SchematicClipboard>().transform;
+
+        press_keys(&mut world, &[KeyCode::ShiftLeft], KeyCode::KeyT);
+        assert!(transform(&world).mirror_z && !transform(&world).mirror_x);
+        press_keys(&mut world, &[], KeyCode::KeyT);
+        assert!(transform(&world).mirror_z && transform(&world).mirror_x);
+        press_keys(&mut world, &[KeyCode::ShiftRight], KeyCode::KeyT);
+        assert!(!transform(&world).mirror_z && transform(&world).mirror_x);
+
+        let mirrored = SchematicTransform {
+            mirror_z: true,
+            ..default()
+        };
+        let size = UVec3::new(3, 2, 5);
+        assert_eq!(
+            mirrored.apply(UVec3::new(1, 1, 0), size),
+            UVec3::new(1, 1, 4)
+        );
+    }
+}
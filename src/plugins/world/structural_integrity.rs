@@ -0,0 +1,462 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+use bevy::platform::collections::HashSet;
+use bevy::prelude::*;
+
+use crate::plugins::asset_loader::assets::VoxelAtlasHandles;
+use crate::plugins::world::{
+    blocks::{BlockRegistry, BlockRegistryRes},
+    config::WorldHeightLimits,
+    events::{FloatingGroupDetected, VoxelChanged},
+    falling_blocks::SpawnFallingBodyCommandExt,
+    voxel::Voxel,
+    voxel_world::{VoxelAccess, VoxelWorld},
+};
+use crate::state::LoadingState;
+
+pub struct StructuralIntegrityPlugin;
+
+impl Plugin for StructuralIntegrityPlugin {
+    fn build(&self, app: &mut App) {
+        // Settings inserted before the plugin are kept.
+        if !app
+            .world()
+            .contains_resource::<StructuralIntegritySettings>()
+        {
+            let limits = *app.world_mut().get_resource_or_init::<WorldHeightLimits>();
+            app.insert_resource(StructuralIntegritySettings::for_limits(&limits));
+        }
+
+        app.init_resource::<StructuralIntegrityCandidates>()
+            .add_systems(
+                Update,
+                (collect_structural_candidates, detach_floating_groups)
+                    .chain()
+                    .run_if(in_state(LoadingState::Initialized))
+                    .run_if(|settings: Res<StructuralIntegritySettings>| settings.enabled),
+            );
+    }
+}
+
+/// What to do with a group of voxels that lost its connection to an anchor.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum FloatingGroupResponse {
+    /// Only trigger [`FloatingGroupDetected`].
+    Report,
+    /// Trigger [`FloatingGroupDetected`] and drop the group as a falling body.
+    #[default]
+    Fall,
+}
+
+/// Checks, after voxels are removed, whether the solid voxels around them are still
+/// connected to an anchor: a voxel at or below `anchor_height`, an anchor block like
+/// bedrock, or an unloaded chunk.
+///
+/// Off by default; set `enabled` to turn it on.
+#[derive(Resource, Clone, Debug)]
+pub struct StructuralIntegritySettings {
+    pub enabled: bool,
+    /// Highest y that holds up what stands on it, the world floor by default.
+    pub anchor_height: i32,
+    /// Most voxels a single flood fill may visit. Larger groups are assumed to be anchored.
+    pub max_group_size: usize,
+    /// Most voxels visited per update; remaining checks carry over to the next one.
+    pub max_visited_per_update: usize,
+    pub response: FloatingGroupResponse,
+}
+
+impl Default for StructuralIntegritySettings {
+    fn default() -> Self {
+        Self::for_limits(&WorldHeightLimits::default())
+    }
+}
+
+impl StructuralIntegritySettings {
+    /// The defaults, anchored on the floor of a world with `limits`.
+    pub fn for_limits(limits: &WorldHeightLimits) -> Self {
+        Self {
+            enabled: false,
+            anchor_height: limits.min_y(),
+            max_group_size: 4096,
+            max_visited_per_update: 16384,
+            response: FloatingGroupResponse::default(),
+        }
+    }
+}
+
+/// Voxels to flood fill from on the next update.
+#[derive(Resource, Debug, Default)]
+pub struct StructuralIntegrityCandidates(VecDeque<IVec3>);
+
+impl StructuralIntegrityCandidates {
+    pub fn push(&mut self, world: IVec3) {
+        self.0.push_back(world);
+    }
+}
+
+/// Outcome of flood filling the solid voxels connected to one voxel.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Connectivity {
+    /// The voxel isn't solid, or belongs to a floating group found earlier.
+    Skipped,
+    Anchored,
+    /// The search reached `max_group_size` voxels without finding an anchor.
+    Exceeded,
+    /// The connected solid voxels, none of which is anchored.
+    Floating(Vec<IVec3>),
+}
+
+/// What earlier [`connectivity`] searches found, so several searches over one region
+/// share their work.
+#[derive(Debug, Default)]
+pub struct SearchedVoxels {
+    /// Voxels of the groups found floating.
+    floating: HashSet<IVec3>,
+    /// Voxels of searches that were anchored or exceeded `max_group_size`, so are known
+    /// to be held up.
+    connected: HashSet<IVec3>,
+    visited: usize,
+}
+
+impl SearchedVoxels {
+    /// Voxels visited by every search so far.
+    pub fn visited(&self) -> usize {
+        self.visited
+    }
+
+    /// Remember `group` as held up, ending its search with `outcome`.
+    fn hold_up(&mut self, group: Vec<IVec3>, outcome: Connectivity) -> Connectivity {
+        self.visited += group.len();
+        self.connected.extend(group);
+        outcome
+    }
+
+    fn float(&mut self, group: Vec<IVec3>) -> Connectivity {
+        self.visited += group.len();
+        self.floating.extend(group.iter().copied());
+        Connectivity::Floating(group)
+    }
+}
+
+/// Flood fill the solid voxels connected to `start`, stopping at the first anchor or
+/// voxel an earlier search in `searched` found held up.
+///
+/// The fill visits lower voxels first, so terrain standing on the ground is usually
+/// confirmed within a few steps. A search that stops early leaves voxels it reached
+/// unexplored, so only those of floating groups are skipped by later searches.
+pub fn connectivity<W: VoxelAccess + ?Sized>(
+    world: &W,
+    registry: &BlockRegistry,
+    settings: &StructuralIntegritySettings,
+    start: IVec3,
+    searched: &mut SearchedVoxels,
+) -> Connectivity {
+    if searched.floating.contains(&start) {
+        return Connectivity::Skipped;
+    }
+    if searched.connected.contains(&start) {
+        return Connectivity::Anchored;
+    }
+    let Some(voxel) = world.get_voxel(start) else {
+        return Connectivity::Skipped;
+    };
+    if !registry.is_opaque(voxel.block_id()) {
+        return Connectivity::Skipped;
+    }
+
+    let key = |position: IVec3| Reverse((position.y, position.x, position.z));
+    let mut group = vec![start];
+    let mut reached = HashSet::from([start]);
+    let mut frontier = BinaryHeap::from([key(start)]);
+
+    while let Some(Reverse((y, x, z))) = frontier.pop() {
+        let position = IVec3::new(x, y, z);
+        let voxel = world.get_voxel(position).unwrap_or(Voxel::AIR);
+        if position.y <= settings.anchor_height || registry.is_anchor(voxel.block_id()) {
+            return searched.hold_up(group, Connectivity::Anchored);
+        }
+
+        for offset in NEIGHBOUR_OFFSETS {
+            let neighbour = position + offset;
+            if reached.contains(&neighbour) {
+                continue;
+            }
+            if searched.connected.contains(&neighbour) {
+                return searched.hold_up(group, Connectivity::Anchored);
+            }
+            let Some(voxel) = world.get_voxel(neighbour) else {
+                // Whatever is beyond the loaded world might hold the group up.
+                return searched.hold_up(group, Connectivity::Anchored);
+            };
+            if !registry.is_opaque(voxel.block_id()) {
+                continue;
+            }
+            if group.len() >= settings.max_group_size {
+                return searched.hold_up(group, Connectivity::Exceeded);
+            }
+            reached.insert(neighbour);
+            group.push(neighbour);
+            frontier.push(key(neighbour));
+        }
+    }
+
+    searched.float(group)
+}
+
+/// Floating groups connected to any of `seeds`, each reported once.
+pub fn find_floating_groups<W: VoxelAccess + ?Sized>(
+    world: &W,
+    registry: &BlockRegistry,
+    settings: &StructuralIntegritySettings,
+    seeds: impl IntoIterator<Item = IVec3>,
+) -> Vec<Vec<IVec3>> {
+    let mut searched = SearchedVoxels::default();
+    seeds
+        .into_iter()
+        .filter_map(
+            |seed| match connectivity(world, registry, settings, seed, &mut searched) {
+                Connectivity::Floating(group) => Some(group),
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+const NEIGHBOUR_OFFSETS: [IVec3; 6] = [
+    IVec3::NEG_Y,
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Z,
+    IVec3::NEG_Z,
+    IVec3::Y,
+];
+
+/// Only removing a solid voxel can disconnect anything; its solid neighbours are checked.
+fn collect_structural_candidates(
+    mut changes: MessageReader<VoxelChanged>,
+    mut candidates: ResMut<StructuralIntegrityCandidates>,
+    block_registry: Res<BlockRegistryRes>,
+) {
+    let registry = &block_registry.0;
+    for change in changes.read() {
+        if registry.is_opaque(change.previous.block_id())
+            && !registry.is_opaque(change.new.block_id())
+        {
+            for offset in NEIGHBOUR_OFFSETS {
+                candidates.push(change.world + offset);
+            }
+        }
+    }
+}
+
+fn detach_floating_groups(
+    mut commands: Commands,
+    settings: Res<StructuralIntegritySettings>,
+    atlas: Option<Res<VoxelAtlasHandles>>,
+    mut candidates: ResMut<StructuralIntegrityCandidates>,
+    block_registry: Res<BlockRegistryRes>,
+    mut voxel_world: VoxelWorld,
+) {
+    let registry = &block_registry.0;
+    let mut searched = SearchedVoxels::default();
+    let mut groups = Vec::new();
+    while searched.visited() < settings.max_visited_per_update
+        && let Some(seed) = candidates.0.pop_front()
+    {
+        if let Connectivity::Floating(group) =
+            connectivity(&voxel_world, registry, &settings, seed, &mut searched)
+        {
+            groups.push(group);
+        }
+    }
+
+    // Groups are only cut loose once they can be drawn falling.
+    let detached = settings.response == FloatingGroupResponse::Fall && atlas.is_some();
+    for group in groups {
+        if detached {
+            let origin = group
+                .iter()
+                .fold(IVec3::MAX, |origin, position| origin.min(*position));
+            let voxels = group
+                .iter()
+                .filter_map(|position| {
+                    let voxel = voxel_world.get(*position)?;
+                    Some((position - origin, voxel))
+                })
+                .collect();
+            voxel_world.set_batch(group.iter().map(|position| (*position, Voxel::AIR)));
+            commands.spawn_falling_body(origin, voxels);
+        }
+        commands.trigger(FloatingGroupDetected {
+            voxels: group,
+            detached,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::platform::collections::HashMap;
+
+    use super::*;
+    use crate::plugins::world::blocks::{BLOCK_BEDROCK, BLOCK_DIRT, BLOCK_STONE, BLOCK_WATER};
+
+    /// A stone floor at y 0 with a dirt pillar from y 1 to 5 at the origin.
+    fn pillar() -> HashMap<IVec3, Voxel> {
+        let mut world = HashMap::new();
+        for x in -4..=4 {
+            for z in -4..=4 {
+                world.insert(IVec3::new(x, 0, z), Voxel::new(BLOCK_STONE));
+            }
+        }
+        for y in 1..=5 {
+            world.insert(IVec3::new(0, y, 0), Voxel::new(BLOCK_DIRT));
+        }
+        world
+    }
+
+    fn groups(world: &HashMap<IVec3, Voxel>, seeds: &[IVec3]) -> Vec<HashSet<IVec3>> {
+        let registry = BlockRegistryRes::default().0;
+        let settings = StructuralIntegritySettings::default();
+        find_floating_groups(world, &registry, &settings, seeds.iter().copied())
+            .into_iter()
+            .map(|group| group.into_iter().collect())
+            .collect()
+    }
+
+    #[test]
+    fn finds_a_floating_cube_once() {
+        let mut world = pillar();
+        let cube: HashSet<_> = (3..5)
+            .flat_map(|x| (6..8).flat_map(move |y| (3..5).map(move |z| IVec3::new(x, y, z))))
+            .collect();
+        for position in &cube {
+            world.insert(*position, Voxel::new(BLOCK_DIRT));
+        }
+        let seeds = [
+            IVec3::new(0, 5, 0),
+            IVec3::new(3, 6, 3),
+            IVec3::new(4, 7, 4),
+        ];
+        assert_eq!(groups(&world, &seeds), [cube]);
+    }
+
+    #[test]
+    fn cutting_a_pillar_floats_its_top() {
+        let mut world = pillar();
+        world.remove(&IVec3::new(0, 2, 0));
+        let top = (3..=5).map(|y| IVec3::new(0, y, 0)).collect();
+        assert_eq!(
+            groups(&world, &[IVec3::new(0, 1, 0), IVec3::new(0, 3, 0)]),
+            [top]
+        );
+
+        // Fluids don't hold anything up.
+        world.insert(IVec3::new(0, 2, 0), Voxel::new(BLOCK_WATER));
+        assert_eq!(groups(&world, &[IVec3::new(0, 3, 0)]).len(), 1);
+    }
+
+    #[test]
+    fn later_seeds_do_not_float_what_an_earlier_one_anchored() {
+        // A beam two wide resting on the pillar, cut on both sides in one batch.
+        let mut world = pillar();
+        for x in -4..=4 {
+            for z in 0..=1 {
+                world.insert(IVec3::new(x, 5, z), Voxel::new(BLOCK_DIRT));
+            }
+        }
+        for x in [-2, 2] {
+            for z in 0..=1 {
+                world.remove(&IVec3::new(x, 5, z));
+            }
+        }
+        let right = (3..=4)
+            .flat_map(|x| (0..=1).map(move |z| IVec3::new(x, 5, z)))
+            .collect();
+        let seeds = [
+            IVec3::new(-1, 5, 0),
+            IVec3::new(1, 5, 1),
+            IVec3::new(3, 5, 0),
+        ];
+        assert_eq!(groups(&world, &seeds), [right]);
+    }
+
+    #[test]
+    fn anchors_hold_up_what_they_touch() {
+        let mut world = pillar();
+        world.remove(&IVec3::new(0, 2, 0));
+
+        world.insert(IVec3::new(0, 6, 0), Voxel::new(BLOCK_BEDROCK));
+        assert!(groups(&world, &[IVec3::new(0, 3, 0)]).is_empty());
+        world.remove(&IVec3::new(0, 6, 0));
+
+        // An arch standing on one side.
+        for x in 1..=3 {
+            world.insert(IVec3::new(x, 5, 0), Voxel::new(BLOCK_DIRT));
+        }
+        for y in 1..5 {
+            world.insert(IVec3::new(3, y, 0), Voxel::new(BLOCK_DIRT));
+        }
+        assert!(groups(&world, &[IVec3::new(0, 3, 0)]).is_empty());
+    }
+
+    #[test]
+    fn large_groups_count_as_anchored() {
+        let registry = BlockRegistryRes::default().0;
+        let mut world = pillar();
+        world.remove(&IVec3::new(0, 2, 0));
+
+        let small = StructuralIntegritySettings {
+            max_group_size: 2,
+            ..default()
+        };
+        let mut searched = SearchedVoxels::default();
+        assert_eq!(
+            connectivity(
+                &world,
+                &registry,
+                &small,
+                IVec3::new(0, 3, 0),
+                &mut searched
+            ),
+            Connectivity::Exceeded
+        );
+        // What it reached is held up for later searches.
+        assert_eq!(
+            connectivity(
+                &world,
+                &registry,
+                &small,
+                IVec3::new(0, 4, 0),
+                &mut searched
+            ),
+            Connectivity::Anchored
+        );
+    }
+
+    #[test]
+    fn plugin_anchors_on_the_world_floor_and_starts_disabled() {
+        let mut app = App::new();
+        app.insert_resource(WorldHeightLimits {
+            min_chunk_y: -2,
+            max_chunk_y: 1,
+        })
+        .add_plugins(StructuralIntegrityPlugin);
+        let settings = app.world().resource::<StructuralIntegritySettings>();
+        assert!(!settings.enabled);
+        assert_eq!(settings.anchor_height, -64);
+
+        let mut world = pillar();
+        world.remove(&IVec3::new(0, 2, 0));
+        let deep = StructuralIntegritySettings {
+            anchor_height: -64,
+            ..default()
+        };
+        let registry = BlockRegistryRes::default().0;
+        // Nothing reaches y -64, so the floor itself floats along with the pillar's base.
+        let floating = find_floating_groups(&world, &registry, &deep, [IVec3::new(0, 1, 0)]);
+        assert_eq!(floating.len(), 1);
+        assert!(floating[0].contains(&IVec3::new(4, 0, -4)));
+    }
+}
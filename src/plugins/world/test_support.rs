@@ -0,0 +1,74 @@
+//! Headless worlds shared by the tests of the world and the plugins built on it.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+
+use crate::plugins::world::{
+    ChunkEntityMap, Chunks, SpawnChunkCommandExt, blocks::BlockRegistryRes, chunk::Chunk,
+    config::WorldHeightLimits, events::VoxelChanged, heightmap::Heightmaps,
+};
+use crate::state::LoadingState;
+
+/// Height limits from chunk y 0 up to `max_chunk_y`.
+pub(crate) fn height_limits(max_chunk_y: i32) -> WorldHeightLimits {
+    WorldHeightLimits {
+        min_chunk_y: 0,
+        max_chunk_y,
+    }
+}
+
+/// A world with the resources [`VoxelWorld`](super::voxel_world::VoxelWorld) needs and
+/// no chunks, for running systems once.
+pub(crate) fn headless_world(max_chunk_y: i32) -> World {
+    let mut world = World::new();
+    world.init_resource::<ChunkEntityMap>();
+    world.init_resource::<Chunks>();
+    world.init_resource::<Heightmaps>();
+    world.init_resource::<BlockRegistryRes>();
+    world.init_resource::<Messages<VoxelChanged>>();
+    world.insert_resource(height_limits(max_chunk_y));
+    world
+}
+
+/// A headless app in [`LoadingState::Initialized`] with the resources
+/// [`VoxelWorld`](super::voxel_world::VoxelWorld) needs and no chunks.
+pub(crate) fn headless_app(max_chunk_y: i32) -> App {
+    headless_app_in(LoadingState::Initialized, max_chunk_y)
+}
+
+/// Like [`headless_app`], but in `state`.
+pub(crate) fn headless_app_in(state: LoadingState, max_chunk_y: i32) -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, StatesPlugin))
+        .init_resource::<ChunkEntityMap>()
+        .init_resource::<Chunks>()
+        .init_resource::<Heightmaps>()
+        .init_resource::<BlockRegistryRes>()
+        .insert_resource(height_limits(max_chunk_y))
+        .add_message::<VoxelChanged>()
+        .insert_state(state);
+    app
+}
+
+/// Spawn `chunks` and apply the spawns.
+pub(crate) fn spawn_chunks(world: &mut World, chunks: impl IntoIterator<Item = (IVec3, Chunk)>) {
+    for (coord, chunk) in chunks {
+        world.commands().spawn_chunk(chunk, coord);
+    }
+    world.flush();
+}
+
+/// Update `app` until `done`, giving the task pools time to finish. Returns whether
+/// `done` held within a few seconds.
+pub(crate) fn update_until(app: &mut App, mut done: impl FnMut(&World) -> bool) -> bool {
+    for _ in 0..2000 {
+        app.update();
+        if done(app.world()) {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    false
+}
@@ -0,0 +1,767 @@
+use bevy::asset::{AssetLoader, LoadContext, io::Reader};
+use bevy::math::I64Vec3;
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::plugins::world::{
+    blocks::{BLOCK_DIRT, BLOCK_GRASS, BLOCK_STONE, BlockId},
+    byte_reader::{BinaryError, ByteReader},
+    schematic::Schematic,
+    voxel::Voxel,
+};
+
+const VOX_MAGIC: &[u8; 4] = b"VOX ";
+/// Largest `_t` translation accepted on an axis. Transforms nest at most 64 deep, so
+/// positions stay far from overflowing.
+const MAX_VOX_TRANSLATION: i32 = 1 << 20;
+/// Largest scene flattened into a schematic, along each axis and in all.
+pub const MAX_VOX_SCENE_SIZE: u32 = 1024;
+pub const MAX_VOX_SCENE_VOLUME: u64 = 1 << 26;
+/// Most voxels gathered from the scene's models before flattening. Far fewer than
+/// [`MAX_VOX_SCENE_VOLUME`], as each is a position and colour rather than a schematic cell.
+pub const MAX_VOX_SCENE_VOXELS: usize = 1 << 22;
+/// Most scene graph nodes visited while flattening. Groups may list a child more than
+/// once, so a short file can otherwise fan out into an exponential walk.
+const MAX_VOX_NODE_VISITS: u64 = 1 << 16;
+
+/// Palette colour to block assignment.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VoxColorMapping {
+    pub color: [u8; 3],
+    pub block: BlockId,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VoxLoaderSettings {
+    pub mappings: Vec<VoxColorMapping>,
+    /// Map colours without an exact mapping to the block of the closest mapped colour.
+    pub nearest: bool,
+    /// Block for colours that aren't mapped (or for every voxel if the file has no palette).
+    /// `None` leaves them as air.
+    pub fallback: Option<BlockId>,
+}
+
+impl Default for VoxLoaderSettings {
+    fn default() -> Self {
+        Self {
+            mappings: vec![
+                VoxColorMapping {
+                    color: [95, 159, 53],
+                    block: BLOCK_GRASS,
+                },
+                VoxColorMapping {
+                    color: [134, 96, 67],
+                    block: BLOCK_DIRT,
+                },
+                VoxColorMapping {
+                    color: [125, 125, 125],
+                    block: BLOCK_STONE,
+                },
+            ],
+            nearest: true,
+            fallback: Some(BLOCK_STONE),
+        }
+    }
+}
+
+impl VoxLoaderSettings {
+    pub fn block_for(&self, rgba: [u8; 4]) -> Option<BlockId> {
+        let rgb = [rgba[0], rgba[1], rgba[2]];
+        if let Some(mapping) = self.mappings.iter().find(|m| m.color == rgb) {
+            return Some(mapping.block);
+        }
+        if self.nearest
+            && let Some(mapping) = self
+                .mappings
+                .iter()
+                .min_by_key(|m| color_distance_sq(m.color, rgb))
+        {
+            return Some(mapping.block);
+        }
+        self.fallback
+    }
+}
+
+fn color_distance_sq(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| (*a as i32 - *b as i32).pow(2) as u32)
+        .sum()
+}
+
+/// One `SIZE` + `XYZI` pair, in MagicaVoxel (Z-up) coordinates.
+#[derive(Clone, Debug, Default)]
+pub struct VoxModel {
+    pub size: UVec3,
+    /// Position and 1-based palette index of every filled voxel.
+    pub voxels: Vec<(UVec3, u8)>,
+}
+
+#[derive(Clone, Debug)]
+pub enum VoxNode {
+    Transform {
+        child: i32,
+        translation: IVec3,
+        rotation: VoxRotation,
+    },
+    Group {
+        children: Vec<i32>,
+    },
+    Shape {
+        models: Vec<i32>,
+    },
+}
+
+/// Signed permutation matrix, decoded from the packed `_r` byte of an `nTRN` frame.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct VoxRotation([[i32; 3]; 3]);
+
+impl VoxRotation {
+    pub const IDENTITY: Self = Self([[1, 0, 0], [0, 1, 0], [0, 0, 1]]);
+
+    /// `None` if the two column indices the byte packs aren't two different columns.
+    pub fn from_packed(bits: u8) -> Option<Self> {
+        let first = (bits & 0b11) as usize;
+        let second = ((bits >> 2) & 0b11) as usize;
+        if first > 2 || second > 2 || first == second {
+            return None;
+        }
+        let third = 3 - first - second;
+
+        let mut rows = [[0; 3]; 3];
+        for (row, (col, sign_bit)) in [(first, 4), (second, 5), (third, 6)]
+            .into_iter()
+            .enumerate()
+        {
+            rows[row][col] = if bits & (1 << sign_bit) != 0 { -1 } else { 1 };
+        }
+        Some(Self(rows))
+    }
+
+    pub fn apply(&self, v: IVec3) -> IVec3 {
+        let v = v.to_array();
+        IVec3::from_array(
+            self.0
+                .map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2]),
+        )
+    }
+
+    pub fn then(&self, child: &Self) -> Self {
+        let mut out = [[0; 3]; 3];
+        for (i, row) in out.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                *cell = (0..3).map(|k| self.0[i][k] * child.0[k][j]).sum();
+            }
+        }
+        Self(out)
+    }
+}
+
+/// Parsed contents of a `.vox` file.
+#[derive(Clone, Debug)]
+pub struct VoxFile {
+    pub models: Vec<VoxModel>,
+    /// `palette[i]` is the colour of palette index `i + 1`. `None` if the file has no `RGBA` chunk.
+    pub palette: Option<Box<[[u8; 4]; 256]>>,
+    pub nodes: HashMap<i32, VoxNode>,
+}
+
+impl VoxFile {
+    pub fn parse(bytes: &[u8]) -> Result<Self, BinaryError> {
+        let mut cursor = ByteReader::new(bytes);
+        cursor.magic(VOX_MAGIC)?;
+        let _version = cursor.i32()?;
+
+        let main = vox_chunk(&mut cursor)?;
+        if main.id != b"MAIN" {
+            return Err(BinaryError::InvalidData("missing MAIN chunk"));
+        }
+
+        let mut file = VoxFile {
+            models: Vec::new(),
+            palette: None,
+            nodes: HashMap::new(),
+        };
+        let mut pending_size = None;
+        let mut children = ByteReader::new(main.children);
+
+        while !children.is_empty() {
+            let chunk = vox_chunk(&mut children)?;
+            let mut content = ByteReader::new(chunk.content);
+            match chunk.id {
+                b"SIZE" => {
+                    pending_size = Some(UVec3::new(content.u32()?, content.u32()?, content.u32()?));
+                }
+                b"XYZI" => {
+                    let size = pending_size
+                        .take()
+                        .ok_or(BinaryError::InvalidData("XYZI without SIZE"))?;
+                    let count = content.u32()? as usize;
+                    let mut voxels = Vec::with_capacity(content.capacity(count, 4));
+                    for _ in 0..count {
+                        let v = content.take(4)?;
+                        voxels.push((UVec3::new(v[0] as u32, v[1] as u32, v[2] as u32), v[3]));
+                    }
+                    file.models.push(VoxModel { size, voxels });
+                }
+                b"RGBA" => {
+                    let mut palette = Box::new([[0; 4]; 256]);
+                    for entry in palette.iter_mut() {
+                        entry.copy_from_slice(content.take(4)?);
+                    }
+                    file.palette = Some(palette);
+                }
+                b"nTRN" => {
+                    let id = content.i32()?;
+                    vox_dict(&mut content)?;
+                    let child = content.i32()?;
+                    let _reserved = content.i32()?;
+                    let _layer = content.i32()?;
+                    let frames = content.i32()?;
+
+                    // Animated transforms aren't supported; the first frame wins.
+                    let mut translation = IVec3::ZERO;
+                    let mut rotation = VoxRotation::IDENTITY;
+                    for frame in 0..frames {
+                        let attrs = vox_dict(&mut content)?;
+                        if frame != 0 {
+                            continue;
+                        }
+                        if let Some(t) = attrs.get("_t") {
+                            let parts = t
+                                .split_whitespace()
+                                .map(str::parse::<i32>)
+                                .collect::<Result<Vec<_>, _>>()
+                                .map_err(|_| BinaryError::InvalidData("bad _t attribute"))?;
+                            if let [x, y, z] = parts[..] {
+                                translation = IVec3::new(x, y, z);
+                            }
+                            if translation.abs().max_element() > MAX_VOX_TRANSLATION {
+                                return Err(BinaryError::InvalidData("_t attribute out of range"));
+                            }
+                        }
+                        if let Some(r) = attrs.get("_r") {
+                            let bits = r
+                                .parse::<u8>()
+                                .map_err(|_| BinaryError::InvalidData("bad _r attribute"))?;
+                            rotation = VoxRotation::from_packed(bits)
+                                .ok_or(BinaryError::InvalidData("bad _r attribute"))?;
+                        }
+                    }
+                    file.nodes.insert(
+                        id,
+                        VoxNode::Transform {
+                            child,
+                            translation,
+                            rotation,
+                        },
+                    );
+                }
+                b"nGRP" => {
+                    let id = content.i32()?;
+                    vox_dict(&mut content)?;
+                    let count = content.i32()?;
+                    let children = (0..count)
+                        .map(|_| content.i32())
+                        .collect::<Result<Vec<_>, _>>()?;
+                    file.nodes.insert(id, VoxNode::Group { children });
+                }
+                b"nSHP" => {
+                    let id = content.i32()?;
+                    vox_dict(&mut content)?;
+                    let count = content.i32()?;
+                    let mut models = Vec::with_capacity(content.capacity(count.max(0) as usize, 8));
+                    for _ in 0..count {
+                        models.push(content.i32()?);
+                        vox_dict(&mut content)?;
+                    }
+                    file.nodes.insert(id, VoxNode::Shape { models });
+                }
+                // PACK, MATL, LAYR, rOBJ, rCAM, NOTE, IMAP, ...
+                _ => {}
+            }
+        }
+
+        Ok(file)
+    }
+
+    /// Every voxel of the scene with its palette index. MagicaVoxel is Z-up, so positions
+    /// are converted to the engine's Y-up grid as `(x, z, -y)`.
+    /// Files without a scene graph place every model at the origin.
+    ///
+    /// Fails with [`BinaryError::InvalidData`] once the scene holds more than
+    /// [`MAX_VOX_SCENE_VOXELS`] voxels or the walk visits too many nodes.
+    pub fn scene_voxels(&self) -> Result<Vec<(IVec3, u8)>, BinaryError> {
+        let mut out = Vec::new();
+        if self.nodes.contains_key(&0) {
+            let mut visits = 0;
+            self.walk(
+                0,
+                IVec3::ZERO,
+                VoxRotation::IDENTITY,
+                &mut out,
+                &mut visits,
+                0,
+            )?;
+        } else {
+            for model in &self.models {
+                push_voxels(&out, model.voxels.len())?;
+                out.extend(model.voxels.iter().map(|(p, color)| (p.as_ivec3(), *color)));
+            }
+        }
+
+        Ok(out
+            .into_iter()
+            .map(|(p, color)| (IVec3::new(p.x, p.z, -p.y), color))
+            .collect())
+    }
+
+    fn walk(
+        &self,
+        node: i32,
+        translation: IVec3,
+        rotation: VoxRotation,
+        out: &mut Vec<(IVec3, u8)>,
+        visits: &mut u64,
+        depth: usize,
+    ) -> Result<(), BinaryError> {
+        // Guard against cyclic graphs in malformed files.
+        if depth > 64 {
+            return Ok(());
+        }
+        *visits += 1;
+        if *visits > MAX_VOX_NODE_VISITS {
+            return Err(BinaryError::InvalidData("scene graph too large"));
+        }
+        match self.nodes.get(&node) {
+            Some(VoxNode::Transform {
+                child,
+                translation: t,
+                rotation: r,
+            }) => {
+                let child_translation = translation + rotation.apply(*t);
+                self.walk(
+                    *child,
+                    child_translation,
+                    rotation.then(r),
+                    out,
+                    visits,
+                    depth + 1,
+                )?;
+            }
+            Some(VoxNode::Group { children }) => {
+                for child in children {
+                    self.walk(*child, translation, rotation, out, visits, depth + 1)?;
+                }
+            }
+            Some(VoxNode::Shape { models }) => {
+                for model in models.iter().filter_map(|m| self.models.get(*m as usize)) {
+                    push_voxels(out, model.voxels.len())?;
+                    // Models are positioned by their center.
+                    let pivot = (model.size / 2).as_ivec3();
+                    out.extend(model.voxels.iter().map(|(p, color)| {
+                        (translation + rotation.apply(p.as_ivec3() - pivot), *color)
+                    }));
+                }
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn color(&self, index: u8) -> Option<[u8; 4]> {
+        let palette = self.palette.as_ref()?;
+        Some(palette[index.wrapping_sub(1) as usize])
+    }
+
+    fn voxel_for(&self, index: u8, settings: &VoxLoaderSettings) -> Voxel {
+        let block = match self.color(index) {
+            Some(rgba) => settings.block_for(rgba),
+            None => settings.fallback,
+        };
+        block.map(Voxel::new).unwrap_or(Voxel::AIR)
+    }
+
+    /// The whole scene flattened into one schematic. Fails with
+    /// [`BinaryError::InvalidData`] if it is larger than [`MAX_VOX_SCENE_SIZE`] on an axis
+    /// or [`MAX_VOX_SCENE_VOLUME`] in all.
+    pub fn to_schematic(&self, settings: &VoxLoaderSettings) -> Result<Schematic, BinaryError> {
+        self.voxels_to_schematic(self.scene_voxels()?, settings)
+    }
+
+    /// A single model, ignoring the scene graph.
+    pub fn model_to_schematic(
+        &self,
+        model: usize,
+        settings: &VoxLoaderSettings,
+    ) -> Result<Schematic, BinaryError> {
+        let model = self
+            .models
+            .get(model)
+            .ok_or(BinaryError::InvalidData("no such model"))?;
+        let voxels = model
+            .voxels
+            .iter()
+            .map(|(p, color)| (IVec3::new(p.x as i32, p.z as i32, -(p.y as i32)), *color))
+            .collect();
+        self.voxels_to_schematic(voxels, settings)
+    }
+
+    fn voxels_to_schematic(
+        &self,
+        voxels: Vec<(IVec3, u8)>,
+        settings: &VoxLoaderSettings,
+    ) -> Result<Schematic, BinaryError> {
+        if voxels.is_empty() {
+            return Ok(Schematic::from_fn(UVec3::ZERO, |_| Voxel::AIR));
+        }
+
+        let min = voxels.iter().fold(IVec3::MAX, |acc, (p, _)| acc.min(*p));
+        let max = voxels.iter().fold(IVec3::MIN, |acc, (p, _)| acc.max(*p));
+        let size = max.as_i64vec3() - min.as_i64vec3() + I64Vec3::ONE;
+        if size.max_element() > MAX_VOX_SCENE_SIZE as i64
+            || size.element_product() as u64 > MAX_VOX_SCENE_VOLUME
+        {
+            return Err(BinaryError::InvalidData("scene too large"));
+        }
+        let lookup: HashMap<IVec3, Voxel> = voxels
+            .into_iter()
+            .map(|(p, color)| (p - min, self.voxel_for(color, settings)))
+            .collect();
+
+        Ok(Schematic::from_fn(size.as_uvec3(), |local| {
+            lookup.get(&local.as_ivec3()).copied().unwrap_or(Voxel::AIR)
+        }))
+    }
+}
+
+/// Fails if `out` can't take `more` voxels without going over [`MAX_VOX_SCENE_VOXELS`].
+fn push_voxels(out: &[(IVec3, u8)], more: usize) -> Result<(), BinaryError> {
+    if out.len() + more > MAX_VOX_SCENE_VOXELS {
+        return Err(BinaryError::InvalidData("scene too large"));
+    }
+    Ok(())
+}
+
+fn vox_string(cursor: &mut ByteReader) -> Result<String, BinaryError> {
+    let len = cursor.u32()? as usize;
+    Ok(cursor.str(len)?.to_string())
+}
+
+fn vox_dict(cursor: &mut ByteReader) -> Result<HashMap<String, String>, BinaryError> {
+    let count = cursor.u32()?;
+    let mut dict = HashMap::new();
+    for _ in 0..count {
+        let key = vox_string(cursor)?;
+        let value = vox_string(cursor)?;
+        dict.insert(key, value);
+    }
+    Ok(dict)
+}
+
+fn vox_chunk<'a>(cursor: &mut ByteReader<'a>) -> Result<VoxChunk<'a>, BinaryError> {
+    let id = cursor.take(4)?;
+    let content_len = cursor.u32()? as usize;
+    let children_len = cursor.u32()? as usize;
+    Ok(VoxChunk {
+        id,
+        content: cursor.take(content_len)?,
+        children: cursor.take(children_len)?,
+    })
+}
+
+/// A RIFF-style chunk: 4 byte id, then its own content and its children's bytes.
+struct VoxChunk<'a> {
+    id: &'a [u8],
+    content: &'a [u8],
+    children: &'a [u8],
+}
+
+/// Loads a MagicaVoxel `.vox` file as the flattened scene [`Schematic`], with each
+/// model also available as a `Model<index>` labeled sub-asset. Palette colours are
+/// mapped to blocks through [`VoxLoaderSettings`].
+#[derive(Default, TypePath)]
+pub struct VoxLoader;
+
+impl AssetLoader for VoxLoader {
+    type Asset = Schematic;
+    type Settings = VoxLoaderSettings;
+    type Error = BinaryError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        settings: &VoxLoaderSettings,
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Schematic, BinaryError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let file = VoxFile::parse(&bytes)?;
+
+        for idx in 0..file.models.len() {
+            let model = file.model_to_schematic(idx, settings)?;
+            load_context.add_labeled_asset(format!("Model{idx}"), model);
+        }
+
+        file.to_schematic(settings)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["vox"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two models: an L of stone and grass at the origin, and a bar of dirt turned a
+    /// quarter about Z and moved to `(5, 0, 1)`. Also has a `MATL` chunk to skip.
+    const SCENE: &[u8] = include_bytes!("../../../tests/fixtures/scene.vox");
+
+    /// A chunk of `id` holding `content`, followed by its `children`' bytes.
+    fn vox_chunk_bytes(id: &[u8], content: &[u8], children: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend((content.len() as u32).to_le_bytes());
+        bytes.extend((children.len() as u32).to_le_bytes());
+        bytes.extend(content);
+        bytes.extend(children);
+        bytes
+    }
+
+    #[test]
+    fn parses_models_palette_and_nodes() {
+        let file = VoxFile::parse(SCENE).unwrap();
+        assert_eq!(file.models.len(), 2);
+        assert_eq!(file.models[0].size, UVec3::new(2, 2, 1));
+        assert_eq!(file.models[0].voxels.len(), 3);
+        assert_eq!(file.models[1].voxels.len(), 2);
+        assert_eq!(file.nodes.len(), 6);
+        assert_eq!(file.color(3), Some([134, 96, 67, 255]));
+    }
+
+    #[test]
+    fn places_models_through_the_scene_graph() {
+        let file = VoxFile::parse(SCENE).unwrap();
+        let mut voxels = file.scene_voxels().unwrap();
+        voxels.sort_by_key(|(p, _)| p.to_array());
+        assert_eq!(
+            voxels,
+            vec![
+                (IVec3::new(-1, 0, 0), 1),
+                (IVec3::new(-1, 0, 1), 1),
+                (IVec3::new(0, 0, 1), 2),
+                (IVec3::new(5, 1, 0), 3),
+                (IVec3::new(5, 1, 1), 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn maps_colours_to_blocks() {
+        let file = VoxFile::parse(SCENE).unwrap();
+        let schematic = file.to_schematic(&VoxLoaderSettings::default()).unwrap();
+        assert_eq!(schematic.size(), UVec3::new(7, 2, 2));
+        assert_eq!(schematic.get(UVec3::new(0, 0, 0)), Voxel::new(BLOCK_STONE));
+        assert_eq!(schematic.get(UVec3::new(1, 0, 1)), Voxel::new(BLOCK_GRASS));
+        assert_eq!(schematic.get(UVec3::new(6, 1, 0)), Voxel::new(BLOCK_DIRT));
+        assert_eq!(schematic.get(UVec3::new(3, 0, 0)), Voxel::AIR);
+
+        let bar = file
+            .model_to_schematic(1, &VoxLoaderSettings::default())
+            .unwrap();
+        assert_eq!(bar.size(), UVec3::new(2, 1, 1));
+    }
+
+    #[test]
+    fn rejects_truncated_and_oversized_data() {
+        assert!(matches!(
+            VoxFile::parse(b"RIFF\x96\0\0\0"),
+            Err(BinaryError::InvalidMagic(_))
+        ));
+        for len in [8, 20, SCENE.len() / 2, SCENE.len() - 1] {
+            assert!(VoxFile::parse(&SCENE[..len]).is_err(), "parsed {len} bytes");
+        }
+
+        // An XYZI claiming far more voxels than it holds.
+        let size = vox_chunk_bytes(b"SIZE", &[[1, 0, 0, 0]; 3].concat(), &[]);
+        let xyzi = vox_chunk_bytes(b"XYZI", &u32::MAX.to_le_bytes(), &[]);
+        let mut bytes = VOX_MAGIC.to_vec();
+        bytes.extend(150u32.to_le_bytes());
+        bytes.extend(vox_chunk_bytes(b"MAIN", &[], &[size, xyzi].concat()));
+        assert!(matches!(
+            VoxFile::parse(&bytes),
+            Err(BinaryError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn rejects_rotations_that_are_not_permutations() {
+        let string =
+            |text: &str| [&(text.len() as u32).to_le_bytes()[..], text.as_bytes()].concat();
+        let int = |value: i32| value.to_le_bytes().to_vec();
+        let rotated = |r: &str| {
+            let mut children = vox_chunk_bytes(b"SIZE", &[[1, 0, 0, 0]; 3].concat(), &[]);
+            children.extend(vox_chunk_bytes(
+                b"XYZI",
+                &[&int(1)[..], &[0, 0, 0, 1]].concat(),
+                &[],
+            ));
+            let transform = [int(0), int(0), int(1), int(-1), int(0), int(1), int(1)].concat();
+            let transform = [transform, string("_r"), string(r)].concat();
+            children.extend(vox_chunk_bytes(b"nTRN", &transform, &[]));
+            let shape = [int(1), int(0), int(1), int(0), int(0)].concat();
+            children.extend(vox_chunk_bytes(b"nSHP", &shape, &[]));
+            let mut bytes = VOX_MAGIC.to_vec();
+            bytes.extend(150u32.to_le_bytes());
+            bytes.extend(vox_chunk_bytes(b"MAIN", &[], &children));
+            VoxFile::parse(&bytes)
+        };
+
+        // Column indices past the matrix, and the same column twice.
+        for r in ["3", "12", "0", "5"] {
+            assert!(
+                matches!(
+                    rotated(r),
+                    Err(BinaryError::InvalidData("bad _r attribute"))
+                ),
+                "accepted _r {r}"
+            );
+        }
+        assert_eq!(VoxRotation::from_packed(4), Some(VoxRotation::IDENTITY));
+        assert!(rotated("4").is_ok());
+    }
+
+    /// A scene of one single voxel model, under each of `translations` in turn.
+    fn translated_scene(translations: &[&str]) -> Vec<u8> {
+        let string =
+            |text: &str| [&(text.len() as u32).to_le_bytes()[..], text.as_bytes()].concat();
+        let int = |value: i32| value.to_le_bytes().to_vec();
+
+        let mut children = vox_chunk_bytes(b"SIZE", &[[1, 0, 0, 0]; 3].concat(), &[]);
+        children.extend(vox_chunk_bytes(
+            b"XYZI",
+            &[&int(1)[..], &[0, 0, 0, 1]].concat(),
+            &[],
+        ));
+        // Root transform 0, a group 1 with a transform for each translation, each
+        // holding the one shape.
+        let shape = 2 + 2 * translations.len() as i32;
+        let transform = |id: i32, child: i32, translation: Option<&str>| {
+            let mut content = [int(id), int(0), int(child), int(-1), int(0), int(1)].concat();
+            match translation {
+                Some(t) => content.extend([int(1), string("_t"), string(t)].concat()),
+                None => content.extend(int(0)),
+            }
+            vox_chunk_bytes(b"nTRN", &content, &[])
+        };
+        children.extend(transform(0, 1, None));
+        let ids: Vec<i32> = (0..translations.len() as i32).map(|i| 2 + 2 * i).collect();
+        let group = [int(1), int(0), int(ids.len() as i32)]
+            .into_iter()
+            .chain(ids.iter().map(|id| int(*id)))
+            .collect::<Vec<_>>()
+            .concat();
+        children.extend(vox_chunk_bytes(b"nGRP", &group, &[]));
+        for (id, translation) in ids.iter().zip(translations) {
+            children.extend(transform(*id, shape, Some(translation)));
+        }
+        let shape_content = [int(shape), int(0), int(1), int(0), int(0)].concat();
+        children.extend(vox_chunk_bytes(b"nSHP", &shape_content, &[]));
+
+        let mut bytes = VOX_MAGIC.to_vec();
+        bytes.extend(150u32.to_le_bytes());
+        bytes.extend(vox_chunk_bytes(b"MAIN", &[], &children));
+        bytes
+    }
+
+    #[test]
+    fn rejects_scenes_too_large_to_flatten() {
+        let settings = VoxLoaderSettings::default();
+        let near = VoxFile::parse(&translated_scene(&["0 0 0", "3 0 0"])).unwrap();
+        assert_eq!(
+            near.to_schematic(&settings).unwrap().size(),
+            UVec3::new(4, 1, 1)
+        );
+
+        let hostile = translated_scene(&["2147483647 0 0", "-2147483648 0 0"]);
+        assert!(matches!(
+            VoxFile::parse(&hostile),
+            Err(BinaryError::InvalidData(_))
+        ));
+
+        let far = VoxFile::parse(&translated_scene(&["0 0 0", "100000 0 0"])).unwrap();
+        assert!(matches!(
+            far.to_schematic(&settings),
+            Err(BinaryError::InvalidData(_))
+        ));
+        // Within the axis cap on every axis, but far over the volume cap.
+        let corners = translated_scene(&["0 0 0", "1000 1000 1000"]);
+        let corners = VoxFile::parse(&corners).unwrap();
+        assert!(corners.to_schematic(&settings).is_err());
+    }
+
+    #[test]
+    fn rejects_self_repeating_group_chains() {
+        let int = |value: i32| value.to_le_bytes().to_vec();
+
+        let mut children = vox_chunk_bytes(b"SIZE", &[[1, 0, 0, 0]; 3].concat(), &[]);
+        children.extend(vox_chunk_bytes(
+            b"XYZI",
+            &[&int(1)[..], &[0, 0, 0, 1]].concat(),
+            &[],
+        ));
+        let root = [int(0), int(0), int(1), int(-1), int(0), int(1), int(0)].concat();
+        children.extend(vox_chunk_bytes(b"nTRN", &root, &[]));
+        // Groups 1..=60 each list the next group twice, doubling the walk at every level.
+        let depth = 60;
+        for id in 1..=depth {
+            let group = [int(id), int(0), int(2), int(id + 1), int(id + 1)].concat();
+            children.extend(vox_chunk_bytes(b"nGRP", &group, &[]));
+        }
+        let shape = [int(depth + 1), int(0), int(1), int(0), int(0)].concat();
+        children.extend(vox_chunk_bytes(b"nSHP", &shape, &[]));
+
+        let mut bytes = VOX_MAGIC.to_vec();
+        bytes.extend(150u32.to_le_bytes());
+        bytes.extend(vox_chunk_bytes(b"MAIN", &[], &children));
+
+        let file = VoxFile::parse(&bytes).unwrap();
+        assert!(matches!(
+            file.scene_voxels(),
+            Err(BinaryError::InvalidData(_))
+        ));
+        assert!(file.to_schematic(&VoxLoaderSettings::default()).is_err());
+    }
+
+    #[test]
+    fn rejects_models_repeated_past_the_voxel_cap() {
+        let int = |value: i32| value.to_le_bytes().to_vec();
+        // A 4096 voxel model, listed by one group as its shape over a thousand times.
+        let voxels = 4096;
+        let repeats = MAX_VOX_SCENE_VOXELS / voxels + 1;
+        let mut xyzi = int(voxels as i32);
+        xyzi.extend([0, 0, 0, 1].repeat(voxels));
+        let mut children = vox_chunk_bytes(b"SIZE", &[[1, 0, 0, 0]; 3].concat(), &[]);
+        children.extend(vox_chunk_bytes(b"XYZI", &xyzi, &[]));
+        let root = [int(0), int(0), int(1), int(-1), int(0), int(1), int(0)].concat();
+        children.extend(vox_chunk_bytes(b"nTRN", &root, &[]));
+        let mut group = [int(1), int(0), int(repeats as i32)].concat();
+        group.extend(int(2).repeat(repeats));
+        children.extend(vox_chunk_bytes(b"nGRP", &group, &[]));
+        let shape = [int(2), int(0), int(1), int(0), int(0)].concat();
+        children.extend(vox_chunk_bytes(b"nSHP", &shape, &[]));
+
+        let mut bytes = VOX_MAGIC.to_vec();
+        bytes.extend(150u32.to_le_bytes());
+        bytes.extend(vox_chunk_bytes(b"MAIN", &[], &children));
+        assert!(bytes.len() < 32 * 1024);
+
+        let file = VoxFile::parse(&bytes).unwrap();
+        assert!(matches!(
+            file.scene_voxels(),
+            Err(BinaryError::InvalidData("scene too large"))
+        ));
+    }
+}
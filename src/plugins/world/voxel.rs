@@ -1,14 +1,17 @@
 use crate::plugins::world::blocks::BlockId;
 
+/// A block id and its state id, packed into 16 bits so chunks stay as small as plain
+/// block ids: the low [`Voxel::BLOCK_BITS`] hold the block, the rest the state.
 #[repr(transparent)]
-#[derive(Copy, Clone, Eq, PartialEq, Default)]
-pub struct Voxel(BlockId);
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct Voxel(u16);
 
 impl core::fmt::Debug for Voxel {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("Voxel")
             .field("solid", &self.is_solid())
             .field("block_id", &self.block_id())
+            .field("state", &self.state())
             .finish()
     }
 }
@@ -16,23 +19,80 @@ impl core::fmt::Debug for Voxel {
 impl Voxel {
     pub const AIR: Self = Self::new(0);
 
+    pub const BLOCK_BITS: u32 = 10;
+    /// Block ids go up to `BLOCKS - 1`.
+    pub const BLOCKS: u16 = 1 << Self::BLOCK_BITS;
+    /// State ids go up to `STATES - 1`; see
+    /// [`BlockStateLayout`](super::block_state::BlockStateLayout).
+    pub const STATES: u16 = 1 << (u16::BITS - Self::BLOCK_BITS);
+
     #[inline]
     pub const fn new(block_id: BlockId) -> Self {
-        Self(block_id)
+        Self::with_state(block_id, 0)
+    }
+
+    #[inline]
+    pub const fn with_state(block_id: BlockId, state: u16) -> Self {
+        debug_assert!(block_id < Self::BLOCKS, "block id out of range");
+        debug_assert!(state < Self::STATES, "state id out of range");
+        Self(block_id & (Self::BLOCKS - 1) | state << Self::BLOCK_BITS)
+    }
+
+    /// `None` if `block_id` or `state` don't fit in a voxel.
+    #[inline]
+    pub const fn checked(block_id: BlockId, state: u16) -> Option<Self> {
+        if block_id < Self::BLOCKS && state < Self::STATES {
+            Some(Self::with_state(block_id, state))
+        } else {
+            None
+        }
     }
 
     #[inline]
     pub const fn is_air(self) -> bool {
-        self.0 == 0
+        self.block_id() == 0
     }
 
     #[inline]
     pub const fn is_solid(self) -> bool {
-        self.0 != 0
+        self.block_id() != 0
     }
 
     #[inline]
     pub const fn block_id(self) -> BlockId {
-        self.0
+        self.0 & (Self::BLOCKS - 1)
+    }
+
+    /// State id within the block's [`BlockStateLayout`](super::block_state::BlockStateLayout).
+    #[inline]
+    pub const fn state(self) -> u16 {
+        self.0 >> Self::BLOCK_BITS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_into_a_block_id() {
+        assert_eq!(size_of::<Voxel>(), size_of::<BlockId>());
+    }
+
+    #[test]
+    fn keeps_block_and_state_apart() {
+        for (block_id, state) in [
+            (0, 0),
+            (1, 0),
+            (4, 15),
+            (Voxel::BLOCKS - 1, Voxel::STATES - 1),
+        ] {
+            let voxel = Voxel::with_state(block_id, state);
+            assert_eq!((voxel.block_id(), voxel.state()), (block_id, state));
+        }
+        assert!(Voxel::with_state(0, 3).is_air());
+        assert_eq!(Voxel::checked(Voxel::BLOCKS, 0), None);
+        assert_eq!(Voxel::checked(1, Voxel::STATES), None);
+        assert_ne!(Voxel::new(4), Voxel::with_state(4, 1));
     }
 }
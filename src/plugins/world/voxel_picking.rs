@@ -2,7 +2,12 @@ use bevy::color::palettes::basic::YELLOW;
 use bevy::math::Ray3d;
 use bevy::prelude::*;
 
-use crate::plugins::world::{ChunkEntityMap, Chunks, chunk::CHUNK_SIZE};
+use crate::plugins::world::{
+    ChunkEntityMap, Chunks,
+    blocks::{BlockRegistry, BlockRegistryRes},
+    chunk::world_to_chunk_local,
+    config::WorldHeightLimits,
+};
 
 pub struct VoxelPickingPlugin;
 
@@ -71,6 +76,13 @@ pub struct VoxelHit {
     pub face: VoxelFace,
 }
 
+impl VoxelHit {
+    /// World voxel coord of the empty cell in front of the hovered face.
+    pub fn adjacent(&self) -> IVec3 {
+        self.world + self.face.normal_i()
+    }
+}
+
 fn toggle_voxel_picking_gizmos(
     keys: Res<ButtonInput<KeyCode>>,
     mut enabled: ResMut<VoxelPickingDebugGizmosEnabled>,
@@ -87,6 +99,8 @@ fn update_voxel_hover(
     camera_q: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
     chunk_map: Res<ChunkEntityMap>,
     mut chunks: ResMut<Chunks>,
+    block_registry: Res<BlockRegistryRes>,
+    limits: Res<WorldHeightLimits>,
     mut hovered: ResMut<HoveredVoxel>,
 ) {
     let Ok(window) = windows.single() else {
@@ -110,16 +124,26 @@ fn update_voxel_hover(
     // Tune this if you want longer/shorter reach.
     let max_distance = 128.0;
 
-    hovered.hit = pick_voxel_dda(ray, max_distance, &chunk_map, &mut chunks);
+    hovered.hit = pick_voxel_dda(
+        ray,
+        max_distance,
+        &chunk_map,
+        &mut chunks,
+        &block_registry.0,
+        &limits,
+    );
 }
 
 /// 3D DDA through the integer voxel grid.
-/// Returns first solid voxel hit + the face we entered through.
+/// Returns first solid voxel hit + the face we entered through. Fluids are passed through.
+/// The walk stops once it leaves the world through its floor or ceiling.
 fn pick_voxel_dda(
     ray: Ray3d,
     max_distance: f32,
     chunk_map: &ChunkEntityMap,
     chunks: &mut Chunks,
+    registry: &BlockRegistry,
+    limits: &WorldHeightLimits,
 ) -> Option<VoxelHit> {
     let origin = ray.origin;
     let dir = ray.direction.normalize();
@@ -210,7 +234,7 @@ fn pick_voxel_dda(
     let mut entered_face: Option<VoxelFace> = None;
 
     // Check starting cell first.
-    if is_solid(cell, chunk_map, chunks) {
+    if is_solid(cell, chunk_map, chunks, registry) {
         let face = entered_face.unwrap_or(VoxelFace::PosY);
         return Some(build_hit(cell, face));
     }
@@ -238,6 +262,9 @@ fn pick_voxel_dda(
             } else {
                 VoxelFace::PosY
             });
+            if (step_y > 0 && cell.y > limits.max_y()) || (step_y < 0 && cell.y < limits.min_y()) {
+                return None;
+            }
         } else {
             cell.z += step_z;
             t = t_max_z;
@@ -249,7 +276,7 @@ fn pick_voxel_dda(
             });
         }
 
-        if is_solid(cell, chunk_map, chunks) {
+        if is_solid(cell, chunk_map, chunks, registry) {
             let face = entered_face.unwrap_or(VoxelFace::PosY);
             return Some(build_hit(cell, face));
         }
@@ -268,7 +295,12 @@ fn build_hit(world_cell: IVec3, face: VoxelFace) -> VoxelHit {
     }
 }
 
-fn is_solid(world_cell: IVec3, chunk_map: &ChunkEntityMap, chunks: &mut Chunks) -> bool {
+fn is_solid(
+    world_cell: IVec3,
+    chunk_map: &ChunkEntityMap,
+    chunks: &mut Chunks,
+    registry: &BlockRegistry,
+) -> bool {
     let (chunk_coord, local) = world_to_chunk_local(world_cell);
 
     let Some(entity) = chunk_map.chunks.get(&chunk_coord) else {
@@ -276,40 +308,11 @@ fn is_solid(world_cell: IVec3, chunk_map: &ChunkEntityMap, chunks: &mut Chunks)
     };
 
     chunks.0.get(entity).is_some_and(|chunk| {
-        !chunk
-            .get(local.x as usize, local.y as usize, local.z as usize)
-            .is_air()
+        let voxel = chunk.get(local.x as usize, local.y as usize, local.z as usize);
+        !voxel.is_air() && !registry.is_fluid(voxel.block_id())
     })
 }
 
-/// Convert world voxel coord -> (chunk coord, local voxel coord).
-fn world_to_chunk_local(world: IVec3) -> (IVec3, IVec3) {
-    let cs = CHUNK_SIZE as i32;
-
-    // Euclidean division so negatives work the way you expect.
-    let chunk = IVec3::new(
-        div_euclid(world.x, cs),
-        div_euclid(world.y, cs),
-        div_euclid(world.z, cs),
-    );
-
-    let local = IVec3::new(
-        rem_euclid(world.x, cs),
-        rem_euclid(world.y, cs),
-        rem_euclid(world.z, cs),
-    );
-
-    (chunk, local)
-}
-
-fn div_euclid(a: i32, b: i32) -> i32 {
-    a.div_euclid(b)
-}
-
-fn rem_euclid(a: i32, b: i32) -> i32 {
-    a.rem_euclid(b)
-}
-
 fn ivec3_floor(v: Vec3) -> IVec3 {
     IVec3::new(v.x.floor() as i32, v.y.floor() as i32, v.z.floor() as i32)
 }
@@ -412,3 +415,62 @@ fn draw_face_outline(gizmos: &mut Gizmos, voxel_world: IVec3, face: VoxelFace) {
     gizmos.line(c, d, YELLOW);
     gizmos.line(d, a, YELLOW);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::world::{
+        blocks::BLOCK_STONE,
+        chunk::{CHUNK_SIZE, Chunk},
+        voxel::Voxel,
+    };
+
+    /// Stone chunks loaded below and above the one chunk layer the limits allow.
+    fn pick_up(limits: WorldHeightLimits) -> Option<VoxelHit> {
+        let mut chunk_map = ChunkEntityMap::default();
+        let mut chunks = Chunks::default();
+        let mut stone = Chunk::new();
+        for z in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    stone.set(x, y, z, Voxel::new(BLOCK_STONE));
+                }
+            }
+        }
+        for (index, coord) in [IVec3::ZERO, IVec3::Y, IVec3::NEG_Y]
+            .into_iter()
+            .enumerate()
+        {
+            let entity = Entity::from_raw_u32(index as u32 + 1).unwrap();
+            chunk_map.insert(coord, entity);
+            let chunk = if coord == IVec3::ZERO {
+                Chunk::new()
+            } else {
+                stone.clone()
+            };
+            chunks.0.insert(entity, chunk);
+        }
+
+        let ray = Ray3d::new(Vec3::new(1.5, 20.5, 1.5), Dir3::Y);
+        let registry = BlockRegistryRes::default();
+        pick_voxel_dda(ray, 100.0, &chunk_map, &mut chunks, &registry.0, &limits)
+    }
+
+    #[test]
+    fn rays_leaving_through_the_ceiling_pick_nothing() {
+        let limits = WorldHeightLimits {
+            min_chunk_y: 0,
+            max_chunk_y: 0,
+        };
+        assert_eq!(pick_up(limits), None);
+
+        // Raising the ceiling brings the chunk above into reach.
+        let hit = pick_up(WorldHeightLimits {
+            max_chunk_y: 1,
+            ..limits
+        })
+        .unwrap();
+        assert_eq!(hit.world, IVec3::new(1, CHUNK_SIZE as i32, 1));
+        assert_eq!(hit.face, VoxelFace::NegY);
+    }
+}
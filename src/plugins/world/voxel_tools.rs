@@ -0,0 +1,432 @@
+use bevy::prelude::*;
+
+use crate::plugins::{
+    player::{
+        PlayerCollider,
+        inventory::{HOTBAR_SLOTS, Inventory},
+    },
+    world::{
+        blocks::BlockRegistryRes,
+        brushes::{brush_active, brush_inactive},
+        edit_history::{EditHistory, EditTransaction},
+        events::{VoxelBroken, VoxelPlaced},
+        items::{ItemRegistryRes, ItemStack, LootRng},
+        mining::{MiningProgress, MiningSettings},
+        voxel::Voxel,
+        voxel_picking::HoveredVoxel,
+        voxel_world::VoxelWorld,
+    },
+};
+use crate::state::LoadingState;
+
+const HOTBAR_KEYS: [KeyCode; HOTBAR_SLOTS] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+pub struct VoxelToolsPlugin;
+
+impl Plugin for VoxelToolsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VoxelToolBindings>()
+            .init_resource::<VoxelToolCooldowns>()
+            .add_systems(
+                Update,
+                (
+                    select_hotbar_slot,
+                    apply_voxel_tools.run_if(brush_inactive),
+                    stop_mining.run_if(brush_active),
+                )
+                    .chain()
+                    .run_if(in_state(LoadingState::Initialized)),
+            );
+    }
+}
+
+/// A single input that can trigger a tool action.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ToolBinding {
+    Mouse(MouseButton),
+    Key(KeyCode),
+}
+
+impl ToolBinding {
+    pub fn pressed(self, mouse: &ButtonInput<MouseButton>, keys: &ButtonInput<KeyCode>) -> bool {
+        match self {
+            ToolBinding::Mouse(button) => mouse.pressed(button),
+            ToolBinding::Key(key) => keys.pressed(key),
+        }
+    }
+
+    pub fn just_pressed(
+        self,
+        mouse: &ButtonInput<MouseButton>,
+        keys: &ButtonInput<KeyCode>,
+    ) -> bool {
+        match self {
+            ToolBinding::Mouse(button) => mouse.just_pressed(button),
+            ToolBinding::Key(key) => keys.just_pressed(key),
+        }
+    }
+}
+
+/// Inputs mapped to each voxel tool action.
+#[derive(Resource, Clone, Debug)]
+pub struct VoxelToolBindings {
+    pub break_block: ToolBinding,
+    pub place_block: ToolBinding,
+    pub pick_block: ToolBinding,
+}
+
+impl Default for VoxelToolBindings {
+    fn default() -> Self {
+        Self {
+            break_block: ToolBinding::Mouse(MouseButton::Left),
+            place_block: ToolBinding::Mouse(MouseButton::Right),
+            // Middle mouse is taken by the free camera's cursor grab.
+            pick_block: ToolBinding::Key(KeyCode::KeyF),
+        }
+    }
+}
+
+/// Repeat timers for actions that keep firing while their binding is held.
+#[derive(Resource, Clone, Debug)]
+pub struct VoxelToolCooldowns {
+    pub place_block: Timer,
+}
+
+impl Default for VoxelToolCooldowns {
+    fn default() -> Self {
+        Self {
+            place_block: Timer::from_seconds(0.25, TimerMode::Repeating),
+        }
+    }
+}
+
+fn select_hotbar_slot(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut inventories: Query<&mut Inventory, With<PlayerCollider>>,
+) {
+    if let Some(slot) = HOTBAR_KEYS.iter().position(|key| keys.just_pressed(*key)) {
+        for mut inventory in &mut inventories {
+            inventory.select(slot);
+        }
+    }
+}
+
+fn stop_mining(mut mining: ResMut<MiningProgress>) {
+    if mining.target.is_some() {
+        mining.reset();
+    }
+}
+
+/// Fires on the initial press, then once per timer period while the binding stays held.
+fn should_fire(
+    binding: ToolBinding,
+    timer: &mut Timer,
+    time: &Time,
+    mouse: &ButtonInput<MouseButton>,
+    keys: &ButtonInput<KeyCode>,
+) -> bool {
+    if binding.just_pressed(mouse, keys) {
+        timer.reset();
+        return true;
+    }
+    if binding.pressed(mouse, keys) {
+        return timer.tick(time.delta()).just_finished();
+    }
+    false
+}
+
+#[allow(clippy::too_many_arguments)]
+fn apply_voxel_tools(
+    mut commands: Commands,
+    time: Res<Time>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<VoxelToolBindings>,
+    mut cooldowns: ResMut<VoxelToolCooldowns>,
+    hovered: Res<HoveredVoxel>,
+    colliders: Query<(&PlayerCollider, &GlobalTransform)>,
+    mut inventory: Single<&mut Inventory, With<PlayerCollider>>,
+    mut history: ResMut<EditHistory>,
+    block_registry: Res<BlockRegistryRes>,
+    item_registry: Res<ItemRegistryRes>,
+    mut loot_rng: ResMut<LootRng>,
+    mining_settings: Res<MiningSettings>,
+    mut mining: ResMut<MiningProgress>,
+    mut voxel_world: VoxelWorld,
+) {
+    let Some(hit) = hovered.hit else {
+        stop_mining(mining);
+        return;
+    };
+
+    if bindings.pick_block.just_pressed(&mouse, &keys)
+        && let Some(voxel) = voxel_world.get(hit.world)
+        && let Some(item) = item_registry.0.block_item(voxel.block_id())
+    {
+        inventory.pick(item);
+    }
+
+    let cooldowns = &mut *cooldowns;
+    let mut transaction = EditTransaction::new();
+
+    // Held to mine; letting go or looking at another voxel starts over.
+    let mined = voxel_world
+        .get(hit.world)
+        .filter(|voxel| bindings.break_block.pressed(&mouse, &keys) && voxel.is_solid())
+        .map(|voxel| {
+            let seconds = block_registry.0.hardness(voxel.block_id()) / mining_settings.speed;
+            (voxel, seconds)
+        })
+        .filter(|(_, seconds)| seconds.is_finite());
+    match mined {
+        Some((voxel, seconds)) => {
+            if mining.mine(hit, voxel, seconds, time.delta_secs())
+                && let Some(voxel) = transaction.set(&mut voxel_world, hit.world, Voxel::AIR)
+            {
+                mining.reset();
+                for stack in item_registry.0.drops(voxel.block_id(), &mut loot_rng.0) {
+                    let lost = inventory.insert(stack, &item_registry.0);
+                    if let Some(lost) = lost {
+                        debug!("Inventory full, dropped {lost:?}");
+                    }
+                    // Undoing the break takes back only what was kept.
+                    transaction.gain(
+                        0,
+                        ItemStack::new(stack.item, stack.count - lost.map_or(0, |lost| lost.count)),
+                    );
+                }
+                history.push(transaction);
+                commands.trigger(VoxelBroken {
+                    world: hit.world,
+                    voxel,
+                });
+                // The hovered voxel is gone; don't place against it this frame.
+                return;
+            }
+        }
+        None => stop_mining(mining),
+    }
+
+    if should_fire(
+        bindings.place_block,
+        &mut cooldowns.place_block,
+        &time,
+        &mouse,
+        &keys,
+    ) {
+        let Some((item, block_id)) = inventory.selected_stack().and_then(|stack| {
+            item_registry
+                .0
+                .block(stack.item)
+                .map(|block_id| (stack.item, block_id))
+        }) else {
+            return;
+        };
+        let target = hit.adjacent();
+
+        // Fluids are replaced like air.
+        let replaceable =
+            |voxel: Voxel| voxel.is_air() || block_registry.0.is_fluid(voxel.block_id());
+        if !voxel_world.get(target).is_some_and(replaceable) {
+            return;
+        }
+        if !block_registry.0.is_fluid(block_id)
+            && colliders
+                .iter()
+                .any(|(collider, gt)| collider.intersects_voxel(gt.translation(), target))
+        {
+            return;
+        }
+
+        let voxel = Voxel::new(block_id);
+        transaction.set(&mut voxel_world, target, voxel);
+        let selected = inventory.selected;
+        inventory.take(selected, 1);
+        transaction.spend(0, ItemStack::new(item, 1));
+        history.push(transaction);
+        commands.trigger(VoxelPlaced {
+            world: target,
+            voxel,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::plugins::world::{
+        ChunkEntityMap, Chunks,
+        blocks::{BLOCK_COAL_ORE, BLOCK_STONE},
+        brushes::BrushTool,
+        chunk::Chunk,
+        edit_history::EditHistoryPlugin,
+        items::ITEM_COAL,
+        test_support::{headless_app, spawn_chunks},
+        voxel_picking::{VoxelFace, VoxelHit},
+        voxel_world::get_voxel,
+    };
+
+    /// A headless app with a stone floor at y 0, a coal ore on it at (5, 1, 5), and a
+    /// player with an empty inventory.
+    fn tool_app() -> (App, Entity) {
+        let mut app = headless_app(1);
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            50,
+        )))
+        .init_resource::<ItemRegistryRes>()
+        .init_resource::<BrushTool>()
+        .init_resource::<HoveredVoxel>()
+        .init_resource::<MiningProgress>()
+        .init_resource::<ButtonInput<MouseButton>>()
+        .init_resource::<ButtonInput<KeyCode>>()
+        .insert_resource(MiningSettings { speed: 1000.0 })
+        .insert_resource(LootRng::new(3))
+        .add_plugins((VoxelToolsPlugin, EditHistoryPlugin));
+
+        let mut chunk = Chunk::new();
+        for x in 0..32 {
+            for z in 0..32 {
+                chunk.set(x, 0, z, Voxel::new(BLOCK_STONE));
+            }
+        }
+        chunk.set(5, 1, 5, Voxel::new(BLOCK_COAL_ORE));
+        spawn_chunks(app.world_mut(), [(IVec3::ZERO, chunk)]);
+
+        let player = app
+            .world_mut()
+            .spawn((
+                PlayerCollider {
+                    half_extents: Vec3::splat(0.3),
+                    offset: Vec3::ZERO,
+                },
+                GlobalTransform::from_translation(Vec3::new(20.0, 5.0, 20.0)),
+                Inventory::default(),
+            ))
+            .id();
+        // The first update has no time delta to mine with.
+        app.update();
+        (app, player)
+    }
+
+    fn voxel(app: &App, world: IVec3) -> Option<Voxel> {
+        get_voxel(
+            app.world().resource::<ChunkEntityMap>(),
+            app.world().resource::<Chunks>(),
+            world,
+        )
+    }
+
+    fn click(app: &mut App, button: MouseButton, world: IVec3) {
+        app.world_mut().resource_mut::<HoveredVoxel>().hit = Some(VoxelHit {
+            chunk: IVec3::ZERO,
+            local: world,
+            world,
+            face: VoxelFace::PosY,
+        });
+        app.world_mut()
+            .resource_mut::<ButtonInput<MouseButton>>()
+            .press(button);
+        app.update();
+        let mut mouse = app.world_mut().resource_mut::<ButtonInput<MouseButton>>();
+        mouse.release(button);
+        mouse.clear();
+    }
+
+    fn press_ctrl(app: &mut App, key: KeyCode) {
+        let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        keys.press(KeyCode::ControlLeft);
+        keys.press(key);
+        app.update();
+        let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        keys.release_all();
+        keys.clear();
+    }
+
+    fn inventory(app: &App, player: Entity) -> &Inventory {
+        app.world().get::<Inventory>(player).unwrap()
+    }
+
+    #[test]
+    fn breaking_and_placing_move_items() {
+        let (mut app, player) = tool_app();
+        let ore = IVec3::new(5, 1, 5);
+        click(&mut app, MouseButton::Right, IVec3::new(3, 0, 3));
+        assert_eq!(voxel(&app, IVec3::new(3, 1, 3)), Some(Voxel::AIR));
+
+        click(&mut app, MouseButton::Left, ore);
+        assert_eq!(voxel(&app, ore), Some(Voxel::AIR));
+        assert_eq!(inventory(&app, player).count(ITEM_COAL), 1);
+
+        // Coal isn't a block.
+        click(&mut app, MouseButton::Right, IVec3::new(8, 0, 8));
+        assert_eq!(voxel(&app, IVec3::new(8, 1, 8)), Some(Voxel::AIR));
+
+        click(&mut app, MouseButton::Left, IVec3::new(3, 0, 3));
+        app.world_mut()
+            .get_mut::<Inventory>(player)
+            .unwrap()
+            .select(1);
+        click(&mut app, MouseButton::Right, IVec3::new(8, 0, 8));
+        assert_eq!(
+            voxel(&app, IVec3::new(8, 1, 8)),
+            Some(Voxel::new(BLOCK_STONE))
+        );
+        assert_eq!(inventory(&app, player).count(BLOCK_STONE), 0);
+    }
+
+    #[test]
+    fn undo_moves_items_back() {
+        let (mut app, player) = tool_app();
+        let ore = IVec3::new(5, 1, 5);
+        click(&mut app, MouseButton::Left, ore);
+        assert_eq!(inventory(&app, player).count(ITEM_COAL), 1);
+
+        press_ctrl(&mut app, KeyCode::KeyZ);
+        assert_eq!(voxel(&app, ore), Some(Voxel::new(BLOCK_COAL_ORE)));
+        assert_eq!(inventory(&app, player).count(ITEM_COAL), 0);
+        press_ctrl(&mut app, KeyCode::KeyY);
+        assert_eq!(voxel(&app, ore), Some(Voxel::AIR));
+        assert_eq!(inventory(&app, player).count(ITEM_COAL), 1);
+
+        app.world_mut().get_mut::<Inventory>(player).unwrap().slots[1] =
+            Some(ItemStack::new(BLOCK_STONE, 1));
+        app.world_mut()
+            .get_mut::<Inventory>(player)
+            .unwrap()
+            .select(1);
+        click(&mut app, MouseButton::Right, IVec3::new(8, 0, 8));
+        assert_eq!(inventory(&app, player).count(BLOCK_STONE), 0);
+        press_ctrl(&mut app, KeyCode::KeyZ);
+        assert_eq!(voxel(&app, IVec3::new(8, 1, 8)), Some(Voxel::AIR));
+        assert_eq!(inventory(&app, player).count(BLOCK_STONE), 1);
+    }
+
+    #[test]
+    fn undo_is_refused_once_the_loot_is_gone() {
+        let (mut app, player) = tool_app();
+        let ore = IVec3::new(5, 1, 5);
+        click(&mut app, MouseButton::Left, ore);
+        app.world_mut()
+            .get_mut::<Inventory>(player)
+            .unwrap()
+            .remove(ItemStack::new(ITEM_COAL, 1));
+
+        press_ctrl(&mut app, KeyCode::KeyZ);
+        assert_eq!(voxel(&app, ore), Some(Voxel::AIR));
+        assert!(app.world().resource::<EditHistory>().can_undo());
+    }
+}
@@ -0,0 +1,286 @@
+use bevy::ecs::system::SystemParam;
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    ChunkEntityMap, Chunks,
+    blocks::BlockRegistryRes,
+    chunk::{CHUNK_SIZE, world_to_chunk_local},
+    config::WorldHeightLimits,
+    edit_history::VoxelEdit,
+    events::VoxelChanged,
+    heightmap::Heightmaps,
+    voxel::Voxel,
+};
+
+/// Voxel access by world voxel coordinate, independent of which chunk the voxel lives in.
+/// Every change is reported as a [`VoxelChanged`] message and kept in the [`Heightmaps`].
+#[derive(SystemParam)]
+pub struct VoxelWorld<'w> {
+    chunk_map: Res<'w, ChunkEntityMap>,
+    chunks: ResMut<'w, Chunks>,
+    changed: MessageWriter<'w, VoxelChanged>,
+    heightmaps: ResMut<'w, Heightmaps>,
+    limits: Res<'w, WorldHeightLimits>,
+    registry: Res<'w, BlockRegistryRes>,
+}
+
+impl VoxelWorld<'_> {
+    /// Voxel at `world`, or `None` if its chunk isn't loaded.
+    pub fn get(&self, world: IVec3) -> Option<Voxel> {
+        get_voxel(&self.chunk_map, &self.chunks, world)
+    }
+
+    pub fn limits(&self) -> &WorldHeightLimits {
+        &self.limits
+    }
+
+    /// Set the voxel at `world`, returning the voxel it replaced.
+    /// Returns `None` (and changes nothing) if its chunk isn't loaded or it is outside
+    /// the [`WorldHeightLimits`].
+    pub fn set(&mut self, world: IVec3, voxel: Voxel) -> Option<Voxel> {
+        if !self.limits.contains(world.y) {
+            return None;
+        }
+        let previous = set_voxel(&self.chunk_map, &mut self.chunks, world, voxel)?;
+        if previous != voxel {
+            self.update_heightmap(world, voxel);
+            self.changed.write(VoxelChanged {
+                world,
+                previous,
+                new: voxel,
+            });
+        }
+        Some(previous)
+    }
+
+    /// Set many voxels as one operation, see [`set_voxels`]. Voxels outside the
+    /// [`WorldHeightLimits`] are skipped.
+    pub fn set_batch(
+        &mut self,
+        voxels: impl IntoIterator<Item = (IVec3, Voxel)>,
+    ) -> Vec<VoxelEdit> {
+        let limits = *self.limits;
+        let edits = set_voxels(
+            &self.chunk_map,
+            &mut self.chunks,
+            voxels
+                .into_iter()
+                .filter(|(world, _)| limits.contains(world.y)),
+        );
+        for edit in &edits {
+            self.update_heightmap(edit.world, edit.new);
+        }
+        self.changed
+            .write_batch(edits.iter().map(|edit| VoxelChanged {
+                world: edit.world,
+                previous: edit.previous,
+                new: edit.new,
+            }));
+        edits
+    }
+
+    fn update_heightmap(&mut self, world: IVec3, voxel: Voxel) {
+        let (chunk_map, chunks) = (&self.chunk_map, &self.chunks);
+        self.heightmaps
+            .voxel_changed(world, voxel, &self.registry.0, &self.limits, |world| {
+                get_voxel(chunk_map, chunks, world)
+            });
+    }
+}
+
+/// Read/write access to voxels by world coordinate.
+///
+/// Implemented by [`VoxelWorld`] and by a plain `HashMap` (missing entries are air),
+/// so world simulations can run on synthetic worlds outside the ECS.
+pub trait VoxelAccess {
+    /// Voxel at `world`, or `None` if it isn't loaded.
+    fn get_voxel(&self, world: IVec3) -> Option<Voxel>;
+
+    /// Set the voxel at `world`, returning the voxel it replaced, or `None` if it isn't loaded.
+    fn set_voxel(&mut self, world: IVec3, voxel: Voxel) -> Option<Voxel>;
+}
+
+impl VoxelAccess for VoxelWorld<'_> {
+    fn get_voxel(&self, world: IVec3) -> Option<Voxel> {
+        self.get(world)
+    }
+
+    fn set_voxel(&mut self, world: IVec3, voxel: Voxel) -> Option<Voxel> {
+        self.set(world, voxel)
+    }
+}
+
+impl VoxelAccess for HashMap<IVec3, Voxel> {
+    fn get_voxel(&self, world: IVec3) -> Option<Voxel> {
+        Some(self.get(&world).copied().unwrap_or(Voxel::AIR))
+    }
+
+    fn set_voxel(&mut self, world: IVec3, voxel: Voxel) -> Option<Voxel> {
+        let previous = if voxel.is_air() {
+            self.remove(&world)
+        } else {
+            self.insert(world, voxel)
+        };
+        Some(previous.unwrap_or(Voxel::AIR))
+    }
+}
+
+pub fn get_voxel(chunk_map: &ChunkEntityMap, chunks: &Chunks, world: IVec3) -> Option<Voxel> {
+    let (chunk_coord, local) = world_to_chunk_local(world);
+    let entity = chunk_map.get(&chunk_coord)?;
+    let chunk = chunks.0.get(&entity)?;
+
+    Some(chunk.get(local.x as usize, local.y as usize, local.z as usize))
+}
+
+pub fn set_voxel(
+    chunk_map: &ChunkEntityMap,
+    chunks: &mut Chunks,
+    world: IVec3,
+    voxel: Voxel,
+) -> Option<Voxel> {
+    let (chunk_coord, local) = world_to_chunk_local(world);
+    let entity = chunk_map.get(&chunk_coord)?;
+    let chunk = chunks.0.get_mut(&entity)?;
+
+    let (x, y, z) = (local.x as usize, local.y as usize, local.z as usize);
+    let previous = chunk.get(x, y, z);
+    if previous == voxel {
+        return Some(previous);
+    }
+    chunk.set(x, y, z, voxel);
+
+    // Faces of the neighbouring chunk may have been hidden or exposed by this edit.
+    for offset in border_offsets(local) {
+        if let Some(neighbour) = chunk_map
+            .get(&(chunk_coord + offset))
+            .and_then(|entity| chunks.0.get_mut(&entity))
+        {
+            neighbour.mark_dirty();
+        }
+    }
+
+    Some(previous)
+}
+
+/// Set many voxels, marking each affected chunk and border neighbour dirty only once
+/// for the whole batch. Voxels in unloaded chunks are skipped.
+/// Returns the edits that actually changed a voxel, in application order.
+pub fn set_voxels(
+    chunk_map: &ChunkEntityMap,
+    chunks: &mut Chunks,
+    voxels: impl IntoIterator<Item = (IVec3, Voxel)>,
+) -> Vec<VoxelEdit> {
+    let mut edits = Vec::new();
+    let mut dirty_neighbours = HashSet::new();
+
+    for (world, voxel) in voxels {
+        let (chunk_coord, local) = world_to_chunk_local(world);
+        let Some(chunk) = chunk_map
+            .get(&chunk_coord)
+            .and_then(|entity| chunks.0.get_mut(&entity))
+        else {
+            continue;
+        };
+
+        let (x, y, z) = (local.x as usize, local.y as usize, local.z as usize);
+        let previous = chunk.get(x, y, z);
+        if previous == voxel {
+            continue;
+        }
+        chunk.set(x, y, z, voxel);
+
+        dirty_neighbours.extend(border_offsets(local).map(|offset| chunk_coord + offset));
+        edits.push(VoxelEdit {
+            world,
+            previous,
+            new: voxel,
+        });
+    }
+
+    for coord in dirty_neighbours {
+        if let Some(neighbour) = chunk_map
+            .get(&coord)
+            .and_then(|entity| chunks.0.get_mut(&entity))
+        {
+            neighbour.mark_dirty();
+        }
+    }
+
+    edits
+}
+
+/// Directions to the neighbouring chunks that share a face with the given local voxel.
+fn border_offsets(local: IVec3) -> impl Iterator<Item = IVec3> {
+    let max = CHUNK_SIZE as i32 - 1;
+    [
+        (local.x == 0, IVec3::NEG_X),
+        (local.x == max, IVec3::X),
+        (local.y == 0, IVec3::NEG_Y),
+        (local.y == max, IVec3::Y),
+        (local.z == 0, IVec3::NEG_Z),
+        (local.z == max, IVec3::Z),
+    ]
+    .into_iter()
+    .filter_map(|(on_border, offset)| on_border.then_some(offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::plugins::world::{
+        blocks::BLOCK_STONE,
+        chunk::Chunk,
+        test_support::{headless_world, spawn_chunks},
+    };
+
+    /// One chunk layer of height limits, with chunks loaded in it and past either end.
+    fn limited_world() -> World {
+        let mut world = headless_world(0);
+        spawn_chunks(
+            &mut world,
+            [IVec3::NEG_Y, IVec3::ZERO, IVec3::Y].map(|coord| (coord, Chunk::new())),
+        );
+        world
+    }
+
+    fn changes(world: &World) -> usize {
+        world.resource::<Messages<VoxelChanged>>().len()
+    }
+
+    #[test]
+    fn set_leaves_voxels_past_the_height_limits() {
+        let mut world = limited_world();
+        let limits = *world.resource::<WorldHeightLimits>();
+        let stone = Voxel::new(BLOCK_STONE);
+        let results = world
+            .run_system_once(move |mut voxel_world: VoxelWorld| {
+                [limits.max_y() + 1, limits.min_y() - 1, limits.max_y()]
+                    .map(|y| voxel_world.set(IVec3::new(1, y, 1), stone))
+            })
+            .unwrap();
+        assert_eq!(results, [None, None, Some(Voxel::AIR)]);
+        assert_eq!(changes(&world), 1);
+    }
+
+    #[test]
+    fn set_batch_skips_voxels_past_the_height_limits() {
+        let mut world = limited_world();
+        let limits = *world.resource::<WorldHeightLimits>();
+        let stone = Voxel::new(BLOCK_STONE);
+        let edits = world
+            .run_system_once(move |mut voxel_world: VoxelWorld| {
+                voxel_world.set_batch(
+                    [limits.max_y() + 1, limits.min_y() - 1, limits.min_y()]
+                        .map(|y| (IVec3::new(1, y, 1), stone)),
+                )
+            })
+            .unwrap();
+        let edited: Vec<IVec3> = edits.iter().map(|edit| edit.world).collect();
+        assert_eq!(edited, [IVec3::new(1, limits.min_y(), 1)]);
+        assert_eq!(changes(&world), 1);
+    }
+}
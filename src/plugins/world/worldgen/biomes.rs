@@ -0,0 +1,564 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    blocks::{
+        BLOCK_BEDROCK, BLOCK_DIRT, BLOCK_GRASS, BLOCK_GRAVEL, BLOCK_SAND, BLOCK_STONE, BLOCK_WATER,
+        BlockId,
+    },
+    chunk::{CHUNK_SIZE, Chunk},
+    noise::{fbm_2d, hash_unit},
+    voxel::Voxel,
+    worldgen::TerrainGenerator,
+};
+
+pub type BiomeId = u8;
+
+pub const BIOME_PLAINS: BiomeId = 0;
+pub const BIOME_DESERT: BiomeId = 1;
+pub const BIOME_MOUNTAINS: BiomeId = 2;
+pub const BIOME_MARSH: BiomeId = 3;
+
+/// Voxels across one cycle of the climate noise; biomes are roughly this wide.
+const CLIMATE_SCALE: f32 = 256.0;
+/// Voxels across one cycle of the base layer of height noise.
+const HEIGHT_SCALE: f32 = 64.0;
+/// Distance in climate space over which neighbouring biomes' heights blend together.
+const BLEND_WIDTH: f32 = 0.2;
+
+const TEMPERATURE_SALT: u64 = 0x7E3A_11C5;
+const HUMIDITY_SALT: u64 = 0x4B1D_0F2E;
+const HEIGHT_SALT: u64 = 0x2C9F_6A03;
+const DECORATION_SALT: u64 = 0x61E0_D5B7;
+
+/// Chunk columns whose samples a [`BiomeGenerator`] keeps. Every chunk of a column, its
+/// carving, decoration and biome map reuse them instead of sampling the noise again.
+const CACHED_COLUMNS: usize = 256;
+
+/// Extra features a biome places on its terrain.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Decoration {
+    /// Replace the surface block with `block` in about `chance` of the columns above sea level.
+    SurfacePatch { block: BlockId, chance: f32 },
+    /// Each tree attempt in a chunk grows a tree here with `chance`, if it lands on grass
+    /// or dirt. Trunks are `min_height..=max_height` logs tall.
+    Trees {
+        chance: f32,
+        min_height: i32,
+        max_height: i32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Biome {
+    pub name: &'static str,
+    /// Temperature (x) and humidity (y), each in `[0, 1]`, where the biome is found.
+    pub climate: Vec2,
+    pub surface: BlockId,
+    /// Block under the surface, down to `subsurface_depth`, and on the surface under water.
+    pub subsurface: BlockId,
+    pub subsurface_depth: i32,
+    /// Height the terrain varies around, by up to `height_variation` either way.
+    pub base_height: f32,
+    pub height_variation: f32,
+    pub decorations: Vec<Decoration>,
+}
+
+/// Biomes by id. Ids are assigned in insertion order.
+#[derive(Clone, Debug, Default)]
+pub struct BiomeRegistry {
+    biomes: Vec<Biome>,
+}
+
+impl BiomeRegistry {
+    pub fn get(&self, id: BiomeId) -> Option<&Biome> {
+        self.biomes.get(id as usize)
+    }
+
+    pub fn insert(&mut self, biome: Biome) -> BiomeId {
+        self.biomes.push(biome);
+        (self.biomes.len() - 1) as BiomeId
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (BiomeId, &Biome)> {
+        self.biomes
+            .iter()
+            .enumerate()
+            .map(|(id, biome)| (id as BiomeId, biome))
+    }
+
+    pub fn len(&self) -> usize {
+        self.biomes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.biomes.is_empty()
+    }
+}
+
+#[derive(Resource)]
+pub struct BiomeRegistryRes(pub BiomeRegistry);
+
+impl Default for BiomeRegistryRes {
+    fn default() -> Self {
+        let mut registry = BiomeRegistry::default();
+
+        let plains = registry.insert(Biome {
+            name: "plains",
+            climate: Vec2::new(0.5, 0.5),
+            surface: BLOCK_GRASS,
+            subsurface: BLOCK_DIRT,
+            subsurface_depth: 4,
+            base_height: 20.0,
+            height_variation: 4.0,
+            decorations: vec![Decoration::Trees {
+                chance: 0.2,
+                min_height: 4,
+                max_height: 6,
+            }],
+        });
+        let desert = registry.insert(Biome {
+            name: "desert",
+            climate: Vec2::new(0.85, 0.15),
+            surface: BLOCK_SAND,
+            subsurface: BLOCK_SAND,
+            subsurface_depth: 5,
+            base_height: 19.0,
+            height_variation: 6.0,
+            decorations: vec![Decoration::SurfacePatch {
+                block: BLOCK_GRAVEL,
+                chance: 0.01,
+            }],
+        });
+        let mountains = registry.insert(Biome {
+            name: "mountains",
+            climate: Vec2::new(0.15, 0.35),
+            surface: BLOCK_STONE,
+            subsurface: BLOCK_STONE,
+            subsurface_depth: 1,
+            base_height: 40.0,
+            height_variation: 24.0,
+            decorations: vec![Decoration::SurfacePatch {
+                block: BLOCK_GRAVEL,
+                chance: 0.08,
+            }],
+        });
+        let marsh = registry.insert(Biome {
+            name: "marsh",
+            climate: Vec2::new(0.6, 0.9),
+            surface: BLOCK_GRASS,
+            subsurface: BLOCK_DIRT,
+            subsurface_depth: 3,
+            base_height: 14.0,
+            height_variation: 2.0,
+            decorations: vec![Decoration::Trees {
+                chance: 0.35,
+                min_height: 3,
+                max_height: 5,
+            }],
+        });
+        debug_assert_eq!(
+            [plains, desert, mountains, marsh],
+            [BIOME_PLAINS, BIOME_DESERT, BIOME_MOUNTAINS, BIOME_MARSH]
+        );
+
+        BiomeRegistryRes(registry)
+    }
+}
+
+/// Biome ids of the voxel columns of one chunk column.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ColumnBiomes(Box<[BiomeId; CHUNK_SIZE * CHUNK_SIZE]>);
+
+impl ColumnBiomes {
+    pub fn from_fn(mut f: impl FnMut(usize, usize) -> BiomeId) -> Self {
+        let mut biomes = Box::new([0; CHUNK_SIZE * CHUNK_SIZE]);
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                biomes[x + z * CHUNK_SIZE] = f(x, z);
+            }
+        }
+        Self(biomes)
+    }
+
+    pub fn get(&self, x: usize, z: usize) -> BiomeId {
+        self.0[x + z * CHUNK_SIZE]
+    }
+}
+
+/// Biome of every generated voxel column, stored per chunk column.
+#[derive(Resource, Debug, Default)]
+pub struct BiomeMap {
+    columns: HashMap<IVec2, ColumnBiomes>,
+}
+
+impl BiomeMap {
+    pub fn contains(&self, column: IVec2) -> bool {
+        self.columns.contains_key(&column)
+    }
+
+    pub fn insert(&mut self, column: IVec2, biomes: ColumnBiomes) {
+        self.columns.insert(column, biomes);
+    }
+
+    pub fn get(&self, column: IVec2) -> Option<&ColumnBiomes> {
+        self.columns.get(&column)
+    }
+
+    pub fn remove(&mut self, column: IVec2) -> Option<ColumnBiomes> {
+        self.columns.remove(&column)
+    }
+
+    /// Biome at the world voxel column (x, z), if its chunk column has been generated.
+    pub fn biome_at(&self, world_x: i32, world_z: i32) -> Option<BiomeId> {
+        let size = CHUNK_SIZE as i32;
+        let column = IVec2::new(world_x.div_euclid(size), world_z.div_euclid(size));
+        let biomes = self.columns.get(&column)?;
+        Some(biomes.get(
+            world_x.rem_euclid(size) as usize,
+            world_z.rem_euclid(size) as usize,
+        ))
+    }
+}
+
+/// Temperature and humidity at a voxel column, each in `[0, 1]`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Climate {
+    pub temperature: f32,
+    pub humidity: f32,
+}
+
+impl Climate {
+    fn distance(self, biome: &Biome) -> f32 {
+        Vec2::new(self.temperature, self.humidity).distance(biome.climate)
+    }
+}
+
+/// What the generator decided for one voxel column.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ColumnSample {
+    pub biome: BiomeId,
+    /// Height of the surface voxel.
+    pub height: i32,
+}
+
+/// Samples of the voxel columns of one chunk column.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ColumnSamples(Box<[ColumnSample; CHUNK_SIZE * CHUNK_SIZE]>);
+
+impl ColumnSamples {
+    pub fn get(&self, x: usize, z: usize) -> ColumnSample {
+        self.0[x + z * CHUNK_SIZE]
+    }
+}
+
+/// The last [`CACHED_COLUMNS`] chunk columns sampled, dropping the oldest first.
+#[derive(Debug, Default)]
+struct ColumnCache {
+    samples: HashMap<IVec2, Arc<ColumnSamples>>,
+    /// Cached columns, oldest first.
+    order: VecDeque<IVec2>,
+}
+
+impl ColumnCache {
+    fn get(&self, column: IVec2) -> Option<Arc<ColumnSamples>> {
+        self.samples.get(&column).cloned()
+    }
+
+    fn insert(&mut self, column: IVec2, samples: Arc<ColumnSamples>) {
+        // Another thread may have sampled the same column meanwhile.
+        if self.samples.insert(column, samples).is_some() {
+            return;
+        }
+        self.order.push_back(column);
+        while self.order.len() > CACHED_COLUMNS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.samples.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Heightmap terrain whose biome comes from the closest match in the biome registry to the
+/// column's climate. Heights are blended between biomes that are nearly as close, so
+/// borders slope instead of stepping.
+#[derive(Clone, Debug)]
+pub struct BiomeGenerator {
+    seed: u64,
+    registry: BiomeRegistry,
+    /// Air up to this height is filled with water.
+    pub sea_level: i32,
+    /// Recently sampled chunk columns, shared by clones.
+    columns: Arc<Mutex<ColumnCache>>,
+}
+
+impl BiomeGenerator {
+    pub fn new(seed: u64, registry: BiomeRegistry) -> Self {
+        assert!(
+            !registry.is_empty(),
+            "BiomeGenerator needs at least one biome"
+        );
+        Self {
+            seed,
+            registry,
+            sea_level: 14,
+            columns: Arc::default(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn registry(&self) -> &BiomeRegistry {
+        &self.registry
+    }
+
+    pub fn climate_at(&self, world_x: i32, world_z: i32) -> Climate {
+        let point = Vec2::new(world_x as f32, world_z as f32) / CLIMATE_SCALE;
+        // Fractal noise clusters around 0.5; stretch it so the extremes show up.
+        let stretch = |value: f32| ((value - 0.5) * 2.5 + 0.5).clamp(0.0, 1.0);
+        Climate {
+            temperature: stretch(fbm_2d(self.seed ^ TEMPERATURE_SALT, point, 3)),
+            humidity: stretch(fbm_2d(self.seed ^ HUMIDITY_SALT, point, 3)),
+        }
+    }
+
+    pub fn biome_at(&self, world_x: i32, world_z: i32) -> BiomeId {
+        self.nearest_biome(self.climate_at(world_x, world_z))
+    }
+
+    pub fn height_at(&self, world_x: i32, world_z: i32) -> i32 {
+        self.sample(world_x, world_z).height
+    }
+
+    pub fn sample(&self, world_x: i32, world_z: i32) -> ColumnSample {
+        let climate = self.climate_at(world_x, world_z);
+        let point = Vec2::new(world_x as f32, world_z as f32) / HEIGHT_SCALE;
+        let relief = fbm_2d(self.seed ^ HEIGHT_SALT, point, 4) * 2.0 - 1.0;
+
+        let biome = self.nearest_biome(climate);
+        let nearest = climate.distance(&self.registry.biomes[biome as usize]);
+
+        // Weights relative to the nearest biome, which always gets 1, so they can't all
+        // vanish far from every biome's climate.
+        let mut height = 0.0;
+        let mut total_weight = 0.0;
+        for (_, biome) in self.registry.iter() {
+            let distance = climate.distance(biome);
+            let weight =
+                (-(distance * distance - nearest * nearest) / (BLEND_WIDTH * BLEND_WIDTH)).exp();
+            height += weight * (biome.base_height + relief * biome.height_variation);
+            total_weight += weight;
+        }
+        let height = height / total_weight;
+
+        ColumnSample {
+            biome,
+            height: height.round() as i32,
+        }
+    }
+
+    /// [`Self::sample`] of every voxel column in the chunk `column`, from the cache if it
+    /// was sampled recently.
+    pub fn column_samples(&self, column: IVec2) -> Arc<ColumnSamples> {
+        let cached = self
+            .columns
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(column);
+        if let Some(samples) = cached {
+            return samples;
+        }
+
+        let min = column * CHUNK_SIZE as i32;
+        let mut samples = Box::new([ColumnSample::default(); CHUNK_SIZE * CHUNK_SIZE]);
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                samples[x + z * CHUNK_SIZE] = self.sample(min.x + x as i32, min.y + z as i32);
+            }
+        }
+        let samples = Arc::new(ColumnSamples(samples));
+
+        self.columns
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(column, samples.clone());
+        samples
+    }
+
+    fn nearest_biome(&self, climate: Climate) -> BiomeId {
+        self.registry
+            .iter()
+            .min_by(|(_, a), (_, b)| climate.distance(a).total_cmp(&climate.distance(b)))
+            .map(|(id, _)| id)
+            .unwrap_or_default()
+    }
+
+    /// Surface block of a column, after decorations.
+    pub fn surface_block(&self, biome: &Biome, world_x: i32, world_z: i32, height: i32) -> BlockId {
+        if height < self.sea_level {
+            return biome.subsurface;
+        }
+        let roll = hash_unit(self.seed ^ DECORATION_SALT, IVec3::new(world_x, 0, world_z));
+        let mut threshold = 0.0;
+        for decoration in &biome.decorations {
+            match *decoration {
+                Decoration::SurfacePatch { block, chance } => {
+                    threshold += chance;
+                    if roll < threshold {
+                        return block;
+                    }
+                }
+                Decoration::Trees { .. } => {}
+            }
+        }
+        biome.surface
+    }
+}
+
+impl TerrainGenerator for BiomeGenerator {
+    fn generate_chunk(&self, coord: IVec3) -> Chunk {
+        let mut chunk = Chunk::new();
+        let min = coord * CHUNK_SIZE as i32;
+        let samples = self.column_samples(coord.xz());
+
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let (world_x, world_z) = (min.x + x as i32, min.z + z as i32);
+                let ColumnSample { biome, height } = samples.get(x, z);
+                let biome = &self.registry.biomes[biome as usize];
+                let surface = self.surface_block(biome, world_x, world_z, height);
+
+                for y in 0..CHUNK_SIZE {
+                    let world_y = min.y + y as i32;
+                    let block_id = if world_y > height {
+                        if world_y > self.sea_level {
+                            continue;
+                        }
+                        BLOCK_WATER
+                    } else if world_y == 0 {
+                        BLOCK_BEDROCK
+                    } else if world_y == height {
+                        surface
+                    } else if world_y > height - biome.subsurface_depth {
+                        biome.subsurface
+                    } else {
+                        BLOCK_STONE
+                    };
+                    chunk.set(x, y, z, Voxel::new(block_id));
+                }
+            }
+        }
+
+        chunk
+    }
+
+    fn column_biomes(&self, column: IVec2) -> Option<ColumnBiomes> {
+        let samples = self.column_samples(column);
+        Some(ColumnBiomes::from_fn(|x, z| samples.get(x, z).biome))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generator(seed: u64) -> BiomeGenerator {
+        BiomeGenerator::new(seed, BiomeRegistryRes::default().0)
+    }
+
+    /// Columns spread over a few biomes' widths.
+    fn probes() -> impl Iterator<Item = (i32, i32)> {
+        (0..8).map(|i| (i * 300, i * -150))
+    }
+
+    #[test]
+    fn biomes_are_fixed_by_the_seed() {
+        for (seed, biomes, heights) in [
+            (
+                42,
+                [0, 1, 3, 2, 0, 3, 2, 2],
+                [28, 19, 17, 35, 17, 14, 38, 50],
+            ),
+            (
+                7,
+                [2, 0, 3, 1, 1, 3, 1, 2],
+                [55, 20, 14, 15, 16, 14, 29, 37],
+            ),
+        ] {
+            let generator = generator(seed);
+            let samples: Vec<_> = probes().map(|(x, z)| generator.sample(x, z)).collect();
+            let expected: Vec<_> = biomes
+                .into_iter()
+                .zip(heights)
+                .map(|(biome, height)| ColumnSample { biome, height })
+                .collect();
+            assert_eq!(samples, expected, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn cached_columns_match_direct_samples() {
+        let generator = generator(42);
+        for column in [IVec2::ZERO, IVec2::new(-1, 2), IVec2::new(9, -4)] {
+            let min = column * CHUNK_SIZE as i32;
+            let samples = generator.column_samples(column);
+            let biomes = generator.column_biomes(column).unwrap();
+            for (x, z) in [(0, 0), (31, 0), (4, 7), (31, 31)] {
+                let direct = generator.sample(min.x + x as i32, min.y + z as i32);
+                assert_eq!(samples.get(x, z), direct);
+                assert_eq!(biomes.get(x, z), direct.biome);
+            }
+            assert!(Arc::ptr_eq(&samples, &generator.column_samples(column)));
+        }
+    }
+
+    #[test]
+    fn cache_stays_bounded() {
+        let generator = generator(1);
+        let first = generator.column_samples(IVec2::ZERO);
+        for x in 1..=CACHED_COLUMNS as i32 {
+            generator.column_samples(IVec2::new(x, 0));
+        }
+        assert_eq!(
+            generator.columns.lock().unwrap().samples.len(),
+            CACHED_COLUMNS
+        );
+        assert_eq!(*generator.column_samples(IVec2::ZERO), *first);
+    }
+
+    #[test]
+    fn cache_drops_the_oldest_column() {
+        let generator = generator(1);
+        let columns: Vec<_> = (0..=CACHED_COLUMNS as i32)
+            .map(|x| generator.column_samples(IVec2::new(x, 0)))
+            .collect();
+        let cache = generator.columns.lock().unwrap();
+        assert_eq!(cache.get(IVec2::ZERO), None);
+        for (x, samples) in columns.iter().enumerate().skip(1) {
+            let cached = cache.get(IVec2::new(x as i32, 0)).unwrap();
+            assert!(Arc::ptr_eq(&cached, samples), "column {x} was evicted");
+        }
+    }
+
+    #[test]
+    fn chunks_follow_their_samples() {
+        let generator = generator(42);
+        let chunk = generator.generate_chunk(IVec3::ZERO);
+        assert_eq!(chunk.get(0, 0, 0).block_id(), BLOCK_BEDROCK);
+        for (x, z) in [(3, 5), (20, 11)] {
+            let ColumnSample { height, .. } = generator.column_samples(IVec2::ZERO).get(x, z);
+            let above = chunk.get(x, height as usize + 1, z).block_id();
+            assert_ne!(chunk.get(x, height as usize, z).block_id(), 0);
+            assert!(above == 0 || above == BLOCK_WATER);
+        }
+        assert_eq!(
+            chunk.voxels(),
+            generator.generate_chunk(IVec3::ZERO).voxels()
+        );
+    }
+}
@@ -0,0 +1,359 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    chunk::{CHUNK_SIZE, Chunk},
+    noise::FeatureRng,
+    voxel::Voxel,
+};
+
+const WORM_SALT: u64 = 0x3F0C_A7E5;
+const RAVINE_SALT: u64 = 0x5A71_9B2D;
+
+/// An ellipsoid of voxels to remove, in world voxel coords.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CarveShape {
+    pub center: Vec3,
+    pub radii: Vec3,
+}
+
+/// Removes terrain along features that can span many chunks, like caves.
+///
+/// Each feature grows from an origin chunk column using only the seed and that column, so
+/// every chunk it passes through carves exactly the same shape, whatever order chunks are
+/// generated in.
+pub trait Carver: Send + Sync + 'static {
+    /// How many chunk columns from its origin a feature can reach.
+    fn reach(&self) -> i32;
+
+    /// The shapes of the features starting in the chunk column `origin`.
+    fn features(&self, seed: u64, origin: IVec2) -> Vec<CarveShape>;
+}
+
+/// Carve every feature of `carvers` that reaches into the chunk at `coord`. Only voxels
+/// `can_carve` accepts (given the world position and voxel) are replaced with air.
+pub fn carve_chunk(
+    carvers: &[Box<dyn Carver>],
+    seed: u64,
+    coord: IVec3,
+    chunk: &mut Chunk,
+    can_carve: impl Fn(IVec3, Voxel) -> bool,
+) {
+    let size = CHUNK_SIZE as i32;
+    let chunk_min = coord * size;
+    let chunk_max = chunk_min + IVec3::splat(size - 1);
+
+    for carver in carvers {
+        let reach = carver.reach();
+        for origin_z in coord.z - reach..=coord.z + reach {
+            for origin_x in coord.x - reach..=coord.x + reach {
+                for shape in carver.features(seed, IVec2::new(origin_x, origin_z)) {
+                    let min = (shape.center - shape.radii)
+                        .floor()
+                        .as_ivec3()
+                        .max(chunk_min);
+                    let max = (shape.center + shape.radii)
+                        .ceil()
+                        .as_ivec3()
+                        .min(chunk_max);
+                    if min.cmpgt(max).any() {
+                        continue;
+                    }
+
+                    for z in min.z..=max.z {
+                        for y in min.y..=max.y {
+                            for x in min.x..=max.x {
+                                let world = IVec3::new(x, y, z);
+                                let offset = (world.as_vec3() + Vec3::splat(0.5) - shape.center)
+                                    / shape.radii;
+                                if offset.length_squared() > 1.0 {
+                                    continue;
+                                }
+                                let local = (world - chunk_min).as_uvec3();
+                                let (lx, ly, lz) =
+                                    (local.x as usize, local.y as usize, local.z as usize);
+                                let voxel = chunk.get(lx, ly, lz);
+                                if !voxel.is_air() && can_carve(world, voxel) {
+                                    chunk.set(lx, ly, lz, Voxel::AIR);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Winding tunnels that wander in every direction, growing wider in the middle.
+#[derive(Copy, Clone, Debug)]
+pub struct WormCaves {
+    /// Chance that a chunk column starts a cave.
+    pub chance: f32,
+    pub min_y: i32,
+    pub max_y: i32,
+    /// Steps of one voxel each.
+    pub min_length: u32,
+    pub max_length: u32,
+    pub min_radius: f32,
+    pub max_radius: f32,
+}
+
+impl Default for WormCaves {
+    fn default() -> Self {
+        Self {
+            chance: 0.3,
+            min_y: 4,
+            max_y: 48,
+            min_length: 40,
+            max_length: 120,
+            min_radius: 1.2,
+            max_radius: 2.8,
+        }
+    }
+}
+
+impl Carver for WormCaves {
+    fn reach(&self) -> i32 {
+        ((self.max_length as f32 + self.max_radius) / CHUNK_SIZE as f32).ceil() as i32
+    }
+
+    fn features(&self, seed: u64, origin: IVec2) -> Vec<CarveShape> {
+        let mut rng = FeatureRng::for_cell(seed ^ WORM_SALT, IVec3::new(origin.x, 0, origin.y));
+        if rng.next_f32() >= self.chance {
+            return Vec::new();
+        }
+
+        let size = CHUNK_SIZE as i32;
+        let mut position = Vec3::new(
+            (origin.x * size + rng.range(0, size - 1)) as f32,
+            rng.range(self.min_y, self.max_y) as f32,
+            (origin.y * size + rng.range(0, size - 1)) as f32,
+        );
+        let length = rng.range(self.min_length as i32, self.max_length as i32) as u32;
+        let radius = rng.range_f32(self.min_radius, self.max_radius);
+        let mut yaw = rng.range_f32(0.0, 2.0 * PI);
+        let mut pitch = rng.range_f32(-0.3, 0.3);
+
+        let mut shapes = Vec::with_capacity(length as usize);
+        for step in 0..length {
+            let progress = step as f32 / length as f32;
+            let width = radius * (0.6 + 0.6 * (progress * PI).sin());
+            shapes.push(CarveShape {
+                center: position,
+                radii: Vec3::splat(width),
+            });
+
+            position += Vec3::new(
+                yaw.cos() * pitch.cos(),
+                pitch.sin(),
+                yaw.sin() * pitch.cos(),
+            );
+            yaw += rng.range_f32(-0.3, 0.3);
+            pitch = (pitch * 0.8 + rng.range_f32(-0.2, 0.2)).clamp(-0.8, 0.8);
+        }
+        shapes
+    }
+}
+
+/// Long, narrow and deep cracks, mostly level and open to the surface when shallow enough.
+#[derive(Copy, Clone, Debug)]
+pub struct Ravines {
+    /// Chance that a chunk column starts a ravine.
+    pub chance: f32,
+    pub min_y: i32,
+    pub max_y: i32,
+    pub min_length: u32,
+    pub max_length: u32,
+    pub max_width: f32,
+    pub max_depth: f32,
+}
+
+impl Default for Ravines {
+    fn default() -> Self {
+        Self {
+            chance: 0.02,
+            min_y: 16,
+            max_y: 32,
+            min_length: 60,
+            max_length: 140,
+            max_width: 3.5,
+            max_depth: 16.0,
+        }
+    }
+}
+
+impl Carver for Ravines {
+    fn reach(&self) -> i32 {
+        ((self.max_length as f32 + self.max_width) / CHUNK_SIZE as f32).ceil() as i32
+    }
+
+    fn features(&self, seed: u64, origin: IVec2) -> Vec<CarveShape> {
+        let mut rng = FeatureRng::for_cell(seed ^ RAVINE_SALT, IVec3::new(origin.x, 0, origin.y));
+        if rng.next_f32() >= self.chance {
+            return Vec::new();
+        }
+
+        let size = CHUNK_SIZE as i32;
+        let mut position = Vec3::new(
+            (origin.x * size + rng.range(0, size - 1)) as f32,
+            rng.range(self.min_y, self.max_y) as f32,
+            (origin.y * size + rng.range(0, size - 1)) as f32,
+        );
+        let length = rng.range(self.min_length as i32, self.max_length as i32) as u32;
+        let width = rng.range_f32(self.max_width * 0.5, self.max_width);
+        let depth = rng.range_f32(self.max_depth * 0.5, self.max_depth);
+        let mut yaw = rng.range_f32(0.0, 2.0 * PI);
+
+        let mut shapes = Vec::with_capacity(length as usize);
+        for step in 0..length {
+            let taper = (step as f32 / length as f32 * PI).sin().max(0.15);
+            shapes.push(CarveShape {
+                center: position,
+                radii: Vec3::new(width * taper, depth * taper, width * taper),
+            });
+
+            position += Vec3::new(yaw.cos(), rng.range_f32(-0.1, 0.1), yaw.sin());
+            yaw += rng.range_f32(-0.08, 0.08);
+        }
+        shapes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::world::{blocks::BLOCK_STONE, chunk::world_to_chunk_local};
+
+    const SEED: u64 = 9;
+
+    fn worms() -> WormCaves {
+        WormCaves {
+            chance: 1.0,
+            ..default()
+        }
+    }
+
+    fn stone() -> Chunk {
+        let mut chunk = Chunk::new();
+        for z in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    chunk.set(x, y, z, Voxel::new(BLOCK_STONE));
+                }
+            }
+        }
+        chunk
+    }
+
+    fn carved(carvers: &[Box<dyn Carver>], coord: IVec3) -> Chunk {
+        let mut chunk = stone();
+        carve_chunk(carvers, SEED, coord, &mut chunk, |_, _| true);
+        chunk
+    }
+
+    fn is_air(chunk: &Chunk, local: IVec3) -> bool {
+        chunk
+            .get(local.x as usize, local.y as usize, local.z as usize)
+            .is_air()
+    }
+
+    /// Only the features of `carver` that start in `origin`.
+    struct OnlyFrom<C>(C, IVec2);
+
+    impl<C: Carver> Carver for OnlyFrom<C> {
+        fn reach(&self) -> i32 {
+            self.0.reach()
+        }
+
+        fn features(&self, seed: u64, origin: IVec2) -> Vec<CarveShape> {
+            if origin == self.1 {
+                self.0.features(seed, origin)
+            } else {
+                Vec::new()
+            }
+        }
+    }
+
+    #[test]
+    fn neighbours_carve_the_same_caves_in_either_order() {
+        let carvers: Vec<Box<dyn Carver>> = vec![Box::new(worms()), Box::new(Ravines::default())];
+        let (west, east) = (IVec3::new(0, 0, 0), IVec3::new(1, 0, 0));
+        let forward = [carved(&carvers, west), carved(&carvers, east)];
+        let [east_first, west_second] = [carved(&carvers, east), carved(&carvers, west)];
+        assert_eq!(forward[0].voxels(), west_second.voxels());
+        assert_eq!(forward[1].voxels(), east_first.voxels());
+
+        // Each side of the shared face is carved where some shape covers it.
+        let in_a_shape = |world: IVec3| {
+            carvers.iter().any(|carver| {
+                let reach = carver.reach();
+                (-reach..=1 + reach).any(|x| {
+                    (-reach..=reach).any(|z| {
+                        carver
+                            .features(SEED, IVec2::new(x, z))
+                            .into_iter()
+                            .any(|shape| {
+                                let offset = (world.as_vec3() + 0.5 - shape.center) / shape.radii;
+                                offset.length_squared() <= 1.0
+                            })
+                    })
+                })
+            })
+        };
+        let mut open = 0;
+        for y in 0..CHUNK_SIZE as i32 {
+            for z in 0..CHUNK_SIZE as i32 {
+                for world in [IVec3::new(31, y, z), IVec3::new(32, y, z)] {
+                    let (coord, local) = world_to_chunk_local(world);
+                    let air = is_air(&forward[coord.x as usize], local);
+                    assert_eq!(air, in_a_shape(world), "{world}");
+                    open += air as u32;
+                }
+            }
+        }
+        assert!(open > 0, "no cave crosses the face");
+    }
+
+    #[test]
+    fn worms_reach_past_their_column() {
+        let carvers: Vec<Box<dyn Carver>> = vec![Box::new(OnlyFrom(worms(), IVec2::ZERO))];
+        let shapes = worms().features(SEED, IVec2::ZERO);
+        let size = CHUNK_SIZE as f32;
+        let outside: Vec<IVec3> = shapes
+            .iter()
+            .map(|shape| shape.center.floor().as_ivec3())
+            .filter(|center| {
+                let column = world_to_chunk_local(*center).0.xz();
+                column != IVec2::ZERO && center.y >= 0 && (center.y as f32) < size
+            })
+            .collect();
+        assert!(!outside.is_empty(), "the worm stays in its column");
+        for center in outside {
+            let (coord, local) = world_to_chunk_local(center);
+            assert!(
+                is_air(&carved(&carvers, coord), local),
+                "{center} isn't carved"
+            );
+        }
+    }
+
+    #[test]
+    fn carves_only_what_it_may() {
+        let carvers: Vec<Box<dyn Carver>> = vec![Box::new(worms())];
+        let mut chunk = stone();
+        carve_chunk(&carvers, SEED, IVec3::ZERO, &mut chunk, |world, _| {
+            world.x < 16
+        });
+        let air: Vec<IVec3> = (0..CHUNK_SIZE as i32)
+            .flat_map(|x| {
+                (0..CHUNK_SIZE as i32)
+                    .flat_map(move |y| (0..CHUNK_SIZE as i32).map(move |z| IVec3::new(x, y, z)))
+            })
+            .filter(|local| is_air(&chunk, *local))
+            .collect();
+        assert!(!air.is_empty());
+        assert!(air.iter().all(|local| local.x < 16));
+    }
+}
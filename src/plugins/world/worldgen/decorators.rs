@@ -0,0 +1,318 @@
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    blocks::{BLOCK_DIRT, BLOCK_GRASS, BLOCK_LEAVES, BLOCK_LOG, BlockId},
+    chunk::{CHUNK_SIZE, Chunk, world_to_chunk_local},
+    noise::FeatureRng,
+    schematic::{Schematic, SchematicTransform},
+    voxel::Voxel,
+    worldgen::biomes::{BiomeGenerator, Decoration},
+};
+
+const TREE_SALT: u64 = 0x7EE5_0A4B;
+
+/// The chunk being decorated, and read access to the chunks around it.
+///
+/// Features are rooted in one chunk but may spill into its neighbours. Decorating a chunk
+/// runs the decorators of every chunk around it, and only writes landing in the decorated
+/// chunk are kept, so no chunk is written after it has been decorated.
+pub struct DecorationRegion<'a> {
+    target: IVec3,
+    chunk: &'a mut Chunk,
+    neighbours: &'a dyn Fn(IVec3) -> Option<&'a Chunk>,
+}
+
+impl<'a> DecorationRegion<'a> {
+    pub fn new(
+        target: IVec3,
+        chunk: &'a mut Chunk,
+        neighbours: &'a dyn Fn(IVec3) -> Option<&'a Chunk>,
+    ) -> Self {
+        Self {
+            target,
+            chunk,
+            neighbours,
+        }
+    }
+
+    /// Coord of the chunk being decorated.
+    pub fn target(&self) -> IVec3 {
+        self.target
+    }
+
+    /// Voxel at `world`, if its chunk is in the region.
+    pub fn get(&self, world: IVec3) -> Option<Voxel> {
+        let (coord, local) = world_to_chunk_local(world);
+        let local = local.as_uvec3();
+        let (x, y, z) = (local.x as usize, local.y as usize, local.z as usize);
+        if coord == self.target {
+            Some(self.chunk.get(x, y, z))
+        } else {
+            (self.neighbours)(coord).map(|chunk| chunk.get(x, y, z))
+        }
+    }
+
+    /// Place `voxel` at `world` if it is air or one of `replaces`. Writes outside the
+    /// decorated chunk are dropped; that chunk places them when it is decorated.
+    ///
+    /// Features only write into air or into blocks they take priority over, so where
+    /// features overlap the result doesn't depend on the order they are placed in.
+    pub fn place(&mut self, world: IVec3, voxel: Voxel, replaces: &[BlockId]) -> bool {
+        let (coord, local) = world_to_chunk_local(world);
+        if coord != self.target {
+            return false;
+        }
+        let local = local.as_uvec3();
+        let (x, y, z) = (local.x as usize, local.y as usize, local.z as usize);
+        let current = self.chunk.get(x, y, z);
+        if !current.is_air() && !replaces.contains(&current.block_id()) {
+            return false;
+        }
+        self.chunk.set(x, y, z, voxel);
+        true
+    }
+
+    /// Stamp `schematic` with its min corner at `origin`. Like [`Self::place`], each voxel
+    /// only goes into air or one of the blocks `replaces` lists for it, and only the part
+    /// inside the decorated chunk is written.
+    pub fn stamp(
+        &mut self,
+        schematic: &Schematic,
+        origin: IVec3,
+        transform: SchematicTransform,
+        replaces: impl Fn(Voxel) -> &'static [BlockId],
+    ) {
+        schematic.stamp_into_chunk(
+            self.chunk,
+            self.target,
+            origin,
+            transform,
+            |current, voxel| current.is_air() || replaces(voxel).contains(&current.block_id()),
+        );
+    }
+}
+
+/// Places features, like trees and structures, on carved terrain.
+///
+/// A feature must be decided from the seed and the carved terrain of its origin chunk
+/// alone, ignoring other features, and may reach at most one chunk from its origin.
+pub trait Decorator: Send + Sync + 'static {
+    fn decorate(&self, terrain: &BiomeGenerator, origin: IVec3, region: &mut DecorationRegion);
+}
+
+/// Trees growing on grass and dirt, wherever the biome has [`Decoration::Trees`].
+#[derive(Copy, Clone, Debug)]
+pub struct Trees {
+    /// Columns of each chunk tried for a tree.
+    pub attempts: u32,
+    pub leaf_radius: i32,
+}
+
+impl Default for Trees {
+    fn default() -> Self {
+        Self {
+            attempts: 8,
+            leaf_radius: 2,
+        }
+    }
+}
+
+impl Trees {
+    /// Whether `voxel` is something a tree can stand on or be blocked by; other trees
+    /// don't count, as they may or may not have been placed yet.
+    fn is_ground(voxel: Voxel) -> bool {
+        !voxel.is_air() && !matches!(voxel.block_id(), BLOCK_LOG | BLOCK_LEAVES)
+    }
+
+    /// A tree with a trunk `height` logs tall, rooted on the ground at the bottom
+    /// center of the schematic. The ground layer itself is left as air.
+    pub fn schematic(&self, height: i32) -> Schematic {
+        let radius = self.leaf_radius;
+        let size = IVec3::new(2 * radius + 1, height + 2, 2 * radius + 1).as_uvec3();
+        Schematic::from_fn(size, |local| {
+            let d = local.as_ivec3() - IVec3::new(radius, 0, radius);
+            if d.x == 0 && d.z == 0 && (1..=height).contains(&d.y) {
+                return Voxel::new(BLOCK_LOG);
+            }
+            if !(height - 2..=height + 1).contains(&d.y) {
+                return Voxel::AIR;
+            }
+            let layer_radius = if d.y < height { radius } else { radius - 1 };
+            let (dx, dz) = (d.x.abs(), d.z.abs());
+            let corner = layer_radius > 1 && dx == layer_radius && dz == layer_radius;
+            if dx <= layer_radius && dz <= layer_radius && !corner {
+                Voxel::new(BLOCK_LEAVES)
+            } else {
+                Voxel::AIR
+            }
+        })
+    }
+
+    /// Trunks grow through leaves of other trees; leaves only fill air.
+    fn replaces(voxel: Voxel) -> &'static [BlockId] {
+        if voxel.block_id() == BLOCK_LOG {
+            &[BLOCK_LEAVES]
+        } else {
+            &[]
+        }
+    }
+}
+
+impl Decorator for Trees {
+    fn decorate(&self, terrain: &BiomeGenerator, origin: IVec3, region: &mut DecorationRegion) {
+        let size = CHUNK_SIZE as i32;
+        let min = origin * size;
+        let mut rng = FeatureRng::for_cell(terrain.seed() ^ TREE_SALT, origin);
+        let samples = terrain.column_samples(origin.xz());
+
+        for _ in 0..self.attempts {
+            let (x, z) = (rng.range(0, size - 1), rng.range(0, size - 1));
+            let roll = rng.next_f32();
+            let height_roll = rng.next_f32();
+
+            let (world_x, world_z) = (min.x + x, min.z + z);
+            let biome = samples.get(x as usize, z as usize).biome;
+            let Some(biome) = terrain.registry().get(biome) else {
+                continue;
+            };
+            let Some((min_height, max_height)) =
+                biome
+                    .decorations
+                    .iter()
+                    .find_map(|decoration| match *decoration {
+                        Decoration::Trees {
+                            chance,
+                            min_height,
+                            max_height,
+                        } if roll < chance => Some((min_height, max_height)),
+                        _ => None,
+                    })
+            else {
+                continue;
+            };
+
+            // The highest ground with open space above it, within the origin chunk. The
+            // space above the top layer is in the chunk above.
+            let ground = (min.y..min.y + size).rev().find_map(|y| {
+                let position = IVec3::new(world_x, y, world_z);
+                let voxel = region.get(position)?;
+                let above = region.get(position + IVec3::Y)?;
+                (Self::is_ground(voxel) && !Self::is_ground(above)).then_some((position, voxel))
+            });
+            let Some((ground, voxel)) = ground else {
+                continue;
+            };
+            if !matches!(voxel.block_id(), BLOCK_GRASS | BLOCK_DIRT) {
+                continue;
+            }
+
+            let height = min_height + (height_roll * (max_height - min_height + 1) as f32) as i32;
+            let tree = self.schematic(height.min(max_height));
+            let radius = self.leaf_radius;
+            region.stamp(
+                &tree,
+                ground - IVec3::new(radius, 0, radius),
+                SchematicTransform::default(),
+                Self::replaces,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::world::{
+        blocks::BLOCK_STONE,
+        worldgen::biomes::{Biome, BiomeRegistry},
+    };
+
+    /// A terrain generator whose only biome grows a 4 log tree in every attempt.
+    fn forest() -> BiomeGenerator {
+        let mut registry = BiomeRegistry::default();
+        registry.insert(Biome {
+            name: "forest",
+            climate: Vec2::splat(0.5),
+            surface: BLOCK_GRASS,
+            subsurface: BLOCK_DIRT,
+            subsurface_depth: 3,
+            base_height: 20.0,
+            height_variation: 0.0,
+            decorations: vec![Decoration::Trees {
+                chance: 1.0,
+                min_height: 4,
+                max_height: 4,
+            }],
+        });
+        BiomeGenerator::new(1, registry)
+    }
+
+    fn logs(chunk: &Chunk) -> usize {
+        chunk
+            .voxels()
+            .iter()
+            .filter(|voxel| voxel.block_id() == BLOCK_LOG)
+            .count()
+    }
+
+    #[test]
+    fn trees_grow_on_the_ground() {
+        let mut chunk = Chunk::new();
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for y in 0..10 {
+                    chunk.set(x, y, z, Voxel::new(BLOCK_STONE));
+                }
+                chunk.set(x, 10, z, Voxel::new(BLOCK_GRASS));
+            }
+        }
+        let neighbours = |_: IVec3| None;
+        let mut region = DecorationRegion::new(IVec3::ZERO, &mut chunk, &neighbours);
+        Trees::default().decorate(&forest(), IVec3::ZERO, &mut region);
+        assert!(logs(&chunk) > 0);
+    }
+
+    #[test]
+    fn trees_grow_on_the_top_layer() {
+        let mut below = Chunk::new();
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                below.set(x, CHUNK_SIZE - 1, z, Voxel::new(BLOCK_GRASS));
+            }
+        }
+        // Trees rooted in the chunk below have their trunks in this one.
+        let mut chunk = Chunk::new();
+        let neighbours = |coord: IVec3| (coord == IVec3::NEG_Y).then_some(&below);
+        let mut region = DecorationRegion::new(IVec3::ZERO, &mut chunk, &neighbours);
+        Trees::default().decorate(&forest(), IVec3::NEG_Y, &mut region);
+        assert!(logs(&chunk) > 0);
+    }
+
+    #[test]
+    fn tree_trunks_grow_through_leaves_but_not_the_ground() {
+        let trees = Trees::default();
+        let tree = trees.schematic(4);
+        assert_eq!(tree.size(), UVec3::new(5, 6, 5));
+        assert_eq!(tree.get(UVec3::new(2, 0, 2)), Voxel::AIR);
+        assert_eq!(tree.get(UVec3::new(2, 4, 2)), Voxel::new(BLOCK_LOG));
+        assert_eq!(tree.get(UVec3::new(2, 5, 2)), Voxel::new(BLOCK_LEAVES));
+
+        // Leaves of another tree above a stone floor.
+        let mut chunk = Chunk::new();
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set(x, 0, z, Voxel::new(BLOCK_STONE));
+                chunk.set(x, 3, z, Voxel::new(BLOCK_LEAVES));
+                chunk.set(x, 5, z, Voxel::new(BLOCK_STONE));
+            }
+        }
+        let neighbours = |_: IVec3| None;
+        let mut region = DecorationRegion::new(IVec3::ZERO, &mut chunk, &neighbours);
+        region.stamp(&tree, IVec3::ZERO, default(), Trees::replaces);
+        assert_eq!(chunk.get(2, 0, 2), Voxel::new(BLOCK_STONE));
+        assert_eq!(chunk.get(2, 3, 2), Voxel::new(BLOCK_LOG));
+        assert_eq!(chunk.get(2, 5, 2), Voxel::new(BLOCK_STONE));
+        assert_eq!(chunk.get(1, 2, 2), Voxel::new(BLOCK_LEAVES));
+        assert_eq!(logs(&chunk), 4);
+    }
+}
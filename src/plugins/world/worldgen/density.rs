@@ -0,0 +1,284 @@
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    blocks::{BLOCK_BEDROCK, BLOCK_STONE, BLOCK_WATER, BlockId},
+    chunk::{CHUNK_SIZE, Chunk},
+    noise::fbm_3d,
+    voxel::Voxel,
+    worldgen::{
+        TerrainGenerator,
+        biomes::{BiomeGenerator, ColumnBiomes, ColumnSample},
+        carvers::{Carver, Ravines, WormCaves, carve_chunk},
+        decorators::{DecorationRegion, Decorator, Trees},
+        ores::{OreRule, place_ores},
+    },
+};
+
+const DENSITY_SALT: u64 = 0x1D3E_57A9;
+
+#[derive(Copy, Clone, Debug)]
+pub struct DensitySettings {
+    /// How far, in voxels, the noise can push the surface from the biome height. This is
+    /// what makes overhangs and arches.
+    pub overhang_amplitude: f32,
+    /// Voxels across one cycle of the density noise.
+    pub noise_scale: f32,
+    /// Underwater columns keep at least this many voxels above a cave, so the sea doesn't
+    /// hang over open air.
+    pub seabed_thickness: i32,
+}
+
+impl Default for DensitySettings {
+    fn default() -> Self {
+        Self {
+            overhang_amplitude: 6.0,
+            noise_scale: 24.0,
+            seabed_thickness: 4,
+        }
+    }
+}
+
+/// Terrain where a voxel is solid when its density is positive: the height below the biome
+/// surface plus 3D noise. Ores replace stone, caves and ravines are carved in the next
+/// stage, and trees grow in the one after.
+pub struct DensityGenerator {
+    terrain: BiomeGenerator,
+    pub settings: DensitySettings,
+    pub carvers: Vec<Box<dyn Carver>>,
+    pub ores: Vec<OreRule>,
+    pub decorators: Vec<Box<dyn Decorator>>,
+}
+
+impl DensityGenerator {
+    /// Worm caves, ravines, trees and the default ore rules over `terrain`.
+    pub fn new(terrain: BiomeGenerator) -> Self {
+        Self {
+            terrain,
+            settings: DensitySettings::default(),
+            carvers: vec![Box::new(WormCaves::default()), Box::new(Ravines::default())],
+            ores: OreRule::default_rules(),
+            decorators: vec![Box::new(Trees::default())],
+        }
+    }
+
+    pub fn terrain(&self) -> &BiomeGenerator {
+        &self.terrain
+    }
+
+    /// Density at `world` for a column whose biome surface is at `height`.
+    pub fn density(&self, world: IVec3, height: i32) -> f32 {
+        let gradient = (height - world.y) as f32;
+        let amplitude = self.settings.overhang_amplitude;
+        if gradient.abs() > amplitude {
+            return gradient;
+        }
+        let point = world.as_vec3() / self.settings.noise_scale;
+        let noise = fbm_3d(self.terrain.seed() ^ DENSITY_SALT, point, 3) * 2.0 - 1.0;
+        gradient + noise * amplitude
+    }
+
+    fn is_solid(&self, world: IVec3, height: i32) -> bool {
+        world.y == 0 || self.density(world, height) > 0.0
+    }
+}
+
+impl TerrainGenerator for DensityGenerator {
+    fn generate_chunk(&self, coord: IVec3) -> Chunk {
+        let mut chunk = Chunk::new();
+        let size = CHUNK_SIZE as i32;
+        let min = coord * size;
+        let sea_level = self.terrain.sea_level;
+        let registry = self.terrain.registry();
+        let max_depth = registry
+            .iter()
+            .map(|(_, biome)| biome.subsurface_depth)
+            .max()
+            .unwrap_or(0);
+        let samples = self.terrain.column_samples(coord.xz());
+
+        for z in 0..size {
+            for x in 0..size {
+                let (world_x, world_z) = (min.x + x, min.z + z);
+                let ColumnSample { biome, height } = samples.get(x as usize, z as usize);
+                let biome = registry.get(biome).expect("sampled biome is registered");
+                let surface = self.terrain.surface_block(biome, world_x, world_z, height);
+
+                // Voxels above the chunk decide how deep below the surface the top ones are.
+                let solid: Vec<bool> = (min.y..min.y + size + max_depth + 1)
+                    .map(|y| self.is_solid(IVec3::new(world_x, y, world_z), height))
+                    .collect();
+
+                for y in 0..size {
+                    let world_y = min.y + y;
+                    let block_id: BlockId = if !solid[y as usize] {
+                        if world_y > sea_level {
+                            continue;
+                        }
+                        BLOCK_WATER
+                    } else if world_y == 0 {
+                        BLOCK_BEDROCK
+                    } else if world_y < 0 {
+                        BLOCK_STONE
+                    } else {
+                        let depth = (1..=max_depth)
+                            .take_while(|above| solid[(y + above) as usize])
+                            .count() as i32;
+                        if depth == 0 {
+                            surface
+                        } else if depth < biome.subsurface_depth {
+                            biome.subsurface
+                        } else {
+                            BLOCK_STONE
+                        }
+                    };
+                    chunk.set(x as usize, y as usize, z as usize, Voxel::new(block_id));
+                }
+            }
+        }
+
+        place_ores(&self.ores, self.terrain.seed(), coord, &mut chunk);
+        chunk
+    }
+
+    fn carve_chunk(&self, coord: IVec3, chunk: &mut Chunk) {
+        let size = CHUNK_SIZE as i32;
+        let min = coord * size;
+        let sea_level = self.terrain.sea_level;
+        let samples = self.terrain.column_samples(coord.xz());
+
+        let seabed = self.settings.seabed_thickness;
+        carve_chunk(
+            &self.carvers,
+            self.terrain.seed(),
+            coord,
+            chunk,
+            |world, voxel| {
+                let local = world - min;
+                let height = samples.get(local.x as usize, local.z as usize).height;
+                let underwater = height <= sea_level && world.y > height - seabed;
+                voxel.block_id() != BLOCK_BEDROCK && voxel.block_id() != BLOCK_WATER && !underwater
+            },
+        );
+    }
+
+    fn decorate(&self, origin: IVec3, region: &mut DecorationRegion) {
+        for decorator in &self.decorators {
+            decorator.decorate(&self.terrain, origin, region);
+        }
+    }
+
+    fn column_biomes(&self, column: IVec2) -> Option<ColumnBiomes> {
+        self.terrain.column_biomes(column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::world::worldgen::biomes::BiomeRegistryRes;
+
+    /// Worm caves in every column, wide and low enough to reach the seabed and bedrock.
+    fn cavernous(seed: u64) -> DensityGenerator {
+        let mut generator =
+            DensityGenerator::new(BiomeGenerator::new(seed, BiomeRegistryRes::default().0));
+        generator.carvers = vec![Box::new(WormCaves {
+            chance: 1.0,
+            min_y: 0,
+            max_y: 16,
+            min_radius: 3.0,
+            max_radius: 4.0,
+            ..default()
+        })];
+        generator
+    }
+
+    #[test]
+    fn carving_spares_bedrock_water_and_the_seabed() {
+        let generator = cavernous(42);
+        let sea_level = generator.terrain().sea_level;
+        let seabed = generator.settings.seabed_thickness;
+        let (mut carved, mut water, mut kept_seabed) = (0, 0, 0);
+        // Around an ocean at (1500, -750).
+        for x in 45..48 {
+            for z in -25..-22 {
+                let coord = IVec3::new(x, 0, z);
+                let generated = generator.generate_chunk(coord);
+                let mut chunk = generated.clone();
+                generator.carve_chunk(coord, &mut chunk);
+                let samples = generator.terrain().column_samples(coord.xz());
+                for lz in 0..CHUNK_SIZE {
+                    for lx in 0..CHUNK_SIZE {
+                        let height = samples.get(lx, lz).height;
+                        assert_eq!(chunk.get(lx, 0, lz), Voxel::new(BLOCK_BEDROCK));
+                        for y in 0..CHUNK_SIZE {
+                            let (before, after) = (generated.get(lx, y, lz), chunk.get(lx, y, lz));
+                            if before.block_id() == BLOCK_WATER {
+                                water += 1;
+                                assert_eq!(after, before);
+                            } else if height <= sea_level && y as i32 > height - seabed {
+                                kept_seabed += !before.is_air() as u32;
+                                assert_eq!(after, before, "seabed at {lx} {y} {lz} of {coord}");
+                            } else if before != after {
+                                carved += 1;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        assert!(
+            carved > 0 && water > 0 && kept_seabed > 0,
+            "{carved} {water} {kept_seabed}"
+        );
+    }
+
+    #[test]
+    fn makes_ores_caves_and_overhangs_the_same_way_for_a_seed() {
+        let generator =
+            DensityGenerator::new(BiomeGenerator::new(42, BiomeRegistryRes::default().0));
+        let ore_blocks: Vec<BlockId> = generator.ores.iter().map(|rule| rule.block).collect();
+        let (mut ores, mut caves, mut overhangs) = (0, 0, 0);
+        for x in -2..2 {
+            for z in -2..2 {
+                let column: Vec<Chunk> = (0..3)
+                    .map(|y| {
+                        let coord = IVec3::new(x, y, z);
+                        let mut chunk = generator.generate_chunk(coord);
+                        generator.carve_chunk(coord, &mut chunk);
+                        chunk
+                    })
+                    .collect();
+                let samples = generator.terrain().column_samples(IVec2::new(x, z));
+                for lz in 0..CHUNK_SIZE {
+                    for lx in 0..CHUNK_SIZE {
+                        let height = samples.get(lx, lz).height;
+                        let mut below_ground = false;
+                        for y in (1..3 * CHUNK_SIZE).rev() {
+                            let voxel = column[y / CHUNK_SIZE].get(lx, y % CHUNK_SIZE, lz);
+                            ores += ore_blocks.contains(&voxel.block_id()) as u32;
+                            if voxel.is_air() && below_ground {
+                                if (y as i32) < height - 8 {
+                                    caves += 1;
+                                } else {
+                                    overhangs += 1;
+                                }
+                            }
+                            below_ground |= !voxel.is_air() && voxel.block_id() != BLOCK_WATER;
+                        }
+                    }
+                }
+            }
+        }
+        assert!(
+            ores > 100 && caves > 100 && overhangs > 10,
+            "{ores} {caves} {overhangs}"
+        );
+
+        let coord = IVec3::new(1, 0, 1);
+        let again = DensityGenerator::new(BiomeGenerator::new(42, BiomeRegistryRes::default().0));
+        assert_eq!(
+            generator.generate_chunk(coord).voxels(),
+            again.generate_chunk(coord).voxels()
+        );
+    }
+}
@@ -0,0 +1,705 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::time::{Duration, Instant};
+
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, IoTaskPool, Task, futures::check_ready};
+
+use crate::plugins::world::{
+    ChunkEntityMap, SpawnChunkCommandExt,
+    blocks::BlockRegistryRes,
+    byte_reader::BinaryError,
+    chunk::{CHUNK_SIZE, Chunk},
+    lighting::LightMap,
+    persistence::{ChunkPersistence, ChunkSave, insert_chunk_save, read_chunk_save, unload_chunk},
+    worldgen::{
+        TerrainGeneratorResource,
+        biomes::{BiomeMap, ColumnBiomes},
+        pipeline::{GenerationPipeline, GenerationStage, ProtoChunk},
+    },
+};
+use crate::state::LoadingState;
+
+pub struct ChunkGenerationPlugin;
+
+impl Plugin for ChunkGenerationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkGenerationSettings>()
+            .init_resource::<ChunkGenerationQueue>()
+            .init_resource::<ChunkGenerationMetrics>()
+            .add_systems(
+                Update,
+                (
+                    request_chunks_near_viewers,
+                    dispatch_generation_jobs,
+                    advance_generated_chunks,
+                )
+                    .chain()
+                    .run_if(in_state(LoadingState::Initialized)),
+            );
+    }
+}
+
+/// Chunks are generated around entities with this component, nearest first.
+#[derive(Component, Copy, Clone, Debug, Default)]
+pub struct ChunkViewer;
+
+#[derive(Resource, Clone, Debug)]
+pub struct ChunkGenerationSettings {
+    /// Horizontal distance, in chunks, around each viewer that is generated.
+    pub view_distance: i32,
+    /// Chunks further than this from every viewer, horizontally in chunks, are saved and
+    /// unloaded. Keep it above `view_distance`, so chunks at the edge of the view don't
+    /// unload and load again as a viewer moves back and forth.
+    pub unload_distance: i32,
+    /// Inclusive range of chunk y coords that are generated.
+    pub min_chunk_y: i32,
+    pub max_chunk_y: i32,
+    /// Most terrain and carving jobs run in parallel.
+    pub max_in_flight: usize,
+    /// Most chunks decorated or lit per update. These stages read the chunks around them,
+    /// so they run on the main thread.
+    pub max_stage_steps: usize,
+}
+
+impl Default for ChunkGenerationSettings {
+    fn default() -> Self {
+        Self {
+            view_distance: 3,
+            unload_distance: 5,
+            min_chunk_y: 0,
+            max_chunk_y: 2,
+            max_in_flight: 8,
+            max_stage_steps: 16,
+        }
+    }
+}
+
+/// Result of a terrain or carving job.
+struct GeneratedStage {
+    chunk: Chunk,
+    stage: GenerationStage,
+    biomes: Option<ColumnBiomes>,
+    duration: Duration,
+}
+
+/// Chunks waiting to be generated, the jobs generating them, and the chunks part way
+/// through the [`GenerationPipeline`].
+///
+/// Chunks in view are generated to [`GenerationStage::Lit`] and spawned. The chunks
+/// around them are generated as far as the stages of the chunks in view need, one stage
+/// less for each chunk further out, and never spawned.
+///
+/// Chunks in view are first looked for on disk, off the main thread. What is found is
+/// remembered while the chunk stays in view: chunks with no save are generated, and
+/// chunks whose save can't be read are left out rather than retried every update.
+#[derive(Resource)]
+pub struct ChunkGenerationQueue {
+    pending: HashSet<IVec3>,
+    in_flight: HashMap<IVec3, Task<GeneratedStage>>,
+    pipeline: GenerationPipeline,
+    targets: HashMap<IVec3, GenerationStage>,
+    loading: HashMap<IVec3, Task<Result<Option<ChunkSave>, BinaryError>>>,
+    /// Chunks in view that have no save.
+    unsaved: HashSet<IVec3>,
+    /// Chunks in view that failed to load, and chunks out of view that failed to unload.
+    failed: HashSet<IVec3>,
+}
+
+impl Default for ChunkGenerationQueue {
+    fn default() -> Self {
+        let settings = ChunkGenerationSettings::default();
+        Self {
+            pending: HashSet::new(),
+            in_flight: HashMap::new(),
+            pipeline: GenerationPipeline::new(settings.min_chunk_y, settings.max_chunk_y),
+            targets: HashMap::new(),
+            loading: HashMap::new(),
+            unsaved: HashSet::new(),
+            failed: HashSet::new(),
+        }
+    }
+}
+
+impl ChunkGenerationQueue {
+    /// Queue the chunk at `coord`. Returns `false` if it is already queued or generating.
+    pub fn request(&mut self, coord: IVec3) -> bool {
+        !self.in_flight.contains_key(&coord)
+            && self.pipeline.get(coord).is_none()
+            && self.pending.insert(coord)
+    }
+
+    /// Drop the chunk at `coord` from the queue, cancelling its job if it has started.
+    pub fn cancel(&mut self, coord: IVec3) -> bool {
+        let pending = self.pending.remove(&coord);
+        let in_flight = self.in_flight.remove(&coord).is_some();
+        let staged = self.pipeline.remove(coord).is_some();
+        pending || in_flight || staged
+    }
+
+    pub fn contains(&self, coord: IVec3) -> bool {
+        self.pending.contains(&coord)
+            || self.in_flight.contains_key(&coord)
+            || self.pipeline.get(coord).is_some()
+    }
+
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn in_flight_len(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Saved chunks being read from disk.
+    pub fn loading_len(&self) -> usize {
+        self.loading.len()
+    }
+
+    /// Whether loading or unloading the chunk at `coord` failed, and won't be retried
+    /// until it enters or leaves the view.
+    pub fn has_failed(&self, coord: IVec3) -> bool {
+        self.failed.contains(&coord)
+    }
+
+    pub fn pipeline(&self) -> &GenerationPipeline {
+        &self.pipeline
+    }
+
+    /// Stage the chunk at `coord` is being generated to, if it is wanted at all.
+    pub fn target(&self, coord: IVec3) -> Option<GenerationStage> {
+        self.targets.get(&coord).copied()
+    }
+
+    /// Whether the chunk at `coord` still has stages to go through.
+    fn wants_advance(&self, coord: IVec3) -> bool {
+        let stage = self.pipeline.stage(coord);
+        self.target(coord)
+            .is_some_and(|target| stage.is_some_and(|stage| stage < target))
+    }
+}
+
+#[derive(Resource, Clone, Debug, Default)]
+pub struct ChunkGenerationMetrics {
+    /// Chunks waiting for a terrain job.
+    pub queued: usize,
+    /// Jobs running.
+    pub in_flight: usize,
+    /// Chunks part way through generation.
+    pub staged: usize,
+    /// Chunks generated to the last stage.
+    pub completed: u64,
+    pub cancelled: u64,
+    /// Terrain and carving jobs finished, including those of chunks that are only
+    /// generated as neighbours and never completed.
+    pub jobs: u64,
+    /// Time the last terrain or carving job took.
+    pub last_duration: Duration,
+    /// Time spent in all finished jobs.
+    pub total_duration: Duration,
+}
+
+impl ChunkGenerationMetrics {
+    /// Average time a terrain or carving job took.
+    pub fn average_duration(&self) -> Duration {
+        if self.jobs == 0 {
+            return Duration::ZERO;
+        }
+        self.total_duration.div_f64(self.jobs as f64)
+    }
+}
+
+/// Viewer positions in chunk coords.
+fn viewer_chunks(viewers: &Query<&GlobalTransform, With<ChunkViewer>>) -> Vec<IVec3> {
+    viewers
+        .iter()
+        .map(|transform| {
+            (transform.translation() / CHUNK_SIZE as f32)
+                .floor()
+                .as_ivec3()
+        })
+        .collect()
+}
+
+fn distance_to_viewers(coord: IVec3, viewers: &[IVec3]) -> i32 {
+    viewers
+        .iter()
+        .map(|viewer| (coord - viewer).length_squared())
+        .min()
+        .unwrap_or(i32::MAX)
+}
+
+/// Set the stage each chunk near a viewer is generated to, queue missing chunks, load
+/// saved ones in view, unload those far from every viewer, and cancel those no longer
+/// needed.
+///
+/// Only chunks in view that are neither loaded nor saved are generated, along with the
+/// chunks around them their stages need.
+#[allow(clippy::too_many_arguments)]
+fn request_chunks_near_viewers(
+    mut commands: Commands,
+    settings: Res<ChunkGenerationSettings>,
+    persistence: Res<ChunkPersistence>,
+    chunk_map: Res<ChunkEntityMap>,
+    viewers: Query<&GlobalTransform, With<ChunkViewer>>,
+    mut queue: ResMut<ChunkGenerationQueue>,
+    mut metrics: ResMut<ChunkGenerationMetrics>,
+) {
+    let viewers = viewer_chunks(&viewers);
+    let distance = settings.view_distance;
+    let mut in_view = HashSet::new();
+    for viewer in &viewers {
+        for z in viewer.z - distance..=viewer.z + distance {
+            for x in viewer.x - distance..=viewer.x + distance {
+                for y in settings.min_chunk_y..=settings.max_chunk_y {
+                    in_view.insert(IVec3::new(x, y, z));
+                }
+            }
+        }
+    }
+
+    // Saves may have been written since a chunk was last in view or loaded, so forget
+    // about it then. Dropping a read cancels it.
+    let loaded = |coord: &IVec3| chunk_map.get(coord).is_some();
+    queue
+        .unsaved
+        .retain(|coord| in_view.contains(coord) && !loaded(coord));
+    queue
+        .failed
+        .retain(|coord| in_view.contains(coord) != loaded(coord));
+    queue.loading.retain(|coord, _| in_view.contains(coord));
+
+    let rings = GenerationStage::Lit as i32;
+    let mut targets: HashMap<IVec3, GenerationStage> = HashMap::new();
+    for coord in &in_view {
+        let coord = *coord;
+        if loaded(&coord) || queue.failed.contains(&coord) || queue.loading.contains_key(&coord) {
+            continue;
+        }
+        if !queue.unsaved.contains(&coord) {
+            let path = persistence.path(coord);
+            let read = IoTaskPool::get().spawn(async move { read_chunk_save(&path) });
+            queue.loading.insert(coord, read);
+            continue;
+        }
+        for z in -rings..=rings {
+            for x in -rings..=rings {
+                for y in settings.min_chunk_y..=settings.max_chunk_y {
+                    let ring = x.abs().max(z.abs()).max((y - coord.y).abs());
+                    let Some(stage) = GenerationStage::Lit.back(ring as u32) else {
+                        continue;
+                    };
+                    let target = IVec3::new(coord.x + x, y, coord.z + z);
+                    let entry = targets.entry(target).or_insert(stage);
+                    *entry = (*entry).max(stage);
+                }
+            }
+        }
+    }
+
+    let unneeded: Vec<IVec3> = queue
+        .pending
+        .iter()
+        .chain(queue.in_flight.keys())
+        .chain(queue.pipeline.coords().collect::<Vec<_>>().iter())
+        .copied()
+        .filter(|coord| !targets.contains_key(coord))
+        .collect();
+    for coord in unneeded {
+        // Staged chunks that are no longer needed are just dropped, not cancelled.
+        let generating = queue.pending.contains(&coord) || queue.in_flight.contains_key(&coord);
+        queue.cancel(coord);
+        if generating {
+            metrics.cancelled += 1;
+        }
+    }
+
+    for coord in targets.keys() {
+        if !queue.contains(*coord) {
+            queue.request(*coord);
+        }
+    }
+    queue.targets = targets;
+    queue.pipeline.min_chunk_y = settings.min_chunk_y;
+    queue.pipeline.max_chunk_y = settings.max_chunk_y;
+
+    // Without viewers there is nothing to measure the distance from.
+    if !viewers.is_empty() {
+        for coord in chunk_map.coords() {
+            let far = viewers
+                .iter()
+                .all(|viewer| (coord - viewer).xz().abs().max_element() > settings.unload_distance);
+            if far && !queue.failed.contains(&coord) {
+                commands.queue(move |world: &mut World| {
+                    if let Err(err) = unload_chunk(world, coord) {
+                        error!("Failed to unload chunk {coord}: {err}");
+                        world
+                            .resource_mut::<ChunkGenerationQueue>()
+                            .failed
+                            .insert(coord);
+                    }
+                });
+            }
+        }
+    }
+
+    // Spawned once the commands are applied, so the loop above won't read them again.
+    let mut read = Vec::new();
+    for (coord, task) in queue.loading.iter_mut() {
+        if let Some(result) = check_ready(task) {
+            read.push((*coord, result));
+        }
+    }
+    for (coord, result) in read {
+        queue.loading.remove(&coord);
+        match result {
+            Ok(Some(save)) => commands.queue(move |world: &mut World| {
+                // The biomes of its column were dropped if the whole column was unloaded.
+                let column = coord.xz();
+                if !world.resource::<BiomeMap>().contains(column)
+                    && let Some(biomes) = world
+                        .resource::<TerrainGeneratorResource>()
+                        .0
+                        .column_biomes(column)
+                {
+                    world.resource_mut::<BiomeMap>().insert(column, biomes);
+                }
+                insert_chunk_save(world, coord, save);
+            }),
+            Ok(None) => {
+                queue.unsaved.insert(coord);
+            }
+            Err(err) => {
+                error!("Failed to load chunk {coord}: {err}");
+                queue.failed.insert(coord);
+            }
+        }
+    }
+}
+
+/// Start terrain jobs for pending chunks, and carving jobs for chunks whose neighbours
+/// have their terrain, nearest to a viewer first.
+fn dispatch_generation_jobs(
+    settings: Res<ChunkGenerationSettings>,
+    generator: Res<TerrainGeneratorResource>,
+    biome_map: Res<BiomeMap>,
+    viewers: Query<&GlobalTransform, With<ChunkViewer>>,
+    mut queue: ResMut<ChunkGenerationQueue>,
+    mut metrics: ResMut<ChunkGenerationMetrics>,
+) {
+    let free = settings.max_in_flight.saturating_sub(queue.in_flight.len());
+    if free > 0 {
+        let viewers = viewer_chunks(&viewers);
+        let carvable = queue.pipeline.coords().filter(|coord| {
+            queue.pipeline.stage(*coord) == Some(GenerationStage::Terrain)
+                && queue.wants_advance(*coord)
+                && queue.pipeline.can_advance(*coord)
+        });
+        let mut nearest: BinaryHeap<_> = queue
+            .pending
+            .iter()
+            .copied()
+            .chain(carvable)
+            .map(|coord| {
+                let distance = distance_to_viewers(coord, &viewers);
+                Reverse((distance, coord.x, coord.y, coord.z))
+            })
+            .collect();
+
+        let pool = AsyncComputeTaskPool::get();
+        for _ in 0..free {
+            let Some(Reverse((_, x, y, z))) = nearest.pop() else {
+                break;
+            };
+            let coord = IVec3::new(x, y, z);
+            let generator = generator.0.clone();
+
+            let task = if queue.pending.remove(&coord) {
+                let needs_biomes = !biome_map.contains(coord.xz());
+                pool.spawn(async move {
+                    let start = Instant::now();
+                    let chunk = generator.generate_chunk(coord);
+                    let biomes = needs_biomes
+                        .then(|| generator.column_biomes(coord.xz()))
+                        .flatten();
+                    GeneratedStage {
+                        chunk,
+                        stage: GenerationStage::Terrain,
+                        biomes,
+                        duration: start.elapsed(),
+                    }
+                })
+            } else {
+                let Some(mut proto) = queue.pipeline.remove(coord) else {
+                    continue;
+                };
+                pool.spawn(async move {
+                    let start = Instant::now();
+                    generator.carve_chunk(coord, &mut proto.chunk);
+                    GeneratedStage {
+                        chunk: proto.chunk,
+                        stage: GenerationStage::Carved,
+                        biomes: None,
+                        duration: start.elapsed(),
+                    }
+                })
+            };
+            queue.in_flight.insert(coord, task);
+        }
+    }
+
+    metrics.queued = queue.pending.len();
+    metrics.in_flight = queue.in_flight.len();
+}
+
+/// Collect finished jobs, decorate and light chunks whose neighbours are ready, and spawn
+/// the chunks that reach the last stage.
+#[allow(clippy::too_many_arguments)]
+fn advance_generated_chunks(
+    mut commands: Commands,
+    settings: Res<ChunkGenerationSettings>,
+    generator: Res<TerrainGeneratorResource>,
+    registry: Res<BlockRegistryRes>,
+    chunk_map: Res<ChunkEntityMap>,
+    viewers: Query<&GlobalTransform, With<ChunkViewer>>,
+    mut queue: ResMut<ChunkGenerationQueue>,
+    mut biome_map: ResMut<BiomeMap>,
+    mut light_map: ResMut<LightMap>,
+    mut metrics: ResMut<ChunkGenerationMetrics>,
+) {
+    let mut finished = Vec::new();
+    for (coord, task) in queue.in_flight.iter_mut() {
+        if let Some(generated) = check_ready(task) {
+            finished.push((*coord, generated));
+        }
+    }
+
+    for (coord, generated) in finished {
+        queue.in_flight.remove(&coord);
+        metrics.jobs += 1;
+        metrics.last_duration = generated.duration;
+        metrics.total_duration += generated.duration;
+
+        if let Some(biomes) = generated.biomes
+            && !biome_map.contains(coord.xz())
+        {
+            biome_map.insert(coord.xz(), biomes);
+        }
+        let mut proto = ProtoChunk::new(generated.chunk);
+        proto.stage = generated.stage;
+        queue.pipeline.insert(coord, proto);
+    }
+
+    let viewers = viewer_chunks(&viewers);
+    let mut ready: Vec<IVec3> = queue
+        .pipeline
+        .coords()
+        .filter(|coord| {
+            queue.pipeline.stage(*coord) >= Some(GenerationStage::Carved)
+                && queue.wants_advance(*coord)
+                && queue.pipeline.can_advance(*coord)
+        })
+        .collect();
+    ready.sort_by_key(|coord| {
+        (
+            distance_to_viewers(*coord, &viewers),
+            coord.x,
+            coord.y,
+            coord.z,
+        )
+    });
+
+    for coord in ready.into_iter().take(settings.max_stage_steps) {
+        let stage = queue.pipeline.advance(coord, &*generator.0, &registry.0);
+        if stage != Some(GenerationStage::Lit) {
+            continue;
+        }
+        metrics.completed += 1;
+
+        // Loaded some other way while it was generating.
+        if chunk_map.get(&coord).is_some() {
+            continue;
+        }
+        let Some(proto) = queue.pipeline.get(coord) else {
+            continue;
+        };
+        if let Some(light) = proto.light.clone() {
+            light_map.insert(coord, light);
+        }
+        commands.spawn_chunk(proto.chunk.clone(), coord);
+    }
+
+    metrics.in_flight = queue.in_flight.len();
+    metrics.staged = queue.pipeline.len();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::plugins::world::{
+        Chunks,
+        block_entity::{BlockEntities, BlockEntityTypes},
+        blocks::BLOCK_STONE,
+        test_support::{headless_app, update_until},
+        voxel::Voxel,
+        worldgen::{FlatGenerator, biomes::BiomeMap},
+    };
+
+    /// One layer of flat chunks streamed around a viewer at chunk (1, 0, 1), saved to a
+    /// fresh directory named after `name`.
+    fn jobs_app(name: &str) -> (App, Entity, PathBuf) {
+        let directory = std::env::temp_dir().join(format!("aettesaga-jobs-{name}"));
+        let _ = std::fs::remove_dir_all(&directory);
+
+        let mut app = headless_app(0);
+        app.init_resource::<BlockEntities>()
+            .init_resource::<BlockEntityTypes>()
+            .init_resource::<BiomeMap>()
+            .init_resource::<LightMap>()
+            .insert_resource(ChunkPersistence {
+                directory: directory.clone(),
+            })
+            .insert_resource(TerrainGeneratorResource(Arc::new(FlatGenerator {
+                ground_height: 8,
+            })))
+            .add_plugins(ChunkGenerationPlugin)
+            .insert_resource(ChunkGenerationSettings {
+                view_distance: 1,
+                unload_distance: 2,
+                min_chunk_y: 0,
+                max_chunk_y: 0,
+                max_in_flight: 1,
+                max_stage_steps: 16,
+            });
+        let viewer = app
+            .world_mut()
+            .spawn((ChunkViewer, GlobalTransform::from_xyz(40.0, 5.0, 40.0)))
+            .id();
+        (app, viewer, directory)
+    }
+
+    fn loaded(world: &World) -> usize {
+        world.resource::<ChunkEntityMap>().coords().count()
+    }
+
+    fn move_viewer(app: &mut App, viewer: Entity, x: f32) {
+        app.world_mut()
+            .entity_mut(viewer)
+            .insert(GlobalTransform::from_xyz(x, 5.0, 40.0));
+    }
+
+    #[test]
+    fn generates_nearest_first() {
+        let (mut app, _, _) = jobs_app("nearest");
+        let mut first = None;
+        assert!(update_until(&mut app, |world| {
+            first = first.or(world.resource::<ChunkEntityMap>().coords().next());
+            loaded(world) == 9
+        }));
+        assert_eq!(first, Some(IVec3::new(1, 0, 1)));
+        let metrics = app.world().resource::<ChunkGenerationMetrics>();
+        assert_eq!(metrics.completed, 9);
+        // Each had a terrain and a carving job, as did some of their neighbours.
+        assert!(metrics.jobs >= 2 * 9);
+        assert!(metrics.average_duration() <= metrics.total_duration / 18);
+    }
+
+    #[test]
+    fn unloads_far_chunks_and_loads_them_back() {
+        let (mut app, viewer, directory) = jobs_app("unload");
+        assert!(update_until(&mut app, |world| loaded(world) == 9));
+        let centre = app
+            .world()
+            .resource::<ChunkEntityMap>()
+            .get(&IVec3::new(1, 0, 1))
+            .unwrap();
+        let mut chunks = app.world_mut().resource_mut::<Chunks>();
+        chunks
+            .0
+            .get_mut(&centre)
+            .unwrap()
+            .set(0, 20, 0, Voxel::new(BLOCK_STONE));
+
+        assert!(
+            app.world()
+                .resource::<LightMap>()
+                .contains(IVec3::new(0, 0, 1))
+        );
+        app.world_mut()
+            .resource_mut::<BiomeMap>()
+            .insert(IVec2::new(0, 1), ColumnBiomes::from_fn(|_, _| 0));
+
+        // Three chunks over, the nearest old chunk is within the unload distance.
+        move_viewer(&mut app, viewer, 40.0 + 3.0 * CHUNK_SIZE as f32);
+        assert!(update_until(&mut app, |world| {
+            let chunk_map = world.resource::<ChunkEntityMap>();
+            chunk_map.get(&IVec3::new(0, 0, 1)).is_none()
+                && chunk_map.get(&IVec3::new(2, 0, 1)).is_some()
+        }));
+        assert!(directory.join("0_0_1.chunk").exists());
+        // Its light and the biomes of its now empty column go with it.
+        assert!(
+            !app.world()
+                .resource::<LightMap>()
+                .contains(IVec3::new(0, 0, 1))
+        );
+        assert!(
+            !app.world()
+                .resource::<BiomeMap>()
+                .contains(IVec2::new(0, 1))
+        );
+        assert!(directory.join("1_0_1.chunk").exists());
+        assert!(!directory.join("2_0_1.chunk").exists());
+
+        move_viewer(&mut app, viewer, 40.0);
+        assert!(update_until(&mut app, |world| {
+            world
+                .resource::<ChunkEntityMap>()
+                .get(&IVec3::new(0, 0, 1))
+                .is_some()
+        }));
+        let world = app.world();
+        let centre = world
+            .resource::<ChunkEntityMap>()
+            .get(&IVec3::new(1, 0, 1))
+            .unwrap();
+        let chunk = &world.resource::<Chunks>().0[&centre];
+        assert_eq!(chunk.get(0, 20, 0).block_id(), BLOCK_STONE);
+        assert_eq!(world.resource::<ChunkGenerationQueue>().loading_len(), 0);
+    }
+
+    #[test]
+    fn unreadable_saves_are_not_retried() {
+        let (mut app, _, directory) = jobs_app("unreadable");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("1_0_1.chunk"), b"not a chunk").unwrap();
+
+        assert!(update_until(&mut app, |world| loaded(world) == 8));
+        for _ in 0..20 {
+            app.update();
+        }
+        let queue = app.world().resource::<ChunkGenerationQueue>();
+        assert!(queue.has_failed(IVec3::new(1, 0, 1)));
+        assert_eq!(queue.loading_len(), 0);
+        assert_eq!(loaded(app.world()), 8);
+    }
+
+    #[test]
+    fn average_duration_counts_every_job() {
+        let metrics = ChunkGenerationMetrics {
+            jobs: 1 << 33,
+            completed: 1,
+            total_duration: Duration::from_secs(1 << 34),
+            ..default()
+        };
+        assert_eq!(metrics.average_duration(), Duration::from_secs(2));
+        assert_eq!(
+            ChunkGenerationMetrics::default().average_duration(),
+            Duration::ZERO
+        );
+    }
+}
@@ -0,0 +1,185 @@
+pub mod biomes;
+pub mod carvers;
+pub mod decorators;
+pub mod density;
+pub mod jobs;
+pub mod ores;
+pub mod pipeline;
+
+use std::sync::Arc;
+
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    SpawnChunkCommandExt,
+    blocks::{BLOCK_BEDROCK, BLOCK_DIRT, BLOCK_GRASS, BlockRegistryRes},
+    chunk::{CHUNK_SIZE, Chunk},
+    config::{WorldConfig, WorldPreset},
+    lighting::LightMap,
+    voxel::Voxel,
+};
+use biomes::{Biome, BiomeGenerator, BiomeMap, BiomeRegistry, BiomeRegistryRes, ColumnBiomes};
+use decorators::DecorationRegion;
+use density::DensityGenerator;
+use jobs::{ChunkGenerationPlugin, ChunkGenerationSettings};
+use pipeline::GenerationPipeline;
+
+/// Seed of worlds created without one.
+pub const DEFAULT_WORLD_SEED: u64 = 0x5EED_CAFE;
+
+/// Generates the world described by the [`WorldConfig`] resource, which is read once when
+/// the plugin is built.
+pub struct WorldGenPlugin;
+
+impl Plugin for WorldGenPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldConfig>()
+            .init_resource::<BiomeRegistryRes>()
+            .init_resource::<BiomeMap>()
+            .add_plugins(ChunkGenerationPlugin);
+
+        let config = app.world().resource::<WorldConfig>().clone();
+        let registry = &app.world().resource::<BiomeRegistryRes>().0;
+        let generator = preset_generator(config.preset, config.seed, registry);
+        app.insert_resource(TerrainGeneratorResource(generator))
+            .insert_resource(ChunkGenerationSettings {
+                view_distance: config.view_distance,
+                unload_distance: config.view_distance + 2,
+                min_chunk_y: config.min_chunk_y,
+                max_chunk_y: config.max_chunk_y,
+                ..default()
+            });
+    }
+}
+
+/// The generator of worlds made with `preset`.
+pub fn preset_generator(
+    preset: WorldPreset,
+    seed: u64,
+    biomes: &BiomeRegistry,
+) -> Arc<dyn TerrainGenerator> {
+    match preset {
+        WorldPreset::Flat => Arc::new(FlatGenerator { ground_height: 8 }),
+        WorldPreset::Default => Arc::new(DensityGenerator::new(BiomeGenerator::new(
+            seed,
+            biomes.clone(),
+        ))),
+        WorldPreset::Amplified => {
+            let mut amplified = BiomeRegistry::default();
+            for (_, biome) in biomes.iter() {
+                amplified.insert(Biome {
+                    base_height: biome.base_height * 1.5,
+                    height_variation: biome.height_variation * 2.5,
+                    ..biome.clone()
+                });
+            }
+            let mut generator = DensityGenerator::new(BiomeGenerator::new(seed, amplified));
+            generator.settings.overhang_amplitude *= 2.0;
+            Arc::new(generator)
+        }
+        WorldPreset::Void => Arc::new(VoidGenerator),
+    }
+}
+
+/// Produces the voxels of a chunk from nothing but its coordinate, so the same
+/// generator and seed always produce the same world.
+///
+/// Chunks are generated in stages, see [`GenerationStage`](pipeline::GenerationStage).
+/// Generators without caves or features only need [`TerrainGenerator::generate_chunk`].
+pub trait TerrainGenerator: Send + Sync + 'static {
+    /// Base terrain of the chunk at `coord`.
+    fn generate_chunk(&self, coord: IVec3) -> Chunk;
+
+    /// Remove caves and the like from the terrain of the chunk at `coord`.
+    fn carve_chunk(&self, _coord: IVec3, _chunk: &mut Chunk) {}
+
+    /// Place the features rooted in the chunk at `origin` into `region`, which may be
+    /// `origin` or one of the chunks around it.
+    fn decorate(&self, _origin: IVec3, _region: &mut DecorationRegion) {}
+
+    /// Biomes of the chunk column at `column` (chunk x, z), for generators that have them.
+    fn column_biomes(&self, _column: IVec2) -> Option<ColumnBiomes> {
+        None
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct TerrainGeneratorResource(pub Arc<dyn TerrainGenerator>);
+
+/// A flat slab: bedrock at y = 0, dirt, and grass on top at `ground_height - 1`.
+#[derive(Copy, Clone, Debug)]
+pub struct FlatGenerator {
+    pub ground_height: i32,
+}
+
+impl TerrainGenerator for FlatGenerator {
+    fn generate_chunk(&self, coord: IVec3) -> Chunk {
+        let mut chunk = Chunk::new();
+        let min_y = coord.y * CHUNK_SIZE as i32;
+        for y in 0..CHUNK_SIZE {
+            let world_y = min_y + y as i32;
+            let block_id = match world_y {
+                0 => BLOCK_BEDROCK,
+                y if y < 0 || y >= self.ground_height => continue,
+                y if y == self.ground_height - 1 => BLOCK_GRASS,
+                _ => BLOCK_DIRT,
+            };
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    chunk.set(x, y, z, Voxel::new(block_id));
+                }
+            }
+        }
+        chunk
+    }
+}
+
+/// Nothing but air.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct VoidGenerator;
+
+impl TerrainGenerator for VoidGenerator {
+    fn generate_chunk(&self, _coord: IVec3) -> Chunk {
+        Chunk::new()
+    }
+}
+
+pub trait GenerateChunkCommandExt {
+    /// Generate the chunk at `coord` with the current [`TerrainGeneratorResource`] and spawn it.
+    ///
+    /// This runs every stage on the main thread when the command is applied, so the
+    /// terrain of the 7×7 chunk columns around it is generated too and then discarded.
+    /// That takes as long as generating a few hundred chunks; chunks around viewers are
+    /// streamed in by the [`ChunkGenerationQueue`](jobs::ChunkGenerationQueue) instead.
+    fn generate_chunk(&mut self, coord: IVec3);
+}
+
+impl<'w, 's> GenerateChunkCommandExt for Commands<'w, 's> {
+    fn generate_chunk(&mut self, coord: IVec3) {
+        self.queue(move |world: &mut World| {
+            let generator = world.resource::<TerrainGeneratorResource>().0.clone();
+            let settings = world.resource::<ChunkGenerationSettings>();
+            let mut pipeline = GenerationPipeline::new(settings.min_chunk_y, settings.max_chunk_y);
+            pipeline.generate(
+                &[coord],
+                &*generator,
+                &world.resource::<BlockRegistryRes>().0,
+            );
+            let Some(proto) = pipeline.remove(coord) else {
+                return;
+            };
+
+            let column = coord.xz();
+            if !world.resource::<BiomeMap>().contains(column)
+                && let Some(biomes) = generator.column_biomes(column)
+            {
+                world.resource_mut::<BiomeMap>().insert(column, biomes);
+            }
+            if let Some(light) = proto.light {
+                world.resource_mut::<LightMap>().insert(coord, light);
+            }
+
+            world.commands().spawn_chunk(proto.chunk, coord);
+        })
+    }
+}
@@ -0,0 +1,174 @@
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    blocks::{BLOCK_COAL_ORE, BLOCK_IRON_ORE, BLOCK_STONE, BlockId},
+    chunk::{CHUNK_SIZE, Chunk},
+    noise::FeatureRng,
+    voxel::Voxel,
+};
+
+const ORE_SALT: u64 = 0x0DE5_EED5;
+
+/// Where and how often veins of one ore are generated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OreRule {
+    pub block: BlockId,
+    /// Only voxels of this block are turned into ore.
+    pub replaces: BlockId,
+    /// Inclusive world height range veins start in.
+    pub min_y: i32,
+    pub max_y: i32,
+    pub veins_per_chunk: u32,
+    /// Voxels visited by each vein's random walk.
+    pub vein_size: u32,
+}
+
+impl OreRule {
+    pub fn default_rules() -> Vec<OreRule> {
+        vec![
+            OreRule {
+                block: BLOCK_COAL_ORE,
+                replaces: BLOCK_STONE,
+                min_y: 1,
+                max_y: 64,
+                veins_per_chunk: 8,
+                vein_size: 12,
+            },
+            OreRule {
+                block: BLOCK_IRON_ORE,
+                replaces: BLOCK_STONE,
+                min_y: 1,
+                max_y: 40,
+                veins_per_chunk: 4,
+                vein_size: 7,
+            },
+        ]
+    }
+}
+
+/// Grow the veins of every rule inside the chunk at `coord`. Veins start within the chunk
+/// and are clipped at its faces: the parts of the walk outside it place nothing.
+pub fn place_ores(rules: &[OreRule], seed: u64, coord: IVec3, chunk: &mut Chunk) {
+    let size = CHUNK_SIZE as i32;
+    let min_y = coord.y * size;
+
+    for (index, rule) in rules.iter().enumerate() {
+        let (from, to) = (rule.min_y.max(min_y), rule.max_y.min(min_y + size - 1));
+        if from > to {
+            continue;
+        }
+
+        let mut rng = FeatureRng::for_cell(seed ^ ORE_SALT.wrapping_add(index as u64), coord);
+        for _ in 0..rule.veins_per_chunk {
+            let mut local = IVec3::new(
+                rng.range(0, size - 1),
+                rng.range(from, to) - min_y,
+                rng.range(0, size - 1),
+            );
+            for _ in 0..rule.vein_size {
+                if local.cmpge(IVec3::ZERO).all() && local.cmplt(IVec3::splat(size)).all() {
+                    let (x, y, z) = (local.x as usize, local.y as usize, local.z as usize);
+                    if chunk.get(x, y, z).block_id() == rule.replaces {
+                        chunk.set(x, y, z, Voxel::new(rule.block));
+                    }
+                }
+
+                let axis = rng.below(3) as usize;
+                let step = if rng.below(2) == 0 { -1 } else { 1 };
+                local[axis] += step;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::world::blocks::BLOCK_DIRT;
+
+    fn stone() -> Chunk {
+        let mut chunk = Chunk::new();
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    chunk.set(x, y, z, Voxel::new(BLOCK_STONE));
+                }
+            }
+        }
+        chunk
+    }
+
+    fn rule(veins_per_chunk: u32, vein_size: u32) -> OreRule {
+        OreRule {
+            block: BLOCK_COAL_ORE,
+            replaces: BLOCK_STONE,
+            min_y: 0,
+            max_y: 31,
+            veins_per_chunk,
+            vein_size,
+        }
+    }
+
+    /// Ore voxels in each x layer of the chunk.
+    fn ore_per_layer(chunk: &Chunk) -> Vec<usize> {
+        (0..CHUNK_SIZE)
+            .map(|x| {
+                (0..CHUNK_SIZE * CHUNK_SIZE)
+                    .filter(|i| {
+                        chunk.get(x, i % CHUNK_SIZE, i / CHUNK_SIZE).block_id() == BLOCK_COAL_ORE
+                    })
+                    .count()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn veins_do_not_pile_up_on_chunk_faces() {
+        let mut chunk = stone();
+        place_ores(&[rule(200, 200)], 3, IVec3::ZERO, &mut chunk);
+        let layers = ore_per_layer(&chunk);
+        let inner = layers[8..24].iter().sum::<usize>() as f32 / 16.0;
+        for face in [layers[0], layers[CHUNK_SIZE - 1]] {
+            assert!(
+                (face as f32) < inner,
+                "face {face} vs inner {inner}: {layers:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn replaces_only_its_block_and_height_range() {
+        let mut chunk = stone();
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set(x, 5, z, Voxel::new(BLOCK_DIRT));
+            }
+        }
+        let rule = OreRule {
+            min_y: 40,
+            max_y: 50,
+            ..rule(50, 20)
+        };
+        let mut low = chunk.clone();
+        place_ores(std::slice::from_ref(&rule), 3, IVec3::ZERO, &mut low);
+        assert_eq!(low.voxels(), chunk.voxels());
+
+        let mut high = chunk;
+        place_ores(&[rule], 3, IVec3::Y, &mut high);
+        assert!((0..CHUNK_SIZE).all(|x| high.get(x, 5, 0).block_id() == BLOCK_DIRT));
+        assert!(ore_per_layer(&high).iter().sum::<usize>() > 0);
+    }
+
+    #[test]
+    fn veins_depend_on_seed_and_chunk_only() {
+        let place = |seed, coord| {
+            let mut chunk = stone();
+            place_ores(&OreRule::default_rules(), seed, coord, &mut chunk);
+            chunk
+        };
+        let chunk = place(3, IVec3::ZERO);
+        assert_eq!(chunk.voxels(), place(3, IVec3::ZERO).voxels());
+        assert_ne!(chunk.voxels(), place(4, IVec3::ZERO).voxels());
+        assert_ne!(chunk.voxels(), place(3, IVec3::X).voxels());
+    }
+}
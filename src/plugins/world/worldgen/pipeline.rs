@@ -0,0 +1,310 @@
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+
+use crate::plugins::world::{
+    blocks::BlockRegistry,
+    chunk::Chunk,
+    lighting::{ChunkLight, SkySource, compute_sky_light},
+    worldgen::{TerrainGenerator, decorators::DecorationRegion},
+};
+
+/// How far a chunk has come through generation. Each stage builds on the previous one.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum GenerationStage {
+    /// Base terrain, fluids and ores.
+    Terrain,
+    /// Caves and ravines removed.
+    Carved,
+    /// Trees and structures placed, including those rooted in neighbouring chunks.
+    Decorated,
+    /// Sky light computed; the chunk is finished.
+    Lit,
+}
+
+impl GenerationStage {
+    pub fn next(self) -> Option<Self> {
+        match self {
+            Self::Terrain => Some(Self::Carved),
+            Self::Carved => Some(Self::Decorated),
+            Self::Decorated => Some(Self::Lit),
+            Self::Lit => None,
+        }
+    }
+
+    pub fn previous(self) -> Option<Self> {
+        match self {
+            Self::Terrain => None,
+            Self::Carved => Some(Self::Terrain),
+            Self::Decorated => Some(Self::Carved),
+            Self::Lit => Some(Self::Decorated),
+        }
+    }
+
+    /// The stage `steps` before this one, if there is one.
+    pub fn back(self, steps: u32) -> Option<Self> {
+        (0..steps).try_fold(self, |stage, _| stage.previous())
+    }
+}
+
+/// A chunk still being generated.
+#[derive(Clone, Debug)]
+pub struct ProtoChunk {
+    pub chunk: Chunk,
+    pub stage: GenerationStage,
+    /// Set once the chunk is [`GenerationStage::Lit`].
+    pub light: Option<ChunkLight>,
+}
+
+impl ProtoChunk {
+    pub fn new(chunk: Chunk) -> Self {
+        Self {
+            chunk,
+            stage: GenerationStage::Terrain,
+            light: None,
+        }
+    }
+}
+
+/// Chunks moving through the generation stages.
+///
+/// A chunk only advances to a stage once every chunk around it, within the generated
+/// range of chunk y coords, has reached the stage before. Stages that look at or write
+/// into neighbouring chunks therefore always see the same neighbours, and a chunk comes
+/// out the same whatever order its region was generated in.
+#[derive(Clone, Debug)]
+pub struct GenerationPipeline {
+    chunks: HashMap<IVec3, ProtoChunk>,
+    /// Inclusive range of chunk y coords that exist.
+    pub min_chunk_y: i32,
+    pub max_chunk_y: i32,
+}
+
+impl GenerationPipeline {
+    pub fn new(min_chunk_y: i32, max_chunk_y: i32) -> Self {
+        Self {
+            chunks: HashMap::new(),
+            min_chunk_y,
+            max_chunk_y,
+        }
+    }
+
+    pub fn get(&self, coord: IVec3) -> Option<&ProtoChunk> {
+        self.chunks.get(&coord)
+    }
+
+    pub fn stage(&self, coord: IVec3) -> Option<GenerationStage> {
+        self.get(coord).map(|proto| proto.stage)
+    }
+
+    pub fn coords(&self) -> impl Iterator<Item = IVec3> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn insert(&mut self, coord: IVec3, proto: ProtoChunk) {
+        self.chunks.insert(coord, proto);
+    }
+
+    pub fn remove(&mut self, coord: IVec3) -> Option<ProtoChunk> {
+        self.chunks.remove(&coord)
+    }
+
+    /// The chunks around `coord` that exist, not counting `coord` itself.
+    pub fn neighbourhood(&self, coord: IVec3) -> impl Iterator<Item = IVec3> + '_ {
+        (-1..=1)
+            .flat_map(|z| (-1..=1).flat_map(move |y| (-1..=1).map(move |x| IVec3::new(x, y, z))))
+            .filter(|offset| *offset != IVec3::ZERO)
+            .map(move |offset| coord + offset)
+            .filter(|neighbour| (self.min_chunk_y..=self.max_chunk_y).contains(&neighbour.y))
+    }
+
+    /// Whether the chunk at `coord` may advance to its next stage.
+    pub fn can_advance(&self, coord: IVec3) -> bool {
+        let Some(next) = self.stage(coord).and_then(GenerationStage::next) else {
+            return false;
+        };
+        let required = next
+            .previous()
+            .expect("a stage after the first has a previous one");
+        self.neighbourhood(coord)
+            .all(|neighbour| self.stage(neighbour).is_some_and(|stage| stage >= required))
+    }
+
+    /// Run the next stage on the chunk at `coord`, or generate its terrain if it isn't in
+    /// the pipeline yet. Returns the stage reached, or `None` if the chunk can't advance.
+    pub fn advance(
+        &mut self,
+        coord: IVec3,
+        generator: &dyn TerrainGenerator,
+        registry: &BlockRegistry,
+    ) -> Option<GenerationStage> {
+        let Some(stage) = self.stage(coord) else {
+            self.insert(coord, ProtoChunk::new(generator.generate_chunk(coord)));
+            return Some(GenerationStage::Terrain);
+        };
+        if !self.can_advance(coord) {
+            return None;
+        }
+
+        let next = stage.next()?;
+        match next {
+            GenerationStage::Terrain => unreachable!("terrain is the first stage"),
+            GenerationStage::Carved => {
+                let proto = self.chunks.get_mut(&coord)?;
+                generator.carve_chunk(coord, &mut proto.chunk);
+            }
+            GenerationStage::Decorated => {
+                let mut proto = self.chunks.remove(&coord)?;
+                let neighbours = |neighbour: IVec3| self.get(neighbour).map(|proto| &proto.chunk);
+                let mut region = DecorationRegion::new(coord, &mut proto.chunk, &neighbours);
+                for origin in std::iter::once(coord).chain(self.neighbourhood(coord)) {
+                    generator.decorate(origin, &mut region);
+                }
+                self.chunks.insert(coord, proto);
+            }
+            GenerationStage::Lit => {
+                let chunk_at = |neighbour: IVec3| self.get(neighbour).map(|proto| &proto.chunk);
+                let light = compute_sky_light(
+                    coord,
+                    &chunk_at,
+                    registry,
+                    SkySource::ChunkAbove {
+                        top_chunk_y: self.max_chunk_y,
+                    },
+                );
+                self.chunks.get_mut(&coord)?.light = light;
+            }
+        }
+
+        self.chunks.get_mut(&coord)?.stage = next;
+        Some(next)
+    }
+
+    /// Finish `coords` and every chunk they need, in the given order, advancing
+    /// whichever chunk can go furthest first. Used to generate a region outside of the
+    /// streamed world, like for previews.
+    pub fn generate(
+        &mut self,
+        coords: &[IVec3],
+        generator: &dyn TerrainGenerator,
+        registry: &BlockRegistry,
+    ) {
+        let mut targets: HashMap<IVec3, GenerationStage> = HashMap::new();
+        for coord in coords {
+            for (ring, stage) in [
+                (0, GenerationStage::Lit),
+                (1, GenerationStage::Decorated),
+                (2, GenerationStage::Carved),
+                (3, GenerationStage::Terrain),
+            ] {
+                for z in -ring..=ring {
+                    for y in -ring..=ring {
+                        for x in -ring..=ring {
+                            let target = *coord + IVec3::new(x, y, z);
+                            if !(self.min_chunk_y..=self.max_chunk_y).contains(&target.y) {
+                                continue;
+                            }
+                            let entry = targets.entry(target).or_insert(stage);
+                            *entry = (*entry).max(stage);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut order: Vec<IVec3> = coords.to_vec();
+        for coord in targets.keys() {
+            if !order.contains(coord) {
+                order.push(*coord);
+            }
+        }
+        loop {
+            let mut progressed = false;
+            for coord in &order {
+                if self
+                    .stage(*coord)
+                    .is_none_or(|stage| stage < targets[coord])
+                    && self.advance(*coord, generator, registry).is_some()
+                {
+                    progressed = true;
+                }
+            }
+            if !progressed {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::world::{
+        blocks::{BLOCK_LEAVES, BLOCK_LOG, BlockRegistryRes},
+        chunk::CHUNK_SIZE,
+        worldgen::{
+            DEFAULT_WORLD_SEED,
+            biomes::{BiomeGenerator, BiomeRegistryRes},
+            density::DensityGenerator,
+        },
+    };
+
+    fn generate(coords: &[IVec3], one_by_one: bool) -> GenerationPipeline {
+        let generator = DensityGenerator::new(BiomeGenerator::new(
+            DEFAULT_WORLD_SEED,
+            BiomeRegistryRes::default().0,
+        ));
+        let registry = BlockRegistryRes::default().0;
+        let mut pipeline = GenerationPipeline::new(0, 2);
+        if one_by_one {
+            for coord in coords {
+                pipeline.generate(&[*coord], &generator, &registry);
+            }
+        } else {
+            pipeline.generate(coords, &generator, &registry);
+        }
+        pipeline
+    }
+
+    #[test]
+    fn border_trees_do_not_depend_on_generation_order() {
+        // Two columns with a tree growing across the border between them.
+        let (a, b) = (IVec3::new(-5, 0, -6), IVec3::new(-4, 0, -6));
+        let coords: Vec<IVec3> = (0..=2).flat_map(|y| [a.with_y(y), b.with_y(y)]).collect();
+        let forward = generate(&coords, false);
+
+        let chunk = |coord: IVec3| &forward.get(coord).unwrap().chunk;
+        let crosses = (0..=2).any(|y| {
+            (0..CHUNK_SIZE * CHUNK_SIZE).any(|i| {
+                let (ly, lz) = (i % CHUNK_SIZE, i / CHUNK_SIZE);
+                chunk(a.with_y(y)).get(CHUNK_SIZE - 1, ly, lz).block_id() == BLOCK_LEAVES
+                    && chunk(b.with_y(y)).get(0, ly, lz).block_id() == BLOCK_LEAVES
+            })
+        });
+        assert!(crosses, "no tree crosses the border");
+        assert!(coords.iter().any(|coord| {
+            chunk(*coord)
+                .voxels()
+                .iter()
+                .any(|voxel| voxel.block_id() == BLOCK_LOG)
+        }));
+
+        let mut reversed = coords.clone();
+        reversed.reverse();
+        for other in [generate(&reversed, false), generate(&reversed, true)] {
+            for coord in &coords {
+                let (expected, actual) = (forward.get(*coord).unwrap(), other.get(*coord).unwrap());
+                assert_eq!(actual.stage, GenerationStage::Lit);
+                assert_eq!(actual.chunk.voxels(), expected.chunk.voxels(), "{coord}");
+                assert_eq!(actual.light, expected.light, "{coord}");
+            }
+        }
+    }
+}